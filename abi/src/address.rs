@@ -0,0 +1,80 @@
+use {
+  curve25519_dalek::edwards::CompressedEdwardsY,
+  sha3::{Digest, Sha3_256},
+};
+
+/// Derives an address from a base address and a list of seeds.
+///
+/// The derived address is the first sha3 hash of the base address,
+/// the seeds and a little-endian u64 bump, starting at bump zero,
+/// that is not a point on the Ed25519 curve. This guarantees that
+/// no private key exists for the derived address, so it can only
+/// ever be modified by its owning contract.
+///
+/// Returns the derived address along with the bump that produced it.
+/// This is the only derivation used by both the VM and the SDK, so
+/// contracts and builtins always agree on derived addresses.
+pub fn derive_address(base: &[u8; 32], seeds: &[&[u8]]) -> ([u8; 32], u64) {
+  let mut bump: u64 = 0;
+  loop {
+    if let Some(address) = derive_address_with_bump(base, seeds, bump) {
+      return (address, bump);
+    }
+    bump += 1;
+  }
+}
+
+/// Derives an address using a known bump value.
+///
+/// Returns [`None`] if the resulting hash lies on the Ed25519 curve.
+/// Contracts that store the bump found by [`derive_address`] can use
+/// it to verify derived addresses without repeating the search.
+pub fn derive_address_with_bump(
+  base: &[u8; 32],
+  seeds: &[&[u8]],
+  bump: u64,
+) -> Option<[u8; 32]> {
+  let mut hasher = Sha3_256::default();
+  hasher.update(base);
+  for seed in seeds {
+    hasher.update(seed);
+  }
+  hasher.update(bump.to_le_bytes());
+  let address = hasher.finalize().into();
+  match is_on_curve(&address) {
+    true => None,
+    false => Some(address),
+  }
+}
+
+/// Checks if the given address lies on the Ed25519 elliptic curve,
+/// which means that a private key could exist for this address.
+pub fn is_on_curve(address: &[u8; 32]) -> bool {
+  CompressedEdwardsY::from_slice(address)
+    .decompress()
+    .is_some()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn derived_address_is_off_curve_test() {
+    let base = [7u8; 32];
+    for i in 0..1000u32 {
+      let (address, bump) = derive_address(&base, &[&i.to_le_bytes()]);
+      assert!(!is_on_curve(&address));
+
+      // all smaller bumps produced on-curve hashes
+      for skipped in 0..bump {
+        let seeds: &[&[u8]] = &[&i.to_le_bytes()];
+        assert_eq!(derive_address_with_bump(&base, seeds, skipped), None);
+      }
+      assert_eq!(
+        derive_address_with_bump(&base, &[&i.to_le_bytes()], bump),
+        Some(address)
+      );
+    }
+  }
+}
@@ -0,0 +1,77 @@
+use {
+  borsh::{BorshDeserialize, BorshSerialize},
+  std::io::{Error as StdError, ErrorKind},
+};
+
+/// This is a parameter on the transaction that indicates that
+/// a contract is going to touch this account. Only accounts
+/// speciefied in the accounts list in a transaction can be
+/// accessed by smart contracts.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccountRef<K> {
+  pub address: K,
+  pub writable: bool,
+  pub signer: bool,
+}
+
+impl<K> AccountRef<K> {
+  pub fn readonly(
+    address: impl TryInto<K>,
+    signer: bool,
+  ) -> Result<Self, StdError> {
+    Ok(Self {
+      address: address.try_into().map_err(|_| {
+        StdError::new(ErrorKind::InvalidInput, "invalid pubkey")
+      })?,
+      writable: false,
+      signer,
+    })
+  }
+
+  pub fn writable(
+    address: impl TryInto<K>,
+    signer: bool,
+  ) -> Result<Self, StdError> {
+    Ok(Self {
+      address: address.try_into().map_err(|_| {
+        StdError::new(ErrorKind::InvalidInput, "invalid pubkey")
+      })?,
+      writable: true,
+      signer,
+    })
+  }
+}
+
+/// A view of an input account as seen by the invoked contract.
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct AccountView<K> {
+  pub signer: bool,
+  pub writable: bool,
+  pub executable: bool,
+  pub owner: Option<K>,
+  pub data: Option<Vec<u8>>,
+}
+
+/// This is the self-cointained input type that is passed to the
+/// contract code containing all accounts data referenced by the
+/// transaction.
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub struct Environment<K> {
+  /// Address of the contract invoking this contract.
+  ///
+  /// For top-level contracts, that are invoked by the transaction
+  /// directly this value is None.
+  ///
+  /// This value is used in contracts to ensure that certain
+  /// instructions are internal and not permitted to be called
+  /// by external contracts, or explicitly specify access policy
+  /// to those instructions based on the caller address.
+  pub caller: Option<K>,
+
+  /// Address of the contract that is being invoked
+  pub address: K,
+
+  /// A list of all input accounts specified by the transaction
+  pub accounts: Vec<(K, AccountView<K>)>,
+}
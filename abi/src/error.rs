@@ -0,0 +1,153 @@
+use {
+  borsh::{BorshDeserialize, BorshSerialize},
+  thiserror::Error,
+};
+
+#[derive(Debug, Clone, Error, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SignatureError {
+  #[error("Signature verification failed")]
+  InvalidSignature,
+
+  #[error("Missing Signers")]
+  MissingSigners,
+}
+
+/// Errors returned by contracts.
+///
+/// Errors are borsh-encoded by contracts and identified by their
+/// variant position, so new variants may only be appended at the end.
+#[derive(Debug, Error, Clone, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ContractError {
+  #[error("Invalid transaction nonce value for this payer")]
+  InvalidTransactionNonce,
+
+  #[error("Account already exists")]
+  AccountAlreadyExists,
+
+  #[error("Account does not exist")]
+  AccountDoesNotExist,
+
+  #[error("The number of input accounts exceeds the maximum limit")]
+  TooManyInputAccounts,
+
+  #[error("Account is exceeding the maximum size limit")]
+  AccountTooLarge,
+
+  #[error("Log is exceeding the maximum size limit")]
+  LogTooLarge,
+
+  #[error("Logs count for this transaction is exceeding the maximum limit")]
+  TooManyLogs,
+
+  #[error("Invalid contract input accounts")]
+  InvalidInputAccounts,
+
+  #[error("Attempt to modify an account not owned by the contract")]
+  InvalidAccountOwner,
+
+  #[error(
+    "Contract attempting to write to an account not in the transaction \
+     accounts list"
+  )]
+  InvalidOutputAccount,
+
+  #[error("The specified account is not writable")]
+  AccountNotWritable,
+
+  #[error("Contract does not exit")]
+  ContractDoesNotExit,
+
+  #[error("The called account is not executable")]
+  AccountIsNotExecutable,
+
+  #[error("Signature Error: {0}")]
+  SignatureError(#[from] SignatureError),
+
+  #[error("Invalid contract input paramters data")]
+  InvalidInputParameters,
+
+  #[error("This contract is not allowed to perform this operation")]
+  UnauthorizedOperation,
+
+  #[error("Runtime Error: {0}")]
+  Runtime(String),
+
+  #[error("Contract error: {0}")]
+  Other(String),
+
+  #[error("The transaction has used up all compute units before completing")]
+  _ComputationalBudgetExhausted,
+
+  #[error("The transaction has no instructions or too many of them")]
+  InvalidInstructionsCount,
+
+  #[error("The transaction has expired before it was included in a block")]
+  TransactionExpired,
+
+  #[error("Event has too many topics or is exceeding the maximum size limit")]
+  InvalidEvent,
+
+  /// A contract-defined failure.
+  ///
+  /// Codes are defined by contracts and are stable across versions
+  /// of a contract, so clients can map them to user-facing messages.
+  /// The optional data carries additional failure details and is
+  /// limited by the maximum log size.
+  #[error("Contract failed with error code {code}")]
+  Custom { code: u32, data: Option<Vec<u8>> },
+
+  #[error("The contract was built against an unsupported ABI version {0}")]
+  UnsupportedAbiVersion(u32),
+
+  #[error("The payer can't cover the storage deposit of {0} system coins")]
+  InsufficientStorageDeposit(u64),
+}
+
+impl ContractError {
+  /// Creates a contract-defined error with a stable numeric code.
+  pub fn custom(code: u32) -> Self {
+    Self::Custom { code, data: None }
+  }
+
+  /// Creates a contract-defined error with a stable numeric
+  /// code and additional failure details.
+  pub fn custom_with_data(code: u32, data: Vec<u8>) -> Self {
+    Self::Custom {
+      code,
+      data: Some(data),
+    }
+  }
+}
+
+impl From<std::io::Error> for ContractError {
+  fn from(e: std::io::Error) -> Self {
+    ContractError::Other(e.to_string())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use {super::ContractError, borsh::BorshDeserialize};
+
+  #[test]
+  fn custom_error_abi_test() {
+    // the SDK encodes errors with borsh, where the enum variant
+    // is identified by its position, so the position of the
+    // custom error variant must never change.
+    let mut bytes = vec![22];
+    bytes.extend_from_slice(&7u32.to_le_bytes());
+    bytes.push(1);
+    bytes.extend_from_slice(&3u32.to_le_bytes());
+    bytes.extend_from_slice(&[1, 2, 3]);
+
+    match ContractError::try_from_slice(&bytes).unwrap() {
+      ContractError::Custom { code, data } => {
+        assert_eq!(code, 7);
+        assert_eq!(data, Some(vec![1, 2, 3]));
+      }
+      other => panic!("unexpected error variant {other:?}"),
+    }
+  }
+}
@@ -0,0 +1,265 @@
+use {
+  crate::output::Topic,
+  borsh::{BorshDeserialize, BorshSerialize},
+  std::io::{Error, ErrorKind, Write},
+};
+
+/// A machine-readable description of a contract interface.
+///
+/// It is generated from the contract Rust types by the SDK and uploaded
+/// alongside the contract bytecode, so that clients and explorers can
+/// encode instructions and decode accounts data and events without
+/// replicating their borsh layouts by hand.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Interface {
+  /// Human readable name of the contract.
+  pub name: String,
+
+  /// The ABI version the contract was built against.
+  pub abi_version: u32,
+
+  /// Instructions accepted by the contract, ordered by their
+  /// borsh variant index in the contract parameters.
+  pub instructions: Vec<InstructionDef>,
+
+  /// Types of data stored in accounts owned by the contract.
+  pub accounts: Vec<Type>,
+
+  /// Events emitted by the contract.
+  pub events: Vec<EventDef>,
+
+  /// Definitions of all user-defined types referenced by
+  /// instructions, accounts data and events.
+  pub types: Vec<TypeDef>,
+}
+
+/// Describes a single instruction of a contract.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstructionDef {
+  pub name: String,
+
+  /// Instruction parameters in their encoding order.
+  pub fields: Vec<FieldDef>,
+
+  /// Input accounts expected by the instruction in order.
+  pub accounts: Vec<AccountDef>,
+}
+
+/// Describes a single input account expected by an instruction.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccountDef {
+  pub name: String,
+  pub description: String,
+  pub signer: bool,
+  pub writable: bool,
+
+  /// The account address is derived from the contract address.
+  pub derived: bool,
+
+  /// The expected owner of the account, if any.
+  pub owner: Option<String>,
+}
+
+/// Describes an event emitted by a contract.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventDef {
+  pub name: String,
+
+  /// The first topic of the event, identifying its type.
+  pub topic: Topic,
+
+  /// The type of the borsh-encoded event data.
+  pub data: Type,
+}
+
+/// Describes a user-defined struct or enum type.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TypeDef {
+  pub name: String,
+  pub kind: TypeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TypeKind {
+  Struct(Vec<FieldDef>),
+  Enum(Vec<VariantDef>),
+}
+
+/// A variant of an enum type, variants are encoded by their index.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariantDef {
+  pub name: String,
+  pub fields: Vec<FieldDef>,
+}
+
+/// A named field of a struct, enum variant or an instruction.
+///
+/// Fields of tuple structs and variants are named by their position.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FieldDef {
+  pub name: String,
+  pub ty: Type,
+}
+
+/// The borsh encoding of a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Type {
+  Unit,
+  Bool,
+  U8,
+  U16,
+  U32,
+  U64,
+  U128,
+  I8,
+  I16,
+  I32,
+  I64,
+  I128,
+  F32,
+  F64,
+  String,
+  Pubkey,
+  Option(Box<Type>),
+  Vec(Box<Type>),
+  Array(Box<Type>, u32),
+  Tuple(Vec<Type>),
+  Map(Box<Type>, Box<Type>),
+
+  /// A reference to a user-defined type in [`Interface::types`].
+  Defined(String),
+}
+
+// Borsh derives can't be used on recursive types, they generate
+// bounds on field types that the compiler can't resolve, so the
+// encoding of type descriptions is implemented by hand.
+
+impl BorshSerialize for Type {
+  fn serialize<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+    match self {
+      Type::Unit => 0u8.serialize(writer),
+      Type::Bool => 1u8.serialize(writer),
+      Type::U8 => 2u8.serialize(writer),
+      Type::U16 => 3u8.serialize(writer),
+      Type::U32 => 4u8.serialize(writer),
+      Type::U64 => 5u8.serialize(writer),
+      Type::U128 => 6u8.serialize(writer),
+      Type::I8 => 7u8.serialize(writer),
+      Type::I16 => 8u8.serialize(writer),
+      Type::I32 => 9u8.serialize(writer),
+      Type::I64 => 10u8.serialize(writer),
+      Type::I128 => 11u8.serialize(writer),
+      Type::F32 => 12u8.serialize(writer),
+      Type::F64 => 13u8.serialize(writer),
+      Type::String => 14u8.serialize(writer),
+      Type::Pubkey => 15u8.serialize(writer),
+      Type::Option(inner) => {
+        16u8.serialize(writer)?;
+        inner.serialize(writer)
+      }
+      Type::Vec(inner) => {
+        17u8.serialize(writer)?;
+        inner.serialize(writer)
+      }
+      Type::Array(inner, len) => {
+        18u8.serialize(writer)?;
+        inner.serialize(writer)?;
+        len.serialize(writer)
+      }
+      Type::Tuple(items) => {
+        19u8.serialize(writer)?;
+        (items.len() as u32).serialize(writer)?;
+        items.iter().try_for_each(|item| item.serialize(writer))
+      }
+      Type::Map(key, value) => {
+        20u8.serialize(writer)?;
+        key.serialize(writer)?;
+        value.serialize(writer)
+      }
+      Type::Defined(name) => {
+        21u8.serialize(writer)?;
+        name.serialize(writer)
+      }
+    }
+  }
+}
+
+impl BorshDeserialize for Type {
+  fn deserialize(buf: &mut &[u8]) -> Result<Self, Error> {
+    Ok(match u8::deserialize(buf)? {
+      0 => Type::Unit,
+      1 => Type::Bool,
+      2 => Type::U8,
+      3 => Type::U16,
+      4 => Type::U32,
+      5 => Type::U64,
+      6 => Type::U128,
+      7 => Type::I8,
+      8 => Type::I16,
+      9 => Type::I32,
+      10 => Type::I64,
+      11 => Type::I128,
+      12 => Type::F32,
+      13 => Type::F64,
+      14 => Type::String,
+      15 => Type::Pubkey,
+      16 => Type::Option(Box::new(Type::deserialize(buf)?)),
+      17 => Type::Vec(Box::new(Type::deserialize(buf)?)),
+      18 => {
+        Type::Array(Box::new(Type::deserialize(buf)?), u32::deserialize(buf)?)
+      }
+      19 => {
+        let len = u32::deserialize(buf)?;
+        let mut items = Vec::new();
+        for _ in 0..len {
+          items.push(Type::deserialize(buf)?);
+        }
+        Type::Tuple(items)
+      }
+      20 => Type::Map(
+        Box::new(Type::deserialize(buf)?),
+        Box::new(Type::deserialize(buf)?),
+      ),
+      21 => Type::Defined(String::deserialize(buf)?),
+      variant => {
+        return Err(Error::new(
+          ErrorKind::InvalidData,
+          format!("unexpected type variant {variant}"),
+        ))
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn type_encoding_roundtrip_test() {
+    let ty = Type::Map(
+      Box::new(Type::Pubkey),
+      Box::new(Type::Tuple(vec![
+        Type::Option(Box::new(Type::Defined("Mint".to_owned()))),
+        Type::Array(Box::new(Type::U8), 32),
+        Type::Vec(Box::new(Type::String)),
+      ])),
+    );
+
+    let bytes = ty.try_to_vec().unwrap();
+    assert_eq!(bytes[..3], [20, 15, 19]);
+    assert_eq!(Type::try_from_slice(&bytes).unwrap(), ty);
+    assert!(Type::try_from_slice(&[22]).is_err());
+  }
+}
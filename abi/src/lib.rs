@@ -0,0 +1,45 @@
+//! Contract ABI types
+//!
+//! This crate defines the types that cross the boundary between the
+//! virtual machine and smart contracts. They are shared by the validator
+//! and the contracts SDK, so both sides always agree on the binary
+//! layout of contract inputs and outputs.
+//!
+//! All types are borsh-encoded and identified by field and variant
+//! position, so any change to their layout must be accompanied by a
+//! bump of [`ABI_VERSION`].
+//!
+//! Types that reference accounts are generic over the address type,
+//! because the validator and the SDK each have their own pubkey type
+//! with an identical 32 bytes binary representation.
+
+mod address;
+mod env;
+mod error;
+mod interface;
+mod output;
+
+pub use {
+  address::{derive_address, derive_address_with_bump, is_on_curve},
+  env::{AccountRef, AccountView, Environment},
+  error::{ContractError, SignatureError},
+  interface::{
+    AccountDef,
+    EventDef,
+    FieldDef,
+    InstructionDef,
+    Interface,
+    Type,
+    TypeDef,
+    TypeKind,
+    VariantDef,
+  },
+  output::{Output, Topic, MAX_EVENT_TOPICS},
+};
+
+/// Version of the contract ABI implemented by this crate.
+///
+/// Contracts export this value through an `abi_version` function
+/// and the VM refuses to install contracts built against a different
+/// version of the ABI.
+pub const ABI_VERSION: u32 = 2;
@@ -0,0 +1,123 @@
+use {
+  crate::env::AccountRef,
+  borsh::{BorshDeserialize, BorshSerialize},
+};
+
+/// The maximum number of topics a single event may carry.
+pub const MAX_EVENT_TOPICS: usize = 4;
+
+/// Events are indexed by their topics, a topic is either a hashed
+/// event name or any other 32 bytes value like an account address.
+pub type Topic = [u8; 32];
+
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub enum Output<K> {
+  /// This type represents a log entry emitted by a smart contract.
+  ///
+  /// Log entries are key-value pairs that are emitted by a contract and
+  /// visible to external observers through the RPC interface.
+  LogEntry(String, String),
+
+  /// Represents creation of a new account that is owned by a calling
+  /// contract.
+  ///
+  /// The modified account should be set as writable in the transaction
+  /// inputs, otherwise the transaction will fail.
+  CreateOwnedAccount(K, Option<Vec<u8>>),
+
+  /// Represents an overrwrite to the contents of an account owned
+  /// by the contract.
+  ///
+  /// The modified account should be set as writable in the transaction
+  /// inputs, otherwise the transaction will fail.
+  ///
+  /// To delete the data contents of an account without deleting the
+  /// account itself (for example to reset it to some initial state),
+  /// use [`None`] as the second parameter to this constructor.
+  WriteAccountData(K, Option<Vec<u8>>),
+
+  /// Represents a deletion of an account that is owned by the contract.
+  ///
+  /// The modified account should be set as writable in the transaction
+  /// inputs, and its owner should match the executing contract.
+  DeleteOwnedAccount(K),
+
+  /// Represents a request for cross contract invocation to another contract.
+  ContractInvoke {
+    /// Address of the contract to be invoked
+    contract: K,
+    /// Input accounts to the contract to be invoked.
+    ///
+    /// Those accounts must already be referenced by the calling
+    /// contract, with the same or higher writability flags.
+    accounts: Vec<AccountRef<K>>,
+
+    /// Input bytes to the invoked contract
+    params: Vec<u8>,
+  },
+
+  /// Represents creation of a new contract account.
+  ///
+  /// This output is only allowed to be emitted by the WASM_VM contract
+  /// during contract installation. Returning this value from any other
+  /// contract will fail the entire transaction.
+  CreateExecutableAccount(K, Vec<u8>),
+
+  /// Represents a structured event emitted by a smart contract.
+  ///
+  /// Events are indexed by the emitting contract and their topics, the
+  /// first topic by convention identifies the event type and the rest
+  /// are values that clients may want to search for, like accounts
+  /// involved in the event. The data is an opaque payload, usually the
+  /// borsh encoding of the event type.
+  Event { topics: Vec<Topic>, data: Vec<u8> },
+
+  /// Represents a change of the owner and the contents of a plain
+  /// account that is not owned by any contract. The account is created
+  /// if it does not exist yet.
+  ///
+  /// This output is only allowed to be emitted by the System contract.
+  /// Returning this value from any other contract will fail the entire
+  /// transaction.
+  AssignAccount {
+    /// Address of the plain account, it should be set as
+    /// writable in the transaction inputs.
+    address: K,
+
+    /// The new owner of the account, None keeps it plain.
+    owner: Option<K>,
+
+    /// The new contents of the account.
+    data: Option<Vec<u8>>,
+  },
+
+  /// Represents a deletion of a plain account that is not owned by
+  /// any contract.
+  ///
+  /// This output is only allowed to be emitted by the System contract.
+  /// Returning this value from any other contract will fail the entire
+  /// transaction.
+  CloseAccount(K),
+
+  /// Represents a cross contract invocation in which the calling
+  /// contract signs for one of its own accounts.
+  ///
+  /// The named signer must be a derived account that is owned by the
+  /// calling contract and referenced as a signer in `accounts`. Other
+  /// accounts may be referenced as signers only if they have signed
+  /// the calling invocation.
+  SignedContractInvoke {
+    /// Address of the contract to be invoked
+    contract: K,
+
+    /// Input accounts to the contract to be invoked.
+    accounts: Vec<AccountRef<K>>,
+
+    /// Input bytes to the invoked contract
+    params: Vec<u8>,
+
+    /// The account owned by the calling contract that
+    /// signs this invocation.
+    signer: K,
+  },
+}
@@ -1,8 +1,9 @@
 import { Account } from "./account";
 import { Block } from "./block";
 import { Pubkey } from "./pubkey";
-import { Transaction, TransactionHash, TransactionResult } from "./transaction";
+import { Event, Transaction, TransactionHash, TransactionResult } from "./transaction";
 
+import bs58 from "bs58";
 import fetch from 'cross-fetch';
 
 export enum Commitment {
@@ -16,11 +17,26 @@ export enum Commitment {
  */
 export class Client {
   private host: String;
+  private chain: Uint8Array | null = null;
 
   constructor(host: String) {
     this.host = host;
   }
 
+  /**
+   * Returns the genesis hash of the chain this client is connected to.
+   * All transactions are signed for a specific chain and are rejected
+   * by validators of other chains.
+   */
+  async getChainHash(): Promise<Uint8Array> {
+    if (this.chain === null) {
+      const result = await fetch(`${this.host}/info`);
+      const obj = await result.json() as any;
+      this.chain = bs58.decode(obj["chain"] as string);
+    }
+    return this.chain;
+  }
+
   async getNextAccountNonce(address: Pubkey): Promise<number> {
     const account = await this.getAccount(address, Commitment.Confirmed);
     return account !== null ? account.nonce + 1 : 1;
@@ -106,6 +122,31 @@ export class Client {
     return await blockResult.json() as any;
   }
 
+  /**
+   * Queries events emitted by a contract, events with a given topic,
+   * or both. Account addresses are also valid topics.
+   */
+  async getEvents(filter: { contract?: Pubkey, topic?: string }): Promise<{
+    block: number;
+    transaction: TransactionHash;
+    event: Event;
+  }[]> {
+    const query = new URLSearchParams();
+    if (filter.contract !== undefined) {
+      query.set("contract", filter.contract.toString());
+    }
+    if (filter.topic !== undefined) {
+      query.set("topic", filter.topic);
+    }
+    const result = await fetch(`${this.host}/events?${query.toString()}`);
+    if (result.status == 200) {
+      const obj = await result.json() as any;
+      return obj["events"];
+    } else {
+      throw Error(`invalid return code ${result.status} from server: ${await result.text()}`);
+    }
+  }
+
   async getAccount(address: Pubkey, commitment: Commitment = Commitment.Confirmed): Promise<Account | null> {
     const commutmentQuery = `?commitment=${commitment}`;
     const result = await fetch(`${this.host}/account/${address.toString()}${commutmentQuery}`);
@@ -118,4 +159,39 @@ export class Client {
       throw Error(`invalid return code ${result.status} from server: ${await result.text()}`);
     }
   }
-}
\ No newline at end of file
+
+  /**
+   * Returns the interface description of a contract uploaded alongside
+   * its bytecode, or null if the contract was deployed without one.
+   * It describes the contract instructions, accounts data and events.
+   */
+  async getInterface(contract: Pubkey, commitment: Commitment = Commitment.Confirmed): Promise<any | null> {
+    const commutmentQuery = `?commitment=${commitment}`;
+    const result = await fetch(`${this.host}/interface/${contract.toString()}${commutmentQuery}`);
+    if (result.status == 200) {
+      let obj = await result.json() as any;
+      return obj['interface'];
+    } else if (result.status == 404) {
+      return null
+    } else {
+      throw Error(`invalid return code ${result.status} from server: ${await result.text()}`);
+    }
+  }
+
+  /**
+   * Returns the metadata of a non-fungible token minted through the
+   * Currency builtin, or null if the mint is not an NFT.
+   */
+  async getNftMetadata(mint: Pubkey, commitment: Commitment = Commitment.Confirmed): Promise<any | null> {
+    const commutmentQuery = `?commitment=${commitment}`;
+    const result = await fetch(`${this.host}/nft/${mint.toString()}${commutmentQuery}`);
+    if (result.status == 200) {
+      let obj = await result.json() as any;
+      return obj['metadata'];
+    } else if (result.status == 404) {
+      return null
+    } else {
+      throw Error(`invalid return code ${result.status} from server: ${await result.text()}`);
+    }
+  }
+}
@@ -179,13 +179,15 @@ export class Currency {
    * @param decimals number of decimals this coin has
    * @param name optional human readable name of the coin
    * @param symbol optional human readable symbol of the coin
+   * @param freezeAuthority optional public key of the account allowed to freeze coin accounts
    */
   static create(
     seed: Uint8Array,
     authority: Keypair,
     decimals: number,
     name: string | null,
-    symbol: string | null): TransactionCreationParams {
+    symbol: string | null,
+    freezeAuthority: Pubkey | null = null): TransactionCreationParams {
     let mintAddress = CURRENCY_CONTRACT_ADDR.derive([seed]);
 
     // params in BORSH format
@@ -219,6 +221,14 @@ export class Currency {
       writer.writeString(symbol);
     }
 
+    // optional freeze authority
+    if (freezeAuthority === null) {
+      writer.writeU8(0);
+    } else {
+      writer.writeU8(1);
+      writer.writeFixedArray(freezeAuthority.bytes);
+    }
+
     return {
       contract: CURRENCY_CONTRACT_ADDR,
       payer: authority,
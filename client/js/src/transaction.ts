@@ -11,26 +11,105 @@ export interface AccountRef {
   address: string;
 };
 
+export interface Instruction {
+  contract: string;
+  accounts: number[];
+  params: string;
+}
+
 export interface Transaction {
   nonce: number;
-  contract: string;
+  expiry?: number;
   accounts: AccountRef[];
+  instructions: Instruction[];
   payer: string,
-  params: string,
   signatures: string[]
 }
 
+/**
+ * Parameters of a transaction with a single instruction that
+ * invokes one contract with all the transaction accounts.
+ */
 export interface TransactionCreationParams {
   contract: Pubkey;
   payer: Keypair;
   accounts: AccountRef[];
   signers: Keypair[];
   params: Uint8Array;
+  expiry?: number;
+}
+
+/**
+ * Parameters of a transaction that executes many instructions
+ * atomically, either all of them succeed or none of them is applied.
+ */
+export interface InstructionsCreationParams {
+  payer: Keypair;
+  accounts: AccountRef[];
+  instructions: {
+    contract: Pubkey;
+    accounts: number[];
+    params: Uint8Array;
+  }[];
+  signers: Keypair[];
+  expiry?: number;
+}
+
+/**
+ * Combines many single-instruction transaction params, like the ones
+ * returned by `Currency.mint` or `Currency.create`, into one transaction
+ * that executes all of them atomically. Accounts that are referenced by
+ * many instructions are listed only once in the transaction.
+ */
+export function combineInstructions(
+  payer: Keypair,
+  inputs: TransactionCreationParams[]
+): InstructionsCreationParams {
+  let accounts: AccountRef[] = [];
+  let signers: Keypair[] = [];
+
+  // the combined transaction expires with the earliest of its inputs
+  const expiries = inputs
+    .map((input) => input.expiry)
+    .filter((e): e is number => e !== undefined);
+  const expiry = expiries.length !== 0 ? Math.min(...expiries) : undefined;
+
+  const accountIndex = (acc: AccountRef): number => {
+    const existing = accounts.findIndex((a) => a.address === acc.address);
+    if (existing === -1) {
+      accounts.push({ ...acc });
+      return accounts.length - 1;
+    }
+    accounts[existing].writable ||= acc.writable;
+    accounts[existing].signer ||= acc.signer;
+    return existing;
+  };
+
+  const instructions = inputs.map((input) => {
+    for (const signer of input.signers) {
+      if (!signers.some((s) => s.publicKey.equals(signer.publicKey))) {
+        signers.push(signer);
+      }
+    }
+    return {
+      contract: input.contract,
+      accounts: input.accounts.map(accountIndex),
+      params: input.params
+    };
+  });
+
+  // signatures follow the order of signing accounts
+  signers = accounts
+    .filter((acc) => acc.signer)
+    .map((acc) => signers.find((s) => s.publicKey.toString() === acc.address))
+    .filter((s): s is Keypair => s !== undefined);
+
+  return { payer, accounts, instructions, signers, expiry };
 }
 
 export async function createManyTransactions(
   client: Client,
-  inputs: TransactionCreationParams[]
+  inputs: (TransactionCreationParams | InstructionsCreationParams)[]
 ): Promise<Transaction[]> {
   let transactions = [];
   let nonces: Record<string, number> = {};
@@ -42,59 +121,153 @@ export async function createManyTransactions(
       nonces[input.payer.publicKey.toString()] += 1;
     }
     let txnonce = nonces[input.payer.publicKey.toString()];
-    transactions.push(await createTransaction(txnonce, input));
+    transactions.push(await createTransaction(client, input, txnonce));
   }
   return transactions;
 }
 
-export async function createTransaction(
-  clientOrNonce: Client | number,
-  input: TransactionCreationParams
-): Promise<Transaction> {
-  let nonce = 0;
-  if (clientOrNonce instanceof Client) {
-    nonce = await clientOrNonce.getNextAccountNonce(input.payer.publicKey);
-  } else {
-    nonce = clientOrNonce;
-  }
-
-  const hasher = new SHA3(256);
-  hasher.update(Buffer.from(input.contract.bytes));
-
-  // nonce as le bytes
+function u64LE(value: number): Buffer {
   const buffer = new ArrayBuffer(8);
-  new DataView(buffer).setBigUint64(0, BigInt(nonce), true);
-  hasher.update(Buffer.from(buffer));
-  hasher.update(Buffer.from(input.payer.publicKey.bytes));
+  new DataView(buffer).setBigUint64(0, BigInt(value), true);
+  return Buffer.from(buffer);
+}
 
+/**
+ * Computes the message signed by the payer and all signers of a
+ * transaction. This must be kept in sync with `Transaction::hash_fields`.
+ */
+function hashFields(
+  chain: Uint8Array,
+  nonce: number,
+  payer: Keypair,
+  input: InstructionsCreationParams
+): Buffer {
+  const hasher = new SHA3(256);
+  hasher.update(Buffer.from(chain));
+  hasher.update(u64LE(nonce));
+  hasher.update(Buffer.from(payer.publicKey.bytes));
+  if (input.expiry !== undefined) {
+    hasher.update(Buffer.from([0x1]));
+    hasher.update(u64LE(input.expiry));
+  } else {
+    hasher.update(Buffer.from([0x0]));
+  }
+  hasher.update(u64LE(input.accounts.length));
   for (const acc of input.accounts) {
     hasher.update(Buffer.from(bs58.decode(acc.address)));
     hasher.update(acc.writable ? Buffer.from([0x1]) : Buffer.from([0x0]));
     hasher.update(acc.signer ? Buffer.from([0x1]) : Buffer.from([0x0]));
   }
-  hasher.update(Buffer.from(input.params));
-  const digest = hasher.digest();
+  hasher.update(u64LE(input.instructions.length));
+  for (const instruction of input.instructions) {
+    hasher.update(Buffer.from(instruction.contract.bytes));
+    hasher.update(u64LE(instruction.accounts.length));
+    hasher.update(Buffer.from(instruction.accounts));
+    hasher.update(u64LE(instruction.params.length));
+    hasher.update(Buffer.from(instruction.params));
+  }
+  return hasher.digest();
+}
+
+/**
+ * Creates and signs a transaction for the chain the client is connected to.
+ * If no nonce is given, then the next nonce of the payer is fetched.
+ */
+export async function createTransaction(
+  client: Client,
+  input: TransactionCreationParams | InstructionsCreationParams,
+  nonce?: number
+): Promise<Transaction> {
+  if (nonce === undefined) {
+    nonce = await client.getNextAccountNonce(input.payer.publicKey);
+  }
+
+  // single contract params are a transaction with one
+  // instruction that references all transaction accounts.
+  const ixinput: InstructionsCreationParams = "instructions" in input ? input : {
+    payer: input.payer,
+    accounts: input.accounts,
+    signers: input.signers,
+    expiry: input.expiry,
+    instructions: [{
+      contract: input.contract,
+      accounts: input.accounts.map((_, index) => index),
+      params: input.params
+    }]
+  };
+
+  const chain = await client.getChainHash();
+  const digest = hashFields(chain, nonce, input.payer, ixinput);
 
   return {
     nonce: nonce,
-    contract: input.contract.toString(),
-    accounts: input.accounts.map((acc) => ({
+    expiry: ixinput.expiry,
+    accounts: ixinput.accounts.map((acc) => ({
       address: acc.address,
       signer: acc.signer,
       writable: acc.writable,
     })),
+    instructions: ixinput.instructions.map((ix) => ({
+      contract: ix.contract.toString(),
+      accounts: ix.accounts,
+      params: bs58.encode(ix.params)
+    })),
     payer: input.payer.publicKey.toString(),
-    params: bs58.encode(input.params),
     signatures: [
       bs58.encode(await input.payer.sign(digest)),
-    ].concat(await Promise.all(input.signers.map(async (s) => bs58.encode(await s.sign(digest)))))
+    ].concat(await Promise.all(ixinput.signers.map(async (s) => bs58.encode(await s.sign(digest)))))
   };
 }
 
+export interface Invocation {
+  contract: string;
+  caller: string | null;
+}
+
+/**
+ * Changes made to the global state by a single transaction.
+ */
+export interface Receipt {
+  created: string[];
+  written: string[];
+  deleted: string[];
+  invocations: Invocation[];
+  state: Record<string, {
+    nonce: number;
+    owner: string | null;
+    executable: boolean;
+    data: string | null;
+  } | null>;
+}
+
+/**
+ * A structured event emitted by a contract. Topics and data are
+ * base58 encoded.
+ */
+export interface Event {
+  contract: string;
+  topics: string[];
+  data: string;
+}
+
+/**
+ * The reason of a failed transaction. Contract-defined failures carry
+ * a numeric code that is stable across contract versions and optional
+ * base58 encoded data, other failures only have a message.
+ */
+export interface TransactionError {
+  message: string;
+  code?: number;
+  data?: string | null;
+}
+
 export interface TransactionResult {
   block: number;
   commitment: Commitment;
   hash: string;
+  error: TransactionError | null;
   output: any;
+  events: Event[];
+  receipt: Receipt;
   transaction: Transaction;
 }
\ No newline at end of file
@@ -0,0 +1,190 @@
+use {
+  proc_macro2::TokenStream,
+  quote::{quote, quote_spanned},
+  syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    spanned::Spanned,
+    Data,
+    DeriveInput,
+    Error,
+    Expr,
+    Fields,
+    Ident,
+    Lit,
+    Meta,
+    Token,
+  },
+};
+
+/// A single constraint in an `#[account(...)]` attribute.
+enum Constraint {
+  Signer,
+  Writable,
+  Owned,
+  Owner(Expr),
+  Derived(Expr),
+}
+
+impl Parse for Constraint {
+  fn parse(input: ParseStream) -> syn::Result<Self> {
+    let name: Ident = input.parse()?;
+    match name.to_string().as_str() {
+      "signer" => Ok(Constraint::Signer),
+      "writable" => Ok(Constraint::Writable),
+      "owned" => Ok(Constraint::Owned),
+      "owner" => {
+        input.parse::<Token![=]>()?;
+        Ok(Constraint::Owner(input.parse()?))
+      }
+      "derived" => {
+        input.parse::<Token![=]>()?;
+        match input.parse()? {
+          Expr::Array(seeds) => Ok(Constraint::Derived(Expr::Array(seeds))),
+          other => Err(Error::new(
+            other.span(),
+            "derived seeds are expected to be a list: derived = [seed, ...]",
+          )),
+        }
+      }
+      _ => Err(Error::new(
+        name.span(),
+        "unknown account constraint, expected one of: signer, writable, \
+         owned, owner = <expr> or derived = [<seeds>]",
+      )),
+    }
+  }
+}
+
+/// Implements `rensa_sdk::Accounts` for a struct that lists
+/// the expected input accounts of an instruction in order.
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+  let name = &input.ident;
+  let fields = match input.data {
+    Data::Struct(ref data) => match data.fields {
+      Fields::Named(ref fields) => &fields.named,
+      _ => {
+        return Err(Error::new(
+          input.span(),
+          "accounts layouts must be structs with named fields",
+        ))
+      }
+    },
+    _ => {
+      return Err(Error::new(
+        input.span(),
+        "accounts layouts must be structs with named fields",
+      ))
+    }
+  };
+
+  let lifetime = match input.generics.lifetimes().collect::<Vec<_>>()[..] {
+    [lifetime] => lifetime.lifetime.clone(),
+    _ => {
+      return Err(Error::new(
+        input.generics.span(),
+        "accounts layouts are expected to have exactly one lifetime \
+         parameter, for example: struct Accounts<'a>",
+      ))
+    }
+  };
+
+  let count = fields.len();
+  let mut validations = vec![];
+  let mut layouts = vec![];
+  let mut idents = vec![];
+
+  for (index, field) in fields.iter().enumerate() {
+    let ident = field.ident.as_ref().unwrap();
+    let mut constraints = vec![];
+    let mut description = vec![];
+
+    for attr in &field.attrs {
+      if attr.path.is_ident("account") {
+        constraints.extend(attr.parse_args_with(
+          Punctuated::<Constraint, Token![,]>::parse_terminated,
+        )?);
+      } else if attr.path.is_ident("doc") {
+        if let Ok(Meta::NameValue(doc)) = attr.parse_meta() {
+          if let Lit::Str(line) = doc.lit {
+            description.push(line.value().trim().to_owned());
+          }
+        }
+      }
+    }
+
+    let mut checks = vec![];
+    let (mut signer, mut writable, mut derived) = (false, false, false);
+    let mut owner = quote!(None);
+
+    for constraint in constraints {
+      checks.push(match constraint {
+        Constraint::Signer => {
+          signer = true;
+          quote!(#ident.require_signer()?;)
+        }
+        Constraint::Writable => {
+          writable = true;
+          quote!(#ident.require_writable()?;)
+        }
+        Constraint::Owned => {
+          owner = quote!(Some("self"));
+          quote!(#ident.require_owner(&env.address)?;)
+        }
+        Constraint::Owner(expr) => {
+          let text = quote!(#expr).to_string();
+          owner = quote!(Some(#text));
+          quote_spanned!(expr.span()=> #ident.require_owner(&(#expr))?;)
+        }
+        Constraint::Derived(Expr::Array(seeds)) => {
+          derived = true;
+          let seeds = seeds.elems.iter().map(|seed| {
+            quote_spanned! { seed.span()=>
+              ::core::convert::AsRef::<[u8]>::as_ref(&(#seed))
+            }
+          });
+          quote!(#ident.require_derived(&env.address, &[#(#seeds),*])?;)
+        }
+        Constraint::Derived(_) => unreachable!(),
+      });
+    }
+
+    validations.push(quote! {
+      let #ident = rensa_sdk::AccountInfo::new(&env.accounts[#index]);
+      #(#checks)*
+    });
+
+    let name = ident.to_string();
+    let description = description.join(" ");
+    layouts.push(quote! {
+      rensa_sdk::AccountLayout {
+        name: #name,
+        description: #description,
+        signer: #signer,
+        writable: #writable,
+        derived: #derived,
+        owner: #owner,
+      }
+    });
+
+    idents.push(ident);
+  }
+
+  Ok(quote! {
+    impl<#lifetime> rensa_sdk::Accounts<#lifetime> for #name<#lifetime> {
+      fn from_env(
+        env: &#lifetime rensa_sdk::Environment,
+      ) -> Result<Self, rensa_sdk::ContractError> {
+        if env.accounts.len() != #count {
+          return Err(rensa_sdk::ContractError::InvalidInputAccounts);
+        }
+        #(#validations)*
+        Ok(Self { #(#idents),* })
+      }
+
+      fn layout() -> Vec<rensa_sdk::AccountLayout> {
+        vec![#(#layouts),*]
+      }
+    }
+  })
+}
@@ -0,0 +1,107 @@
+use {
+  proc_macro2::TokenStream,
+  quote::quote,
+  syn::{spanned::Spanned, Data, DeriveInput, Error, Fields},
+};
+
+/// Generates the field descriptions of a struct or an enum variant,
+/// fields of tuples are named by their position.
+pub fn field_defs(fields: &Fields) -> Vec<TokenStream> {
+  fields
+    .iter()
+    .enumerate()
+    .map(|(index, field)| {
+      let name = match field.ident {
+        Some(ref ident) => ident.to_string(),
+        None => index.to_string(),
+      };
+      let ty = &field.ty;
+      quote! {
+        rensa_sdk::FieldDef {
+          name: #name.to_owned(),
+          ty: <#ty as rensa_sdk::Describe>::ty(),
+        }
+      }
+    })
+    .collect()
+}
+
+/// Generates definitions of user-defined types referenced by fields.
+pub fn field_types(fields: &Fields) -> Vec<TokenStream> {
+  fields
+    .iter()
+    .map(|field| {
+      let ty = &field.ty;
+      quote!(<#ty as rensa_sdk::Describe>::define(types);)
+    })
+    .collect()
+}
+
+/// Implements `rensa_sdk::Describe` for a user-defined struct or enum.
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+  let name = &input.ident;
+  let name_str = name.to_string();
+
+  if !input.generics.params.is_empty() {
+    return Err(Error::new(
+      input.generics.span(),
+      "describing generic types is not supported",
+    ));
+  }
+
+  let (kind, referenced) = match input.data {
+    Data::Struct(ref data) => {
+      let fields = field_defs(&data.fields);
+      let referenced = field_types(&data.fields);
+      (
+        quote!(rensa_sdk::TypeKind::Struct(vec![#(#fields),*])),
+        referenced,
+      )
+    }
+    Data::Enum(ref data) => {
+      let mut variants = vec![];
+      let mut referenced = vec![];
+      for variant in &data.variants {
+        let vname = variant.ident.to_string();
+        let fields = field_defs(&variant.fields);
+        referenced.extend(field_types(&variant.fields));
+        variants.push(quote! {
+          rensa_sdk::VariantDef {
+            name: #vname.to_owned(),
+            fields: vec![#(#fields),*],
+          }
+        });
+      }
+      (
+        quote!(rensa_sdk::TypeKind::Enum(vec![#(#variants),*])),
+        referenced,
+      )
+    }
+    Data::Union(_) => {
+      return Err(Error::new(
+        input.span(),
+        "unions have no borsh encoding and can't be described",
+      ))
+    }
+  };
+
+  Ok(quote! {
+    impl rensa_sdk::Describe for #name {
+      fn ty() -> rensa_sdk::Type {
+        rensa_sdk::Type::Defined(#name_str.to_owned())
+      }
+
+      fn define(types: &mut Vec<rensa_sdk::TypeDef>) {
+        if types.iter().any(|t| t.name == #name_str) {
+          return;
+        }
+        // defined before its fields, so recursive types terminate
+        types.push(rensa_sdk::TypeDef {
+          name: #name_str.to_owned(),
+          kind: #kind,
+        });
+        #(#referenced)*
+      }
+    }
+  })
+}
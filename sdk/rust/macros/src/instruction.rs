@@ -0,0 +1,178 @@
+use {
+  crate::describe::{field_defs, field_types},
+  proc_macro2::TokenStream,
+  quote::{format_ident, quote},
+  syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    spanned::Spanned,
+    Data,
+    DeriveInput,
+    Error,
+    Fields,
+    Ident,
+    Path,
+    Token,
+  },
+};
+
+/// A single argument of an `#[instruction(...)]` attribute.
+enum Argument {
+  Accounts(Path),
+  Handler(Path),
+}
+
+impl Parse for Argument {
+  fn parse(input: ParseStream) -> syn::Result<Self> {
+    let name: Ident = input.parse()?;
+    input.parse::<Token![=]>()?;
+    match name.to_string().as_str() {
+      "accounts" => Ok(Argument::Accounts(input.parse()?)),
+      "handler" => Ok(Argument::Handler(input.parse()?)),
+      _ => Err(Error::new(
+        name.span(),
+        "unknown instruction argument, expected one of: \
+         accounts = <type> or handler = <function>",
+      )),
+    }
+  }
+}
+
+/// Generates decoding of borsh-encoded invocation parameters into
+/// the annotated enum and, when every variant names its handler,
+/// dispatching of the decoded instruction to its handler. It also
+/// implements `rensa_sdk::Instructions` for interface descriptions.
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+  let name = &input.ident;
+  let variants = match input.data {
+    Data::Enum(ref data) => &data.variants,
+    _ => {
+      return Err(Error::new(
+        input.span(),
+        "instructions must be declared as enums",
+      ))
+    }
+  };
+
+  let mut arms = vec![];
+  let mut unhandled = vec![];
+  let mut descriptions = vec![];
+
+  for variant in variants {
+    let ident = &variant.ident;
+    let (mut accounts, mut handler) = (None, None);
+
+    for attr in &variant.attrs {
+      if attr.path.is_ident("instruction") {
+        for arg in attr.parse_args_with(
+          Punctuated::<Argument, Token![,]>::parse_terminated,
+        )? {
+          match arg {
+            Argument::Accounts(path) => accounts = Some(path),
+            Argument::Handler(path) => handler = Some(path),
+          }
+        }
+      }
+    }
+
+    let vname = ident.to_string();
+    let fields = field_defs(&variant.fields);
+    let referenced = field_types(&variant.fields);
+    let layout = match accounts {
+      Some(ref accounts) => quote! {
+        <#accounts as rensa_sdk::Accounts>::layout()
+          .into_iter()
+          .map(Into::into)
+          .collect()
+      },
+      None => quote!(vec![]),
+    };
+    descriptions.push(quote! {{
+      #(#referenced)*
+      rensa_sdk::InstructionDef {
+        name: #vname.to_owned(),
+        fields: vec![#(#fields),*],
+        accounts: #layout,
+      }
+    }});
+
+    let handler = match handler {
+      Some(handler) => handler,
+      None => {
+        unhandled.push(variant);
+        continue;
+      }
+    };
+
+    let accounts = accounts.map(
+      |accounts| quote!(<#accounts as rensa_sdk::Accounts>::from_env(env)?,),
+    );
+
+    arms.push(match variant.fields {
+      Fields::Named(ref fields) => {
+        let fields: Vec<_> = fields
+          .named
+          .iter()
+          .map(|f| f.ident.as_ref().unwrap())
+          .collect();
+        quote! {
+          Self::#ident { #(#fields),* } => #handler(env, #accounts #(#fields),*)
+        }
+      }
+      Fields::Unnamed(ref fields) => {
+        let fields: Vec<_> = (0..fields.unnamed.len())
+          .map(|i| format_ident!("arg{}", i))
+          .collect();
+        quote! {
+          Self::#ident(#(#fields),*) => #handler(env, #accounts #(#fields),*)
+        }
+      }
+      Fields::Unit => quote!(Self::#ident => #handler(env, #accounts)),
+    });
+  }
+
+  let dispatch = if unhandled.is_empty() {
+    Some(quote! {
+      /// Decodes the invocation parameters and invokes the handler
+      /// of the decoded instruction with its validated accounts.
+      pub fn dispatch(
+        env: &rensa_sdk::Environment,
+        params: &[u8],
+      ) -> Result<Vec<rensa_sdk::Output>, rensa_sdk::ContractError> {
+        match Self::decode(params)? {
+          #(#arms),*
+        }
+      }
+    })
+  } else if arms.is_empty() {
+    None
+  } else {
+    return Err(Error::new(
+      unhandled[0].span(),
+      "either all or none of the instructions must declare a handler",
+    ));
+  };
+
+  Ok(quote! {
+    #[allow(dead_code)]
+    impl #name {
+      /// Decodes borsh-encoded invocation parameters.
+      pub fn decode(
+        params: &[u8],
+      ) -> Result<Self, rensa_sdk::ContractError> {
+        <Self as rensa_sdk::borsh::BorshDeserialize>::try_from_slice(params)
+          .map_err(|_| rensa_sdk::ContractError::InvalidInputParameters)
+      }
+
+      #dispatch
+    }
+
+    impl rensa_sdk::Instructions for #name {
+      fn describe(
+        types: &mut Vec<rensa_sdk::TypeDef>,
+      ) -> Vec<rensa_sdk::InstructionDef> {
+        vec![#(#descriptions),*]
+      }
+    }
+  })
+}
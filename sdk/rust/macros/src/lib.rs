@@ -1,70 +1,109 @@
 use {
   proc_macro::TokenStream,
   quote::quote,
-  syn::{parse_macro_input, parse_quote, Abi, FnArg, ItemFn},
+  syn::{parse_macro_input, DeriveInput, FnArg, ItemFn},
 };
 
+mod accounts;
+mod describe;
+mod instruction;
+
+/// Declares the input accounts layout of an instruction.
+///
+/// See the `rensa_sdk::Accounts` trait for supported constraints.
+#[proc_macro_derive(Accounts, attributes(account))]
+pub fn derive_accounts(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  TokenStream::from(
+    accounts::derive(input).unwrap_or_else(|e| e.to_compile_error()),
+  )
+}
+
+/// Describes the borsh encoding of a user-defined type in
+/// the contract interface description.
+#[proc_macro_derive(Describe)]
+pub fn derive_describe(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  TokenStream::from(
+    describe::derive(input).unwrap_or_else(|e| e.to_compile_error()),
+  )
+}
+
+/// Declares the instructions of a contract as a borsh-encoded enum.
+///
+/// Generates `decode(params)` on the enum and, if all variants are
+/// annotated with `#[instruction(handler = f)]`, also generates
+/// `dispatch(env, params)` that invokes `f(env, <fields>)` for the
+/// decoded variant. When a variant also declares its accounts layout
+/// using `#[instruction(accounts = T, handler = f)]` then the accounts
+/// are validated and passed to the handler as `f(env, accounts, ...)`.
+///
+/// The enum also implements `rensa_sdk::Instructions`, so all fields
+/// of its variants are expected to implement `rensa_sdk::Describe`.
+#[proc_macro_derive(Instruction, attributes(instruction))]
+pub fn derive_instruction(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  TokenStream::from(
+    instruction::derive(input).unwrap_or_else(|e| e.to_compile_error()),
+  )
+}
+
 #[proc_macro_attribute]
 pub fn main(_input: TokenStream, annotated_item: TokenStream) -> TokenStream {
-  let mut input_fn = parse_macro_input!(annotated_item as ItemFn);
+  let input_fn = parse_macro_input!(annotated_item as ItemFn);
 
-  // first, decorate and export the entrypoint function
-  decorate_entrypoint_abi(&mut input_fn);
-
-  // make idiomatic rust types ABI and FFI friendly
-  adjust_entrypoint_params(&mut input_fn);
+  // make sure the annotated function has the expected shape
+  if let Err(error) = verify_entrypoint_params(&input_fn) {
+    return error;
+  }
 
-  // then transalate Rust-style error handling to an ABI firendly
-  // representation of return pointer and abort() or error
-  input_fn = wrap_entrypoint_abi(input_fn);
+  // then generate an exported function with an ABI friendly
+  // signature that transaltes Rust-style error handling to
+  // a representation of return pointer and abort() on error
+  let entrypoint = generate_entrypoint_abi(&input_fn);
 
-  TokenStream::from(quote!(#input_fn))
-}
+  TokenStream::from(quote! {
+    // the annotated function is kept intact, so it can be invoked
+    // natively by tests, outside of the WASM runtime.
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    #input_fn
 
-/// this is the main function of a contract, decorate
-/// it with extern and no_mangle and rename it to "main".
-/// this is expected by the VM ABI.
-fn decorate_entrypoint_abi(input_fn: &mut ItemFn) {
-  input_fn.attrs.push(parse_quote! {
-    #[no_mangle]
-  });
-  input_fn.sig.abi = Some(Abi {
-    extern_token: parse_quote!(extern),
-    name: None,
-  });
-  input_fn.sig.ident = parse_quote!(main);
-  input_fn.sig.output = parse_quote! ( -> Box<Vec<rensa_sdk::Output>> );
+    #entrypoint
+  })
 }
 
-fn adjust_entrypoint_params(input_fn: &mut ItemFn) {
-  if let Some(FnArg::Typed(fn_pat)) = input_fn.sig.inputs.iter_mut().nth(1) {
-    fn_pat.ty = Box::new(parse_quote!(&Vec<u8>))
-  } else {
-    quote! {
+fn verify_entrypoint_params(input_fn: &ItemFn) -> Result<(), TokenStream> {
+  match input_fn.sig.inputs.iter().nth(1) {
+    Some(FnArg::Typed(_)) if input_fn.sig.inputs.len() == 2 => Ok(()),
+    _ => Err(TokenStream::from(quote! {
       compile_error!(
         "contract entrypoint function is expected have the signature of \
          fn(&Environment, &[u8]) -> Result<Vec<Output>, ContractError>"
-      )
-    };
+      );
+    })),
   }
 }
 
-fn wrap_entrypoint_abi(mut input_fn: ItemFn) -> ItemFn {
-  // the original body of the function in idomatic rust
-  let body = input_fn.block;
-
-  // wrap it in FFI-compatible pointer on success
-  // and on failure call the VM-exported [`abort`]
-  // with the error value
-  input_fn.block = Box::new(parse_quote!({
-    let output = move || -> Result<Vec<rensa_sdk::Output>, rensa_sdk::ContractError> {
-      #body
-    };
-    match output() {
-      Ok(result) => Box::new(result),
-      Err(error) => rensa_sdk::abort(error)
+/// The main function of a contract is exported as an extern "main"
+/// function, this is expected by the VM ABI. On success it returns
+/// an FFI-compatible pointer to the outputs and on failure it calls
+/// the VM-exported [`abort`] with the error value.
+///
+/// The export exists only in WASM builds, on other targets it would
+/// clash with the main function of the host executable.
+fn generate_entrypoint_abi(input_fn: &ItemFn) -> proc_macro2::TokenStream {
+  let ident = &input_fn.sig.ident;
+  quote! {
+    #[cfg(target_arch = "wasm32")]
+    #[export_name = "main"]
+    extern "C" fn __rensa_entrypoint(
+      env: &rensa_sdk::Environment,
+      params: &Vec<u8>,
+    ) -> Box<Vec<rensa_sdk::Output>> {
+      match #ident(env, params) {
+        Ok(result) => Box::new(result),
+        Err(error) => rensa_sdk::abort(error),
+      }
     }
-  }));
-
-  input_fn
+  }
 }
@@ -1,5 +1,5 @@
 use {
-  crate::{ContractError, Environment, Output},
+  crate::{ContractError, Environment, Output, ABI_VERSION},
   borsh::{BorshDeserialize, BorshSerialize},
   std::{ffi::CString, os::raw::c_char},
 };
@@ -13,6 +13,15 @@ extern "C" {
   fn abi_abort(error: u64);
 }
 
+/// Version of the ABI this contract was built against.
+///
+/// The VM checks this value when installing a contract and rejects
+/// contracts that were built against an incompatible ABI.
+#[no_mangle]
+pub extern "C" fn abi_version() -> u32 {
+  ABI_VERSION
+}
+
 #[no_mangle]
 pub extern "C" fn allocate(size: u32) -> *mut u8 {
   let mut buf = Vec::with_capacity(size as usize);
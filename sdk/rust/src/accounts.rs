@@ -0,0 +1,138 @@
+use {
+  crate::{
+    env::AccountView,
+    ContractError,
+    Environment,
+    Pubkey,
+    SignatureError,
+  },
+  std::{
+    fmt::{Display, Formatter},
+    ops::Deref,
+  },
+};
+
+/// An input account of a contract invocation along with its address.
+#[derive(Debug, Clone, Copy)]
+pub struct AccountInfo<'a> {
+  pub address: &'a Pubkey,
+  pub view: &'a AccountView,
+}
+
+impl<'a> AccountInfo<'a> {
+  pub fn new((address, view): &'a (Pubkey, AccountView)) -> Self {
+    Self { address, view }
+  }
+
+  /// Fails unless the account signed the transaction.
+  pub fn require_signer(&self) -> Result<(), ContractError> {
+    if !self.view.signer {
+      return Err(ContractError::SignatureError(
+        SignatureError::MissingSigners,
+      ));
+    }
+    Ok(())
+  }
+
+  /// Fails unless the invoked contract is allowed to modify the account.
+  pub fn require_writable(&self) -> Result<(), ContractError> {
+    if !self.view.writable {
+      return Err(ContractError::AccountNotWritable);
+    }
+    Ok(())
+  }
+
+  /// Fails unless the account is owned by the given contract.
+  pub fn require_owner(&self, owner: &Pubkey) -> Result<(), ContractError> {
+    if self.view.owner.as_ref() != Some(owner) {
+      return Err(ContractError::InvalidAccountOwner);
+    }
+    Ok(())
+  }
+
+  /// Fails unless the account address is derived from
+  /// the given base address and seeds.
+  pub fn require_derived(
+    &self,
+    base: &Pubkey,
+    seeds: &[&[u8]],
+  ) -> Result<(), ContractError> {
+    if base.derive(seeds) != *self.address {
+      return Err(ContractError::InvalidInputAccounts);
+    }
+    Ok(())
+  }
+}
+
+impl Deref for AccountInfo<'_> {
+  type Target = AccountView;
+
+  fn deref(&self) -> &Self::Target {
+    self.view
+  }
+}
+
+impl AsRef<[u8]> for AccountInfo<'_> {
+  fn as_ref(&self) -> &[u8] {
+    self.address.as_ref()
+  }
+}
+
+/// A declared layout of input accounts expected by an instruction.
+///
+/// This trait is implemented by `#[derive(Accounts)]` on structs that
+/// list the expected accounts in order, each field may declare its
+/// constraints using the `#[account(...)]` attribute:
+///
+///   - `signer`: the account must sign the transaction.
+///   - `writable`: the contract must be allowed to modify the account.
+///   - `owned`: the account must be owned by the invoked contract.
+///   - `owner = <expr>`: the account must be owned by the given contract.
+///   - `derived = [<seeds>]`: the account address must be derived from
+///     the invoked contract address and the given seeds. Seeds may refer
+///     to accounts declared before this one.
+///
+/// Accounts are validated in order and the first violated
+/// constraint fails the invocation.
+pub trait Accounts<'a>: Sized {
+  /// Validates the input accounts of an invocation against the layout.
+  fn from_env(env: &'a Environment) -> Result<Self, ContractError>;
+
+  /// Describes the expected accounts in order.
+  fn layout() -> Vec<AccountLayout>;
+}
+
+/// Describes a single expected input account of an instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountLayout {
+  pub name: &'static str,
+  pub description: &'static str,
+  pub signer: bool,
+  pub writable: bool,
+  pub derived: bool,
+  pub owner: Option<&'static str>,
+}
+
+/// Renders the layout in the same notation used by builtin contracts
+/// docs, where `d` stands for a derived address, `rw` for writable and
+/// `s` for signer, for example: `[drw-] The mint address`.
+impl Display for AccountLayout {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "[{}{}{}] {}",
+      if self.derived { "d" } else { "-" },
+      if self.writable { "rw" } else { "--" },
+      if self.signer { "s" } else { "-" },
+      if self.description.is_empty() {
+        self.name
+      } else {
+        self.description
+      }
+    )?;
+    if let Some(owner) = self.owner {
+      write!(f, " (owned by {owner})")?;
+    }
+    Ok(())
+  }
+}
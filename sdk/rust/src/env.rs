@@ -1,20 +1,12 @@
-use {
-  crate::pubkey::Pubkey,
-  borsh::{BorshDeserialize, BorshSerialize},
-};
+use crate::pubkey::Pubkey;
 
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
-pub struct AccountView {
-  pub signer: bool,
-  pub writable: bool,
-  pub executable: bool,
-  pub owner: Option<Pubkey>,
-  pub data: Option<Vec<u8>>,
-}
+/// A view of an input account as seen by the contract.
+pub type AccountView = rensa_abi::AccountView<Pubkey>;
 
-#[derive(Debug, BorshDeserialize)]
-pub struct Environment {
-  pub caller: Option<Pubkey>,
-  pub address: Pubkey,
-  pub accounts: Vec<(Pubkey, AccountView)>,
-}
+/// A reference to an input account passed along with
+/// a cross-contract invocation.
+pub type AccountRef = rensa_abi::AccountRef<Pubkey>;
+
+/// The input environment of a contract invocation containing
+/// all accounts data referenced by the transaction.
+pub type Environment = rensa_abi::Environment<Pubkey>;
@@ -0,0 +1,357 @@
+use {
+  crate::{accounts::AccountLayout, Pubkey, Topic, ABI_VERSION},
+  rensa_abi::{AccountDef, EventDef, InstructionDef, Interface, Type, TypeDef},
+  sha3::{Digest, Sha3_256},
+  std::collections::{BTreeMap, HashMap},
+};
+
+/// Computes the topic that identifies events with the given name.
+///
+/// This is the same hashing that is used by the VM for builtin
+/// contracts events, so all events are identified consistently.
+pub fn topic(name: &str) -> Topic {
+  let mut hasher = Sha3_256::default();
+  hasher.update(name.as_bytes());
+  hasher.finalize().into()
+}
+
+/// Types that can describe their borsh encoding in a contract interface.
+///
+/// This trait is implemented for primitive types and standard containers,
+/// user-defined structs and enums implement it using `#[derive(Describe)]`.
+pub trait Describe {
+  /// The type of this value as it appears in fields.
+  fn ty() -> Type;
+
+  /// Appends definitions of this type and all user-defined
+  /// types it refers to, unless they are already defined.
+  fn define(_types: &mut Vec<TypeDef>) {}
+}
+
+/// Implemented by `#[derive(Instruction)]` on the instructions enum
+/// of a contract, when all instruction fields implement [`Describe`].
+pub trait Instructions {
+  /// Describes all instructions in their encoding order.
+  fn describe(types: &mut Vec<TypeDef>) -> Vec<InstructionDef>;
+}
+
+impl From<AccountLayout> for AccountDef {
+  fn from(layout: AccountLayout) -> Self {
+    Self {
+      name: layout.name.to_owned(),
+      description: layout.description.to_owned(),
+      signer: layout.signer,
+      writable: layout.writable,
+      derived: layout.derived,
+      owner: layout.owner.map(ToOwned::to_owned),
+    }
+  }
+}
+
+/// Builds the interface description of a contract.
+///
+/// The description is generated natively from the contract types,
+/// for example in a test or a build step of the contract, and then
+/// uploaded in its borsh encoding alongside the contract bytecode
+/// through the `Describe` instruction of the Wasm builtin:
+///
+/// ```ignore
+/// let interface = InterfaceBuilder::new("counter")
+///   .instructions::<Counter>()
+///   .account::<CounterState>()
+///   .event::<Incremented>()
+///   .build();
+/// ```
+pub struct InterfaceBuilder {
+  interface: Interface,
+}
+
+impl InterfaceBuilder {
+  pub fn new(name: &str) -> Self {
+    Self {
+      interface: Interface {
+        name: name.to_owned(),
+        abi_version: ABI_VERSION,
+        instructions: vec![],
+        accounts: vec![],
+        events: vec![],
+        types: vec![],
+      },
+    }
+  }
+
+  /// Describes the instructions accepted by the contract.
+  pub fn instructions<I: Instructions>(mut self) -> Self {
+    self.interface.instructions = I::describe(&mut self.interface.types);
+    self
+  }
+
+  /// Adds a type of data stored in accounts owned by the contract.
+  pub fn account<T: Describe>(mut self) -> Self {
+    T::define(&mut self.interface.types);
+    self.interface.accounts.push(T::ty());
+    self
+  }
+
+  /// Adds an event emitted by the contract.
+  ///
+  /// The event is identified by the topic of its type name.
+  pub fn event<T: Describe>(mut self) -> Self {
+    T::define(&mut self.interface.types);
+    let data = T::ty();
+    let name = match data {
+      Type::Defined(ref name) => name.clone(),
+      ref other => format!("{other:?}"),
+    };
+    self.interface.events.push(EventDef {
+      topic: topic(&name),
+      name,
+      data,
+    });
+    self
+  }
+
+  pub fn build(self) -> Interface {
+    self.interface
+  }
+}
+
+macro_rules! describe_primitive {
+  ($($t:ty => $v:ident),*) => {
+    $(impl Describe for $t {
+      fn ty() -> Type {
+        Type::$v
+      }
+    })*
+  };
+}
+
+describe_primitive! {
+  () => Unit,
+  bool => Bool,
+  u8 => U8,
+  u16 => U16,
+  u32 => U32,
+  u64 => U64,
+  u128 => U128,
+  i8 => I8,
+  i16 => I16,
+  i32 => I32,
+  i64 => I64,
+  i128 => I128,
+  f32 => F32,
+  f64 => F64,
+  String => String,
+  Pubkey => Pubkey
+}
+
+impl<T: Describe> Describe for Option<T> {
+  fn ty() -> Type {
+    Type::Option(Box::new(T::ty()))
+  }
+
+  fn define(types: &mut Vec<TypeDef>) {
+    T::define(types)
+  }
+}
+
+impl<T: Describe> Describe for Vec<T> {
+  fn ty() -> Type {
+    Type::Vec(Box::new(T::ty()))
+  }
+
+  fn define(types: &mut Vec<TypeDef>) {
+    T::define(types)
+  }
+}
+
+impl<T: Describe> Describe for Box<T> {
+  fn ty() -> Type {
+    T::ty()
+  }
+
+  fn define(types: &mut Vec<TypeDef>) {
+    T::define(types)
+  }
+}
+
+impl<T: Describe, const N: usize> Describe for [T; N] {
+  fn ty() -> Type {
+    Type::Array(Box::new(T::ty()), N as u32)
+  }
+
+  fn define(types: &mut Vec<TypeDef>) {
+    T::define(types)
+  }
+}
+
+impl<K: Describe, V: Describe> Describe for BTreeMap<K, V> {
+  fn ty() -> Type {
+    Type::Map(Box::new(K::ty()), Box::new(V::ty()))
+  }
+
+  fn define(types: &mut Vec<TypeDef>) {
+    K::define(types);
+    V::define(types);
+  }
+}
+
+impl<K: Describe, V: Describe> Describe for HashMap<K, V> {
+  fn ty() -> Type {
+    Type::Map(Box::new(K::ty()), Box::new(V::ty()))
+  }
+
+  fn define(types: &mut Vec<TypeDef>) {
+    K::define(types);
+    V::define(types);
+  }
+}
+
+macro_rules! describe_tuple {
+  ($($t:ident),*) => {
+    impl<$($t: Describe),*> Describe for ($($t,)*) {
+      fn ty() -> Type {
+        Type::Tuple(vec![$($t::ty()),*])
+      }
+
+      fn define(types: &mut Vec<TypeDef>) {
+        $($t::define(types);)*
+      }
+    }
+  };
+}
+
+describe_tuple!(A, B);
+describe_tuple!(A, B, C);
+describe_tuple!(A, B, C, D);
+
+#[cfg(test)]
+mod test {
+  use {
+    super::*,
+    crate::{
+      AccountInfo,
+      Accounts,
+      Describe,
+      Environment,
+      Instruction,
+      Output,
+    },
+    borsh::{BorshDeserialize, BorshSerialize},
+    rensa_abi::{ContractError, FieldDef, TypeKind, VariantDef},
+  };
+
+  #[derive(BorshSerialize, BorshDeserialize, Describe)]
+  struct Mint {
+    authority: Pubkey,
+    supply: u64,
+    meta: Option<Metadata>,
+  }
+
+  #[derive(BorshSerialize, BorshDeserialize, Describe)]
+  enum Metadata {
+    Name(String),
+    Uri { uri: String, checksum: [u8; 32] },
+  }
+
+  #[allow(dead_code)]
+  #[derive(Accounts)]
+  struct CreateAccounts<'a> {
+    #[account(signer)]
+    authority: AccountInfo<'a>,
+
+    /// The new mint
+    #[account(writable, derived = [b"mint", authority])]
+    mint: AccountInfo<'a>,
+  }
+
+  #[derive(BorshSerialize, BorshDeserialize, Instruction)]
+  enum Token {
+    #[instruction(accounts = CreateAccounts, handler = create)]
+    Create { supply: u64, meta: Option<Metadata> },
+    #[instruction(handler = burn)]
+    Burn(u64),
+  }
+
+  fn create(
+    _: &Environment,
+    _: CreateAccounts,
+    _: u64,
+    _: Option<Metadata>,
+  ) -> Result<Vec<Output>, ContractError> {
+    Ok(vec![])
+  }
+
+  fn burn(_: &Environment, _: u64) -> Result<Vec<Output>, ContractError> {
+    Ok(vec![])
+  }
+
+  #[test]
+  fn interface_description_test() {
+    let interface = InterfaceBuilder::new("token")
+      .instructions::<Token>()
+      .account::<Mint>()
+      .event::<Mint>()
+      .build();
+
+    assert_eq!(interface.abi_version, ABI_VERSION);
+    assert_eq!(interface.instructions.len(), 2);
+    assert_eq!(interface.instructions[0].name, "Create");
+    assert_eq!(interface.instructions[0].fields, vec![
+      FieldDef {
+        name: "supply".to_owned(),
+        ty: Type::U64,
+      },
+      FieldDef {
+        name: "meta".to_owned(),
+        ty: Type::Option(Box::new(Type::Defined("Metadata".to_owned()))),
+      }
+    ]);
+    assert_eq!(interface.instructions[0].accounts[1], AccountDef {
+      name: "mint".to_owned(),
+      description: "The new mint".to_owned(),
+      signer: false,
+      writable: true,
+      derived: true,
+      owner: None,
+    });
+    assert_eq!(interface.instructions[1].fields, vec![FieldDef {
+      name: "0".to_owned(),
+      ty: Type::U64,
+    }]);
+    assert!(interface.instructions[1].accounts.is_empty());
+
+    // every user-defined type is defined exactly once
+    let names: Vec<_> = interface.types.iter().map(|t| &t.name).collect();
+    assert_eq!(names, vec!["Metadata", "Mint"]);
+    assert_eq!(
+      interface.types[0].kind,
+      TypeKind::Enum(vec![
+        VariantDef {
+          name: "Name".to_owned(),
+          fields: vec![FieldDef {
+            name: "0".to_owned(),
+            ty: Type::String,
+          }],
+        },
+        VariantDef {
+          name: "Uri".to_owned(),
+          fields: vec![
+            FieldDef {
+              name: "uri".to_owned(),
+              ty: Type::String,
+            },
+            FieldDef {
+              name: "checksum".to_owned(),
+              ty: Type::Array(Box::new(Type::U8), 32),
+            },
+          ],
+        },
+      ])
+    );
+
+    assert_eq!(interface.accounts, vec![Type::Defined("Mint".to_owned())]);
+    assert_eq!(interface.events[0].name, "Mint");
+    assert_eq!(interface.events[0].topic, topic("Mint"));
+  }
+}
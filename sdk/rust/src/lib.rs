@@ -1,16 +1,48 @@
 /// Entrypoint annotiation
 pub use rensa_sdk_macros::main;
 
+/// Instructions, accounts layout and interface declarations
+pub use rensa_sdk_macros::{Accounts, Describe, Instruction};
+
+/// Serialization format of contract inputs and outputs
+pub use borsh;
+
+// lets the derive macros refer to `rensa_sdk` paths within this crate
+extern crate self as rensa_sdk;
+
+#[cfg(target_arch = "wasm32")]
 mod abi;
+mod accounts;
 mod env;
-mod error;
+mod interface;
+#[cfg(not(target_arch = "wasm32"))]
+mod native;
 mod output;
 mod pubkey;
 
+#[cfg(target_arch = "wasm32")]
+pub use abi::{abort, log};
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::{abort, log};
 pub use {
-  abi::{abort, log},
-  env::{AccountView, Environment},
-  error::{ContractError, SignatureError},
+  accounts::{AccountInfo, AccountLayout, Accounts},
+  env::{AccountRef, AccountView, Environment},
+  interface::{topic, Describe, InterfaceBuilder, Instructions},
   output::Output,
   pubkey::Pubkey,
+  rensa_abi::{
+    AccountDef,
+    ContractError,
+    EventDef,
+    FieldDef,
+    InstructionDef,
+    Interface,
+    SignatureError,
+    Topic,
+    Type,
+    TypeDef,
+    TypeKind,
+    VariantDef,
+    ABI_VERSION,
+  },
 };
@@ -0,0 +1,22 @@
+//! Host implementations of the VM-provided functions.
+//!
+//! When a contract is compiled for the host, for example when it is
+//! tested natively using the test harness, there is no VM to import
+//! those functions from.
+
+use crate::ContractError;
+
+/// Debug log a message during contract execution.
+/// When running natively messages are printed to stdout.
+pub fn log(msg: &str) {
+  println!("{msg}");
+}
+
+/// Interrupt the execution of a smart contract with an error value.
+///
+/// When running natively the error value is carried by a panic, so
+/// the host can recover it and treat it like an error returned from
+/// the contract.
+pub fn abort(error: ContractError) -> ! {
+  std::panic::panic_any(error)
+}
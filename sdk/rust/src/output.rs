@@ -1,18 +1,5 @@
-use {
-  crate::{env::AccountView, pubkey::Pubkey},
-  borsh::BorshSerialize,
-};
+use crate::pubkey::Pubkey;
 
-#[derive(Debug, BorshSerialize)]
-pub enum Output {
-  LogEntry(String, String),
-  CreateOwnedAccount(Pubkey, Option<Vec<u8>>),
-  WriteAccountData(Pubkey, Option<Vec<u8>>),
-  DeleteOwnedAccount(Pubkey),
-  ContractInvoke {
-    contract: Pubkey,
-    accounts: Vec<(Pubkey, AccountView)>,
-    params: Vec<u8>,
-  },
-  CreateExecutableAccount(Pubkey, Vec<u8>),
-}
+/// Outputs returned by contracts, either state changes, logs, events
+/// or requests for cross-contract invocations.
+pub type Output = rensa_abi::Output<Pubkey>;
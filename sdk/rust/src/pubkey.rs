@@ -18,12 +18,57 @@ use {
 )]
 pub struct Pubkey([u8; 32]);
 
+impl Pubkey {
+  /// Given a list of seeds this method will generate a new
+  /// derived pubkey that is not on the Ed25519 curve
+  /// (no private key exists for the resulting pubkey).
+  ///
+  /// This is the same derivation that is used by the VM, so
+  /// contracts can verify that the addresses they are given
+  /// were derived from the expected seeds.
+  pub fn derive(&self, seeds: &[&[u8]]) -> Self {
+    self.find_derived(seeds).0
+  }
+
+  /// Same as [`Pubkey::derive`] but also returns the bump value
+  /// that was found to produce an address off the Ed25519 curve.
+  pub fn find_derived(&self, seeds: &[&[u8]]) -> (Self, u64) {
+    let (address, bump) = rensa_abi::derive_address(&self.0, seeds);
+    (Self(address), bump)
+  }
+
+  /// Derives an address using a previously found bump value.
+  ///
+  /// This is cheaper than [`Pubkey::derive`] for contracts that
+  /// store the bump of their derived accounts, as it skips the
+  /// search for an off-curve address. Returns [`None`] if the
+  /// resulting address lies on the Ed25519 curve.
+  pub fn derive_with_bump(&self, seeds: &[&[u8]], bump: u64) -> Option<Self> {
+    rensa_abi::derive_address_with_bump(&self.0, seeds, bump).map(Self)
+  }
+
+  /// Checks if the given pubkey lies on the Ed25519 elliptic curve.
+  ///
+  /// When true, then it means that there exists a private key that
+  /// make up together a valid Ed25519 keypair. Otherwise, when false
+  /// it means that there is no corresponding valid private key.
+  pub fn has_private_key(&self) -> bool {
+    rensa_abi::is_on_curve(&self.0)
+  }
+}
+
 impl AsRef<[u8]> for Pubkey {
   fn as_ref(&self) -> &[u8] {
     &self.0
   }
 }
 
+impl From<[u8; 32]> for Pubkey {
+  fn from(bytes: [u8; 32]) -> Self {
+    Self(bytes)
+  }
+}
+
 impl Deref for Pubkey {
   type Target = [u8];
 
@@ -67,3 +112,37 @@ impl TryFrom<&str> for Pubkey {
     FromStr::from_str(value)
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::Pubkey;
+
+  /// The same vectors are verified by the VM, so contracts and
+  /// the VM always agree on derived addresses.
+  #[test]
+  fn pubkey_derive_vectors() {
+    let vectors: serde_json::Value =
+      serde_json::from_str(include_str!("../../../abi/vectors/derive.json"))
+        .unwrap();
+
+    for vector in vectors.as_array().unwrap() {
+      let pubkey = |name: &str| -> Pubkey {
+        vector[name].as_str().unwrap().parse().unwrap()
+      };
+      let seeds: Vec<Vec<u8>> = vector["seeds"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| bs58::decode(s.as_str().unwrap()).into_vec().unwrap())
+        .collect();
+      let seeds: Vec<&[u8]> = seeds.iter().map(|s| s.as_slice()).collect();
+      let bump = vector["bump"].as_u64().unwrap();
+      let (base, derived) = (pubkey("base"), pubkey("derived"));
+
+      assert_eq!(base.derive(&seeds), derived);
+      assert_eq!(base.find_derived(&seeds), (derived, bump));
+      assert_eq!(base.derive_with_bump(&seeds, bump), Some(derived));
+      assert!(!derived.has_private_key());
+    }
+  }
+}
@@ -0,0 +1,679 @@
+//! Contracts test harness
+//!
+//! Runs smart contracts against an in-memory state using the same
+//! virtual machine that validators use, so contracts can be tested
+//! with ordinary `cargo test` without running a validator.
+//!
+//! Contracts are deployed either natively, by passing the function
+//! annotated with `#[main]` to [`TestRuntime::deploy`], or through
+//! the WASM runtime using their compiled bytecode and
+//! [`TestRuntime::deploy_wasm`]. In both cases the contract observes
+//! the same environment and its outputs go through the same checks,
+//! including signers, writability, account ownership and genesis
+//! limits, and may invoke builtin contracts like Currency or Sha3.
+//!
+//! This crate depends on the whole `rensa` node crate through a path
+//! dependency, including its networking, storage and WASM runtime. It
+//! is not published on its own and builds only from a checkout of this
+//! repository, which takes as long as building a validator. Contracts
+//! add it to their dev-dependencies only:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! rensa-sdk-testing = { path = "<checkout>/sdk/rust/testing" }
+//! ```
+
+use {
+  borsh::{BorshDeserialize, BorshSerialize},
+  chrono::Utc,
+  multihash::Multihash,
+  rensa::{
+    consensus::{validator::Validator, Block, Genesis, Limits},
+    vm::{
+      self,
+      BlockOutput,
+      Executable,
+      Machine,
+      MachineError,
+      State,
+      StateError,
+    },
+  },
+  std::{
+    collections::{BTreeMap, HashMap},
+    marker::PhantomData,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    time::Duration,
+  },
+};
+
+pub use rensa::{
+  primitives::{Account, Keypair, Pubkey},
+  vm::{
+    AccountRef,
+    ContractError,
+    Event,
+    Invocation,
+    Receipt,
+    StateDiff,
+    Transaction,
+    WASM_VM_BUILTIN_ADDR,
+  },
+};
+
+lazy_static::lazy_static! {
+  /// Address of the builtin Currency contract.
+  pub static ref CURRENCY_BUILTIN_ADDR: Pubkey =
+    "Currency1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
+      .parse()
+      .unwrap();
+
+  /// Address of the builtin Sha3 contract.
+  pub static ref SHA3_BUILTIN_ADDR: Pubkey =
+    "Sha3xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
+      .parse()
+      .unwrap();
+}
+
+/// The signature of a contract function annotated with `#[main]`.
+pub type ContractEntrypoint =
+  fn(
+    &rensa_sdk::Environment,
+    &[u8],
+  ) -> Result<Vec<rensa_sdk::Output>, ContractError>;
+
+/// Converts a VM address to the address type used by contracts.
+pub fn sdk_pubkey(pubkey: &Pubkey) -> rensa_sdk::Pubkey {
+  rensa_sdk::Pubkey::from(<[u8; 32]>::try_from(pubkey.as_ref()).unwrap())
+}
+
+/// Converts a contract address to the address type used by the VM.
+pub fn vm_pubkey(pubkey: &rensa_sdk::Pubkey) -> Pubkey {
+  Pubkey::from(<[u8; 32]>::try_from(pubkey.as_ref()).unwrap())
+}
+
+/// Chain state that lives only in memory for the duration of a test.
+#[derive(Debug, Default)]
+pub struct InMemState {
+  db: HashMap<Pubkey, Account>,
+}
+
+impl InMemState {
+  /// Applies the state changes produced by a transaction.
+  pub fn apply(&mut self, diff: &StateDiff) {
+    for (addr, account) in diff.iter() {
+      match account {
+        Some(account) => self.db.insert(*addr, account.clone()),
+        None => self.db.remove(addr),
+      };
+    }
+  }
+}
+
+impl State for InMemState {
+  fn get(&self, address: &Pubkey) -> Option<Account> {
+    self.db.get(address).cloned()
+  }
+
+  fn set(
+    &mut self,
+    address: Pubkey,
+    account: Account,
+  ) -> Result<Option<Account>, StateError> {
+    Ok(self.db.insert(address, account))
+  }
+
+  fn remove(&mut self, address: Pubkey) -> Result<(), StateError> {
+    self.db.remove(&address);
+    Ok(())
+  }
+
+  fn hash(&self) -> Multihash {
+    unimplemented!() // state hashes are not used by tests
+  }
+}
+
+/// The result of executing a single transaction.
+pub struct Outcome {
+  output: BlockOutput,
+}
+
+impl Outcome {
+  /// True if the transaction ran to completion.
+  pub fn is_ok(&self) -> bool {
+    self.output.errors.is_empty()
+  }
+
+  /// The error that failed the transaction, if any.
+  pub fn error(&self) -> Option<&ContractError> {
+    self.output.errors.values().next()
+  }
+
+  /// Log entries emitted by all contracts invoked by the transaction.
+  pub fn logs(&self) -> &[(String, String)] {
+    self
+      .output
+      .logs
+      .values()
+      .next()
+      .map(|l| l.as_slice())
+      .unwrap_or_default()
+  }
+
+  /// Events emitted by all contracts invoked by the transaction.
+  pub fn events(&self) -> &[Event] {
+    self
+      .output
+      .events
+      .values()
+      .next()
+      .map(|e| e.as_slice())
+      .unwrap_or_default()
+  }
+
+  /// Accounts changed by the transaction and all contract invocations.
+  pub fn receipt(&self) -> &Receipt {
+    self.output.receipts.values().next().unwrap()
+  }
+
+  /// Changes to the state made by the transaction.
+  pub fn state(&self) -> &StateDiff {
+    &self.output.state
+  }
+}
+
+/// A minimal chain that executes every transaction in its own block.
+pub struct TestRuntime {
+  vm: Machine,
+  state: InMemState,
+  payer: Keypair,
+  height: u64,
+}
+
+impl Default for TestRuntime {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl TestRuntime {
+  /// Creates a test chain with the Currency, Sha3 and WasmVM
+  /// builtins enabled and the default validator limits.
+  pub fn new() -> Self {
+    let payer = Keypair::unique();
+    let genesis = Genesis::<Vec<Transaction>> {
+      chain_id: "test".to_owned(),
+      epoch_blocks: 32,
+      genesis_time: Utc::now(),
+      slot_interval: Duration::from_secs(2),
+      state: BTreeMap::new(),
+      contracts: BTreeMap::new(),
+      upgrades: vec![],
+      builtins: vec![
+        *CURRENCY_BUILTIN_ADDR,
+        *SHA3_BUILTIN_ADDR,
+        *WASM_VM_BUILTIN_ADDR,
+      ],
+      limits: Limits {
+        max_block_size: 100_000,
+        max_justification_age: 100,
+        minimum_stake: 100,
+        max_log_size: 512,
+        max_logs_count: 32,
+        max_account_size: 65536,
+        max_input_accounts: 32,
+        max_block_transactions: 2000,
+        max_contract_size: 614400,
+        max_transaction_params_size: 2048,
+        max_transaction_instructions: 16,
+        storage_deposit_per_account: 0,
+        storage_deposit_per_byte: 0,
+      },
+      system_coin: "RensaToken1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
+        .parse()
+        .unwrap(),
+      validators: vec![Validator {
+        pubkey: payer.public(),
+        stake: 200000,
+      }],
+      _marker: PhantomData,
+    };
+
+    Self::with_genesis(&genesis, payer).unwrap()
+  }
+
+  /// Creates a test chain from a custom genesis, the payer
+  /// pays for all transactions created by this runtime.
+  pub fn with_genesis(
+    genesis: &Genesis<Vec<Transaction>>,
+    payer: Keypair,
+  ) -> Result<Self, MachineError> {
+    let mut state = InMemState::default();
+    for (addr, account) in &genesis.state {
+      state.set(*addr, account.clone()).unwrap();
+    }
+
+    Ok(Self {
+      vm: Machine::new(genesis)?,
+      state,
+      payer,
+      height: genesis.height(),
+    })
+  }
+
+  /// The genesis hash of the test chain, transactions
+  /// must be signed for this chain.
+  pub fn chain(&self) -> &Multihash {
+    self.vm.chain()
+  }
+
+  /// The account that pays for transactions created by [`invoke`].
+  pub fn payer(&self) -> &Keypair {
+    &self.payer
+  }
+
+  /// Deploys a contract that runs natively in the test process.
+  ///
+  /// The contract observes the exact same bytes it would observe
+  /// when running in the WASM runtime.
+  pub fn deploy(&mut self, address: Pubkey, entrypoint: ContractEntrypoint) {
+    self.add_account(address, Account {
+      executable: true,
+      owner: Some(*WASM_VM_BUILTIN_ADDR),
+      ..Account::default()
+    });
+    self.vm.host_contract(address, host(entrypoint));
+  }
+
+  /// Deploys a contract from its compiled WASM bytecode.
+  pub fn deploy_wasm(&mut self, address: Pubkey, bytecode: Vec<u8>) {
+    self.add_account(address, Account {
+      executable: true,
+      owner: Some(*WASM_VM_BUILTIN_ADDR),
+      data: Some(bytecode),
+      ..Account::default()
+    });
+  }
+
+  /// Creates or overwrites an account, bypassing all contracts.
+  pub fn add_account(&mut self, address: Pubkey, account: Account) {
+    self.state.set(address, account).unwrap();
+  }
+
+  /// Reads the current contents of an account.
+  pub fn account(&self, address: &Pubkey) -> Option<Account> {
+    self.state.get(address)
+  }
+
+  /// Invokes a contract in a transaction paid by the runtime payer.
+  ///
+  /// Signers are in addition to the payer, whose signature is
+  /// always included.
+  pub fn invoke(
+    &mut self,
+    contract: Pubkey,
+    accounts: Vec<AccountRef>,
+    params: Vec<u8>,
+    signers: &[&Keypair],
+  ) -> Result<Outcome, MachineError> {
+    let nonce = self
+      .state
+      .get(&self.payer.public())
+      .map(|a| a.nonce)
+      .unwrap_or(0);
+
+    let transaction = Transaction::new(
+      contract,
+      self.chain(),
+      nonce,
+      &self.payer,
+      accounts,
+      params,
+      signers,
+    )
+    .expect("too many transaction accounts");
+
+    self.execute(transaction)
+  }
+
+  /// Executes a transaction in a new block and applies its state changes.
+  pub fn execute(
+    &mut self,
+    transaction: Transaction,
+  ) -> Result<Outcome, MachineError> {
+    let height = self.height + 1;
+    let output = vec![transaction].execute(&self.vm, &self.state, height)?;
+
+    self.state.apply(&output.state);
+    self.height = height;
+
+    Ok(Outcome { output })
+  }
+}
+
+/// Adapts a contract function to the VM.
+fn host(entrypoint: ContractEntrypoint) -> vm::HostContractEntrypoint {
+  Arc::new(move |env: &vm::Environment, params: &[u8]| {
+    invoke_native(entrypoint, env, params)
+  })
+}
+
+/// Invokes a contract function passing its inputs and outputs through
+/// their binary ABI encoding, exactly like the WASM runtime does.
+fn invoke_native(
+  entrypoint: ContractEntrypoint,
+  env: &vm::Environment,
+  params: &[u8],
+) -> Result<Vec<vm::Output>, ContractError> {
+  let env = rensa_sdk::Environment::try_from_slice(&env.try_to_vec()?)?;
+
+  // contracts abort by panicking with the error value when running
+  // natively, any other panic is a runtime failure, like a trap in
+  // the WASM runtime.
+  let outputs =
+    panic::catch_unwind(AssertUnwindSafe(|| entrypoint(&env, params)))
+      .map_err(|payload| match payload.downcast::<ContractError>() {
+        Ok(error) => *error,
+        Err(_) => ContractError::Runtime("contract panicked".into()),
+      })??;
+
+  Ok(Vec::<vm::Output>::try_from_slice(&outputs.try_to_vec()?)?)
+}
+
+#[cfg(test)]
+mod test {
+  use {
+    super::*,
+    rensa_sdk::{main, Environment, Output, SignatureError},
+  };
+
+  #[derive(BorshSerialize, BorshDeserialize)]
+  enum Instruction {
+    /// Accounts expected by this instruction:
+    ///   0. [-rw-] The storage account
+    ///   1. [---s] The authority
+    Store(Vec<u8>),
+
+    /// Accounts expected by this instruction:
+    ///   0. [-rw-] An account owned by Sha3
+    Hash,
+
+    Fail(u32),
+  }
+
+  #[main]
+  fn contract(
+    env: &Environment,
+    params: &[u8],
+  ) -> Result<Vec<Output>, ContractError> {
+    match Instruction::try_from_slice(params)? {
+      Instruction::Store(data) => {
+        let (storage, _) = &env.accounts[0];
+        let (_, authority) = &env.accounts[1];
+        if !authority.signer {
+          return Err(ContractError::SignatureError(
+            SignatureError::MissingSigners,
+          ));
+        }
+        Ok(vec![
+          Output::LogEntry("action".into(), "store".into()),
+          Output::CreateOwnedAccount(*storage, Some(data)),
+        ])
+      }
+      Instruction::Hash => {
+        let (account, _) = &env.accounts[0];
+        Ok(vec![Output::ContractInvoke {
+          contract: sdk_pubkey(&SHA3_BUILTIN_ADDR),
+          accounts: vec![rensa_sdk::AccountRef {
+            address: *account,
+            writable: true,
+            signer: false,
+          }],
+          params: vec![],
+        }])
+      }
+      Instruction::Fail(code) => rensa_sdk::abort(ContractError::custom(code)),
+    }
+  }
+
+  fn deployed() -> (TestRuntime, Pubkey) {
+    let mut runtime = TestRuntime::new();
+    let address = Keypair::unique().public();
+    runtime.deploy(address, contract);
+    (runtime, address)
+  }
+
+  #[test]
+  fn native_contract_outputs_test() {
+    let (mut runtime, address) = deployed();
+    let storage = address.derive(&[b"storage"]);
+    let authority = Keypair::unique();
+
+    let outcome = runtime
+      .invoke(
+        address,
+        vec![
+          AccountRef::writable(storage, false).unwrap(),
+          AccountRef::readonly(authority.public(), true).unwrap(),
+        ],
+        Instruction::Store(vec![1, 2, 3]).try_to_vec().unwrap(),
+        &[&authority],
+      )
+      .unwrap();
+
+    assert!(outcome.is_ok());
+    assert_eq!(outcome.logs(), &[("action".to_owned(), "store".to_owned())]);
+    assert_eq!(outcome.receipt().created, vec![storage]);
+
+    let account = runtime.account(&storage).unwrap();
+    assert_eq!(account.owner, Some(address));
+    assert_eq!(account.data, Some(vec![1, 2, 3]));
+  }
+
+  #[test]
+  fn signer_and_writable_checks_test() {
+    let (mut runtime, address) = deployed();
+    let storage = address.derive(&[b"storage"]);
+    let authority = Keypair::unique();
+    let params = Instruction::Store(vec![1]).try_to_vec().unwrap();
+
+    // the authority did not sign the transaction
+    let outcome = runtime
+      .invoke(
+        address,
+        vec![
+          AccountRef::writable(storage, false).unwrap(),
+          AccountRef::readonly(authority.public(), false).unwrap(),
+        ],
+        params.clone(),
+        &[],
+      )
+      .unwrap();
+
+    assert!(matches!(
+      outcome.error(),
+      Some(ContractError::SignatureError(_))
+    ));
+
+    // the storage account is not writable
+    let outcome = runtime
+      .invoke(
+        address,
+        vec![
+          AccountRef::readonly(storage, false).unwrap(),
+          AccountRef::readonly(authority.public(), true).unwrap(),
+        ],
+        params,
+        &[&authority],
+      )
+      .unwrap();
+
+    assert!(matches!(
+      outcome.error(),
+      Some(ContractError::AccountNotWritable)
+    ));
+    assert!(runtime.account(&storage).is_none());
+  }
+
+  #[test]
+  fn builtin_invocation_test() {
+    let (mut runtime, address) = deployed();
+    let hashed = SHA3_BUILTIN_ADDR.derive(&[b"hashed"]);
+    runtime.add_account(hashed, Account {
+      owner: Some(*SHA3_BUILTIN_ADDR),
+      data: Some(b"hello".to_vec()),
+      ..Account::default()
+    });
+
+    let outcome = runtime
+      .invoke(
+        address,
+        vec![AccountRef::writable(hashed, false).unwrap()],
+        Instruction::Hash.try_to_vec().unwrap(),
+        &[],
+      )
+      .unwrap();
+
+    assert!(outcome.is_ok());
+
+    let invocations = &outcome.receipt().invocations;
+    assert_eq!(invocations.len(), 2);
+    assert_eq!(invocations[1].contract, *SHA3_BUILTIN_ADDR);
+    assert_eq!(invocations[1].caller, Some(address));
+
+    // sha3("hello")
+    assert_eq!(
+      runtime.account(&hashed).unwrap().data.unwrap()[..4],
+      [0x33, 0x38, 0xbe, 0x69]
+    );
+  }
+
+  #[test]
+  fn aborted_contract_error_test() {
+    let (mut runtime, address) = deployed();
+
+    let params = Instruction::Fail(7).try_to_vec().unwrap();
+    let outcome = runtime.invoke(address, vec![], params, &[]).unwrap();
+
+    assert!(matches!(
+      outcome.error(),
+      Some(ContractError::Custom { code: 7, .. })
+    ));
+  }
+
+  mod declarative {
+    use {
+      super::*,
+      rensa_sdk::{AccountInfo, Accounts, Environment, Instruction, Output},
+    };
+
+    #[allow(dead_code)]
+    #[derive(Accounts)]
+    struct IncrementAccounts<'a> {
+      /// The counter owner
+      #[account(signer)]
+      authority: AccountInfo<'a>,
+
+      /// The counter of the authority
+      #[account(writable, derived = [b"counter", authority])]
+      counter: AccountInfo<'a>,
+    }
+
+    #[derive(BorshSerialize, BorshDeserialize, Instruction)]
+    enum Counter {
+      #[instruction(accounts = IncrementAccounts, handler = increment)]
+      Increment { by: u64 },
+    }
+
+    fn increment(
+      _env: &Environment,
+      accounts: IncrementAccounts,
+      by: u64,
+    ) -> Result<Vec<Output>, ContractError> {
+      let current = match accounts.counter.data {
+        Some(ref data) => u64::try_from_slice(data)?,
+        None => 0,
+      };
+      Ok(vec![Output::CreateOwnedAccount(
+        *accounts.counter.address,
+        Some((current + by).try_to_vec()?),
+      )])
+    }
+
+    #[rensa_sdk::main]
+    fn contract(
+      env: &Environment,
+      params: &[u8],
+    ) -> Result<Vec<Output>, ContractError> {
+      Counter::dispatch(env, params)
+    }
+
+    #[test]
+    fn accounts_layout_test() {
+      let layout: Vec<_> = IncrementAccounts::layout()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+      assert_eq!(layout, vec![
+        "[---s] The counter owner",
+        "[drw-] The counter of the authority",
+      ]);
+    }
+
+    #[test]
+    fn dispatch_and_validation_test() {
+      let mut runtime = TestRuntime::new();
+      let address = Keypair::unique().public();
+      runtime.deploy(address, contract);
+
+      let authority = Keypair::unique();
+      let counter = address.derive(&[b"counter", authority.public().as_ref()]);
+      let params = Counter::Increment { by: 3 }.try_to_vec().unwrap();
+
+      // counter address not derived from the authority
+      let outcome = runtime
+        .invoke(
+          address,
+          vec![
+            AccountRef::readonly(authority.public(), true).unwrap(),
+            AccountRef::writable(address.derive(&[b"other"]), false).unwrap(),
+          ],
+          params.clone(),
+          &[&authority],
+        )
+        .unwrap();
+      assert!(matches!(
+        outcome.error(),
+        Some(ContractError::InvalidInputAccounts)
+      ));
+
+      // valid invocation, twice
+      for expected in [3u64, 6] {
+        let outcome = runtime
+          .invoke(
+            address,
+            vec![
+              AccountRef::readonly(authority.public(), true).unwrap(),
+              AccountRef::writable(counter, false).unwrap(),
+            ],
+            params.clone(),
+            &[&authority],
+          )
+          .unwrap();
+        assert!(outcome.is_ok());
+        assert_eq!(
+          runtime.account(&counter).unwrap().data,
+          Some(expected.try_to_vec().unwrap())
+        );
+      }
+
+      // malformed parameters
+      let outcome = runtime.invoke(address, vec![], vec![9], &[]).unwrap();
+      assert!(matches!(
+        outcome.error(),
+        Some(ContractError::InvalidInputParameters)
+      ));
+    }
+  }
+}
@@ -1,15 +1,28 @@
 use {
   crate::{
-    consensus::Genesis,
+    consensus::{validator::Validator, Block, Genesis, Limits},
     dbsync::DatabaseSync,
-    primitives::Keypair,
-    vm::Transaction,
+    primitives::{Account, Keypair, Pubkey, ToBase58String},
+    vm::{
+      builtin::{
+        currency::{coin_address, CoinAccount, Mint, CURRENCY_BUILTIN_ADDR},
+        governance::{self, GOVERNANCE_STATE_ADDR},
+        BUILTIN_CONTRACTS,
+      },
+      Transaction,
+    },
   },
+  anyhow::{anyhow, bail},
+  borsh::BorshSerialize,
+  chrono::{DateTime, Utc},
   clap::Parser,
   libp2p::{multiaddr::Protocol, Multiaddr},
   std::{
+    collections::BTreeMap,
+    marker::PhantomData,
     net::{IpAddr, SocketAddr},
     path::PathBuf,
+    str::FromStr,
     time::Duration,
   },
 };
@@ -61,6 +74,18 @@ pub struct CliOpts {
   #[clap(long, help = "The number of N most recent block to store")]
   blocks_history: Option<u64>,
 
+  #[clap(
+    long,
+    help = "The number of finalized blocks between state snapshots"
+  )]
+  snapshot_interval: Option<u64>,
+
+  #[clap(
+    long,
+    help = "Start a new node from a recent state snapshot fetched from peers"
+  )]
+  snapshot_sync: bool,
+
   #[clap(
     long,
     help = "A connection string to a SQL database for uploading blockchain data"
@@ -122,16 +147,7 @@ impl CliOpts {
   /// Retreives the genesis block config from its JSON
   /// serialized form from the path provided by the user.
   pub fn genesis(&self) -> Result<Genesis<Vec<Transaction>>, std::io::Error> {
-    let json =
-      std::fs::read_to_string(&self.genesis).map_err(std::io::Error::from)?;
-    let mut genesis: Genesis<Vec<Transaction>> =
-      serde_json::from_str(&json).map_err(std::io::Error::from)?;
-
-    // we're sorting validators in the genesis because we want the same
-    // hash for two gensis files with the exact same list of parameters
-    // and validators but only differing in the order of their appearance.
-    genesis.validators.sort();
-    Ok(genesis)
+    Genesis::load(&self.genesis)
   }
 
   /// If an RPC port is provided provided, returns all socketaddrs on which
@@ -183,6 +199,29 @@ impl CliOpts {
     })
   }
 
+  /// Specifies how often, in finalized blocks, a snapshot of the state is
+  /// stored for new nodes to start from instead of replaying all blocks
+  /// since genesis.
+  ///
+  /// Nodes verify snapshots by comparing them across validators, so this
+  /// value should be the same on all validators of a chain. The default is
+  /// a snapshot every 30 minutes, which is half of the default blocks
+  /// history, so blocks finalized after the most recent snapshot are still
+  /// stored by peers when a new node catches up from it.
+  pub fn snapshot_interval(&self) -> u64 {
+    self.snapshot_interval.unwrap_or_else(|| {
+      let slot = self.genesis().unwrap().slot_interval.as_millis() as u64;
+      let interval = Duration::from_secs(30 * 60).as_millis() as u64; // 30m
+      interval / slot
+    })
+  }
+
+  /// Whether a node that has no finalized blocks yet should start from a
+  /// recent state snapshot fetched from peers instead of the genesis.
+  pub fn snapshot_sync(&self) -> bool {
+    self.snapshot_sync
+  }
+
   /// An optional field that specifies a connection to a SQL database for
   /// dbsync.
   ///
@@ -200,3 +239,253 @@ impl CliOpts {
     }
   }
 }
+
+/// Tools for generating and validating genesis files.
+///
+/// Invoked as `rensa genesis <command>` instead of running a validator.
+#[derive(Debug, Parser)]
+#[clap(name = "rensa genesis", version)]
+pub enum GenesisCommand {
+  /// Generates validator keys and writes a new genesis file.
+  Create(CreateGenesisOpts),
+
+  /// Checks that a genesis file describes a chain validators can run.
+  Validate {
+    #[clap(parse(from_os_str), help = "path to the chain genesis file")]
+    genesis: PathBuf,
+  },
+}
+
+impl GenesisCommand {
+  pub fn run(self) -> anyhow::Result<()> {
+    match self {
+      GenesisCommand::Create(opts) => {
+        let keypairs: Vec<_> =
+          (0..opts.validators).map(|_| Keypair::unique()).collect();
+        let pubkeys: Vec<_> = keypairs.iter().map(|k| k.public()).collect();
+
+        let genesis = opts.build(&pubkeys)?;
+        genesis.validate()?;
+        std::fs::write(&opts.output, serde_json::to_string_pretty(&genesis)?)?;
+
+        for keypair in keypairs {
+          println!("pubkey: {}", keypair.public());
+          println!(
+            "secret: {}",
+            bs58::encode(keypair.secret().as_bytes()).into_string()
+          );
+          println!();
+        }
+        println!("genesis: {}", opts.output.display());
+        println!("genesis hash: {}", genesis.hash()?.to_b58());
+      }
+      GenesisCommand::Validate { genesis: path } => {
+        let genesis = Genesis::<Vec<Transaction>>::load(&path)?;
+        genesis.validate()?;
+        println!("genesis: {} is valid", path.display());
+        println!("genesis hash: {}", genesis.hash()?.to_b58());
+      }
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug, Parser)]
+pub struct CreateGenesisOpts {
+  #[clap(long, help = "globally unique identifier of the chain")]
+  chain_id: String,
+
+  #[clap(
+    long,
+    help = "number of validator keypairs to generate",
+    default_value = "1"
+  )]
+  validators: usize,
+
+  #[clap(
+    long,
+    help = "stake of every generated validator",
+    default_value = "1000"
+  )]
+  stake: u64,
+
+  #[clap(
+    long,
+    help = "minimum stake of validators accepted by the consensus",
+    default_value = "1000"
+  )]
+  minimum_stake: u64,
+
+  #[clap(
+    long,
+    help = "initial system coin balance of a wallet as <pubkey>=<amount>"
+  )]
+  balance: Vec<Balance>,
+
+  #[clap(
+    long,
+    help = "address of the system coin mint",
+    default_value = "RensaToken1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
+  )]
+  system_coin: Pubkey,
+
+  #[clap(long, help = "account allowed to mint new system coins")]
+  mint_authority: Option<Pubkey>,
+
+  #[clap(long, help = "name of the system coin", default_value = "Rensa")]
+  coin_name: String,
+
+  #[clap(
+    long,
+    help = "ticker symbol of the system coin",
+    default_value = "RNS"
+  )]
+  coin_symbol: String,
+
+  #[clap(
+    long,
+    help = "decimal places of the system coin",
+    default_value = "9"
+  )]
+  decimals: u8,
+
+  #[clap(long, help = "number of blocks in an epoch", default_value = "32")]
+  epoch_blocks: u64,
+
+  #[clap(long, help = "slot length in milliseconds", default_value = "2000")]
+  slot_interval: u64,
+
+  #[clap(long, help = "start time of the chain, defaults to now")]
+  genesis_time: Option<DateTime<Utc>>,
+
+  #[clap(
+    long,
+    parse(from_os_str),
+    help = "path of the generated genesis file",
+    default_value = "genesis.json"
+  )]
+  output: PathBuf,
+}
+
+impl CreateGenesisOpts {
+  /// Builds a genesis with all builtins enabled, the given validators
+  /// and the system coin mint along with coin accounts of all initial
+  /// balances.
+  pub fn build(
+    &self,
+    validators: &[Pubkey],
+  ) -> anyhow::Result<Genesis<Vec<Transaction>>> {
+    let mut state = BTreeMap::new();
+    let mut supply = 0u64;
+    for Balance { wallet, amount } in &self.balance {
+      let address = coin_address(&self.system_coin, wallet);
+      if state.contains_key(&address) {
+        bail!("wallet {wallet} has more than one balance");
+      }
+
+      let coin = CoinAccount {
+        mint: self.system_coin,
+        owner: *wallet,
+        balance: *amount,
+        frozen: false,
+      };
+      state.insert(address, Account {
+        nonce: 0,
+        executable: false,
+        owner: Some(*CURRENCY_BUILTIN_ADDR),
+        data: Some(coin.try_to_vec()?),
+      });
+
+      supply = supply
+        .checked_add(*amount)
+        .ok_or_else(|| anyhow!("system coin supply overflows"))?;
+    }
+
+    let mint = Mint {
+      authority: self.mint_authority,
+      supply,
+      decimals: self.decimals,
+      name: Some(self.coin_name.clone()),
+      symbol: Some(self.coin_symbol.clone()),
+      freeze_authority: None,
+      nft: false,
+      frozen_accounts: 0,
+    };
+    state.insert(self.system_coin, Account {
+      nonce: 0,
+      executable: false,
+      owner: Some(*CURRENCY_BUILTIN_ADDR),
+      data: Some(mint.try_to_vec()?),
+    });
+
+    // all builtins are enabled, governance starts with an empty state
+    state.insert(*GOVERNANCE_STATE_ADDR, governance::genesis_account());
+
+    let mut builtins: Vec<_> = BUILTIN_CONTRACTS.keys().cloned().collect();
+    builtins.sort();
+
+    let mut validators: Vec<_> = validators
+      .iter()
+      .map(|pubkey| Validator {
+        pubkey: *pubkey,
+        stake: self.stake,
+      })
+      .collect();
+    validators.sort();
+
+    Ok(Genesis {
+      chain_id: self.chain_id.clone(),
+      genesis_time: self.genesis_time.unwrap_or_else(Utc::now),
+      slot_interval: Duration::from_millis(self.slot_interval),
+      epoch_blocks: self.epoch_blocks,
+      limits: Limits {
+        max_justification_age: 100,
+        minimum_stake: self.minimum_stake,
+        max_input_accounts: 32,
+        max_account_size: 65536,
+        max_log_size: 512,
+        max_logs_count: 32,
+        max_block_transactions: 2000,
+        max_contract_size: 614400,
+        max_block_size: 1_000_000,
+        max_transaction_params_size: 2048,
+        max_transaction_instructions: 16,
+        storage_deposit_per_account: 0,
+        storage_deposit_per_byte: 0,
+      },
+      builtins,
+      validators,
+      system_coin: self.system_coin,
+      state,
+      contracts: BTreeMap::new(),
+      upgrades: vec![],
+      _marker: PhantomData,
+    })
+  }
+}
+
+/// An initial system coin balance of a wallet.
+#[derive(Debug, Clone)]
+pub struct Balance {
+  pub wallet: Pubkey,
+  pub amount: u64,
+}
+
+impl FromStr for Balance {
+  type Err = String;
+
+  /// Parses balances written as <pubkey>=<amount>
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    let (wallet, amount) = value
+      .split_once('=')
+      .ok_or_else(|| format!("expected <pubkey>=<amount>, got {value}"))?;
+    Ok(Self {
+      wallet: wallet
+        .parse()
+        .map_err(|_| format!("invalid wallet pubkey {wallet}"))?,
+      amount: amount
+        .parse()
+        .map_err(|_| format!("invalid balance amount {amount}"))?,
+    })
+  }
+}
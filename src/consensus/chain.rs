@@ -34,13 +34,15 @@ use {
     block::{self, Block, BlockData},
     forktree::{TreeNode, VolatileBlock},
     orphans::Orphans,
-    validator::Validator,
+    validator::{Stakes, Validator},
     vote::Vote,
     Genesis,
+    Limits,
     Produced,
+    Protocol,
   },
   crate::{
-    primitives::{Pubkey, ToBase58String},
+    primitives::ToBase58String,
     vm::{
       self,
       Executed,
@@ -110,7 +112,7 @@ pub struct Chain<'g, D: BlockData, S: StateStore> {
 
   /// This is a dynamic collection of all known validators along
   /// with the amount of tokens they are staking (and their voting power).
-  stakes: HashMap<Pubkey, u64>,
+  stakes: Stakes,
 
   /// This is the last block that was finalized and we are
   /// guaranteed that it will never be reverted. The runtime
@@ -172,6 +174,10 @@ pub struct Chain<'g, D: BlockData, S: StateStore> {
   /// The virtual machine that executes transactions
   /// contained within a block
   virtual_machine: &'g vm::Machine,
+
+  /// The protocol rules in effect at the last finalized block,
+  /// including changes enacted by governance.
+  protocol: Protocol,
 }
 
 impl<'g, D: BlockData, S: StateStore> Chain<'g, D, S> {
@@ -181,6 +187,9 @@ impl<'g, D: BlockData, S: StateStore> Chain<'g, D, S> {
     finalized: Finalized<'g, D, S>,
   ) -> Self {
     let epoch_duration = genesis.slot_interval * genesis.epoch_blocks as u32;
+    let protocol = machine
+      .protocols()
+      .effective(finalized.state(), finalized.height());
     Self {
       genesis,
       finalized,
@@ -189,12 +198,9 @@ impl<'g, D: BlockData, S: StateStore> Chain<'g, D, S> {
       ownvotes: HashMap::new(),
       events: VecDeque::new(),
       finalized_history: HashMap::new(),
-      stakes: genesis
-        .validators
-        .iter()
-        .map(|v| (v.pubkey, v.stake))
-        .collect(),
+      stakes: Stakes::new(genesis),
       virtual_machine: machine,
+      protocol,
     }
   }
 
@@ -253,17 +259,13 @@ impl<'g, D: BlockData, S: StateStore> Chain<'g, D, S> {
       .genesis
       .validators
       .iter()
-      .filter(|v| v.stake < self.genesis.limits.minimum_stake)
+      .filter(|v| v.stake < self.finalized_limits().minimum_stake)
   }
 
   /// The sum of all staked tokens that are taking part in
   /// the consensus.
   pub fn total_stake(&self) -> u64 {
-    self
-      .stakes
-      .iter()
-      .filter(|(_, s)| **s >= self.genesis.limits.minimum_stake)
-      .fold(0, |a, (_, s)| a + s)
+    self.stakes.total(self.finalized_limits())
   }
 
   /// The minimum voted stake that constitutes a 2/3 majority
@@ -276,6 +278,22 @@ impl<'g, D: BlockData, S: StateStore> Chain<'g, D, S> {
   fn epoch(&self, block: &dyn Block<D>) -> u64 {
     block.height() / self.genesis.epoch_blocks
   }
+
+  /// Consensus limits are those in effect at the last finalized
+  /// block, while the validity of a block is governed by the
+  /// limits in effect at its own height.
+  pub fn finalized_limits(&self) -> &Limits {
+    &self.protocol.limits
+  }
+
+  /// Limits that apply to the next block on top of the current
+  /// head, transactions are admitted to the mempool under them.
+  pub fn next_limits(&self) -> Limits {
+    self.with_head(|state, head| {
+      let protocols = self.virtual_machine.protocols();
+      protocols.effective(state, head.height() + 1).limits
+    })
+  }
 }
 
 impl<'g, 'f, D: BlockData, S: StateStore> Chain<'g, D, S> {
@@ -306,7 +324,7 @@ impl<'g, 'f, D: BlockData, S: StateStore> Chain<'g, D, S> {
     &self.finalized.hash().unwrap() == hash
       || self.finalized_history.iter().any(|(_, e)| e.contains(hash))
       || (self.finalized_history.len()
-        < self.genesis.limits.max_justification_age as usize
+        < self.finalized_limits().max_justification_age as usize
         && hash == &self.genesis.hash().unwrap())
   }
 
@@ -315,8 +333,9 @@ impl<'g, 'f, D: BlockData, S: StateStore> Chain<'g, D, S> {
   /// The justification must be the last finalized block,
   /// and the target block must be one of its descendants.
   fn injest_vote(&mut self, vote: &Vote) {
-    if let Some(stake) = self.stakes.get(&vote.validator) {
-      if *stake < self.genesis.limits.minimum_stake {
+    if self.stakes.contains(&vote.validator) {
+      let stake = self.stakes.stake(&vote.validator, self.finalized_limits());
+      if stake == 0 {
         debug!(
           "Rejecting vote from {} because it has not enough stake",
           vote.validator
@@ -353,7 +372,7 @@ impl<'g, 'f, D: BlockData, S: StateStore> Chain<'g, D, S> {
             .collect();
 
           // apply votes to the target and all its ancestors
-          target.add_votes(*stake, vote.validator);
+          target.add_votes(stake, vote.validator);
 
           // find out which blocks got confirmed after counting the vote
           // and signal their confirmation by emitting an event
@@ -426,6 +445,12 @@ impl<'g, 'f, D: BlockData, S: StateStore> Chain<'g, D, S> {
 
       // this block is a root in the forktree so it operates
       // on the finalized state directly.
+      verify_producer(
+        &self.stakes,
+        self.virtual_machine,
+        self.finalized.state(),
+        &block,
+      )?;
       let block = VolatileBlock::new(Executed::new(
         self.finalized.state(),
         Arc::new(block),
@@ -449,11 +474,15 @@ impl<'g, 'f, D: BlockData, S: StateStore> Chain<'g, D, S> {
           // that the VM receives for executing this block is a union of
           // all parent blocks state and the finalized state with priority
           // given to most recent blocks.
-          parent.add_child(VolatileBlock::new(Executed::new(
-            &Overlayed::new(self.finalized.state(), &parent.state()),
+          let parent_state = parent.state();
+          let state = Overlayed::new(self.finalized.state(), &parent_state);
+          verify_producer(&self.stakes, self.virtual_machine, &state, &block)?;
+          let block = VolatileBlock::new(Executed::new(
+            &state,
             Arc::new(block),
             self.virtual_machine,
-          )?));
+          )?);
+          parent.add_child(block);
           emit_event(&parent.children.back().unwrap().value.block);
           return Ok(Ok(()));
         }
@@ -468,7 +497,7 @@ impl<'g, 'f, D: BlockData, S: StateStore> Chain<'g, D, S> {
   /// This method will validate signatures on the block and attempt
   /// to insert it into the volatile state of the chain.
   pub fn include(&mut self, block: block::Produced<D>) {
-    if !self.stakes.contains_key(&block.signature.0) {
+    if !self.stakes.contains(&block.signature.0) {
       warn!(
         "Rejecting block {block} from non-staking proposer {}",
         block.signature.0
@@ -476,16 +505,6 @@ impl<'g, 'f, D: BlockData, S: StateStore> Chain<'g, D, S> {
       return;
     }
 
-    if *self.stakes.get(&block.signature.0).unwrap()
-      < self.genesis.limits.minimum_stake
-    {
-      warn!(
-        "Rejecting block {block} from {} because it has not enough stake.",
-        block.signature.0
-      );
-      return;
-    }
-
     if block.hash().is_err() || block.parent().is_err() {
       warn!("rejecting block {block}. Unreadable hashes");
       return;
@@ -654,6 +673,10 @@ impl<'g, 'f, D: BlockData, S: StateStore> Chain<'g, D, S> {
     let newroot_hash = subtree.value.hash().unwrap();
     // apply root's state diff and set it as the new finalized block
     self.finalized.apply(subtree.value.block);
+    self.protocol = self
+      .virtual_machine
+      .protocols()
+      .effective(self.finalized.state(), self.finalized.height());
 
     // this is the list of trees in the forktree that didn't make
     // it and will be removed permanently from the consensus tree.
@@ -777,7 +800,7 @@ impl<'g, D: BlockData, S: StateStore> Chain<'g, D, S> {
     };
 
     // clear old epochs
-    let window = self.genesis.limits.max_justification_age;
+    let window = self.finalized_limits().max_justification_age;
     self
       .finalized_history
       .retain(|e, _| e >= &epoch.saturating_sub(window));
@@ -795,6 +818,51 @@ impl<'g, D: BlockData, S: StateStore> Chain<'g, D, S> {
     }
     None
   }
+
+  /// Returns a non-finalized block with the given hash along with
+  /// up to `count - 1` of its non-finalized ancestors, ordered from
+  /// the oldest ancestor to the block itself.
+  pub fn get_with_ancestors(
+    &self,
+    hash: &Multihash,
+    count: usize,
+  ) -> Vec<Produced<D>> {
+    let mut blocks = vec![];
+    let mut hash = *hash;
+    while blocks.len() < count {
+      match self.get(&hash) {
+        Some(block) => {
+          hash = block.parent;
+          blocks.push(block.underlying.as_ref().clone());
+        }
+        None => break,
+      }
+    }
+    blocks.reverse();
+    blocks
+  }
+
+  /// Checks if blocks were received that can't be included
+  /// until the block with the given hash arrives.
+  pub fn is_missing(&self, hash: &Multihash) -> bool {
+    self.orphans.is_missing(hash)
+  }
+}
+
+/// Checks that the producer of a block takes part in the consensus under
+/// the limits in effect at the height of the block on top of the state of
+/// its parent, the same limits the block is executed under.
+fn verify_producer<D: BlockData>(
+  stakes: &Stakes,
+  vm: &vm::Machine,
+  state: &dyn State,
+  block: &Produced<D>,
+) -> Result<(), MachineError> {
+  let limits = vm.protocols().effective(state, block.height).limits;
+  match stakes.stake(&block.signature.0, &limits) {
+    0 => Err(MachineError::InsufficientStake(block.signature.0)),
+    _ => Ok(()),
+  }
 }
 
 impl<D: BlockData, S: StateStore> Unpin for Chain<'_, D, S> {}
@@ -864,6 +932,8 @@ mod test {
       genesis_time: Utc::now(),
       slot_interval: Duration::from_secs(2),
       state: BTreeMap::new(),
+      contracts: BTreeMap::new(),
+      upgrades: vec![],
       builtins: vec![],
       limits: Limits {
         max_block_size: 100_000,
@@ -876,6 +946,9 @@ mod test {
         max_block_transactions: 2000,
         max_contract_size: 614400,
         max_transaction_params_size: 2048,
+        max_transaction_instructions: 16,
+        storage_deposit_per_account: 0,
+        storage_deposit_per_byte: 0,
       },
       system_coin: "RensaToken1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
         .parse()
@@ -898,7 +971,10 @@ mod test {
       // blocks have no txs, so the statehash won't change across
       // blocks, but it needs to be a valid hash otherwise the block
       // gets rejected and not appended to the chain.
-      (b.hash().unwrap(), *vec![].execute(&vm, s).unwrap().hash())
+      (
+        b.hash().unwrap(),
+        *vec![].execute(&vm, s, b.height() + 1).unwrap().hash(),
+      )
     });
 
     let block = block::Produced::new(
@@ -957,6 +1033,8 @@ mod test {
       genesis_time: Utc::now(),
       slot_interval: Duration::from_secs(2),
       state: BTreeMap::new(),
+      contracts: BTreeMap::new(),
+      upgrades: vec![],
       builtins: vec![],
       limits: Limits {
         max_block_size: 100_000,
@@ -969,6 +1047,9 @@ mod test {
         max_block_transactions: 2000,
         max_contract_size: 614400,
         max_transaction_params_size: 2048,
+        max_transaction_instructions: 16,
+        storage_deposit_per_account: 0,
+        storage_deposit_per_byte: 0,
       },
       system_coin: "RensaToken1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
         .parse()
@@ -992,7 +1073,10 @@ mod test {
       // blocks have no txs, so the statehash won't change across
       // blocks, but it needs to be a valid hash otherwise the block
       // gets rejected and not appended to the chain.
-      (b.hash().unwrap(), *vec![].execute(&vm, s).unwrap().hash())
+      (
+        b.hash().unwrap(),
+        *vec![].execute(&vm, s, b.height() + 1).unwrap().hash(),
+      )
     });
 
     let block = block::Produced::new(
@@ -354,7 +354,10 @@ mod tests {
           parent.height + 1,
           parent.hash().unwrap(),
           data,
-          *vec![].execute(vm, &StateDiff::default()).unwrap().hash(),
+          *vec![]
+            .execute(vm, &StateDiff::default(), parent.height + 1)
+            .unwrap()
+            .hash(),
           vec![],
         )
         .unwrap(),
@@ -381,6 +384,8 @@ mod tests {
       genesis_time: Utc::now(),
       slot_interval: Duration::from_secs(2),
       state: BTreeMap::new(),
+      contracts: BTreeMap::new(),
+      upgrades: vec![],
       builtins: vec![],
       limits: Limits {
         max_block_size: 100_000,
@@ -393,6 +398,9 @@ mod tests {
         max_block_transactions: 2000,
         max_contract_size: 614400,
         max_transaction_params_size: 2048,
+        max_transaction_instructions: 16,
+        storage_deposit_per_account: 0,
+        storage_deposit_per_byte: 0,
       },
       system_coin: "RensaToken1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
         .parse()
@@ -409,7 +417,10 @@ mod tests {
     // blocks have no txs, so the statehash won't change across
     // blocks, but it needs to be a valid hash otherwise the block
     // gets rejected and not appended to the chain.
-    let statehash = *vec![].execute(&vm, &StateDiff::default()).unwrap().hash();
+    let statehash = *vec![]
+      .execute(&vm, &StateDiff::default(), 1)
+      .unwrap()
+      .hash();
 
     let produced = Arc::new(
       Produced::new(&keypair, 1, Multihash::default(), 1u8, statehash, vec![])
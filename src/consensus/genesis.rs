@@ -1,9 +1,25 @@
 use {
-  super::{validator::Validator, vote::Vote, Block, BlockData},
+  super::{
+    protocol::{ProtocolSchedule, Upgrade},
+    validator::Validator,
+    vote::Vote,
+    Block,
+    BlockData,
+  },
   crate::{
     primitives::{Account, Pubkey, ToBase58String},
-    vm::{State, StateDiff},
+    vm::{
+      builtin::{
+        currency::{coin_address, CoinAccount, Mint, CURRENCY_BUILTIN_ADDR},
+        governance::{self, GOVERNANCE_BUILTIN_ADDR, GOVERNANCE_STATE_ADDR},
+        BUILTIN_CONTRACTS,
+      },
+      State,
+      StateDiff,
+      WASM_VM_BUILTIN_ADDR,
+    },
   },
+  borsh::{BorshDeserialize, BorshSerialize},
   chrono::{DateTime, Utc},
   ed25519_dalek::Signature,
   multihash::{
@@ -19,17 +35,94 @@ use {
     fmt::Debug,
     io::{Error as StdIoError, ErrorKind},
     marker::PhantomData,
+    path::{Path, PathBuf},
     time::Duration,
   },
+  thiserror::Error,
 };
 
+/// Version of the canonical genesis encoding that is hashed into the
+/// identity of a chain.
+///
+/// Version 0 was the unversioned encoding of earlier releases that
+/// left out some of the fields, chains identified by it have to be
+/// recreated, see `MIGRATION.md`.
+pub const GENESIS_ENCODING_VERSION: u8 = 1;
+
+/// Reasons for rejecting a genesis configuration.
+#[derive(Debug, Error)]
+pub enum GenesisError {
+  #[error("The chain id is empty")]
+  EmptyChainId,
+
+  #[error("An epoch must have at least one block")]
+  ZeroEpochBlocks,
+
+  #[error("The slot interval must be longer than zero")]
+  ZeroSlotInterval,
+
+  #[error("Invalid limits: {0}")]
+  InvalidLimits(&'static str),
+
+  #[error("The genesis has no validators")]
+  NoValidators,
+
+  #[error("Validator {0} is listed more than once")]
+  DuplicateValidator(Pubkey),
+
+  #[error("Validator {0} stake of {1} is below the minimum stake")]
+  InsufficientStake(Pubkey, u64),
+
+  #[error("Builtin {0} is not known to this version of the validator")]
+  UnknownBuiltin(Pubkey),
+
+  #[error("Builtin {0} is listed more than once")]
+  DuplicateBuiltin(Pubkey),
+
+  #[error("Builtin {0} is required by the genesis state but not enabled")]
+  MissingBuiltin(Pubkey),
+
+  #[error("System coin {0} is not a Currency mint in the genesis state")]
+  InvalidSystemCoin(Pubkey),
+
+  #[error("Account {0} is not a valid system coin account")]
+  InvalidCoinAccount(Pubkey),
+
+  #[error("System coin supply of {0} does not match the sum of balances {1}")]
+  SupplyMismatch(u64, u64),
+
+  #[error("Executable account {0} is not owned by the WasmVM builtin")]
+  InvalidExecutable(Pubkey),
+
+  #[error("Contract {0} is not loaded into the genesis state")]
+  UnloadedContract(Pubkey),
+
+  #[error("Upgrade at epoch {0} is not scheduled after the previous upgrade")]
+  UnorderedUpgrade(u64),
+
+  #[error("Builtin {0} can't be disabled because it is not enabled")]
+  DisabledBuiltin(Pubkey),
+
+  #[error("Governance is enabled but its state account {0} is not in genesis")]
+  MissingGovernanceState(Pubkey),
+}
+
 /// Configures blockchain set limits across all validators.
 ///
 /// Those limits ensure that a malicious contract would be able
 /// to halt validators during execution or DoS them. They keep all
 /// resources usage within a transaction bounded to limits defined
 /// in genesis.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(
+  Debug,
+  Clone,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+  BorshSerialize,
+  BorshDeserialize,
+)]
 #[serde(rename_all = "camelCase")]
 pub struct Limits {
   /// The maximum age in epochs of a finalized block that can be
@@ -81,6 +174,68 @@ pub struct Limits {
   /// Transactions that need to transmit more data should be
   /// split up into smaller transactions.
   pub max_transaction_params_size: usize,
+
+  /// The maximum number of instructions within a single transaction.
+  ///
+  /// All instructions of a transaction are executed atomically, so
+  /// this value bounds the amount of work a single transaction can do.
+  ///
+  /// Genesis files that predate multi-instruction transactions don't
+  /// specify it and allow a single instruction per transaction.
+  #[serde(default = "single_instruction")]
+  pub max_transaction_instructions: usize,
+
+  /// The amount of system coins locked for every account kept in the
+  /// state. The deposit is paid by the payer of the transaction that
+  /// creates the account and refunded when the account is deleted.
+  ///
+  /// Genesis files that predate storage deposits don't specify
+  /// any deposit rates, so they default to zero.
+  #[serde(default)]
+  pub storage_deposit_per_account: u64,
+
+  /// The amount of system coins locked for every byte of account data.
+  /// The deposit is paid by the payer of the transaction that grows the
+  /// account data and refunded when the data shrinks.
+  ///
+  /// Setting both deposit rates to zero disables storage deposits.
+  #[serde(default)]
+  pub storage_deposit_per_byte: u64,
+}
+
+impl Limits {
+  /// Checks that the limits allow the chain to make progress.
+  ///
+  /// Limits are validated when they are defined in genesis, scheduled
+  /// by an upgrade or proposed through governance.
+  pub fn validate(&self) -> Result<(), GenesisError> {
+    let error = if self.max_justification_age == 0 {
+      "maxJustificationAge must be greater than zero"
+    } else if self.max_block_size == 0 {
+      "maxBlockSize must be greater than zero"
+    } else if self.max_block_transactions == 0 {
+      "maxBlockTransactions must be greater than zero"
+    } else if self.max_transaction_instructions == 0 {
+      "maxTransactionInstructions must be greater than zero"
+    } else if self.max_input_accounts == 0 {
+      "maxInputAccounts must be greater than zero"
+    } else if self.max_logs_count == 0 {
+      "maxLogsCount must be greater than zero"
+    } else if self.max_log_size == 0 {
+      "maxLogSize must be greater than zero"
+    } else if self.max_contract_size == 0 {
+      "maxContractSize must be greater than zero"
+    } else if self.max_transaction_params_size >= self.max_block_size {
+      "maxTransactionParamsSize must be smaller than maxBlockSize"
+    } else {
+      return Ok(());
+    };
+    Err(GenesisError::InvalidLimits(error))
+  }
+}
+
+fn single_instruction() -> usize {
+  1
 }
 
 /// The genesis block of the blockchain.
@@ -152,6 +307,22 @@ pub struct Genesis<D: BlockData> {
   /// any produced block gets finalized.
   pub state: BTreeMap<Pubkey, Account>,
 
+  /// WASM contracts installed at the very first block, maps contract
+  /// addresses to paths of their compiled bytecode. Relative paths are
+  /// resolved against the directory of the genesis file.
+  ///
+  /// See [`Genesis::load_contracts`], once loaded the contracts become
+  /// executable accounts in the genesis state.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub contracts: BTreeMap<Pubkey, PathBuf>,
+
+  /// Protocol upgrades that change the limits and builtins of the chain
+  /// at a later epoch, ordered by their activation epoch.
+  ///
+  /// See [`ProtocolSchedule`] for the rules in effect at a given height.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub upgrades: Vec<Upgrade>,
+
   /// Block data stored in the first block.
   ///
   /// This is specific to the execution layer that is responsible
@@ -160,48 +331,337 @@ pub struct Genesis<D: BlockData> {
   pub _marker: PhantomData<D>,
 }
 
-impl<D: BlockData> Block<D> for Genesis<D> {
-  /// The hash of the genesis is used to determine a
-  /// unique fingerprint of a blockchain configuration.
-  fn hash(&self) -> Result<Multihash, StdIoError> {
-    let mut sha3 = Sha3_256::default();
-    sha3.update(self.chain_id.as_bytes());
-    sha3.update(&self.genesis_time.timestamp_millis().to_le_bytes());
-    sha3.update(&self.slot_interval.as_millis().to_le_bytes());
-    sha3.update(&self.epoch_blocks.to_le_bytes());
-
-    sha3.update(&self.limits.max_block_size.to_le_bytes());
-    sha3.update(&self.limits.max_justification_age.to_le_bytes());
-    sha3.update(&self.limits.minimum_stake.to_le_bytes());
-    sha3.update(&self.limits.max_log_size.to_le_bytes());
-    sha3.update(&self.limits.max_logs_count.to_le_bytes());
-    sha3.update(&self.limits.max_account_size.to_le_bytes());
-    sha3.update(&self.limits.max_input_accounts.to_le_bytes());
-    sha3.update(&self.limits.max_block_transactions.to_le_bytes());
-    sha3.update(&self.limits.max_contract_size.to_le_bytes());
-
-    for builtin in &self.builtins {
-      sha3.update(builtin);
+impl<D: BlockData> Genesis<D> {
+  /// Reads a genesis configuration from its JSON serialized form
+  /// and installs all its contracts, contract paths are relative
+  /// to the location of the genesis file.
+  pub fn load(path: &Path) -> Result<Self, StdIoError> {
+    let json = std::fs::read_to_string(path)?;
+    let mut genesis: Self = serde_json::from_str(&json)?;
+
+    // we're sorting validators in the genesis because we want the same
+    // hash for two gensis files with the exact same list of parameters
+    // and validators but only differing in the order of their appearance.
+    genesis.validators.sort();
+
+    genesis.load_contracts(path.parent().unwrap_or_else(|| Path::new(".")))?;
+    Ok(genesis)
+  }
+
+  /// Checks that the genesis describes a chain that validators
+  /// are able to run.
+  ///
+  /// Genesis files are written by hand or generated by tools, so
+  /// they are validated before a validator starts running a chain.
+  pub fn validate(&self) -> Result<(), GenesisError> {
+    if self.chain_id.is_empty() {
+      return Err(GenesisError::EmptyChainId);
+    }
+
+    if self.epoch_blocks == 0 {
+      return Err(GenesisError::ZeroEpochBlocks);
+    }
+
+    if self.slot_interval.is_zero() {
+      return Err(GenesisError::ZeroSlotInterval);
+    }
+
+    self.limits.validate()?;
+    self.validate_validators()?;
+    self.validate_builtins(&self.builtins)?;
+    self.validate_upgrades()?;
+    self.validate_state()
+  }
+
+  fn validate_validators(&self) -> Result<(), GenesisError> {
+    if self.validators.is_empty() {
+      return Err(GenesisError::NoValidators);
+    }
+
+    for (i, validator) in self.validators.iter().enumerate() {
+      if self.validators[..i]
+        .iter()
+        .any(|v| v.pubkey == validator.pubkey)
+      {
+        return Err(GenesisError::DuplicateValidator(validator.pubkey));
+      }
+
+      if validator.stake < self.limits.minimum_stake {
+        return Err(GenesisError::InsufficientStake(
+          validator.pubkey,
+          validator.stake,
+        ));
+      }
+    }
+    Ok(())
+  }
+
+  fn validate_builtins(&self, builtins: &[Pubkey]) -> Result<(), GenesisError> {
+    for (i, builtin) in builtins.iter().enumerate() {
+      if !BUILTIN_CONTRACTS.contains_key(builtin) {
+        return Err(GenesisError::UnknownBuiltin(*builtin));
+      }
+
+      if builtins[..i].contains(builtin) {
+        return Err(GenesisError::DuplicateBuiltin(*builtin));
+      }
     }
 
+    // the system coin is managed by the currency contract
+    if !builtins.contains(&CURRENCY_BUILTIN_ADDR) {
+      return Err(GenesisError::MissingBuiltin(*CURRENCY_BUILTIN_ADDR));
+    }
+
+    let has_contracts = self.state.values().any(|acc| acc.executable);
+    if has_contracts && !builtins.contains(&WASM_VM_BUILTIN_ADDR) {
+      return Err(GenesisError::MissingBuiltin(*WASM_VM_BUILTIN_ADDR));
+    }
+
+    Ok(())
+  }
+
+  fn validate_upgrades(&self) -> Result<(), GenesisError> {
+    let mut previous = 0;
+    let mut builtins = self.builtins.clone();
+    for upgrade in &self.upgrades {
+      // epoch zero is governed by the genesis rules
+      if upgrade.epoch <= previous {
+        return Err(GenesisError::UnorderedUpgrade(upgrade.epoch));
+      }
+      previous = upgrade.epoch;
+
+      for builtin in &upgrade.disable_builtins {
+        if !builtins.contains(builtin) {
+          return Err(GenesisError::DisabledBuiltin(*builtin));
+        }
+        builtins.retain(|b| b != builtin);
+      }
+
+      for builtin in &upgrade.enable_builtins {
+        if builtins.contains(builtin) {
+          return Err(GenesisError::DuplicateBuiltin(*builtin));
+        }
+        builtins.push(*builtin);
+      }
+    }
+
+    // every protocol version has to be runnable on its own
+    for protocol in ProtocolSchedule::new(self).versions() {
+      protocol.limits.validate()?;
+      self.validate_builtins(&protocol.builtins)?;
+    }
+
+    Ok(())
+  }
+
+  fn validate_state(&self) -> Result<(), GenesisError> {
+    if let Some(address) = self.contracts.keys().next() {
+      return Err(GenesisError::UnloadedContract(*address));
+    }
+
+    let mint = self
+      .state
+      .get(&self.system_coin)
+      .filter(|acc| acc.owner == Some(*CURRENCY_BUILTIN_ADDR))
+      .and_then(|acc| acc.data.as_ref())
+      .and_then(|data| Mint::try_from_slice(data).ok())
+      .ok_or(GenesisError::InvalidSystemCoin(self.system_coin))?;
+
+    let mut balances = 0u64;
+    for (address, account) in &self.state {
+      if account.executable && account.owner != Some(*WASM_VM_BUILTIN_ADDR) {
+        return Err(GenesisError::InvalidExecutable(*address));
+      }
+
+      // all system coin accounts must add up to the coin supply
+      let coin = account
+        .data
+        .as_ref()
+        .filter(|_| account.owner == Some(*CURRENCY_BUILTIN_ADDR))
+        .and_then(|data| CoinAccount::try_from_slice(data).ok())
+        .filter(|coin| coin.mint == self.system_coin);
+
+      if let Some(coin) = coin {
+        if *address != coin_address(&self.system_coin, &coin.owner) {
+          return Err(GenesisError::InvalidCoinAccount(*address));
+        }
+        balances = balances
+          .checked_add(coin.balance)
+          .ok_or(GenesisError::InvalidCoinAccount(*address))?;
+      }
+    }
+
+    if balances != mint.supply {
+      return Err(GenesisError::SupplyMismatch(mint.supply, balances));
+    }
+
+    // the governance state account can't be created by transactions,
+    // otherwise any contract could claim its address first.
+    let governed = ProtocolSchedule::new(self)
+      .versions()
+      .iter()
+      .any(|p| p.builtins.contains(&GOVERNANCE_BUILTIN_ADDR));
+    if governed
+      && self.state.get(&GOVERNANCE_STATE_ADDR)
+        != Some(&governance::genesis_account())
+    {
+      return Err(GenesisError::MissingGovernanceState(*GOVERNANCE_STATE_ADDR));
+    }
+
+    Ok(())
+  }
+
+  /// The canonical binary encoding of all consensus relevant fields
+  /// of the genesis, versioned by [`GENESIS_ENCODING_VERSION`].
+  ///
+  /// Integers are little-endian and sized independently of the
+  /// platform, strings, byte arrays and collections are prefixed
+  /// with their length and optional values with a presence byte, so
+  /// no two different configurations share the same encoding.
+  pub fn encode(&self) -> Result<Vec<u8>, StdIoError> {
+    if let Some(address) = self.contracts.keys().next() {
+      return Err(StdIoError::new(
+        ErrorKind::InvalidInput,
+        format!("genesis contract {address} is not loaded"),
+      ));
+    }
+
+    fn bytes(out: &mut Vec<u8>, value: &[u8]) {
+      out.extend_from_slice(&(value.len() as u64).to_le_bytes());
+      out.extend_from_slice(value);
+    }
+
+    fn int(out: &mut Vec<u8>, value: u64) {
+      out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn keys(out: &mut Vec<u8>, keys: &[Pubkey]) {
+      int(out, keys.len() as u64);
+      for key in keys {
+        out.extend_from_slice(key);
+      }
+    }
+
+    fn limits(out: &mut Vec<u8>, limits: &Limits) {
+      int(out, limits.max_justification_age);
+      int(out, limits.minimum_stake);
+      int(out, limits.max_input_accounts as u64);
+      int(out, limits.max_account_size as u64);
+      int(out, limits.max_log_size as u64);
+      int(out, limits.max_logs_count as u64);
+      int(out, limits.max_block_transactions as u64);
+      int(out, limits.max_contract_size as u64);
+      int(out, limits.max_block_size as u64);
+      int(out, limits.max_transaction_params_size as u64);
+      int(out, limits.max_transaction_instructions as u64);
+      int(out, limits.storage_deposit_per_account);
+      int(out, limits.storage_deposit_per_byte);
+    }
+
+    let mut out = vec![GENESIS_ENCODING_VERSION];
+    bytes(&mut out, self.chain_id.as_bytes());
+    out.extend_from_slice(&self.genesis_time.timestamp_millis().to_le_bytes());
+    int(&mut out, self.slot_interval.as_millis() as u64);
+    int(&mut out, self.epoch_blocks);
+    limits(&mut out, &self.limits);
+    keys(&mut out, &self.builtins);
+
+    int(&mut out, self.validators.len() as u64);
     for validator in &self.validators {
-      sha3.update(&validator.pubkey);
-      sha3.update(&validator.stake.to_le_bytes());
+      out.extend_from_slice(&validator.pubkey);
+      int(&mut out, validator.stake);
     }
 
+    out.extend_from_slice(&self.system_coin);
+
+    int(&mut out, self.state.len() as u64);
     for (addr, acc) in &self.state {
-      sha3.update(addr);
+      out.extend_from_slice(addr);
+      int(&mut out, acc.nonce);
+      out.push(acc.executable as u8);
       match &acc.owner {
-        Some(o) => sha3.update(o),
-        None => sha3.update(&[0]),
-      };
-
+        Some(owner) => {
+          out.push(1);
+          out.extend_from_slice(owner);
+        }
+        None => out.push(0),
+      }
       match &acc.data {
-        Some(v) => sha3.update(v),
-        None => sha3.update(&[0]),
+        Some(data) => {
+          out.push(1);
+          bytes(&mut out, data);
+        }
+        None => out.push(0),
       }
     }
 
+    // upgrades come last and only when scheduled, so genesis files
+    // without upgrades keep the same encoding.
+    if !self.upgrades.is_empty() {
+      int(&mut out, self.upgrades.len() as u64);
+      for upgrade in &self.upgrades {
+        int(&mut out, upgrade.epoch);
+        match &upgrade.limits {
+          Some(upgraded) => {
+            out.push(1);
+            limits(&mut out, upgraded);
+          }
+          None => out.push(0),
+        }
+        keys(&mut out, &upgrade.enable_builtins);
+        keys(&mut out, &upgrade.disable_builtins);
+      }
+    }
+
+    Ok(out)
+  }
+
+  /// Reads the bytecode of all genesis contracts and installs them
+  /// as executable accounts owned by the WasmVM builtin in the
+  /// genesis state.
+  ///
+  /// The bytecode is part of the genesis state, so the genesis hash
+  /// depends only on the contents of the contracts and not on their
+  /// location on disk.
+  pub fn load_contracts(&mut self, dir: &Path) -> Result<(), StdIoError> {
+    for (address, path) in std::mem::take(&mut self.contracts) {
+      let bytecode = std::fs::read(dir.join(&path))?;
+
+      if bytecode.len() > self.limits.max_contract_size {
+        return Err(StdIoError::new(
+          ErrorKind::InvalidData,
+          format!("contract {} exceeds the maximum size", path.display()),
+        ));
+      }
+
+      if self.state.contains_key(&address) {
+        return Err(StdIoError::new(
+          ErrorKind::AlreadyExists,
+          format!("genesis contract address {address} is already in use"),
+        ));
+      }
+
+      self.state.insert(address, Account {
+        nonce: 0,
+        executable: true,
+        owner: Some(*WASM_VM_BUILTIN_ADDR),
+        data: Some(bytecode),
+      });
+    }
+    Ok(())
+  }
+}
+
+impl<D: BlockData> Block<D> for Genesis<D> {
+  /// The hash of the genesis is used to determine a
+  /// unique fingerprint of a blockchain configuration.
+  ///
+  /// It is the SHA3-256 hash of the canonical encoding of the genesis,
+  /// see [`Genesis::encode`]. Genesis contracts have to be loaded first,
+  /// otherwise the hash would not cover their bytecode.
+  fn hash(&self) -> Result<Multihash, StdIoError> {
+    let mut sha3 = Sha3_256::default();
+    sha3.update(&self.encode()?);
     MultihashCode::Sha3_256
       .wrap(sha3.finalize())
       .map_err(|e| std::io::Error::new(ErrorKind::Other, e))
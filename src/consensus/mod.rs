@@ -7,6 +7,7 @@ mod chain;
 pub mod forktree;
 pub mod genesis;
 mod orphans;
+pub mod protocol;
 mod schedule;
 pub mod validator;
 mod vote;
@@ -14,7 +15,9 @@ mod vote;
 pub use {
   block::{Block, BlockData, Produced},
   chain::{Chain, ChainEvent},
-  genesis::{Genesis, Limits},
+  genesis::{Genesis, GenesisError, Limits, GENESIS_ENCODING_VERSION},
+  protocol::{Protocol, ProtocolSchedule, Upgrade},
   schedule::{ValidatorSchedule, ValidatorScheduleStream},
+  validator::Stakes,
   vote::Vote,
 };
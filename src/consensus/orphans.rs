@@ -188,6 +188,11 @@ impl<D: BlockData> Orphans<D> {
     };
   }
 
+  /// Checks if any orphaned blocks are waiting for a given parent.
+  pub fn is_missing(&self, parent_hash: &Multihash) -> bool {
+    self.blocks.contains_key(parent_hash)
+  }
+
   pub fn consume_votes(&mut self, block: &Multihash) -> Option<Vec<Vote>> {
     self.votes.remove(block)
   }
@@ -0,0 +1,149 @@
+//! Protocol upgrades
+//!
+//! The rules of a chain, its limits and the set of enabled builtins,
+//! are defined in genesis and may change later by upgrades scheduled
+//! in genesis. An upgrade activates at the first block of an epoch
+//! and every activated upgrade increments the protocol version, the
+//! rules defined in genesis are version zero.
+//!
+//! All blocks at a given height are produced, executed and verified
+//! under the same rules on all validators, so the switch happens
+//! exactly at the activation boundary without any coordination.
+//!
+//! Validators may also change the rules through the governance builtin,
+//! the rules in effect on a chain are the scheduled rules along with all
+//! changes enacted by governance, see [`ProtocolSchedule::effective`].
+
+use {
+  super::{genesis::Limits, BlockData, Genesis},
+  crate::{
+    primitives::Pubkey,
+    vm::{builtin::governance, State},
+  },
+  serde::{Deserialize, Serialize},
+};
+
+/// A change to the rules of the chain scheduled in genesis.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Upgrade {
+  /// The epoch at which the upgrade activates. The new rules apply
+  /// starting from the first block of this epoch.
+  pub epoch: u64,
+
+  /// Replaces all limits of the chain when set, otherwise limits
+  /// from the previous protocol version stay in effect.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub limits: Option<Limits>,
+
+  /// Builtin contracts that become available to transactions.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub enable_builtins: Vec<Pubkey>,
+
+  /// Builtin contracts that stop being available to transactions.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub disable_builtins: Vec<Pubkey>,
+}
+
+/// The rules validators follow from a given block height on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Protocol {
+  /// Zero for the rules defined in genesis, then incremented
+  /// by every activated upgrade.
+  pub version: u32,
+
+  /// The height of the first block that follows those rules.
+  pub activation: u64,
+
+  /// Execution and consensus limits.
+  pub limits: Limits,
+
+  /// Builtin contracts available to transactions.
+  pub builtins: Vec<Pubkey>,
+}
+
+/// All protocol versions of a chain ordered by their activation.
+#[derive(Debug, Clone)]
+pub struct ProtocolSchedule {
+  versions: Vec<Protocol>,
+  epoch_blocks: u64,
+}
+
+impl ProtocolSchedule {
+  /// Applies all genesis upgrades in order on top of the genesis
+  /// rules. Upgrades are expected to be validated by the genesis.
+  pub fn new<D: BlockData>(genesis: &Genesis<D>) -> Self {
+    let mut current = Protocol {
+      version: 0,
+      activation: 0,
+      limits: genesis.limits.clone(),
+      builtins: genesis.builtins.clone(),
+    };
+
+    let mut versions = Vec::with_capacity(genesis.upgrades.len() + 1);
+    for upgrade in &genesis.upgrades {
+      let mut next = current.clone();
+      next.version += 1;
+      next.activation = upgrade.epoch.saturating_mul(genesis.epoch_blocks);
+      if let Some(limits) = &upgrade.limits {
+        next.limits = limits.clone();
+      }
+      next
+        .builtins
+        .retain(|b| !upgrade.disable_builtins.contains(b));
+      for builtin in &upgrade.enable_builtins {
+        if !next.builtins.contains(builtin) {
+          next.builtins.push(*builtin);
+        }
+      }
+      versions.push(std::mem::replace(&mut current, next));
+    }
+    versions.push(current);
+
+    Self {
+      versions,
+      epoch_blocks: genesis.epoch_blocks,
+    }
+  }
+
+  /// The rules scheduled in genesis for the block at a given height,
+  /// without changes enacted by governance.
+  pub fn at(&self, height: u64) -> &Protocol {
+    self
+      .versions
+      .iter()
+      .rev()
+      .find(|p| p.activation <= height)
+      .unwrap_or(&self.versions[0])
+  }
+
+  /// The rules that apply to the block at a given height on top of
+  /// the given state. Those are the scheduled rules along with all
+  /// changes enacted by governance up to the epoch of the block.
+  ///
+  /// Limits are always looked up through this method, so that block
+  /// execution, consensus, the mempool and RPC follow the same rules.
+  pub fn effective(&self, state: &dyn State, height: u64) -> Protocol {
+    governance::apply(self.at(height), state, height / self.epoch_blocks)
+  }
+
+  /// All protocol versions, starting with the genesis rules.
+  pub fn versions(&self) -> &[Protocol] {
+    &self.versions
+  }
+
+  /// The largest block size allowed by any of the scheduled versions.
+  ///
+  /// The network transport is configured once at startup to carry
+  /// blocks of this size, so governance can't raise the block size
+  /// above it.
+  pub fn max_block_size(&self) -> usize {
+    self
+      .versions
+      .iter()
+      .map(|p| p.limits.max_block_size)
+      .max()
+      .unwrap_or_default()
+  }
+}
@@ -1,7 +1,8 @@
 use {
+  super::{BlockData, Genesis, Limits},
   crate::primitives::Pubkey,
   serde::{Deserialize, Serialize},
-  std::cmp::Ordering,
+  std::{cmp::Ordering, collections::HashMap},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,3 +35,46 @@ impl Ord for Validator {
     }
   }
 }
+
+/// The voting power of validators, shared by the consensus and
+/// the virtual machine so that both weigh validators the same.
+///
+/// Stakes are fixed in genesis for now, but validators staking less
+/// than the minimum stake of the rules in effect in an epoch,
+/// including changes enacted by governance, don't take part in it.
+#[derive(Debug, Clone)]
+pub struct Stakes(HashMap<Pubkey, u64>);
+
+impl Stakes {
+  pub fn new<D: BlockData>(genesis: &Genesis<D>) -> Self {
+    Self(
+      genesis
+        .validators
+        .iter()
+        .map(|v| (v.pubkey, v.stake))
+        .collect(),
+    )
+  }
+
+  /// Checks if an account is a validator, regardless of its stake.
+  pub fn contains(&self, validator: &Pubkey) -> bool {
+    self.0.contains_key(validator)
+  }
+
+  /// The stake of a validator under the given limits, zero for
+  /// validators staking less than the minimum stake and other accounts.
+  pub fn stake(&self, validator: &Pubkey, limits: &Limits) -> u64 {
+    self
+      .0
+      .get(validator)
+      .cloned()
+      .filter(|stake| *stake >= limits.minimum_stake)
+      .unwrap_or(0)
+  }
+
+  /// The sum of stakes of all validators that take
+  /// part in the consensus under the given limits.
+  pub fn total(&self, limits: &Limits) -> u64 {
+    self.0.keys().map(|v| self.stake(v, limits)).sum()
+  }
+}
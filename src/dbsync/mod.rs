@@ -3,9 +3,19 @@ use {
     consensus::{Block, Vote},
     consumer::{BlockConsumer, Commitment},
     primitives::{Account, Pubkey, ToBase58String},
-    vm::{AccountRef, Executed, Transaction},
+    vm::{
+      AccountRef,
+      ContractError,
+      Event,
+      Executed,
+      Instruction,
+      Invocation,
+      Receipt,
+      Transaction,
+    },
   },
   ed25519_dalek::Signature,
+  itertools::Itertools,
   sqlx::{AnyPool, Connection, Executor},
   tracing::{debug, error, info},
 };
@@ -72,6 +82,13 @@ impl DatabaseSync {
             .await?;
         }
 
+        // then its instructions
+        for (i, instruction) in tx.instructions.iter().enumerate() {
+          dbtransaction
+            .execute(transaction_instruction_stmt(tx, instruction, i).as_str())
+            .await?;
+        }
+
         // then signatures of signing accounts
         for (i, signature) in tx.signatures.iter().enumerate() {
           dbtransaction
@@ -88,9 +105,41 @@ impl DatabaseSync {
           }
         } else if let Some(error) = block.output.errors.get(tx.hash()) {
           dbtransaction
-            .execute(transaction_error_stmt(tx, error.to_string()).as_str())
+            .execute(transaction_error_stmt(tx, error).as_str())
             .await?;
         }
+
+        // then structured events and their topics
+        if let Some(events) = block.output.events.get(tx.hash()) {
+          for (i, event) in events.iter().enumerate() {
+            dbtransaction
+              .execute(transaction_event_stmt(tx, event, i).as_str())
+              .await?;
+
+            for (j, topic) in event.topics.iter().enumerate() {
+              dbtransaction
+                .execute(transaction_event_topic_stmt(tx, topic, i, j).as_str())
+                .await?;
+            }
+          }
+        }
+
+        // then the receipt with per-transaction state changes
+        if let Some(receipt) = block.output.receipts.get(tx.hash()) {
+          for (i, (addr, acc)) in receipt.state.iter().enumerate() {
+            dbtransaction
+              .execute(
+                transaction_state_diff_stmt(tx, receipt, addr, acc, i).as_str(),
+              )
+              .await?;
+          }
+
+          for (i, invocation) in receipt.invocations.iter().enumerate() {
+            dbtransaction
+              .execute(transaction_invocation_stmt(tx, invocation, i).as_str())
+              .await?;
+          }
+        }
       }
 
       // insert votes
@@ -162,17 +211,31 @@ fn transaction_stmt(
   pos: usize,
 ) -> String {
   format!(
-    "INSERT INTO transaction VALUES ('{}', {}, '{}', {}, '{}', '{}', {})",
+    "INSERT INTO transaction VALUES ('{}', {}, {}, {}, '{}', {})",
     tx.hash().to_b58(),
     block.height,
-    tx.contract,
     tx.nonce,
-    tx.params.to_b58(),
+    tx.expiry.map(|e| e.to_string()).unwrap_or_else(|| "NULL".into()),
     tx.payer,
     pos
   )
 }
 
+fn transaction_instruction_stmt(
+  tx: &Transaction,
+  instruction: &Instruction,
+  pos: usize,
+) -> String {
+  format!(
+    "INSERT INTO transaction_instructions VALUES ('{}', '{}', '{}', '{}', {})",
+    tx.hash().to_b58(),
+    instruction.contract,
+    instruction.accounts.iter().join(","),
+    instruction.params.to_b58(),
+    pos
+  )
+}
+
 fn transaction_account_stmt(
   tx: &Transaction,
   acc: &AccountRef,
@@ -221,11 +284,118 @@ fn transaction_output_stmt(
   )
 }
 
-fn transaction_error_stmt(tx: &Transaction, error: String) -> String {
+fn transaction_error_stmt(tx: &Transaction, error: &ContractError) -> String {
+  let (code, data) = match error {
+    ContractError::Custom { code, data } => (
+      code.to_string(),
+      data
+        .as_ref()
+        .map(|d| format!("'{}'", d.to_b58()))
+        .unwrap_or_else(|| "NULL".into()),
+    ),
+    _ => ("NULL".into(), "NULL".into()),
+  };
+
+  format!(
+    "INSERT INTO transaction_errors VALUES ('{}', '{}', {}, {})",
+    tx.hash().to_b58(),
+    error,
+    code,
+    data
+  )
+}
+
+fn transaction_event_stmt(
+  tx: &Transaction,
+  event: &Event,
+  pos: usize,
+) -> String {
+  format!(
+    "INSERT INTO transaction_events VALUES ('{}', '{}', '{}', {})",
+    tx.hash().to_b58(),
+    event.contract,
+    event.data.to_b58(),
+    pos
+  )
+}
+
+fn transaction_event_topic_stmt(
+  tx: &Transaction,
+  topic: &[u8; 32],
+  event: usize,
+  pos: usize,
+) -> String {
   format!(
-    "INSERT INTO transaction_errors VALUES ('{}', '{}')",
+    "INSERT INTO transaction_event_topics VALUES ('{}', {}, '{}', {})",
     tx.hash().to_b58(),
-    error
+    event,
+    topic.to_b58(),
+    pos
+  )
+}
+
+fn transaction_state_diff_stmt(
+  tx: &Transaction,
+  receipt: &Receipt,
+  account: &Pubkey,
+  data: Option<&Account>,
+  pos: usize,
+) -> String {
+  let change = if receipt.created.contains(account) {
+    "created"
+  } else if receipt.deleted.contains(account) {
+    "deleted"
+  } else {
+    "written"
+  };
+
+  match data {
+    Some(acc) => format!(
+      "INSERT INTO transaction_state_diff VALUES ('{}', '{}', {}, {}, {}, {}, \
+       '{}', {})",
+      tx.hash().to_b58(),
+      account,
+      match acc.data {
+        None => "NULL".to_string(),
+        Some(ref bytes) => format!("'{}'", bytes.to_b58()),
+      },
+      acc.nonce,
+      match acc.owner {
+        None => "NULL".to_string(),
+        Some(ref owner) => format!("'{}'", owner),
+      },
+      match acc.executable {
+        true => 1,
+        false => 0,
+      },
+      change,
+      pos
+    ),
+    None => format!(
+      "INSERT INTO transaction_state_diff VALUES ('{}', '{}', NULL, NULL, \
+       NULL, NULL, '{}', {})",
+      tx.hash().to_b58(),
+      account,
+      change,
+      pos
+    ),
+  }
+}
+
+fn transaction_invocation_stmt(
+  tx: &Transaction,
+  invocation: &Invocation,
+  pos: usize,
+) -> String {
+  format!(
+    "INSERT INTO transaction_invocations VALUES ('{}', '{}', {}, {})",
+    tx.hash().to_b58(),
+    invocation.contract,
+    match invocation.caller {
+      None => "NULL".to_string(),
+      Some(ref caller) => format!("'{}'", caller),
+    },
+    pos
   )
 }
 
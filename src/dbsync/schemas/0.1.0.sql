@@ -25,9 +25,8 @@ create table if not exists state_diff (
 create table if not exists transaction (
     hash char(46) not null primary key,
     block int not null,
-    contract varchar(48) charset utf8 not null,
     nonce int not null,
-    params text null,
+    expiry int null,
     payer char(46) not null,
     `pos` int not null,
     constraint transaction_hash_uindex unique (hash),
@@ -43,9 +42,20 @@ create table if not exists transaction_accounts (
     constraint transaction_accounts_tx_hash foreign key (transaction) references transaction (hash) on delete cascade
 );
 
+create table if not exists transaction_instructions (
+    transaction char(46) not null,
+    contract char(44) charset utf8 not null,
+    accounts text not null,
+    params text null,
+    `pos` int not null,
+    constraint transaction_instructions_tx foreign key (transaction) references transaction (hash) on delete cascade
+);
+
 create table if not exists transaction_errors (
     transaction char(46) not null,
     error text null,
+    code int null,
+    data text null,
     constraint transaction_errors_tx foreign key (transaction) references transaction (hash) on delete cascade
 );
 
@@ -57,6 +67,43 @@ create table if not exists transaction_logs (
     constraint transaction_logs_tx foreign key (transaction) references transaction (hash) on delete cascade
 );
 
+create table if not exists transaction_events (
+    transaction char(46) not null,
+    contract char(44) not null,
+    data text not null,
+    `pos` int not null,
+    constraint transaction_events_tx foreign key (transaction) references transaction (hash) on delete cascade
+);
+
+create table if not exists transaction_event_topics (
+    transaction char(46) not null,
+    event int not null,
+    topic char(44) not null,
+    `pos` int not null,
+    index transaction_event_topics_topic (topic),
+    constraint transaction_event_topics_tx foreign key (transaction) references transaction (hash) on delete cascade
+);
+
+create table if not exists transaction_state_diff (
+    transaction char(46) not null,
+    account char(44) not null,
+    data text null,
+    nonce int null,
+    owner char(44) null,
+    executable tinyint(1) null,
+    `change` char(7) not null,
+    `pos` int not null,
+    constraint transaction_state_diff_tx foreign key (transaction) references transaction (hash) on delete cascade
+);
+
+create table if not exists transaction_invocations (
+    transaction char(46) not null,
+    contract char(44) not null,
+    caller char(44) null,
+    `pos` int not null,
+    constraint transaction_invocations_tx foreign key (transaction) references transaction (hash) on delete cascade
+);
+
 create table if not exists transaction_signatures (
     transaction char(46) not null,
     signature char(88) not null,
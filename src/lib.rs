@@ -0,0 +1,19 @@
+//! Rensa validator
+//!
+//! The validator node is built on top of this library, it is also
+//! used by tools that need to run the virtual machine outside of a
+//! running validator, such as the contracts test harness.
+
+pub mod cli;
+pub mod consensus;
+pub mod consumer;
+pub mod dbsync;
+pub mod network;
+pub mod primitives;
+pub mod producer;
+pub mod rpc;
+pub mod storage;
+pub mod vm;
+
+#[cfg(test)]
+mod test;
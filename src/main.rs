@@ -1,49 +1,40 @@
-mod cli;
-mod consensus;
-mod consumer;
-mod dbsync;
-mod network;
-mod primitives;
-mod producer;
-mod rpc;
-mod storage;
-mod vm;
-
-#[cfg(test)]
-mod test;
-
 use {
-  crate::{
-    consumer::Commitment,
-    network::NetworkEvent,
-    primitives::{OptionalStreamExt, ToBase58String},
-    vm::State,
-  },
   clap::StructOpt,
-  cli::CliOpts,
-  consensus::{
-    Block,
-    Chain,
-    ChainEvent,
-    ValidatorSchedule,
-    ValidatorScheduleStream,
-    Vote,
-  },
-  consumer::{BlockConsumer, BlockConsumers},
   futures::StreamExt,
-  network::{responder::SwarmResponder, Network},
-  producer::BlockProducer,
-  rpc::ApiService,
+  libp2p::PeerId,
+  rensa::{
+    cli::{CliOpts, GenesisCommand},
+    consensus::{
+      Block,
+      Chain,
+      ChainEvent,
+      Genesis,
+      ValidatorSchedule,
+      ValidatorScheduleStream,
+      Vote,
+    },
+    consumer::{BlockConsumer, BlockConsumers, Commitment},
+    network::{
+      responder::SwarmResponder,
+      snapshot::{Snapshot, SnapshotRequest, SnapshotResponse, SnapshotSync},
+      sync::{BlockSync, SyncAction, SyncRequest, MAX_SYNC_BLOCKS},
+      Network,
+      NetworkEvent,
+    },
+    primitives::{OptionalStreamExt, ToBase58String},
+    producer::BlockProducer,
+    rpc::ApiService,
+    storage::{BlockStore, PersistentState, Snapshots},
+    vm::{self, Finalized, State, Transaction},
+  },
   std::sync::Arc,
-  storage::{BlockStore, PersistentState},
-  tracing::{debug, info, Level},
+  tracing::{debug, info, warn, Level},
   tracing_subscriber::{
     filter::filter_fn,
     prelude::__tracing_subscriber_SubscriberExt,
     util::SubscriberInitExt,
     Layer,
   },
-  vm::Finalized,
 };
 
 #[global_allocator]
@@ -68,8 +59,76 @@ fn print_essentials(opts: &CliOpts) -> anyhow::Result<()> {
   Ok(())
 }
 
+/// Fetches the most recent state snapshot attested by peers, so a new
+/// node can start from it instead of the genesis. Gives up if no snapshot
+/// is attested within an epoch, for example on a chain that is younger
+/// than the snapshot interval.
+///
+/// Returns the downloaded snapshot, if any, and the peers that connected
+/// in the meantime.
+async fn fetch_snapshot(
+  genesis: &Genesis<Vec<Transaction>>,
+  network: &mut Network<Vec<Transaction>>,
+) -> anyhow::Result<(Option<Snapshot<Vec<Transaction>>>, Vec<PeerId>)> {
+  let mut sync = SnapshotSync::new(genesis);
+  let timeout =
+    tokio::time::sleep(genesis.slot_interval * genesis.epoch_blocks as u32);
+  tokio::pin!(timeout);
+
+  loop {
+    tokio::select! {
+      _ = &mut timeout, if !sync.is_downloading() => {
+        warn!("No state snapshot is attested by peers, starting from genesis");
+        return Ok((None, sync.peers().cloned().collect()));
+      }
+
+      Some((peer, request)) = sync.next() => {
+        debug!("requesting snapshot {request:?} from {peer}");
+        network.request_snapshot(peer, request)?;
+      }
+
+      Some(event) = network.poll() => {
+        match event {
+          NetworkEvent::PeerConnected(peer) => {
+            sync.add_peer(peer);
+          }
+          NetworkEvent::PeerDisconnected(peer) => {
+            sync.remove_peer(&peer);
+          }
+          NetworkEvent::SnapshotReceived { peer, response } => {
+            if sync.receive(&peer, response).is_ok() {
+              if let Some(snapshot) = sync.complete() {
+                return Ok((Some(snapshot), sync.peers().cloned().collect()));
+              }
+            }
+          }
+          NetworkEvent::SnapshotFailed(peer) => {
+            sync.failed(&peer);
+          }
+          // this node has no state or blocks to serve yet
+          NetworkEvent::SnapshotRequested { id, .. } => {
+            network.respond_snapshot(id, SnapshotResponse::NotAvailable)?;
+          }
+          NetworkEvent::BlocksRequested { id, .. } => {
+            network.respond_blocks(id, vec![])?;
+          }
+          // blocks, votes and transactions are caught up
+          // with through block sync once the chain starts.
+          _ => {}
+        }
+      }
+    }
+  }
+}
+
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> anyhow::Result<()> {
+  // `rensa genesis <command>` manages genesis files
+  // instead of running a validator node.
+  if std::env::args().nth(1).as_deref() == Some("genesis") {
+    return GenesisCommand::parse_from(std::env::args().skip(1)).run();
+  }
+
   let opts = CliOpts::parse();
 
   let loglevel = match opts.verbose {
@@ -112,16 +171,50 @@ async fn main() -> anyhow::Result<()> {
   let me = opts.keypair.public();
   let seed = genesis.hash()?.digest().try_into()?;
 
-  // The blockchain state storage. This survives crashes, and
-  // anything that gets here has went thorugh the complete consensus
-  // process.
-  let storage = PersistentState::new(&genesis, opts.data_dir()?)?;
   let blocks_store = BlockStore::new(
     opts.data_dir()?, // storage dir root
     opts.blocks_history(),
     opts.rpc_endpoints().is_some(), // store tx details only if RPC is enabled
   )?;
 
+  // snapshots of the finalized state stored by this
+  // node for other nodes that join the network later.
+  let snapshots = Snapshots::new(opts.data_dir()?, opts.snapshot_interval())?;
+
+  // A new node may start from a recent snapshot of the state
+  // fetched from peers instead of replaying all blocks since
+  // genesis, which may no longer be stored by any peer.
+  let (snapshot, peers) = match blocks_store.latest(Commitment::Finalized) {
+    None if opts.snapshot_sync() => {
+      fetch_snapshot(&genesis, &mut network).await?
+    }
+    _ => (None, vec![]),
+  };
+
+  // The blockchain state storage. This survives crashes, and
+  // anything that gets here has went thorugh the complete consensus
+  // process.
+  let storage = match snapshot {
+    Some(snapshot) => {
+      info!(
+        "Starting from state snapshot at block {} in {} chunks",
+        snapshot.block,
+        snapshot.chunks.len()
+      );
+      let storage = PersistentState::restore(
+        &genesis,
+        opts.data_dir()?,
+        &snapshot.block,
+        &snapshot.manifest,
+        &snapshot.chunks,
+      )?;
+      blocks_store.restore(&snapshot.block)?;
+      storage
+    }
+    None => PersistentState::new(&genesis, opts.data_dir()?)?,
+  }
+  .with_snapshots(snapshots.clone());
+
   // get the latest finalized block that this validator is aware of
   // so far. It is is the first run of a validator, then it is going
   // to be the genesis block.
@@ -134,13 +227,21 @@ async fn main() -> anyhow::Result<()> {
   // The finalized state and block that graduated from consensus
   // and is guaranteed to never be overriten on any validator beyond
   // this point by any forkchoice rules.
-  let finalized = Finalized::new(latest_block, &storage);
+  let finalized = Finalized::new(Arc::clone(&latest_block), &storage);
 
   // the transaction processing runtime
   let vm = vm::Machine::new(&genesis)?;
 
   // components of the consensus
   let mut chain = Chain::new(&genesis, &vm, finalized);
+
+  // fetches missing blocks directly from peers when this
+  // validator falls behind the rest of the network.
+  let mut block_sync =
+    BlockSync::new(&genesis, &*latest_block, chain.finalized_limits());
+  for peer in peers {
+    block_sync.add_peer(peer);
+  }
   let mut producer = BlockProducer::new(&genesis, opts.keypair.clone());
   let mut schedule = ValidatorScheduleStream::new(
     ValidatorSchedule::new(seed, &genesis)?,
@@ -214,6 +315,21 @@ async fn main() -> anyhow::Result<()> {
         }
       }
 
+      // this node is missing blocks and asks one
+      // of its peers for them.
+      Some(action) = block_sync.next() => {
+        match action {
+          SyncAction::Request(peer, request) => {
+            debug!("requesting {request:?} from {peer}");
+            network.request_blocks(peer, request)?;
+          }
+          SyncAction::Replay(hash) => {
+            debug!("no peer has block {}, requesting replay", hash.to_b58());
+            network.gossip_missing(hash)?;
+          }
+        }
+      }
+
       // Networking worker, receives
       // data from p2p gossip between validators
       Some(event) = network.poll() => {
@@ -222,8 +338,68 @@ async fn main() -> anyhow::Result<()> {
             if let Ok(hash) = block.hash() {
               block_reply_responder.cancel(&hash);
             }
+            let head = chain.with_head(|_, head| head.height());
+            block_sync.observe(&block, head);
             chain.include(block);
           },
+          NetworkEvent::PeerConnected(peer) => {
+            block_sync.add_peer(peer);
+          }
+          NetworkEvent::PeerDisconnected(peer) => {
+            block_sync.remove_peer(&peer);
+          }
+          NetworkEvent::BlocksRequested { id, request } => {
+            let blocks = match request {
+              SyncRequest::Finalized { from, count } => (from..)
+                .take(count.min(MAX_SYNC_BLOCKS) as usize)
+                .map_while(|height| blocks_store.get_finalized(height))
+                .collect(),
+              SyncRequest::Volatile(hash) => {
+                chain.get_with_ancestors(&hash, MAX_SYNC_BLOCKS as usize)
+              }
+            };
+            network.respond_blocks(id, blocks)?;
+          }
+          NetworkEvent::BlocksReceived { peer, blocks } => {
+            if let Ok(blocks) = block_sync.receive(&peer, blocks) {
+              info!("including {} blocks synced from {peer}", blocks.len());
+              let parent = blocks.first().map(|b| b.parent);
+              for block in blocks {
+                chain.include(block);
+              }
+
+              // keep walking back the history until
+              // the synced blocks connect to known blocks.
+              if let Some(parent) = parent.filter(|p| chain.is_missing(p)) {
+                block_sync.missing(parent);
+              }
+            }
+          }
+          NetworkEvent::SyncFailed(peer) => {
+            block_sync.failed(&peer);
+          }
+          NetworkEvent::SnapshotRequested { id, request } => {
+            let response = match request {
+              SnapshotRequest::Manifests => SnapshotResponse::Manifests(
+                snapshots
+                  .manifests()
+                  .into_iter()
+                  .filter_map(|manifest| {
+                    let block = blocks_store.get_finalized(manifest.height)?;
+                    Some((manifest, block))
+                  })
+                  .collect(),
+              ),
+              SnapshotRequest::Chunk { height, index } => snapshots
+                .chunk(height, index)
+                .map(SnapshotResponse::Chunk)
+                .unwrap_or(SnapshotResponse::NotAvailable),
+            };
+            network.respond_snapshot(id, response)?;
+          }
+          // snapshots are fetched only before the chain starts
+          NetworkEvent::SnapshotReceived { .. }
+          | NetworkEvent::SnapshotFailed(_) => {}
           NetworkEvent::VoteReceived(vote) => {
             producer.record_vote(vote);
           },
@@ -231,7 +407,7 @@ async fn main() -> anyhow::Result<()> {
            block_reply_responder.request(block_hash);
           }
           NetworkEvent::TransactionReceived(tx) => {
-            producer.record_transaction(tx);
+            producer.record_transaction(tx, &chain.next_limits());
           }
         }
       },
@@ -255,14 +431,19 @@ async fn main() -> anyhow::Result<()> {
           },
           ChainEvent::BlockDiscarded(block) => {
             info!("discarded block {block}");
-            producer.reuse_discarded(block);
+            producer.reuse_discarded(block, &chain.next_limits());
           }
           ChainEvent::BlockMissing(hash) => {
             info!(
               "Block {} is missing, requesting replay.",
               hash.to_bytes().to_b58()
             );
-            network.gossip_missing(hash)?
+
+            // ask a peer directly if there is one, otherwise
+            // ask the whole network for a replay.
+            if !block_sync.missing(hash) {
+              network.gossip_missing(hash)?
+            }
           }
           ChainEvent::BlockIncluded(block) => {
             info!(
@@ -292,6 +473,7 @@ async fn main() -> anyhow::Result<()> {
               block.height() / genesis.epoch_blocks,
               block.state().hash().to_bytes().to_b58()
             );
+            block_sync.finalized(&*block, chain.finalized_limits());
             consumers.consume(block, Commitment::Finalized)?;
           }
         }
@@ -2,10 +2,12 @@ use crate::vm::Transaction;
 
 mod episub;
 pub mod responder;
+pub mod snapshot;
+pub mod sync;
 
 use {
   crate::{
-    consensus::{BlockData, Genesis, Produced, Vote},
+    consensus::{BlockData, Genesis, Produced, ProtocolSchedule, Vote},
     primitives::{Keypair, Pubkey, ToBase58String},
   },
   episub::{Config, Episub, EpisubEvent, PeerAuthorizer},
@@ -15,16 +17,36 @@ use {
     dns::{DnsConfig, ResolverConfig, ResolverOpts},
     identity::{self, ed25519::SecretKey},
     noise,
+    request_response::{
+      ProtocolSupport,
+      RequestId,
+      RequestResponse,
+      RequestResponseConfig,
+      RequestResponseEvent,
+      RequestResponseMessage,
+      ResponseChannel,
+    },
     swarm::SwarmEvent,
     tcp::TcpConfig,
     yamux::YamuxConfig,
     Multiaddr,
+    NetworkBehaviour,
     PeerId,
     Swarm,
     Transport,
   },
   multihash::Multihash,
-  std::collections::HashSet,
+  snapshot::{
+    SnapshotCodec,
+    SnapshotProtocol,
+    SnapshotRequest,
+    SnapshotResponse,
+  },
+  std::{
+    collections::{HashMap, HashSet},
+    iter,
+  },
+  sync::{SyncCodec, SyncProtocol, SyncRequest},
   tokio::sync::mpsc::{
     error::SendError,
     unbounded_channel,
@@ -67,6 +89,41 @@ async fn create_transport(
   )
 }
 
+/// Gossip between validators and direct block and
+/// snapshot sync requests share the same connections.
+#[derive(NetworkBehaviour)]
+#[behaviour(out_event = "BehaviourEvent", event_process = false)]
+struct Behaviour {
+  episub: Episub,
+  sync: RequestResponse<SyncCodec>,
+  snapshot: RequestResponse<SnapshotCodec>,
+}
+
+#[allow(clippy::large_enum_variant)]
+enum BehaviourEvent {
+  Episub(EpisubEvent),
+  Sync(RequestResponseEvent<SyncRequest, Vec<u8>>),
+  Snapshot(RequestResponseEvent<SnapshotRequest, Vec<u8>>),
+}
+
+impl From<EpisubEvent> for BehaviourEvent {
+  fn from(event: EpisubEvent) -> Self {
+    BehaviourEvent::Episub(event)
+  }
+}
+
+impl From<RequestResponseEvent<SyncRequest, Vec<u8>>> for BehaviourEvent {
+  fn from(event: RequestResponseEvent<SyncRequest, Vec<u8>>) -> Self {
+    BehaviourEvent::Sync(event)
+  }
+}
+
+impl From<RequestResponseEvent<SnapshotRequest, Vec<u8>>> for BehaviourEvent {
+  fn from(event: RequestResponseEvent<SnapshotRequest, Vec<u8>>) -> Self {
+    BehaviourEvent::Snapshot(event)
+  }
+}
+
 // this is a bug in clippy, I filed an issue on GH:
 // https://github.com/rust-lang/rust-clippy/issues/8321
 // remove this when the issue gets closed.
@@ -77,6 +134,42 @@ pub enum NetworkEvent<D: BlockData> {
   BlockReceived(Produced<D>),
   VoteReceived(Vote),
   TransactionReceived(Transaction),
+  PeerConnected(PeerId),
+  PeerDisconnected(PeerId),
+
+  /// A peer asks this validator for blocks, the response
+  /// is sent using [`Network::respond_blocks`].
+  BlocksRequested {
+    id: RequestId,
+    request: SyncRequest,
+  },
+
+  /// Blocks received from a peer in response to a sync request,
+  /// they are not verified yet.
+  BlocksReceived {
+    peer: PeerId,
+    blocks: Vec<Produced<D>>,
+  },
+
+  /// A sync request sent to a peer failed or timed out.
+  SyncFailed(PeerId),
+
+  /// A peer asks this validator for a state snapshot, the
+  /// response is sent using [`Network::respond_snapshot`].
+  SnapshotRequested {
+    id: RequestId,
+    request: SnapshotRequest,
+  },
+
+  /// A response from a peer to a snapshot request,
+  /// it is not verified yet.
+  SnapshotReceived {
+    peer: PeerId,
+    response: SnapshotResponse<D>,
+  },
+
+  /// A snapshot request sent to a peer failed or timed out.
+  SnapshotFailed(PeerId),
 }
 // this is a bug in clippy, I filed an issue on GH:
 // https://github.com/rust-lang/rust-clippy/issues/8321
@@ -89,6 +182,10 @@ pub enum NetworkCommand<D: BlockData> {
   GossipBlock(Produced<D>),
   GossipVote(Vote),
   GossipTransaction(Transaction),
+  RequestBlocks(PeerId, SyncRequest),
+  RespondBlocks(RequestId, Vec<Produced<D>>),
+  RequestSnapshot(PeerId, SnapshotRequest),
+  RespondSnapshot(RequestId, SnapshotResponse<D>),
 }
 
 pub struct Network<D: BlockData> {
@@ -129,34 +226,51 @@ impl<D: BlockData> Network<D> {
       vset.contains(&pubkey)
     });
 
+    // the transport must be able to carry blocks of all protocol
+    // versions, including future upgrades. Governance can't raise
+    // the block size beyond that.
+    let max_transmit_size = ProtocolSchedule::new(genesis).max_block_size();
+
     let mut swarm = Swarm::new(
       create_transport(&keypair).await?,
-      Episub::new(Config {
-        authorizer,
-        active_view_factor: 4,
-        network_size: genesis.validators.len(),
-        max_transmit_size: genesis.limits.max_block_size,
-        history_window: genesis.slot_interval * 6,
-        lazy_push_window: genesis.slot_interval * 4,
-        shuffle_probability: 0.3, // shuffle only 30% of peers at once
-        ..Config::default()
-      }),
+      Behaviour {
+        episub: Episub::new(Config {
+          authorizer,
+          active_view_factor: 4,
+          network_size: genesis.validators.len(),
+          max_transmit_size,
+          history_window: genesis.slot_interval * 6,
+          lazy_push_window: genesis.slot_interval * 4,
+          shuffle_probability: 0.3, // shuffle only 30% of peers at once
+          ..Config::default()
+        }),
+        sync: RequestResponse::new(
+          SyncCodec::new(max_transmit_size),
+          iter::once((
+            SyncProtocol::new(&genesis.chain_id),
+            ProtocolSupport::Full,
+          )),
+          RequestResponseConfig::default(),
+        ),
+        snapshot: RequestResponse::new(
+          SnapshotCodec::new(max_transmit_size),
+          iter::once((
+            SnapshotProtocol::new(&genesis.chain_id),
+            ProtocolSupport::Full,
+          )),
+          RequestResponseConfig::default(),
+        ),
+      },
       id.public().to_peer_id(),
     );
 
     let chainid = genesis.chain_id.clone();
 
-    swarm
-      .behaviour_mut()
-      .subscribe(format!("/{}/vote", &chainid));
-    swarm.behaviour_mut().subscribe(format!("/{}/tx", &chainid));
-    swarm
-      .behaviour_mut()
-      .subscribe(format!("/{}/block", &chainid));
-    swarm
-      .behaviour_mut()
-      .subscribe(format!("/{}/replay", &chainid));
-    swarm.behaviour_mut().subscribe(format!("/{}/tx", &chainid));
+    let episub = &mut swarm.behaviour_mut().episub;
+    episub.subscribe(format!("/{}/vote", &chainid));
+    episub.subscribe(format!("/{}/tx", &chainid));
+    episub.subscribe(format!("/{}/block", &chainid));
+    episub.subscribe(format!("/{}/replay", &chainid));
 
     listenaddrs.for_each(|addr| {
       swarm.listen_on(addr).unwrap();
@@ -165,52 +279,175 @@ impl<D: BlockData> Network<D> {
     let (netin_tx, netin_rx) = unbounded_channel();
     let (netout_tx, mut netout_rx) = unbounded_channel();
 
+    // inbound sync requests waiting for a response from the validator
+    let mut responses = HashMap::<RequestId, ResponseChannel<Vec<u8>>>::new();
+    let mut snapshot_responses =
+      HashMap::<RequestId, ResponseChannel<Vec<u8>>>::new();
+
     tokio::spawn(async move {
       loop {
         tokio::select! {
           Some(event) = swarm.next() => {
-            if let SwarmEvent::Behaviour(EpisubEvent::Message {
-              topic,
-              payload,
-              ..
-            }) = event
-            {
-              if topic == format!("/{}/vote", chainid) {
-                match bincode::deserialize(&payload) {
-                  Ok(vote) => {
-                    netin_tx.send(NetworkEvent::VoteReceived(vote)).unwrap();
+            match event {
+              SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                netin_tx.send(NetworkEvent::PeerConnected(peer_id)).unwrap();
+              }
+              SwarmEvent::ConnectionClosed {
+                peer_id,
+                num_established: 0,
+                ..
+              } => {
+                netin_tx.send(NetworkEvent::PeerDisconnected(peer_id)).unwrap();
+              }
+              SwarmEvent::Behaviour(BehaviourEvent::Sync(event)) => {
+                match event {
+                  RequestResponseEvent::Message {
+                    message: RequestResponseMessage::Request {
+                      request_id,
+                      request,
+                      channel,
+                    },
+                    ..
+                  } => {
+                    responses.insert(request_id, channel);
+                    netin_tx.send(NetworkEvent::BlocksRequested {
+                      id: request_id,
+                      request,
+                    }).unwrap();
                   }
-                  Err(e) => error!("Failed to deserialize vote: {e}"),
-                }
-              } else if topic == format!("/{}/block", chainid) {
-                match bincode::deserialize(&payload) {
-                  Ok(block) => {
-                    debug!("received block {block} through gossip");
-                    netin_tx.send(NetworkEvent::BlockReceived(block)).unwrap();
+                  RequestResponseEvent::Message {
+                    peer,
+                    message: RequestResponseMessage::Response { response, .. },
+                  } => {
+                    match bincode::deserialize(&response) {
+                      Ok(blocks) => {
+                        netin_tx.send(NetworkEvent::BlocksReceived {
+                          peer,
+                          blocks,
+                        }).unwrap();
+                      }
+                      Err(e) => {
+                        error!("Failed to deserialize blocks from {peer}: {e}");
+                        netin_tx.send(NetworkEvent::SyncFailed(peer)).unwrap();
+                      }
+                    }
+                  }
+                  RequestResponseEvent::OutboundFailure {
+                    peer,
+                    error,
+                    ..
+                  } => {
+                    warn!("Sync request to {peer} failed: {error}");
+                    netin_tx.send(NetworkEvent::SyncFailed(peer)).unwrap();
+                  }
+                  RequestResponseEvent::InboundFailure {
+                    peer,
+                    request_id,
+                    error,
+                  } => {
+                    debug!("Sync response to {peer} failed: {error}");
+                    responses.remove(&request_id);
                   }
-                  Err(e) => error!("Failed to deserialize block: {e}"),
+                  RequestResponseEvent::ResponseSent { .. } => {}
                 }
-              } else if topic == format!("/{}/replay", chainid) {
-                match Multihash::from_bytes(&payload) {
-                  Ok(block_hash) => {
-                    debug!(
-                      "received request for a missiong block replay {} through gossip",
-                      payload.as_ref().to_b58());
-                    netin_tx.send(NetworkEvent::MissingBlock(block_hash)).unwrap();
+              }
+              SwarmEvent::Behaviour(BehaviourEvent::Snapshot(event)) => {
+                match event {
+                  RequestResponseEvent::Message {
+                    message: RequestResponseMessage::Request {
+                      request_id,
+                      request,
+                      channel,
+                    },
+                    ..
+                  } => {
+                    snapshot_responses.insert(request_id, channel);
+                    netin_tx.send(NetworkEvent::SnapshotRequested {
+                      id: request_id,
+                      request,
+                    }).unwrap();
+                  }
+                  RequestResponseEvent::Message {
+                    peer,
+                    message: RequestResponseMessage::Response { response, .. },
+                  } => {
+                    match bincode::deserialize(&response) {
+                      Ok(response) => {
+                        netin_tx.send(NetworkEvent::SnapshotReceived {
+                          peer,
+                          response,
+                        }).unwrap();
+                      }
+                      Err(e) => {
+                        error!("Failed to deserialize snapshot from {peer}: {e}");
+                        netin_tx.send(NetworkEvent::SnapshotFailed(peer)).unwrap();
+                      }
+                    }
+                  }
+                  RequestResponseEvent::OutboundFailure {
+                    peer,
+                    error,
+                    ..
+                  } => {
+                    warn!("Snapshot request to {peer} failed: {error}");
+                    netin_tx.send(NetworkEvent::SnapshotFailed(peer)).unwrap();
                   }
-                  Err(e) => error!("Failed to deserialize missing block hash: {e}"),
+                  RequestResponseEvent::InboundFailure {
+                    peer,
+                    request_id,
+                    error,
+                  } => {
+                    debug!("Snapshot response to {peer} failed: {error}");
+                    snapshot_responses.remove(&request_id);
+                  }
+                  RequestResponseEvent::ResponseSent { .. } => {}
                 }
-              } else if topic == format!("/{}/tx", chainid) {
-                match bincode::deserialize(&payload) {
-                  Ok(transaction) => {
-                    debug!("received transaction {transaction} through gossip");
-                    netin_tx.send(NetworkEvent::TransactionReceived(transaction)).unwrap();
+              }
+              SwarmEvent::Behaviour(BehaviourEvent::Episub(
+                EpisubEvent::Message {
+                  topic,
+                  payload,
+                  ..
+                }
+              )) => {
+                if topic == format!("/{}/vote", chainid) {
+                  match bincode::deserialize(&payload) {
+                    Ok(vote) => {
+                      netin_tx.send(NetworkEvent::VoteReceived(vote)).unwrap();
+                    }
+                    Err(e) => error!("Failed to deserialize vote: {e}"),
+                  }
+                } else if topic == format!("/{}/block", chainid) {
+                  match bincode::deserialize(&payload) {
+                    Ok(block) => {
+                      debug!("received block {block} through gossip");
+                      netin_tx.send(NetworkEvent::BlockReceived(block)).unwrap();
+                    }
+                    Err(e) => error!("Failed to deserialize block: {e}"),
+                  }
+                } else if topic == format!("/{}/replay", chainid) {
+                  match Multihash::from_bytes(&payload) {
+                    Ok(block_hash) => {
+                      debug!(
+                        "received request for a missiong block replay {} through gossip",
+                        payload.as_ref().to_b58());
+                      netin_tx.send(NetworkEvent::MissingBlock(block_hash)).unwrap();
+                    }
+                    Err(e) => error!("Failed to deserialize missing block hash: {e}"),
+                  }
+                } else if topic == format!("/{}/tx", chainid) {
+                  match bincode::deserialize(&payload) {
+                    Ok(transaction) => {
+                      debug!("received transaction {transaction} through gossip");
+                      netin_tx.send(NetworkEvent::TransactionReceived(transaction)).unwrap();
+                    }
+                    Err(e) => error!("Failed to deserialize transaction: {e}"),
                   }
-                  Err(e) => error!("Failed to deserialize transaction: {e}"),
+                } else {
+                  warn!("Received a message on an unexpected topic {topic}");
                 }
-              } else {
-                warn!("Received a message on an unexpected topic {topic}");
               }
+              _ => {}
             }
           },
           Some(event) = netout_rx.recv() => {
@@ -223,6 +460,7 @@ impl<D: BlockData> Network<D> {
               NetworkCommand::GossipMissing(block_hash) => {
                 swarm
                 .behaviour_mut()
+                .episub
                 .publish(
                   &format!("/{}/replay", chainid),
                   block_hash.to_bytes())
@@ -231,6 +469,7 @@ impl<D: BlockData> Network<D> {
               NetworkCommand::GossipBlock(block) => {
                 swarm
                 .behaviour_mut()
+                .episub
                 .publish(
                   &format!("/{}/block", chainid),
                   bincode::serialize(&block).expect("failed to serialize block"))
@@ -239,6 +478,7 @@ impl<D: BlockData> Network<D> {
               NetworkCommand::GossipVote(vote) => {
                 swarm
                 .behaviour_mut()
+                .episub
                 .publish(
                   &format!("/{}/vote", chainid),
                   bincode::serialize(&vote).expect("Produced malformed vote"))
@@ -247,11 +487,36 @@ impl<D: BlockData> Network<D> {
               NetworkCommand::GossipTransaction(transaction) => {
                 swarm
                 .behaviour_mut()
+                .episub
                 .publish(
                   &format!("/{}/tx", chainid),
                   bincode::serialize(&transaction).expect("failed to serialize transaction"))
                 .unwrap();
               }
+              NetworkCommand::RequestBlocks(peer, request) => {
+                swarm.behaviour_mut().sync.send_request(&peer, request);
+              }
+              NetworkCommand::RespondBlocks(id, blocks) => {
+                if let Some(channel) = responses.remove(&id) {
+                  let blocks = bincode::serialize(&blocks)
+                    .expect("failed to serialize blocks");
+                  if swarm.behaviour_mut().sync.send_response(channel, blocks).is_err() {
+                    debug!("Sync request {id} is no longer awaiting response");
+                  }
+                }
+              }
+              NetworkCommand::RequestSnapshot(peer, request) => {
+                swarm.behaviour_mut().snapshot.send_request(&peer, request);
+              }
+              NetworkCommand::RespondSnapshot(id, response) => {
+                if let Some(channel) = snapshot_responses.remove(&id) {
+                  let response = bincode::serialize(&response)
+                    .expect("failed to serialize snapshot response");
+                  if swarm.behaviour_mut().snapshot.send_response(channel, response).is_err() {
+                    debug!("Snapshot request {id} is no longer awaiting response");
+                  }
+                }
+              }
             }
           }
         }
@@ -298,6 +563,44 @@ impl<D: BlockData> Network<D> {
     self.netout.send(NetworkCommand::GossipMissing(hash))
   }
 
+  pub fn request_blocks(
+    &mut self,
+    peer: PeerId,
+    request: SyncRequest,
+  ) -> Result<(), SendError<NetworkCommand<D>>> {
+    self
+      .netout
+      .send(NetworkCommand::RequestBlocks(peer, request))
+  }
+
+  pub fn respond_blocks(
+    &mut self,
+    id: RequestId,
+    blocks: Vec<Produced<D>>,
+  ) -> Result<(), SendError<NetworkCommand<D>>> {
+    self.netout.send(NetworkCommand::RespondBlocks(id, blocks))
+  }
+
+  pub fn request_snapshot(
+    &mut self,
+    peer: PeerId,
+    request: SnapshotRequest,
+  ) -> Result<(), SendError<NetworkCommand<D>>> {
+    self
+      .netout
+      .send(NetworkCommand::RequestSnapshot(peer, request))
+  }
+
+  pub fn respond_snapshot(
+    &mut self,
+    id: RequestId,
+    response: SnapshotResponse<D>,
+  ) -> Result<(), SendError<NetworkCommand<D>>> {
+    self
+      .netout
+      .send(NetworkCommand::RespondSnapshot(id, response))
+  }
+
   pub async fn poll(&mut self) -> Option<NetworkEvent<D>> {
     self.netin.recv().await
   }
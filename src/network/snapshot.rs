@@ -0,0 +1,610 @@
+//! State snapshot sync protocol
+//!
+//! A new validator may start from a recent snapshot of the finalized state
+//! instead of replaying all blocks since genesis. Snapshots are fetched
+//! directly from connected peers in two steps:
+//!   1. Every connected peer is asked for the manifests of the snapshots it
+//!      stores along with the finalized blocks they were taken at. Peers
+//!      are authenticated validators, so the response of a peer is its
+//!      attestation that the manifest describes the state at that block.
+//!   2. Once a manifest is attested by validators holding more than two
+//!      thirds of the stake, the same majority that finalizes blocks, the
+//!      manifest is trusted. Honest validators only take snapshots of
+//!      finalized blocks and attest only the snapshots they took, so the
+//!      block is finalized and all accounts in the snapshot are those of
+//!      the honest validators. Its chunks are downloaded one at a time
+//!      from the attesting peers and each chunk is verified against its
+//!      hash in the manifest.
+//!
+//! Before the downloaded state is used, it is also checked against the
+//! state hash of the block it was taken at, see `PersistentState::restore`.
+//!
+//! Blocks finalized after the snapshot are then fetched through the
+//! regular block sync protocol.
+
+use {
+  super::sync::invalid_data,
+  crate::{
+    consensus::{
+      Block,
+      BlockData,
+      Genesis,
+      Produced,
+      ProtocolSchedule,
+      Stakes,
+    },
+    primitives::Pubkey,
+    storage::{Manifest, CHUNK_SIZE, SNAPSHOTS_KEPT},
+  },
+  futures::{AsyncRead, AsyncWrite, AsyncWriteExt, Stream},
+  libp2p::{
+    core::{
+      upgrade::{read_length_prefixed, write_length_prefixed},
+      ProtocolName,
+    },
+    request_response::RequestResponseCodec,
+    PeerId,
+  },
+  multihash::Multihash,
+  serde::{Deserialize, Serialize},
+  std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+  },
+  thiserror::Error,
+  tracing::{info, warn},
+};
+
+/// Requests are tiny, anything larger is malformed.
+const MAX_REQUEST_SIZE: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SnapshotRequest {
+  /// Manifests of all snapshots stored by the peer.
+  Manifests,
+
+  /// The contents of a chunk of a stored snapshot.
+  Chunk { height: u64, index: usize },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "D: Serialize, D: Eq, for<'a> D: Deserialize<'a>")]
+pub enum SnapshotResponse<D: BlockData> {
+  /// Stored snapshots along with the finalized block they were taken at.
+  Manifests(Vec<(Manifest, Produced<D>)>),
+
+  /// The contents of a requested chunk.
+  Chunk(Vec<u8>),
+
+  /// The peer does not have the requested snapshot.
+  NotAvailable,
+}
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+  #[error("Received a snapshot response from {0} that was not requested")]
+  Unrequested(PeerId),
+
+  #[error("Block {0} does not match its snapshot manifest")]
+  InvalidBlock(String),
+
+  #[error("Chunk {0} does not match its snapshot manifest")]
+  InvalidChunk(usize),
+
+  #[error("Peer does not have the requested snapshot")]
+  NotAvailable,
+}
+
+/// The name and version of the snapshot protocol on a given chain.
+#[derive(Debug, Clone)]
+pub struct SnapshotProtocol(String);
+
+impl SnapshotProtocol {
+  pub fn new(chain_id: &str) -> Self {
+    Self(format!("/rensa/{chain_id}/snapshot/1"))
+  }
+}
+
+impl ProtocolName for SnapshotProtocol {
+  fn protocol_name(&self) -> &[u8] {
+    self.0.as_bytes()
+  }
+}
+
+/// Encodes snapshot requests and responses as length-prefixed bincode.
+///
+/// Responses are bincode encoded [`SnapshotResponse`] values, they are
+/// decoded by the network layer that knows the type of data carried
+/// by blocks.
+#[derive(Debug, Clone)]
+pub struct SnapshotCodec {
+  max_response_size: usize,
+}
+
+impl SnapshotCodec {
+  /// Responses must be able to carry either a chunk or manifests
+  /// along with blocks of the largest size allowed by any protocol
+  /// version of the chain.
+  pub fn new(max_block_size: usize) -> Self {
+    Self {
+      max_response_size: CHUNK_SIZE * 2 + max_block_size * SNAPSHOTS_KEPT,
+    }
+  }
+}
+
+#[async_trait::async_trait]
+impl RequestResponseCodec for SnapshotCodec {
+  type Protocol = SnapshotProtocol;
+  type Request = SnapshotRequest;
+  type Response = Vec<u8>;
+
+  async fn read_request<T>(
+    &mut self,
+    _: &SnapshotProtocol,
+    io: &mut T,
+  ) -> io::Result<SnapshotRequest>
+  where
+    T: AsyncRead + Unpin + Send,
+  {
+    let bytes = read_length_prefixed(io, MAX_REQUEST_SIZE).await?;
+    bincode::deserialize(&bytes).map_err(invalid_data)
+  }
+
+  async fn read_response<T>(
+    &mut self,
+    _: &SnapshotProtocol,
+    io: &mut T,
+  ) -> io::Result<Vec<u8>>
+  where
+    T: AsyncRead + Unpin + Send,
+  {
+    read_length_prefixed(io, self.max_response_size).await
+  }
+
+  async fn write_request<T>(
+    &mut self,
+    _: &SnapshotProtocol,
+    io: &mut T,
+    request: SnapshotRequest,
+  ) -> io::Result<()>
+  where
+    T: AsyncWrite + Unpin + Send,
+  {
+    let bytes = bincode::serialize(&request).map_err(invalid_data)?;
+    write_length_prefixed(io, bytes).await?;
+    io.close().await
+  }
+
+  async fn write_response<T>(
+    &mut self,
+    _: &SnapshotProtocol,
+    io: &mut T,
+    response: Vec<u8>,
+  ) -> io::Result<()>
+  where
+    T: AsyncWrite + Unpin + Send,
+  {
+    write_length_prefixed(io, response).await?;
+    io.close().await
+  }
+}
+
+/// A snapshot that was downloaded and verified.
+pub struct Snapshot<D: BlockData> {
+  pub manifest: Manifest,
+  pub block: Produced<D>,
+  pub chunks: Vec<Vec<u8>>,
+}
+
+/// A manifest and the peers that attested it.
+struct Attestation<D: BlockData> {
+  manifest: Manifest,
+  block: Produced<D>,
+  peers: Vec<PeerId>,
+}
+
+/// A trusted snapshot whose chunks are being downloaded.
+struct Download<D: BlockData> {
+  snapshot: Snapshot<D>,
+
+  /// Peers that attested the manifest in the order
+  /// they are asked for chunks.
+  peers: VecDeque<PeerId>,
+
+  /// The peer that was asked for the next chunk.
+  inflight: Option<PeerId>,
+}
+
+/// Collects snapshot manifests from peers, decides which snapshot is
+/// trusted and downloads its chunks.
+pub struct SnapshotSync<D: BlockData> {
+  /// Stakes of validators at genesis, this node has no other
+  /// knowledge of the validator set before it has any state.
+  stakes: Stakes,
+
+  /// Limits that decide which validators take part in the
+  /// consensus at the height of a snapshot.
+  schedule: ProtocolSchedule,
+
+  /// Connected peers.
+  peers: VecDeque<PeerId>,
+
+  /// Peers that were asked for their manifests.
+  asked: HashSet<PeerId>,
+
+  /// Manifests received from peers by their hash.
+  attestations: HashMap<Multihash, Attestation<D>>,
+
+  /// The trusted snapshot that is being downloaded.
+  download: Option<Download<D>>,
+}
+
+impl<D: BlockData> SnapshotSync<D> {
+  pub fn new(genesis: &Genesis<D>) -> Self {
+    Self {
+      stakes: Stakes::new(genesis),
+      schedule: ProtocolSchedule::new(genesis),
+      peers: VecDeque::new(),
+      asked: HashSet::new(),
+      attestations: HashMap::new(),
+      download: None,
+    }
+  }
+
+  /// Peers that are currently connected.
+  pub fn peers(&self) -> impl Iterator<Item = &PeerId> {
+    self.peers.iter()
+  }
+
+  /// Returns true once a trusted snapshot is being downloaded.
+  pub fn is_downloading(&self) -> bool {
+    self.download.is_some()
+  }
+
+  pub fn add_peer(&mut self, peer: PeerId) {
+    if !self.peers.contains(&peer) {
+      self.peers.push_back(peer);
+    }
+  }
+
+  pub fn remove_peer(&mut self, peer: &PeerId) {
+    self.failed(peer);
+    self.peers.retain(|p| p != peer);
+    self.asked.remove(peer);
+  }
+
+  /// Called when a request to a peer failed. A chunk that
+  /// could not be downloaded is requested from the next peer.
+  pub fn failed(&mut self, peer: &PeerId) {
+    if let Some(ref mut download) = self.download {
+      if download.inflight.as_ref() == Some(peer) {
+        download.inflight = None;
+        download.peers.retain(|p| p != peer);
+
+        if download.peers.is_empty() {
+          // no attesting peer can serve this snapshot,
+          // start over with manifests from all peers.
+          warn!("abandoning snapshot download, no peers to serve it");
+          let manifest = &download.snapshot.manifest;
+          self.attestations.remove(&manifest.hash());
+          self.asked.clear();
+          self.download = None;
+        }
+      }
+    }
+  }
+
+  /// Processes a response of a peer to a snapshot request.
+  pub fn receive(
+    &mut self,
+    peer: &PeerId,
+    response: SnapshotResponse<D>,
+  ) -> Result<(), SnapshotError> {
+    let result = match response {
+      SnapshotResponse::Manifests(manifests) => self.attest(peer, manifests),
+      SnapshotResponse::Chunk(chunk) => self.chunk(peer, chunk),
+      SnapshotResponse::NotAvailable => Err(SnapshotError::NotAvailable),
+    };
+
+    if let Err(ref e) = result {
+      warn!("snapshot response from {peer} rejected: {e}");
+      self.failed(peer);
+    }
+
+    result
+  }
+
+  /// Returns the downloaded snapshot once all its chunks are received.
+  pub fn complete(&mut self) -> Option<Snapshot<D>> {
+    let snapshot = &self.download.as_ref()?.snapshot;
+    if snapshot.chunks.len() == snapshot.manifest.chunks.len() {
+      self.download.take().map(|d| d.snapshot)
+    } else {
+      None
+    }
+  }
+
+  fn attest(
+    &mut self,
+    peer: &PeerId,
+    manifests: Vec<(Manifest, Produced<D>)>,
+  ) -> Result<(), SnapshotError> {
+    if !self.asked.contains(peer) {
+      return Err(SnapshotError::Unrequested(*peer));
+    }
+
+    // snapshots must be taken at the blocks they claim
+    // and those blocks must be signed by their producers.
+    for (manifest, block) in &manifests {
+      if block.height != manifest.height
+        || block.hash().ok() != Some(manifest.block)
+        || !block.verify_signature()
+      {
+        return Err(SnapshotError::InvalidBlock(block.to_string()));
+      }
+    }
+
+    for (manifest, block) in manifests {
+      let attestation = self
+        .attestations
+        .entry(manifest.hash())
+        .or_insert_with(|| Attestation {
+          manifest,
+          block,
+          peers: vec![],
+        });
+      if !attestation.peers.contains(peer) {
+        attestation.peers.push(*peer);
+      }
+    }
+
+    if self.download.is_none() {
+      self.download = self.trusted().map(|attestation| {
+        info!(
+          "downloading state snapshot at block {} attested by {} peers",
+          attestation.block,
+          attestation.peers.len()
+        );
+        Download {
+          snapshot: Snapshot {
+            manifest: attestation.manifest.clone(),
+            block: attestation.block.clone(),
+            chunks: vec![],
+          },
+          peers: attestation.peers.iter().cloned().collect(),
+          inflight: None,
+        }
+      });
+    }
+
+    Ok(())
+  }
+
+  /// The most recent snapshot attested by validators holding
+  /// more than two thirds of the stake at its height.
+  fn trusted(&self) -> Option<&Attestation<D>> {
+    self
+      .attestations
+      .values()
+      .filter(|attestation| {
+        let limits = &self.schedule.at(attestation.manifest.height).limits;
+        let attested: u64 = attestation
+          .peers
+          .iter()
+          .map(|peer| self.stakes.stake(&Pubkey::from(*peer), limits))
+          .sum();
+        attested * 3 > self.stakes.total(limits) * 2
+      })
+      .max_by_key(|attestation| attestation.manifest.height)
+  }
+
+  fn chunk(
+    &mut self,
+    peer: &PeerId,
+    chunk: Vec<u8>,
+  ) -> Result<(), SnapshotError> {
+    let download = match self.download {
+      Some(ref mut download) if download.inflight.as_ref() == Some(peer) => {
+        download
+      }
+      _ => return Err(SnapshotError::Unrequested(*peer)),
+    };
+
+    let snapshot = &mut download.snapshot;
+    let index = snapshot.chunks.len();
+    if !snapshot.manifest.verify_chunk(index, &chunk) {
+      return Err(SnapshotError::InvalidChunk(index));
+    }
+
+    snapshot.chunks.push(chunk);
+    download.inflight = None;
+    Ok(())
+  }
+}
+
+impl<D: BlockData> Unpin for SnapshotSync<D> {}
+impl<D: BlockData> Stream for SnapshotSync<D> {
+  type Item = (PeerId, SnapshotRequest);
+
+  fn poll_next(
+    mut self: Pin<&mut Self>,
+    _: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    let this = &mut *self;
+    match this.download {
+      Some(ref mut download) => {
+        let snapshot = &download.snapshot;
+        let index = snapshot.chunks.len();
+        if download.inflight.is_some()
+          || index == snapshot.manifest.chunks.len()
+        {
+          return Poll::Pending;
+        }
+
+        // chunks are requested from attesting
+        // peers in turns to spread the load.
+        let peer = match download.peers.pop_front() {
+          Some(peer) => peer,
+          None => return Poll::Pending,
+        };
+        download.peers.push_back(peer);
+        download.inflight = Some(peer);
+
+        let height = snapshot.manifest.height;
+        Poll::Ready(Some((peer, SnapshotRequest::Chunk { height, index })))
+      }
+      None => {
+        let asked = &this.asked;
+        match this.peers.iter().find(|p| !asked.contains(p)) {
+          Some(peer) => {
+            let peer = *peer;
+            this.asked.insert(peer);
+            Poll::Ready(Some((peer, SnapshotRequest::Manifests)))
+          }
+          None => Poll::Pending,
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{
+      consensus::validator::Validator,
+      primitives::Keypair,
+      test::utils::genesis_default,
+      vm::Transaction,
+    },
+    libp2p::identity,
+    multihash::{Code as MultihashCode, MultihashDigest},
+  };
+
+  fn peer_id(keypair: &Keypair) -> PeerId {
+    identity::Keypair::Ed25519(
+      identity::ed25519::SecretKey::from_bytes(keypair.secret().to_bytes())
+        .unwrap()
+        .into(),
+    )
+    .public()
+    .to_peer_id()
+  }
+
+  fn next(
+    sync: &mut SnapshotSync<Vec<Transaction>>,
+  ) -> Option<(PeerId, SnapshotRequest)> {
+    let waker = futures::task::noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    match Pin::new(sync).poll_next(&mut cx) {
+      Poll::Ready(request) => request,
+      Poll::Pending => None,
+    }
+  }
+
+  #[test]
+  fn attested_snapshot_test() {
+    let validators = [Keypair::unique(), Keypair::unique(), Keypair::unique()];
+    let mut genesis = genesis_default(&validators[0]);
+    genesis.validators = validators
+      .iter()
+      .map(|v| Validator {
+        pubkey: v.public(),
+        stake: 100000,
+      })
+      .collect();
+
+    let block = Produced::new(
+      &validators[0],
+      1,
+      genesis.hash().unwrap(),
+      vec![],
+      genesis.hash().unwrap(),
+      vec![],
+    )
+    .unwrap();
+
+    let chunks = vec![b"first".to_vec(), b"second".to_vec()];
+    let manifest = Manifest {
+      height: 1,
+      block: block.hash().unwrap(),
+      chunks: chunks
+        .iter()
+        .map(|c| MultihashCode::Sha3_256.digest(c))
+        .collect(),
+      output_chunks: 0,
+    };
+
+    let (first, second, third) = (
+      peer_id(&validators[1]),
+      peer_id(&validators[2]),
+      peer_id(&validators[0]),
+    );
+    let mut sync = SnapshotSync::new(&genesis);
+    sync.add_peer(first);
+    sync.add_peer(second);
+    sync.add_peer(third);
+
+    // all peers are asked for their manifests
+    assert_eq!(next(&mut sync), Some((first, SnapshotRequest::Manifests)));
+    assert_eq!(next(&mut sync), Some((second, SnapshotRequest::Manifests)));
+    assert_eq!(next(&mut sync), Some((third, SnapshotRequest::Manifests)));
+    assert_eq!(next(&mut sync), None);
+
+    // manifests must match the blocks they were taken at
+    let mut invalid = manifest.clone();
+    invalid.height = 2;
+    let response = SnapshotResponse::Manifests(vec![(invalid, block.clone())]);
+    assert!(matches!(
+      sync.receive(&first, response),
+      Err(SnapshotError::InvalidBlock(_))
+    ));
+
+    // two thirds of the stake are not enough to trust a snapshot
+    let response =
+      SnapshotResponse::Manifests(vec![(manifest.clone(), block.clone())]);
+    sync.receive(&first, response.clone()).unwrap();
+    sync.receive(&second, response.clone()).unwrap();
+    assert!(!sync.is_downloading());
+    assert_eq!(next(&mut sync), None);
+
+    sync.receive(&third, response).unwrap();
+    assert!(sync.is_downloading());
+
+    // chunks that don't match the manifest are requested again
+    // from the next peer that attested the manifest.
+    let chunk = SnapshotRequest::Chunk {
+      height: 1,
+      index: 0,
+    };
+    assert_eq!(next(&mut sync), Some((first, chunk.clone())));
+    assert_eq!(next(&mut sync), None);
+    assert!(matches!(
+      sync.receive(&first, SnapshotResponse::Chunk(b"forged".to_vec())),
+      Err(SnapshotError::InvalidChunk(0))
+    ));
+    assert_eq!(next(&mut sync), Some((second, chunk)));
+    sync
+      .receive(&second, SnapshotResponse::Chunk(chunks[0].clone()))
+      .unwrap();
+    assert!(sync.complete().is_none());
+
+    let chunk = SnapshotRequest::Chunk {
+      height: 1,
+      index: 1,
+    };
+    assert_eq!(next(&mut sync), Some((third, chunk)));
+    sync
+      .receive(&third, SnapshotResponse::Chunk(chunks[1].clone()))
+      .unwrap();
+
+    let snapshot = sync.complete().unwrap();
+    assert_eq!(snapshot.manifest, manifest);
+    assert_eq!(snapshot.block.hash().unwrap(), manifest.block);
+    assert_eq!(snapshot.chunks, chunks);
+    assert!(!sync.is_downloading());
+  }
+}
@@ -0,0 +1,829 @@
+//! Block sync protocol
+//!
+//! A validator that falls behind the rest of the network fetches the
+//! blocks it is missing directly from a connected peer instead of asking
+//! for them one at a time on the replay gossip topic.
+//!
+//! Two kinds of requests are supported:
+//!   1. A range of finalized blocks by height. Those blocks are served
+//!      from the block store of the peer and are verified to form an
+//!      unbroken chain of blocks produced by staking validators that
+//!      extends the last finalized block known to the requesting
+//!      validator. Only blocks that carry the votes of more than 2/3
+//!      of the stake are included, the rest is recovered later.
+//!   2. A volatile block by its hash. The peer responds with the block
+//!      and its ancestors that are not finalized yet, oldest first, so
+//!      a gap in the volatile history is filled in one round trip.
+//!
+//! Verified blocks are included in the chain in bulk, where they go
+//! through the regular block validation and execution. Volatile blocks
+//! that no connected peer can serve are requested through gossip.
+
+use {
+  crate::{
+    consensus::{Block, BlockData, Genesis, Limits, Produced, Stakes},
+    primitives::Pubkey,
+  },
+  futures::{AsyncRead, AsyncWrite, AsyncWriteExt, Stream},
+  libp2p::{
+    core::{
+      upgrade::{read_length_prefixed, write_length_prefixed},
+      ProtocolName,
+    },
+    request_response::RequestResponseCodec,
+    PeerId,
+  },
+  multihash::Multihash,
+  serde::{Deserialize, Serialize},
+  std::{
+    collections::{HashMap, VecDeque},
+    io,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+  },
+  thiserror::Error,
+  tracing::{debug, warn},
+};
+
+/// The maximum number of blocks returned by a single sync response.
+pub const MAX_SYNC_BLOCKS: u64 = 64;
+
+/// Requests are tiny, anything larger is malformed.
+const MAX_REQUEST_SIZE: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncRequest {
+  /// Up to `count` consecutive finalized blocks starting at a height.
+  Finalized { from: u64, count: u64 },
+
+  /// A block that is not finalized yet along with its
+  /// non-finalized ancestors.
+  Volatile(
+    #[serde(with = "crate::primitives::b58::serde::multihash")] Multihash,
+  ),
+}
+
+#[derive(Debug, Error)]
+pub enum SyncError {
+  #[error("Received blocks from {0} that were not requested")]
+  Unrequested(PeerId),
+
+  #[error("Received more blocks than requested")]
+  TooManyBlocks,
+
+  #[error("Block {0} does not extend the known history")]
+  BrokenHistory(String),
+
+  #[error("Block {0} has an invalid signature")]
+  InvalidSignature(String),
+
+  #[error("Block {0} was not produced by a staking validator")]
+  UnknownProducer(String),
+
+  #[error("Block {0} is not finalized by a 2/3 majority of the stake")]
+  NotFinalized(String),
+
+  #[error("Peer does not have the requested blocks")]
+  NotAvailable,
+}
+
+/// The name and version of the sync protocol on a given chain.
+#[derive(Debug, Clone)]
+pub struct SyncProtocol(String);
+
+impl SyncProtocol {
+  pub fn new(chain_id: &str) -> Self {
+    Self(format!("/rensa/{chain_id}/sync/1"))
+  }
+}
+
+impl ProtocolName for SyncProtocol {
+  fn protocol_name(&self) -> &[u8] {
+    self.0.as_bytes()
+  }
+}
+
+/// Encodes sync requests and responses as length-prefixed bincode.
+///
+/// Responses are bincode encoded lists of blocks, they are decoded by
+/// the network layer that knows the type of data carried by blocks.
+#[derive(Debug, Clone)]
+pub struct SyncCodec {
+  max_response_size: usize,
+}
+
+impl SyncCodec {
+  /// Responses must be able to carry [`MAX_SYNC_BLOCKS`] blocks of the
+  /// largest size allowed by any protocol version of the chain.
+  pub fn new(max_block_size: usize) -> Self {
+    Self {
+      max_response_size: max_block_size * MAX_SYNC_BLOCKS as usize,
+    }
+  }
+}
+
+pub(super) fn invalid_data(error: bincode::Error) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[async_trait::async_trait]
+impl RequestResponseCodec for SyncCodec {
+  type Protocol = SyncProtocol;
+  type Request = SyncRequest;
+  type Response = Vec<u8>;
+
+  async fn read_request<T>(
+    &mut self,
+    _: &SyncProtocol,
+    io: &mut T,
+  ) -> io::Result<SyncRequest>
+  where
+    T: AsyncRead + Unpin + Send,
+  {
+    let bytes = read_length_prefixed(io, MAX_REQUEST_SIZE).await?;
+    bincode::deserialize(&bytes).map_err(invalid_data)
+  }
+
+  async fn read_response<T>(
+    &mut self,
+    _: &SyncProtocol,
+    io: &mut T,
+  ) -> io::Result<Vec<u8>>
+  where
+    T: AsyncRead + Unpin + Send,
+  {
+    read_length_prefixed(io, self.max_response_size).await
+  }
+
+  async fn write_request<T>(
+    &mut self,
+    _: &SyncProtocol,
+    io: &mut T,
+    request: SyncRequest,
+  ) -> io::Result<()>
+  where
+    T: AsyncWrite + Unpin + Send,
+  {
+    let bytes = bincode::serialize(&request).map_err(invalid_data)?;
+    write_length_prefixed(io, bytes).await?;
+    io.close().await
+  }
+
+  async fn write_response<T>(
+    &mut self,
+    _: &SyncProtocol,
+    io: &mut T,
+    response: Vec<u8>,
+  ) -> io::Result<()>
+  where
+    T: AsyncWrite + Unpin + Send,
+  {
+    write_length_prefixed(io, response).await?;
+    io.close().await
+  }
+}
+
+/// The next step of the block sync.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncAction {
+  /// Send a request to a connected peer.
+  Request(PeerId, SyncRequest),
+
+  /// None of the connected peers has the block,
+  /// ask the whole network for a replay.
+  Replay(Multihash),
+}
+
+/// Decides which blocks to fetch from which peer and verifies
+/// the blocks received in response.
+///
+/// Only one request is in flight at a time. Requests are sent to the
+/// first known peer, a peer that fails a request or responds with
+/// blocks that can't be verified is moved to the back of the queue.
+pub struct BlockSync {
+  /// Connected peers in the order they are asked for blocks.
+  peers: VecDeque<PeerId>,
+
+  /// Height and hash of the most recent block in the finalized
+  /// history, all fetched finalized blocks must extend it.
+  finalized: (u64, Multihash),
+
+  /// Stakes of validators that produce and finalize blocks.
+  stakes: Stakes,
+
+  /// Limits in effect at the most recent finalized block.
+  limits: Limits,
+
+  /// Set once a range of finalized blocks was received, the next
+  /// range is requested after the chain finalized some of them.
+  including: bool,
+
+  /// The height of the most recent block observed on the network
+  /// when it is too far ahead of this validator to be recovered
+  /// through volatile blocks alone.
+  target: u64,
+
+  /// The most recent volatile block that is missing.
+  wanted: Option<Multihash>,
+
+  /// The request that awaits a response.
+  inflight: Option<(PeerId, SyncRequest)>,
+
+  /// Number of failed attempts of the current request,
+  /// it is abandoned once all peers have failed it.
+  attempts: usize,
+
+  /// Volatile blocks that all peers failed to serve.
+  replays: VecDeque<Multihash>,
+
+  /// Wakes up the task polling this stream when a new
+  /// request can be made.
+  waker: Option<Waker>,
+}
+
+impl BlockSync {
+  pub fn new<D, B>(genesis: &Genesis<D>, finalized: &B, limits: &Limits) -> Self
+  where
+    D: BlockData,
+    B: Block<D> + ?Sized,
+  {
+    Self {
+      peers: VecDeque::new(),
+      finalized: (finalized.height(), finalized.hash().unwrap()),
+      stakes: Stakes::new(genesis),
+      limits: limits.clone(),
+      including: false,
+      target: 0,
+      wanted: None,
+      inflight: None,
+      attempts: 0,
+      replays: VecDeque::new(),
+      waker: None,
+    }
+  }
+
+  pub fn add_peer(&mut self, peer: PeerId) {
+    if !self.peers.contains(&peer) {
+      self.peers.push_back(peer);
+      self.wake();
+    }
+  }
+
+  pub fn remove_peer(&mut self, peer: &PeerId) {
+    self.peers.retain(|p| p != peer);
+    self.failed(peer);
+  }
+
+  /// Called for every block received through gossip along with the
+  /// height of the current head of the chain on this validator.
+  pub fn observe<D: BlockData>(&mut self, block: &Produced<D>, head: u64) {
+    let gap = block.height.saturating_sub(head);
+    if gap > 1 {
+      self.wanted = Some(block.parent);
+    }
+
+    if gap > MAX_SYNC_BLOCKS {
+      self.target = self.target.max(block.height);
+    }
+
+    if gap > 1 {
+      self.wake();
+    }
+  }
+
+  /// Requests a block that is missing from the volatile history.
+  ///
+  /// Returns false if there is no connected peer to ask for it,
+  /// the block has to be requested from the network instead.
+  pub fn missing(&mut self, hash: Multihash) -> bool {
+    self.wanted = Some(hash);
+    self.wake();
+    !self.peers.is_empty()
+  }
+
+  /// Called whenever the chain finalizes a block along
+  /// with the limits in effect at that block.
+  pub fn finalized<D, B>(&mut self, block: &B, limits: &Limits)
+  where
+    D: BlockData,
+    B: Block<D> + ?Sized,
+  {
+    if block.height() > self.finalized.0 {
+      self.finalized = (block.height(), block.hash().unwrap());
+      self.limits = limits.clone();
+      self.including = false;
+      self.wake();
+    }
+  }
+
+  /// Called when a request to a peer failed, it is
+  /// retried with the next peer.
+  pub fn failed(&mut self, peer: &PeerId) {
+    if let Some((p, request)) = self.inflight.take() {
+      if &p != peer {
+        self.inflight = Some((p, request));
+        return;
+      }
+
+      self.attempts += 1;
+      if self.attempts >= self.peers.len() {
+        // no peer can serve this request
+        debug!("abandoning sync request {request:?}");
+        self.abandon(&request);
+      } else if let SyncRequest::Volatile(hash) = request {
+        self.wanted.get_or_insert(hash);
+      }
+
+      if let Some(pos) = self.peers.iter().position(|p| p == peer) {
+        let peer = self.peers.remove(pos).unwrap();
+        self.peers.push_back(peer);
+      }
+      self.wake();
+    }
+  }
+
+  fn abandon(&mut self, request: &SyncRequest) {
+    self.attempts = 0;
+    match request {
+      SyncRequest::Finalized { .. } => self.target = 0,
+      SyncRequest::Volatile(hash) => self.replays.push_back(*hash),
+    }
+  }
+
+  fn wake(&mut self) {
+    if let Some(waker) = self.waker.take() {
+      waker.wake();
+    }
+  }
+
+  /// Verifies the blocks received from a peer in response to the
+  /// request in flight and returns them in the order they should be
+  /// included in the chain.
+  pub fn receive<D: BlockData>(
+    &mut self,
+    peer: &PeerId,
+    blocks: Vec<Produced<D>>,
+  ) -> Result<Vec<Produced<D>>, SyncError> {
+    let request = match &self.inflight {
+      Some((p, request)) if p == peer => request.clone(),
+      _ => return Err(SyncError::Unrequested(*peer)),
+    };
+
+    let mut blocks = blocks;
+    let result = match request {
+      SyncRequest::Finalized { count, .. } => {
+        self.verify_finalized(&blocks, count).map(|finalized| {
+          self.including = true;
+          if (blocks.len() as u64) < count {
+            // the peer has no finalized blocks beyond those,
+            // the rest is recovered through volatile blocks.
+            self.target = 0;
+          }
+          blocks.truncate(finalized);
+        })
+      }
+      SyncRequest::Volatile(hash) => verify_volatile(&blocks, &hash),
+    };
+
+    match result {
+      Ok(()) => {
+        self.inflight = None;
+        self.attempts = 0;
+        self.wake();
+        Ok(blocks)
+      }
+      Err(e) => {
+        warn!("sync response from {peer} rejected: {e}");
+        self.failed(peer);
+        Err(e)
+      }
+    }
+  }
+
+  /// Finalized blocks must form an unbroken chain of consecutive
+  /// heights produced by staking validators that extends the most
+  /// recent finalized block.
+  ///
+  /// A block is finalized once validators with more than 2/3 of the
+  /// stake voted on it or on its descendants. Those votes are carried
+  /// by later blocks, so the last returned blocks usually lack them.
+  /// At least the first block must be finalized by votes in the
+  /// returned blocks.
+  ///
+  /// Returns the number of leading blocks that are finalized,
+  /// the remaining blocks are not included.
+  fn verify_finalized<D: BlockData>(
+    &self,
+    blocks: &[Produced<D>],
+    count: u64,
+  ) -> Result<usize, SyncError> {
+    if blocks.len() as u64 > count {
+      return Err(SyncError::TooManyBlocks);
+    }
+
+    let (mut height, mut hash) = self.finalized;
+    let mut positions = HashMap::new();
+    for (position, block) in blocks.iter().enumerate() {
+      if block.height != height + 1 || block.parent != hash {
+        return Err(SyncError::BrokenHistory(block.to_string()));
+      }
+
+      if self.stakes.stake(&block.signature.0, &self.limits) == 0 {
+        return Err(SyncError::UnknownProducer(block.to_string()));
+      }
+
+      if !block.verify_signature() {
+        return Err(SyncError::InvalidSignature(block.to_string()));
+      }
+
+      height = block.height;
+      hash = block
+        .hash()
+        .map_err(|_| SyncError::BrokenHistory(block.to_string()))?;
+      positions.insert(hash, position);
+    }
+
+    // a vote on a block is also a vote on all its ancestors,
+    // so only the most recent target of each validator matters.
+    let mut voters: HashMap<Pubkey, usize> = HashMap::new();
+    for vote in blocks.iter().flat_map(|b| b.votes.iter()) {
+      if let Some(&position) = positions.get(&vote.target) {
+        if vote.verify_signature().is_ok() {
+          let latest = voters.entry(vote.validator).or_insert(position);
+          *latest = position.max(*latest);
+        }
+      }
+    }
+
+    // the number of leading blocks that more than 2/3 of the stake
+    // voted on, found by walking back from the most recent target.
+    let total = self.stakes.total(&self.limits);
+    let mut targets: Vec<(usize, u64)> = voters
+      .iter()
+      .map(|(v, position)| (*position, self.stakes.stake(v, &self.limits)))
+      .collect();
+    targets.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+
+    let mut votes = 0;
+    let mut finalized = 0;
+    for (position, stake) in targets {
+      votes += stake;
+      if votes * 3 > total * 2 {
+        finalized = position + 1;
+        break;
+      }
+    }
+
+    if let Some(first) = blocks.first() {
+      if finalized == 0 {
+        return Err(SyncError::NotFinalized(first.to_string()));
+      }
+    }
+
+    debug!(
+      "verified {finalized} of {} finalized blocks up to height {height}",
+      blocks.len()
+    );
+
+    Ok(finalized)
+  }
+}
+
+/// Volatile blocks must be a chain of consecutive blocks that
+/// ends with the requested block.
+fn verify_volatile<D: BlockData>(
+  blocks: &[Produced<D>],
+  requested: &Multihash,
+) -> Result<(), SyncError> {
+  let last = match blocks.last() {
+    Some(last) => last,
+    None => return Err(SyncError::NotAvailable),
+  };
+
+  if blocks.len() as u64 > MAX_SYNC_BLOCKS {
+    return Err(SyncError::TooManyBlocks);
+  }
+
+  if last.hash().ok().as_ref() != Some(requested) {
+    return Err(SyncError::BrokenHistory(last.to_string()));
+  }
+
+  for pair in blocks.windows(2) {
+    let (parent, child) = (&pair[0], &pair[1]);
+    if child.height != parent.height + 1
+      || parent.hash().ok() != Some(child.parent)
+    {
+      return Err(SyncError::BrokenHistory(child.to_string()));
+    }
+  }
+
+  match blocks.iter().find(|b| !b.verify_signature()) {
+    Some(block) => Err(SyncError::InvalidSignature(block.to_string())),
+    None => Ok(()),
+  }
+}
+
+impl Unpin for BlockSync {}
+impl Stream for BlockSync {
+  type Item = SyncAction;
+
+  fn poll_next(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    if let Some(hash) = self.replays.pop_front() {
+      return Poll::Ready(Some(SyncAction::Replay(hash)));
+    }
+
+    self.waker = Some(cx.waker().clone());
+    if self.inflight.is_some() {
+      return Poll::Pending;
+    }
+
+    let peer = match self.peers.front() {
+      Some(peer) => *peer,
+      None => return Poll::Pending,
+    };
+
+    let (height, _) = self.finalized;
+    let request = if self.target > height && !self.including {
+      // catching up on finalized history comes first,
+      // volatile blocks build on top of it.
+      SyncRequest::Finalized {
+        from: height + 1,
+        count: MAX_SYNC_BLOCKS.min(self.target - height),
+      }
+    } else if let Some(hash) = self.wanted.take() {
+      SyncRequest::Volatile(hash)
+    } else {
+      return Poll::Pending;
+    };
+
+    self.inflight = Some((peer, request.clone()));
+    Poll::Ready(Some(SyncAction::Request(peer, request)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{
+      consensus::{validator::Validator, Genesis, Vote},
+      primitives::Keypair,
+      test::utils::genesis_default,
+      vm::Transaction,
+    },
+    futures::task::ArcWake,
+    std::sync::{
+      atomic::{AtomicUsize, Ordering},
+      Arc,
+    },
+  };
+
+  /// Produces a block that carries the vote of
+  /// its producer on the parent block.
+  fn produce(
+    keypair: &Keypair,
+    parent: &dyn Block<Vec<Transaction>>,
+  ) -> Produced<Vec<Transaction>> {
+    let parent_hash = parent.hash().unwrap();
+    Produced::new(
+      keypair,
+      parent.height() + 1,
+      parent_hash,
+      vec![],
+      parent_hash,
+      vec![Vote::new(keypair, parent_hash, parent_hash)],
+    )
+    .unwrap()
+  }
+
+  fn history(
+    keypair: &Keypair,
+    genesis: &Genesis<Vec<Transaction>>,
+    len: usize,
+  ) -> Vec<Produced<Vec<Transaction>>> {
+    let mut blocks: Vec<Produced<Vec<Transaction>>> = vec![];
+    for _ in 0..len {
+      let block = match blocks.last() {
+        Some(parent) => produce(keypair, parent),
+        None => produce(keypair, genesis),
+      };
+      blocks.push(block);
+    }
+    blocks
+  }
+
+  fn poll(sync: &mut BlockSync) -> Option<SyncAction> {
+    let waker = futures::task::noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    match Pin::new(sync).poll_next(&mut cx) {
+      Poll::Ready(action) => action,
+      Poll::Pending => None,
+    }
+  }
+
+  fn next(sync: &mut BlockSync) -> Option<(PeerId, SyncRequest)> {
+    match poll(sync) {
+      Some(SyncAction::Request(peer, request)) => Some((peer, request)),
+      Some(SyncAction::Replay(hash)) => panic!("unexpected replay {hash:?}"),
+      None => None,
+    }
+  }
+
+  #[test]
+  fn finalized_range_sync_test() {
+    let keypair = Keypair::unique();
+    let genesis = genesis_default(&keypair);
+    let blocks = history(&keypair, &genesis, 100);
+    let (first, second) = (PeerId::random(), PeerId::random());
+
+    let mut sync = BlockSync::new(&genesis, &genesis, &genesis.limits);
+    sync.add_peer(first);
+    sync.add_peer(second);
+
+    // a block that is far ahead triggers a finalized range sync
+    sync.observe(&blocks[99], 0);
+    let (to, request) = next(&mut sync).unwrap();
+    assert_eq!(to, first);
+    assert_eq!(request, SyncRequest::Finalized {
+      from: 1,
+      count: MAX_SYNC_BLOCKS
+    });
+
+    // only one request is in flight at a time
+    assert!(next(&mut sync).is_none());
+
+    // the last block is not finalized by votes in the range
+    let range = blocks[..64].to_vec();
+    assert_eq!(sync.receive(&first, range).unwrap().len(), 63);
+
+    // the next range is requested once the chain finalized
+    // some of the received blocks and continues from there
+    assert!(next(&mut sync).is_none());
+    sync.finalized(&blocks[62], &genesis.limits);
+    let (_, request) = next(&mut sync).unwrap();
+    assert_eq!(request, SyncRequest::Finalized {
+      from: 64,
+      count: 37
+    });
+
+    // blocks that don't extend the finalized history are rejected
+    let range = blocks[65..].to_vec();
+    assert!(matches!(
+      sync.receive(&first, range),
+      Err(SyncError::BrokenHistory(_))
+    ));
+
+    // and the request is retried with another peer that has only
+    // some of the blocks finalized, the rest is fetched as volatile.
+    let (to, request) = next(&mut sync).unwrap();
+    assert_eq!(to, second);
+    assert!(matches!(request, SyncRequest::Finalized { from: 64, .. }));
+    let range = blocks[63..80].to_vec();
+    assert_eq!(sync.receive(&second, range).unwrap().len(), 16);
+
+    let (_, request) = next(&mut sync).unwrap();
+    assert_eq!(request, SyncRequest::Volatile(blocks[98].hash().unwrap()));
+  }
+
+  #[test]
+  fn finalized_votes_sync_test() {
+    let keypair = Keypair::unique();
+    let genesis = genesis_default(&keypair);
+    let blocks = history(&keypair, &genesis, 100);
+    let peer = PeerId::random();
+
+    let mut sync = BlockSync::new(&genesis, &genesis, &genesis.limits);
+    sync.add_peer(peer);
+
+    // finalized blocks must be produced by staking validators
+    sync.observe(&blocks[99], 0);
+    next(&mut sync).unwrap();
+    let forged = history(&Keypair::unique(), &genesis, 64);
+    assert!(matches!(
+      sync.receive(&peer, forged),
+      Err(SyncError::UnknownProducer(_))
+    ));
+
+    // and carry the votes of the majority that finalized them
+    sync.observe(&blocks[99], 0);
+    next(&mut sync).unwrap();
+    assert!(matches!(
+      sync.receive(&peer, blocks[..1].to_vec()),
+      Err(SyncError::NotFinalized(_))
+    ));
+
+    sync.observe(&blocks[99], 0);
+    next(&mut sync).unwrap();
+    assert_eq!(sync.receive(&peer, blocks[..2].to_vec()).unwrap().len(), 1);
+
+    // votes of a minority don't finalize a block
+    let other = Keypair::unique();
+    let mut genesis = genesis;
+    genesis.validators.push(Validator {
+      pubkey: other.public(),
+      stake: genesis.validators[0].stake,
+    });
+    let mut sync = BlockSync::new(&genesis, &genesis, &genesis.limits);
+    sync.add_peer(peer);
+    sync.observe(&blocks[99], 0);
+    next(&mut sync).unwrap();
+    assert!(matches!(
+      sync.receive(&peer, blocks[..64].to_vec()),
+      Err(SyncError::NotFinalized(_))
+    ));
+  }
+
+  #[test]
+  fn volatile_sync_test() {
+    let keypair = Keypair::unique();
+    let genesis = genesis_default(&keypair);
+    let blocks = history(&keypair, &genesis, 10);
+    let (first, second) = (PeerId::random(), PeerId::random());
+
+    let mut sync = BlockSync::new(&genesis, &genesis, &genesis.limits);
+    sync.add_peer(first);
+    sync.add_peer(second);
+
+    // blocks that are one ahead of the head are not a gap
+    sync.observe(&blocks[4], 4);
+    assert!(next(&mut sync).is_none());
+
+    sync.observe(&blocks[9], 4);
+    let (to, request) = next(&mut sync).unwrap();
+    let wanted = blocks[8].hash().unwrap();
+    assert_eq!(to, first);
+    assert_eq!(request, SyncRequest::Volatile(wanted));
+
+    // blocks from peers that were not asked are ignored
+    assert!(matches!(
+      sync.receive(&second, blocks[5..9].to_vec()),
+      Err(SyncError::Unrequested(_))
+    ));
+
+    // a peer that does not have the block is skipped
+    assert!(matches!(
+      sync.receive::<Vec<Transaction>>(&first, vec![]),
+      Err(SyncError::NotAvailable)
+    ));
+    let (to, request) = next(&mut sync).unwrap();
+    assert_eq!(to, second);
+    assert_eq!(request, SyncRequest::Volatile(wanted));
+
+    // ancestors must link to the requested block
+    let mut gapped = blocks[5..9].to_vec();
+    gapped.remove(1);
+    assert!(matches!(
+      sync.receive(&second, gapped),
+      Err(SyncError::BrokenHistory(_))
+    ));
+
+    // once all peers failed a request it is requested through gossip
+    assert_eq!(poll(&mut sync), Some(SyncAction::Replay(wanted)));
+    assert!(next(&mut sync).is_none());
+
+    assert!(sync.missing(wanted));
+    let (to, _) = next(&mut sync).unwrap();
+    let received = sync.receive(&to, blocks[5..9].to_vec()).unwrap();
+    assert_eq!(received.len(), 4);
+    assert!(next(&mut sync).is_none());
+
+    // without peers, missing blocks are requested through gossip
+    sync.remove_peer(&first);
+    sync.remove_peer(&second);
+    assert!(!sync.missing(wanted));
+  }
+
+  #[test]
+  fn sync_wakeup_test() {
+    struct Counter(AtomicUsize);
+    impl ArcWake for Counter {
+      fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.fetch_add(1, Ordering::SeqCst);
+      }
+    }
+
+    let keypair = Keypair::unique();
+    let genesis = genesis_default(&keypair);
+    let blocks = history(&keypair, &genesis, 10);
+    let counter = Arc::new(Counter(AtomicUsize::new(0)));
+    let waker = futures::task::waker(Arc::clone(&counter));
+    let mut cx = Context::from_waker(&waker);
+
+    // a pending stream is polled again once there is a request to make
+    let mut sync = BlockSync::new(&genesis, &genesis, &genesis.limits);
+    assert!(Pin::new(&mut sync).poll_next(&mut cx).is_pending());
+    sync.add_peer(PeerId::random());
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+
+    assert!(Pin::new(&mut sync).poll_next(&mut cx).is_pending());
+    sync.observe(&blocks[9], 4);
+    assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+    assert!(matches!(
+      Pin::new(&mut sync).poll_next(&mut cx),
+      Poll::Ready(Some(SyncAction::Request(..)))
+    ));
+  }
+}
@@ -12,7 +12,7 @@ use {
 
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Account {
-  #[serde(skip)]
+  #[serde(default)]
   pub executable: bool,
 
   #[serde(default)]
@@ -36,6 +36,7 @@ impl Account {
   pub fn hash(&self) -> Multihash {
     let mut hasher = Sha3_256::default();
     hasher.update(&self.nonce.to_le_bytes());
+    hasher.update(&[self.executable as u8]);
     if let Some(ref owner) = self.owner {
       hasher.update(owner.as_ref());
     }
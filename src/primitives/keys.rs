@@ -1,8 +1,6 @@
 use {
   borsh::{BorshDeserialize, BorshSerialize},
-  curve25519_dalek::edwards::CompressedEdwardsY,
   ed25519_dalek::{PublicKey, SecretKey},
-  multihash::{Hasher, Sha3_256},
   rand::RngCore,
   serde::{
     de::{self, Visitor},
@@ -49,23 +47,27 @@ impl Pubkey {
   ///
   /// The same set of seeds will always return the same
   /// derived address, so it can be used as a hashmap
-  /// in contracts.
+  /// in contracts. The derivation is shared with the SDK
+  /// through [`rensa_abi::derive_address`].
   pub fn derive(&self, seeds: &[&[u8]]) -> Self {
-    let mut bump: u64 = 0;
-    loop {
-      let mut hasher = Sha3_256::default();
-      hasher.update(&self.0);
-      for seed in seeds.iter() {
-        hasher.update(seed);
-      }
-      hasher.update(&bump.to_le_bytes());
-      let key = Pubkey(hasher.finalize().try_into().unwrap());
-      if !key.has_private_key() {
-        return key;
-      } else {
-        bump += 1;
-      }
-    }
+    self.find_derived(seeds).0
+  }
+
+  /// Same as [`Pubkey::derive`] but also returns the bump value
+  /// that was found to produce an address off the Ed25519 curve.
+  pub fn find_derived(&self, seeds: &[&[u8]]) -> (Self, u64) {
+    let (address, bump) = rensa_abi::derive_address(&self.0, seeds);
+    (Self(address), bump)
+  }
+
+  /// Derives an address using a previously found bump value.
+  ///
+  /// Returns [`None`] if the resulting address lies on the Ed25519
+  /// curve. Note that a bump smaller than the one returned by
+  /// [`Pubkey::find_derived`] may still produce an off-curve address
+  /// that is different from the one returned by [`Pubkey::derive`].
+  pub fn derive_with_bump(&self, seeds: &[&[u8]], bump: u64) -> Option<Self> {
+    rensa_abi::derive_address_with_bump(&self.0, seeds, bump).map(Self)
   }
 
   /// Checks if the given pubkey lies on the Ed25519 elliptic curve.
@@ -79,9 +81,7 @@ impl Pubkey {
   /// it is not possible to have a signer of a transaction that will
   /// give write access to an account.
   pub fn has_private_key(&self) -> bool {
-    CompressedEdwardsY::from_slice(&self.0)
-      .decompress()
-      .is_some()
+    rensa_abi::is_on_curve(&self.0)
   }
 
   #[cfg(test)]
@@ -97,6 +97,12 @@ impl AsRef<[u8]> for Pubkey {
   }
 }
 
+impl From<[u8; 32]> for Pubkey {
+  fn from(bytes: [u8; 32]) -> Self {
+    Self(bytes)
+  }
+}
+
 impl Deref for Pubkey {
   type Target = [u8];
 
@@ -312,7 +318,7 @@ impl Serialize for Pubkey {
 
 #[cfg(test)]
 mod test {
-  use super::Pubkey;
+  use {super::Pubkey, serde::Deserialize};
 
   #[test]
   fn pubkey_derive_some() {
@@ -330,4 +336,42 @@ mod test {
       assert!(!pk.has_private_key());
     }
   }
+
+  #[derive(Deserialize)]
+  struct DeriveVector {
+    base: Pubkey,
+    seeds: Vec<String>,
+    bump: u64,
+    derived: Pubkey,
+  }
+
+  /// The same vectors are verified by the SDK, so contracts and
+  /// the VM always agree on derived addresses.
+  #[test]
+  fn pubkey_derive_vectors() {
+    let vectors: Vec<DeriveVector> =
+      serde_json::from_str(include_str!("../../abi/vectors/derive.json"))
+        .unwrap();
+    assert!(!vectors.is_empty());
+
+    for vector in vectors {
+      let seeds: Vec<Vec<u8>> = vector
+        .seeds
+        .iter()
+        .map(|s| bs58::decode(s).into_vec().unwrap())
+        .collect();
+      let seeds: Vec<&[u8]> = seeds.iter().map(|s| s.as_slice()).collect();
+
+      assert_eq!(vector.base.derive(&seeds), vector.derived);
+      assert_eq!(
+        vector.base.find_derived(&seeds),
+        (vector.derived, vector.bump)
+      );
+      assert_eq!(
+        vector.base.derive_with_bump(&seeds, vector.bump),
+        Some(vector.derived)
+      );
+      assert!(!vector.derived.has_private_key());
+    }
+  }
 }
@@ -12,24 +12,34 @@ use {
   std::{
     collections::BTreeMap,
     pin::Pin,
-    sync::Arc,
+    sync::{
+      atomic::{AtomicU64, Ordering},
+      Arc,
+    },
     task::{Context, Poll},
   },
   tracing::{debug, info},
 };
 
 struct MempoolState {
+  chain: Multihash,
   validators: DashSet<Pubkey>,
   votes: DashMap<[u8; 64], Vote>,
   txs: DashMap<Multihash, Transaction>,
+
+  /// The height of the most recent block seen by this
+  /// validator, either produced or included.
+  height: AtomicU64,
 }
 
 impl MempoolState {
-  pub fn new(validators: DashSet<Pubkey>) -> Self {
+  pub fn new(chain: Multihash, validators: DashSet<Pubkey>) -> Self {
     Self {
+      chain,
       votes: DashMap::new(),
       txs: DashMap::new(),
       validators,
+      height: AtomicU64::new(0),
     }
   }
 
@@ -41,7 +51,7 @@ impl MempoolState {
   }
 
   pub fn add_transaction(&self, transaction: Transaction) {
-    if transaction.verify_signatures().is_ok() {
+    if transaction.verify_signatures(&self.chain).is_ok() {
       debug!("adding transaction {transaction} to mempool");
       self.txs.insert(*transaction.hash(), transaction);
     }
@@ -66,7 +76,16 @@ impl MempoolState {
   ///      issued by the same payer, and in that case only the first transaction
   ///      hash in the sequence of increasing nonces is used during sorting by
   /// hash.
-  pub fn take_transactions(&self, count: usize) -> Vec<Transaction> {
+  ///
+  /// Transactions that can no longer be included in a block at the given
+  /// height are dropped from the mempool.
+  pub fn take_transactions(
+    &self,
+    count: usize,
+    height: u64,
+  ) -> Vec<Transaction> {
+    self.prune_expired(height);
+
     // keep all transactions sorted by ther hashes (MEV mitigation)
     let mut output = BTreeMap::new();
 
@@ -95,9 +114,14 @@ impl MempoolState {
     output.iter().for_each(|tx| {
       self.txs.remove(tx.hash());
     });
-    
+
     output
   }
+
+  /// Removes all transactions that expire before the given height.
+  pub fn prune_expired(&self, height: u64) {
+    self.txs.retain(|_, tx| !tx.is_expired(height));
+  }
 }
 
 /// This type is responsible for maintaining a list of transactions
@@ -110,7 +134,6 @@ impl MempoolState {
 /// other validators.
 pub struct BlockProducer {
   keypair: Keypair,
-  limits: Limits,
   mempool: Arc<MempoolState>,
   pending: Option<Produced<Vec<Transaction>>>,
 }
@@ -120,7 +143,6 @@ impl Clone for BlockProducer {
     Self {
       keypair: self.keypair.clone(),
       mempool: Arc::clone(&self.mempool),
-      limits: self.limits.clone(),
       pending: None,
     }
   }
@@ -130,8 +152,8 @@ impl BlockProducer {
   pub fn new(genesis: &Genesis<Vec<Transaction>>, keypair: Keypair) -> Self {
     BlockProducer {
       keypair,
-      limits: genesis.limits.clone(),
       mempool: Arc::new(MempoolState::new(
+        genesis.hash().unwrap(),
         genesis.validators.iter().map(|v| v.pubkey).collect(),
       )),
       pending: None,
@@ -144,25 +166,23 @@ impl BlockProducer {
     prev: &dyn Block<Vec<Transaction>>,
     vm: &vm::Machine,
   ) {
-    let prevheight = prev.height();
+    let height = prev.height() + 1;
     let prevhash = prev.hash().unwrap();
 
+    // blocks are produced under the protocol rules in effect
+    // at their height, including changes enacted by governance.
+    let max_transactions =
+      vm.at_block(state, height).limits().max_block_transactions;
+    self.mempool.height.fetch_max(height, Ordering::Relaxed);
+
     let votes = self.mempool.take_votes();
-    let txs = self
-      .mempool
-      .take_transactions(self.limits.max_block_transactions);
+    let txs = self.mempool.take_transactions(max_transactions, height);
 
-    let blockoutput = txs.execute(vm, state).unwrap();
+    let blockoutput = txs.execute(vm, state, height).unwrap();
     let state_hash = blockoutput.hash();
-    let block = Produced::new(
-      &self.keypair,
-      prevheight + 1,
-      prevhash,
-      txs,
-      *state_hash,
-      votes,
-    )
-    .unwrap();
+    let block =
+      Produced::new(&self.keypair, height, prevhash, txs, *state_hash, votes)
+        .unwrap();
     info!(
       "Produced {block} on top of {} with {} transactions with state hash: {}",
       prevhash.to_b58(),
@@ -172,7 +192,11 @@ impl BlockProducer {
     self.pending = Some(block);
   }
 
-  pub fn reuse_discarded(&self, block: Produced<Vec<Transaction>>) {
+  pub fn reuse_discarded(
+    &self,
+    block: Produced<Vec<Transaction>>,
+    limits: &Limits,
+  ) {
     // try to reuse votes
     for vote in block.votes {
       self.record_vote(vote);
@@ -180,7 +204,7 @@ impl BlockProducer {
 
     // try to reinclude transactions
     for tx in block.data {
-      self.record_transaction(tx);
+      self.record_transaction(tx, limits);
     }
   }
 
@@ -188,8 +212,10 @@ impl BlockProducer {
     self.mempool.add_vote(vote);
   }
 
-  pub fn record_transaction(&self, transaction: Transaction) {
-    if transaction.verify_limits(&self.limits).is_ok() {
+  /// Transactions are checked against the limits that apply
+  /// to the next block that could include them.
+  pub fn record_transaction(&self, transaction: Transaction, limits: &Limits) {
+    if transaction.verify_limits(limits).is_ok() {
       self.mempool.add_transaction(transaction);
     }
   }
@@ -229,6 +255,14 @@ impl BlockConsumer<Vec<Transaction>> for BlockProducer {
       for tx in &block.data {
         self.mempool.txs.remove(tx.hash());
       }
+
+      // transactions that expire before the next block
+      // height will never make it into the chain.
+      self.mempool.prune_expired(block.height + 1);
+      self
+        .mempool
+        .height
+        .fetch_max(block.height, Ordering::Relaxed);
     }
   }
 }
@@ -1,10 +1,23 @@
 use {
   crate::{
-    consensus::{Block, Genesis},
+    consensus::{Block, Genesis, Protocol, ProtocolSchedule},
     consumer::Commitment,
     primitives::{Account, Pubkey, ToBase58String},
-    storage::{BlockStore, PersistentState},
-    vm::{State, Transaction},
+    storage::{BlockStore, IndexedEvent, PersistentState},
+    vm::{
+      builtin::{currency, governance::GOVERNANCE_STATE_ADDR, wasm},
+      ContractError,
+      Event,
+      Interface,
+      LegacyTransaction,
+      Overlayed,
+      Receipt,
+      State,
+      StateDiff,
+      Topic,
+      Transaction,
+      TransactionError,
+    },
   },
   axum::{
     extract::{Extension, Path, Query},
@@ -15,6 +28,7 @@ use {
     Router,
   },
   axum_extra::response::ErasedJson,
+  borsh::BorshDeserialize,
   futures::Stream,
   indexmap::IndexMap,
   multihash::Multihash,
@@ -37,6 +51,7 @@ struct ServiceSharedState {
   state: PersistentState,
   blocks: BlockStore,
   genesis: Genesis<BlockType>,
+  protocols: ProtocolSchedule,
   sender: UnboundedSender<Transaction>,
 }
 
@@ -55,6 +70,7 @@ impl ApiService {
     let shared_state = Arc::new(ServiceSharedState {
       state,
       blocks,
+      protocols: ProtocolSchedule::new(&genesis),
       genesis,
       sender,
     });
@@ -63,7 +79,10 @@ impl ApiService {
       .route("/info", get(serve_info))
       .route("/block/:height", get(serve_block))
       .route("/account/:account", get(serve_account))
+      .route("/interface/:contract", get(serve_interface))
+      .route("/nft/:mint", get(serve_nft))
       .route("/transaction/:hash", get(serve_transaction))
+      .route("/events", get(serve_events))
       .route("/transactions", post(serve_send_transactions))
       .layer(Extension(shared_state));
 
@@ -107,7 +126,10 @@ async fn serve_transaction(
         "block": tx.block,
         "commitment": state.blocks.get_block_commitment(tx.block),
         "transaction": tx.transaction,
-        "output": tx.output.map(|o| o.into_iter().collect::<IndexMap<_, _>>())
+        "error": tx.output.as_ref().err().map(error_json),
+        "output": tx.output.map(|o| o.into_iter().collect::<IndexMap<_, _>>()),
+        "events": tx.events.iter().map(event_json).collect::<Vec<_>>(),
+        "receipt": receipt_json(&tx.receipt)
       })),
     )
   } else {
@@ -121,6 +143,122 @@ async fn serve_transaction(
   }
 }
 
+/// Transaction failures, contract-defined errors also carry
+/// their numeric code and optional base58 encoded data.
+fn error_json(error: &ContractError) -> serde_json::Value {
+  match error {
+    ContractError::Custom { code, data } => json!({
+      "message": error.to_string(),
+      "code": code,
+      "data": data.as_ref().map(|d| d.to_b58())
+    }),
+    _ => json!({
+      "message": error.to_string(),
+    }),
+  }
+}
+
+/// Contract events with topics and data encoded as base58.
+fn event_json(event: &Event) -> serde_json::Value {
+  json!({
+    "contract": event.contract,
+    "topics": event.topics.iter().map(|t| t.to_b58()).collect::<Vec<_>>(),
+    "data": event.data.to_b58()
+  })
+}
+
+/// Events of finalized blocks, confirmed blocks are not indexed yet.
+///
+/// Examples:
+///  - /events?contract=Currency1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx
+///  - /events?topic=B5Vsy6UPyGopvAM2GFv9VMyn29As8wjGyMxCQMVAGH6A
+///  - /events?topic=B5Vsy6UPyGopvAM2GFv9VMyn29As8wjGyMxCQMVAGH6A&
+///    contract=Currency1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx
+async fn serve_events(
+  Extension(state): Extension<Arc<ServiceSharedState>>,
+  Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+  let contract = match params.get("contract").map(|c| c.parse::<Pubkey>()) {
+    None => None,
+    Some(Ok(contract)) => Some(contract),
+    Some(Err(_)) => return bad_request("invalid contract address"),
+  };
+
+  let topic = match params.get("topic").map(|t| bs58::decode(t).into_vec()) {
+    None => None,
+    Some(Ok(topic)) => match Topic::try_from(topic.as_slice()) {
+      Ok(topic) => Some(topic),
+      Err(_) => return bad_request("topics must be 32 bytes long"),
+    },
+    Some(Err(_)) => return bad_request("invalid topic encoding"),
+  };
+
+  let events = match (contract, topic) {
+    (None, None) => return bad_request("expecting a contract or a topic"),
+    (Some(contract), None) => state.blocks.get_events_by_contract(&contract),
+    (contract, Some(topic)) => {
+      state.blocks.get_events_by_topic(&topic).map(|events| {
+        events
+          .into_iter()
+          .filter(|e| contract.map(|c| c == e.event.contract).unwrap_or(true))
+          .collect()
+      })
+    }
+  };
+
+  let events: Vec<IndexedEvent> = match events {
+    Ok(events) => events,
+    Err(e) => {
+      return (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        ErasedJson::pretty(json!({ "error": e.to_string() })),
+      )
+    }
+  };
+
+  (
+    StatusCode::OK,
+    ErasedJson::pretty(json!({
+      "events": events
+        .iter()
+        .map(|e| json!({
+          "block": e.block,
+          "transaction": e.transaction.to_b58(),
+          "event": event_json(&e.event)
+        }))
+        .collect::<Vec<_>>()
+    })),
+  )
+}
+
+fn bad_request(error: &str) -> (StatusCode, ErasedJson) {
+  (
+    StatusCode::BAD_REQUEST,
+    ErasedJson::pretty(json!({ "error": error })),
+  )
+}
+
+/// Per-transaction changes to the state, new and modified accounts are
+/// listed with their contents after the transaction, deleted accounts
+/// are null.
+fn receipt_json(receipt: &Receipt) -> serde_json::Value {
+  json!({
+    "created": receipt.created,
+    "written": receipt.written,
+    "deleted": receipt.deleted,
+    "invocations": receipt.invocations,
+    "state": receipt.state
+      .iter()
+      .map(|(addr, acc)| (addr.to_string(), acc.map(|acc| json!({
+        "nonce": acc.nonce,
+        "owner": acc.owner,
+        "executable": acc.executable,
+        "data": acc.data.as_ref().map(|a| a.to_b58())
+      }))))
+      .collect::<IndexMap<_, _>>()
+  })
+}
+
 /// Examples:
 ///  - /accounts/B5Vsy6UPyGopvAM2GFv9VMyn29As8wjGyMxCQMVAGH6A
 ///  - /accounts/B5Vsy6UPyGopvAM2GFv9VMyn29As8wjGyMxCQMVAGH6A?
@@ -166,6 +304,83 @@ async fn serve_account(
   )
 }
 
+/// Serves the interface description of a contract that was uploaded
+/// alongside its bytecode, so explorers can decode its instructions,
+/// accounts data and events.
+///
+/// Examples:
+///  - /interface/B5Vsy6UPyGopvAM2GFv9VMyn29As8wjGyMxCQMVAGH6A
+async fn serve_interface(
+  Path(contract): Path<Pubkey>,
+  Extension(state): Extension<Arc<ServiceSharedState>>,
+  Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+  let address = wasm::interface_address(&contract);
+  let account = match extract_commitment(params) {
+    Commitment::Confirmed => get_confirmed_account(&address, state.as_ref())
+      .or_else(|| state.state.get(&address)),
+    Commitment::Finalized => state.state.get(&address),
+  };
+
+  match account
+    .and_then(|acc| acc.data)
+    .and_then(|data| Interface::try_from_slice(&data).ok())
+  {
+    Some(interface) => (
+      StatusCode::OK,
+      ErasedJson::pretty(json! ({
+        "contract": contract,
+        "interface": interface
+      })),
+    ),
+    None => (
+      StatusCode::NOT_FOUND,
+      ErasedJson::pretty(json! ({
+        "contract": contract,
+        "error": "not_found"
+      })),
+    ),
+  }
+}
+
+/// Serves the metadata of a non-fungible token created
+/// by the currency builtin, including its collection.
+///
+/// Examples:
+///  - /nft/B5Vsy6UPyGopvAM2GFv9VMyn29As8wjGyMxCQMVAGH6A
+async fn serve_nft(
+  Path(mint): Path<Pubkey>,
+  Extension(state): Extension<Arc<ServiceSharedState>>,
+  Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+  let address = currency::metadata_address(&mint);
+  let account = match extract_commitment(params) {
+    Commitment::Confirmed => get_confirmed_account(&address, state.as_ref())
+      .or_else(|| state.state.get(&address)),
+    Commitment::Finalized => state.state.get(&address),
+  };
+
+  match account
+    .and_then(|acc| acc.data)
+    .and_then(|data| currency::Metadata::try_from_slice(&data).ok())
+  {
+    Some(metadata) => (
+      StatusCode::OK,
+      ErasedJson::pretty(json! ({
+        "mint": mint,
+        "metadata": metadata
+      })),
+    ),
+    None => (
+      StatusCode::NOT_FOUND,
+      ErasedJson::pretty(json! ({
+        "mint": mint,
+        "error": "not_found"
+      })),
+    ),
+  }
+}
+
 async fn serve_info(
   Extension(state): Extension<Arc<ServiceSharedState>>,
 ) -> impl IntoResponse {
@@ -181,11 +396,15 @@ async fn serve_info(
     .map(|b| (b.height, b.hash().unwrap()))
     .unwrap_or((0, state.genesis.hash().unwrap()));
 
+  // the protocol rules that apply to the next block
+  let protocol = protocol_at(state.as_ref(), cheight.max(fheight) + 1);
+
   ErasedJson::pretty(json! ({
     "system": {
       "name": "Rensa",
       "version": env!("CARGO_PKG_VERSION")
     },
+    "protocol": protocol,
     "finalized": {
       "height": fheight,
       "block": fhash.to_bytes().to_b58()
@@ -194,6 +413,7 @@ async fn serve_info(
       "height": cheight,
       "block": chash.to_bytes().to_b58(),
     },
+    "chain": state.genesis.hash().unwrap().to_bytes().to_b58(),
     "genesis": state.genesis,
   }))
 }
@@ -230,7 +450,7 @@ async fn serve_block(
           .collect::<IndexMap<_, _>>(),
         "errors": block.output.errors
           .iter()
-          .map(|(txhash, error)| (txhash.to_b58(), error))
+          .map(|(txhash, error)| (txhash.to_b58(), error_json(error)))
           .collect::<IndexMap<_, _>>()
       })),
     )
@@ -244,16 +464,75 @@ async fn serve_block(
   }
 }
 
+/// Reported along with signature errors of transactions submitted in the
+/// single contract format, most likely signed by an outdated client.
+const LEGACY_SIGNATURES: &str = "signatures must cover the chain and \
+                                 expiry, transactions signed by older \
+                                 clients are no longer accepted, see \
+                                 MIGRATION.md";
+
+/// Transactions submitted by clients are accepted in either the
+/// multi-instruction format or the older single contract format.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SubmittedTransaction {
+  Instructions(Transaction),
+  Legacy(LegacyTransaction),
+}
+
+impl TryFrom<SubmittedTransaction> for Transaction {
+  type Error = TransactionError;
+
+  fn try_from(submitted: SubmittedTransaction) -> Result<Self, Self::Error> {
+    match submitted {
+      SubmittedTransaction::Instructions(tx) => Ok(tx),
+      SubmittedTransaction::Legacy(tx) => tx.try_into(),
+    }
+  }
+}
+
 async fn serve_send_transactions(
-  Json(transactions): Json<Vec<Transaction>>,
+  Json(transactions): Json<Vec<SubmittedTransaction>>,
   Extension(state): Extension<Arc<ServiceSharedState>>,
 ) -> impl IntoResponse {
+  let transactions: Vec<(bool, Transaction)> = match transactions
+    .into_iter()
+    .map(|submitted| {
+      let legacy = matches!(submitted, SubmittedTransaction::Legacy(_));
+      Transaction::try_from(submitted).map(|tx| (legacy, tx))
+    })
+    .collect()
+  {
+    Ok(transactions) => transactions,
+    Err(e) => {
+      return (
+        StatusCode::BAD_REQUEST,
+        ErasedJson::pretty(json! ({
+          "error": e.to_string(),
+        })),
+      )
+    }
+  };
+
   let mut outputs = Vec::new();
   let mut nonces = HashMap::new();
+  let chain = state.genesis.hash().unwrap();
 
-  for transaction in transactions.into_iter() {
+  // the height of the next block that could include the transactions
+  let next_height = state
+    .blocks
+    .latest(Commitment::Confirmed)
+    .or_else(|| state.blocks.latest(Commitment::Finalized))
+    .map(|b| b.height)
+    .unwrap_or(0)
+    + 1;
+
+  // the limits that apply to the next block
+  let limits = protocol_at(state.as_ref(), next_height).limits;
+
+  for (legacy, transaction) in transactions {
     // filter out outsized transactions at the RPC level.
-    if let Err(e) = transaction.verify_limits(&state.genesis.limits) {
+    if let Err(e) = transaction.verify_limits(&limits) {
       outputs.push((transaction.hash().to_b58(), e.to_string()));
       continue;
     }
@@ -261,8 +540,23 @@ async fn serve_send_transactions(
     // filter out invalid signatures and addresses at the
     // RPC level before bothering p2p and consensus and
     // other validators.
-    if let Err(e) = transaction.verify_signatures() {
-      outputs.push((transaction.hash().to_b58(), e.to_string()));
+    if let Err(e) = transaction.verify_signatures(&chain) {
+      let error = if legacy {
+        format!("{e}, {LEGACY_SIGNATURES}")
+      } else {
+        e.to_string()
+      };
+      outputs.push((transaction.hash().to_b58(), error));
+      continue;
+    }
+
+    // filter out transactions that can't be included
+    // in any future block anymore.
+    if transaction.is_expired(next_height) {
+      outputs.push((
+        transaction.hash().to_b58(),
+        TransactionError::Expired.to_string(),
+      ));
       continue;
     }
 
@@ -320,6 +614,19 @@ async fn serve_send_transactions(
   )
 }
 
+/// The protocol rules in effect at a given height, including
+/// changes enacted by governance in confirmed blocks.
+fn protocol_at(state: &ServiceSharedState, height: u64) -> Protocol {
+  let governance = get_confirmed_account(&GOVERNANCE_STATE_ADDR, state);
+  let mut confirmed = StateDiff::default();
+  if let Some(account) = governance {
+    confirmed.set(*GOVERNANCE_STATE_ADDR, account).unwrap();
+  }
+
+  let view = Overlayed::new(&state.state, &confirmed);
+  state.protocols.effective(&view, height)
+}
+
 fn extract_commitment(params: HashMap<String, String>) -> Commitment {
   match params.get("commitment") {
     None => Commitment::Finalized,
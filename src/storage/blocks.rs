@@ -3,15 +3,25 @@ use {
   crate::{
     consensus::{Block, Produced},
     consumer::{BlockConsumer, Commitment},
-    vm::{Executed, ExecutedTransaction, Transaction},
+    primitives::Pubkey,
+    vm::{Event, Executed, ExecutedTransaction, Topic, Transaction},
   },
   multihash::Multihash,
+  serde::{Deserialize, Serialize},
   sled::{Db, Tree},
-  std::{path::PathBuf, sync::Arc},
+  std::{collections::BTreeSet, path::PathBuf, sync::Arc},
 };
 
 type BlockType = Vec<Transaction>;
 
+/// An event emitted by a transaction in a stored block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedEvent {
+  pub block: u64,
+  pub transaction: Multihash,
+  pub event: Event,
+}
+
 #[derive(Debug)]
 pub struct BlockStore {
   db: Arc<Db>,
@@ -112,6 +122,32 @@ impl BlockStore {
     None
   }
 
+  /// Returns a finalized block as it was transmitted over the wire,
+  /// this works regardless of whether block outputs are stored.
+  pub fn get_finalized(&self, height: u64) -> Option<Produced<BlockType>> {
+    let finalized = self.db.open_tree(b"finalized").unwrap();
+    finalized
+      .get(&height.to_be_bytes())
+      .unwrap()
+      .and_then(|block| bincode::deserialize(&block).ok())
+  }
+
+  /// Stores the finalized block of a state snapshot that a new node
+  /// starts from in place of the genesis. Later finalized blocks extend
+  /// it, older blocks are never stored by this node.
+  pub fn restore(&self, block: &Produced<BlockType>) -> Result<(), Error> {
+    let finalized = self.db.open_tree(b"finalized")?;
+    let hashes = self.db.open_tree(b"hashes")?;
+    finalized.clear()?;
+    finalized.insert(block.height.to_be_bytes(), bincode::serialize(block)?)?;
+    hashes.insert(
+      block.hash()?.to_bytes(),
+      block.height.to_be_bytes().as_ref(),
+    )?;
+    self.db.flush()?;
+    Ok(())
+  }
+
   pub fn get_block_commitment(&self, height: u64) -> Option<Commitment> {
     let height = height.to_be_bytes();
     let confirmed = self.db.open_tree(b"confirmed").unwrap();
@@ -138,6 +174,49 @@ impl BlockStore {
       .map(|tx| bincode::deserialize(&tx).unwrap())
   }
 
+  /// Returns all events emitted by a contract in stored finalized
+  /// blocks, ordered by the block height and their emission order.
+  pub fn get_events_by_contract(
+    &self,
+    contract: &Pubkey,
+  ) -> Result<Vec<IndexedEvent>, Error> {
+    self.get_indexed_events(b"events_by_contract", contract)
+  }
+
+  /// Returns all events in stored finalized blocks that have the given
+  /// topic at any position, ordered by the block height and their
+  /// emission order.
+  ///
+  /// Account addresses are also topics, so this can be used to query
+  /// for example all currency transfers involving an account.
+  pub fn get_events_by_topic(
+    &self,
+    topic: &Topic,
+  ) -> Result<Vec<IndexedEvent>, Error> {
+    self.get_indexed_events(b"events_by_topic", topic)
+  }
+
+  fn get_indexed_events(
+    &self,
+    index: &[u8],
+    prefix: &[u8],
+  ) -> Result<Vec<IndexedEvent>, Error> {
+    if !self.detailed {
+      return Err(Error::NotDetailed);
+    }
+
+    let index = self.db.open_tree(index)?;
+    let events = self.db.open_tree(b"events")?;
+    let mut indexed = vec![];
+    for key in index.scan_prefix(prefix).keys() {
+      let key = key?;
+      if let Some(event) = events.get(&key[prefix.len()..])? {
+        indexed.push(bincode::deserialize(&event)?);
+      }
+    }
+    Ok(indexed)
+  }
+
   async fn store_raw_block(
     &self,
     block: &Produced<BlockType>,
@@ -181,7 +260,11 @@ impl BlockStore {
   }
 
   /// Stores the results of executed transactions in a block.
-  async fn store_outputs(&self, block: &Executed<BlockType>) {
+  async fn store_outputs(
+    &self,
+    block: &Executed<BlockType>,
+    commitment: Commitment,
+  ) {
     assert!(self.detailed, "detailed storage is set to false");
     let block = block.clone();
     let outputs = self.db.open_tree(b"outputs").unwrap();
@@ -210,12 +293,97 @@ impl BlockStore {
           .map(|e| Err(e.clone()))
           .or_else(|| logs.map(|l| Ok(l.clone())))
           .unwrap(),
+        events: block.output.events.get(txhash).cloned().unwrap_or_default(),
+        receipt: block
+          .output
+          .receipts
+          .get(txhash)
+          .cloned()
+          .unwrap_or_default(),
       };
       let txbytes = bincode::serialize(&tx).unwrap().to_vec();
       txbatch.insert(txhash.to_bytes(), txbytes);
     }
 
     transactions.apply_batch(txbatch).unwrap();
+
+    // a confirmed block may still be replaced by a block of another
+    // fork at the same height, so only finalized events are indexed.
+    if commitment == Commitment::Finalized {
+      self.store_events(&block);
+    }
+  }
+
+  /// Stores all events emitted in a block and indexes them by
+  /// the emitting contract and by each of their topics.
+  ///
+  /// Events are keyed by the block height followed by their position
+  /// in the block, and index keys are the contract address or a topic
+  /// followed by the event key.
+  fn store_events(&self, block: &Executed<BlockType>) {
+    let events = self.db.open_tree(b"events").unwrap();
+    let by_contract = self.db.open_tree(b"events_by_contract").unwrap();
+    let by_topic = self.db.open_tree(b"events_by_topic").unwrap();
+
+    let mut eventsbatch = sled::Batch::default();
+    let mut contractbatch = sled::Batch::default();
+    let mut topicbatch = sled::Batch::default();
+
+    let all = block
+      .output
+      .events
+      .iter()
+      .flat_map(|(tx, events)| events.iter().map(move |e| (tx, e)));
+
+    for (i, (txhash, event)) in all.enumerate() {
+      let key = event_key(block.height, i as u32);
+      let indexed = IndexedEvent {
+        block: block.height,
+        transaction: *txhash,
+        event: event.clone(),
+      };
+
+      eventsbatch.insert(&key[..], bincode::serialize(&indexed).unwrap());
+      contractbatch
+        .insert([event.contract.as_ref(), &key[..]].concat(), vec![]);
+
+      // the same value may appear in many topics of one event
+      let topics: BTreeSet<_> = event.topics.iter().collect();
+      for topic in topics {
+        topicbatch.insert([&topic[..], &key[..]].concat(), vec![]);
+      }
+    }
+
+    events.apply_batch(eventsbatch).unwrap();
+    by_contract.apply_batch(contractbatch).unwrap();
+    by_topic.apply_batch(topicbatch).unwrap();
+  }
+
+  /// Removes all stored events and their index entries
+  /// emitted in blocks lower than the given height.
+  fn prune_events(&self, height: u64) {
+    let events = self.db.open_tree(b"events").unwrap();
+    let by_contract = self.db.open_tree(b"events_by_contract").unwrap();
+    let by_topic = self.db.open_tree(b"events_by_topic").unwrap();
+
+    let mut eventsbatch = sled::Batch::default();
+    let mut contractbatch = sled::Batch::default();
+    let mut topicbatch = sled::Batch::default();
+
+    for entry in events.range(..event_key(height, 0)) {
+      let (key, value) = entry.unwrap();
+      let indexed: IndexedEvent = bincode::deserialize(&value).unwrap();
+      contractbatch
+        .remove([indexed.event.contract.as_ref(), &key[..]].concat());
+      for topic in indexed.event.topics.iter() {
+        topicbatch.remove([&topic[..], &key[..]].concat());
+      }
+      eventsbatch.remove(key);
+    }
+
+    events.apply_batch(eventsbatch).unwrap();
+    by_contract.apply_batch(contractbatch).unwrap();
+    by_topic.apply_batch(topicbatch).unwrap();
   }
 
   /// Removes blocks and all associated outputs, transactions and logs
@@ -259,7 +427,8 @@ impl BlockStore {
         tokio::join!(
           async { prune_tree(outputs, height, false) },
           async { prune_tree(confirmed, height, true) },
-          async { prune_tree(finalized, height, true) }
+          async { prune_tree(finalized, height, true) },
+          async { self.prune_events(height) }
         );
       } else {
         tokio::join!(
@@ -281,6 +450,15 @@ impl Clone for BlockStore {
   }
 }
 
+/// Big endian for lexographic byte order of events
+/// by their block height and position in the block.
+fn event_key(height: u64, pos: u32) -> [u8; 12] {
+  let mut key = [0u8; 12];
+  key[..8].copy_from_slice(&height.to_be_bytes());
+  key[8..].copy_from_slice(&pos.to_be_bytes());
+  key
+}
+
 #[async_trait::async_trait]
 impl BlockConsumer<BlockType> for BlockStore {
   /// The block consumer guarantees that we will get all blocks in order
@@ -295,7 +473,7 @@ impl BlockConsumer<BlockType> for BlockStore {
         // Store the block itself, as it was transmitted over the wire
         self.store_raw_block(block.underlying.as_ref(), commitment),
         // Store transactions outputs for this block
-        self.store_outputs(&block),
+        self.store_outputs(&block, commitment),
         // remove old blocks that are older than the history limit.
         self.prune_older_than(block.height.saturating_sub(self.history_len))
       );
@@ -1,4 +1,5 @@
 mod blocks;
+mod snapshot;
 mod state;
 
 use thiserror::Error;
@@ -13,6 +14,24 @@ pub enum Error {
 
   #[error("System IO Error: {0}")]
   SystemIO(#[from] std::io::Error),
+
+  #[error(
+    "Data directory was initialized by a different genesis, see MIGRATION.md"
+  )]
+  GenesisMismatch,
+
+  #[error("Snapshot chunk {0} does not match its manifest")]
+  InvalidSnapshot(usize),
+
+  #[error("Snapshot does not match the state of block {0}")]
+  SnapshotMismatch(String),
+
+  #[error("Block execution details are not stored by this node")]
+  NotDetailed,
 }
 
-pub use {blocks::BlockStore, state::PersistentState};
+pub use {
+  blocks::{BlockStore, IndexedEvent},
+  snapshot::{Manifest, Snapshots, CHUNK_SIZE, SNAPSHOTS_KEPT},
+  state::PersistentState,
+};
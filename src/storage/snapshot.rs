@@ -0,0 +1,361 @@
+//! State snapshots
+//!
+//! Validators periodically store a snapshot of the finalized state, so
+//! validators that join the network later can start from a recent state
+//! instead of replaying all blocks since genesis, which is impossible once
+//! old blocks were pruned from the history of all nodes.
+//!
+//! A snapshot is the list of all accounts in the state ordered by their
+//! address, split into chunks of roughly [`CHUNK_SIZE`] bytes. Snapshots
+//! are taken at the same heights on all validators and their contents
+//! are deterministic, so the manifests of honest validators at a given
+//! height are identical and can be compared. They are written in the
+//! background while later finalized blocks are applied.
+//!
+//! The account chunks are followed by chunks of the output of the
+//! finalized block. There is no hash of the global state, the state hash
+//! in a block header covers only the changes made by that block, so the
+//! output ties the snapshot to the header: its hash must match the state
+//! hash of the block and all accounts it changed must have the same
+//! contents in the snapshot.
+
+use {
+  super::Error,
+  crate::{
+    primitives::{Account, Pubkey},
+    vm::{BlockOutput, StateDiff},
+  },
+  multihash::{Code as MultihashCode, Multihash, MultihashDigest},
+  serde::{Deserialize, Serialize},
+  sled::Db,
+  std::{
+    collections::BTreeMap,
+    fs,
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+  },
+  tracing::{info, warn},
+};
+
+/// Accounts are packed into chunks of roughly this size in bytes.
+/// A chunk is closed once it reaches this size, so it may exceed
+/// it by at most one encoded account.
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The number of most recent snapshots stored on disk.
+pub const SNAPSHOTS_KEPT: usize = 2;
+
+/// Describes the state of the chain at a finalized block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+  /// Height of the finalized block.
+  pub height: u64,
+
+  /// Hash of the finalized block that produced this state.
+  #[serde(with = "crate::primitives::b58::serde::multihash")]
+  pub block: Multihash,
+
+  /// Hashes of all chunks of the snapshot in order.
+  pub chunks: Vec<Multihash>,
+
+  /// The number of chunks at the end of the snapshot that
+  /// hold the bincode encoded output of the finalized block.
+  pub output_chunks: usize,
+}
+
+impl Manifest {
+  /// Identifies a snapshot, manifests of the same state
+  /// taken by different validators have the same hash.
+  pub fn hash(&self) -> Multihash {
+    MultihashCode::Sha3_256
+      .digest(&bincode::serialize(self).expect("infallible"))
+  }
+
+  /// Checks if the contents of a chunk match the chunk
+  /// at the given position in this manifest.
+  pub fn verify_chunk(&self, index: usize, chunk: &[u8]) -> bool {
+    self.chunks.get(index) == Some(&MultihashCode::Sha3_256.digest(chunk))
+  }
+}
+
+/// Contents of accounts at the height of the snapshot that is being
+/// written, recorded before finalized blocks change them. Accounts
+/// that did not exist at that height are recorded as [`None`].
+type Preimages = BTreeMap<Pubkey, Option<Account>>;
+
+/// Snapshots stored on disk in `<data-dir>/snapshots/<height>/`.
+///
+/// Each snapshot directory contains a bincode encoded manifest and
+/// its chunks as `<index>.chunk` files. Each chunk is a bincode
+/// encoded list of address and account pairs.
+#[derive(Debug, Clone)]
+pub struct Snapshots {
+  directory: PathBuf,
+  interval: u64,
+
+  /// Set while a snapshot is being written in the background.
+  preimages: Arc<Mutex<Option<Preimages>>>,
+
+  /// The thread writing the most recent snapshot.
+  writer: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl Snapshots {
+  /// Creates a new snapshots storage that takes a snapshot of the state
+  /// every `interval` finalized blocks. An interval of zero disables
+  /// taking new snapshots.
+  pub fn new(directory: PathBuf, interval: u64) -> Result<Self, Error> {
+    let mut directory = directory;
+    directory.push("snapshots");
+    std::fs::create_dir_all(directory.clone())?;
+
+    // remove snapshots that were interrupted by a crash
+    for entry in fs::read_dir(&directory)? {
+      let path = entry?.path();
+      if path.extension().map(|e| e == "partial").unwrap_or(false) {
+        fs::remove_dir_all(path)?;
+      }
+    }
+
+    Ok(Self {
+      directory,
+      interval,
+      preimages: Arc::new(Mutex::new(None)),
+      writer: Arc::new(Mutex::new(None)),
+    })
+  }
+
+  /// Checks if a snapshot is taken of the state finalized at a height.
+  pub fn is_due(&self, height: u64) -> bool {
+    self.interval != 0 && height != 0 && height % self.interval == 0
+  }
+
+  /// Manifests of all stored snapshots, most recent first.
+  pub fn manifests(&self) -> Vec<Manifest> {
+    self
+      .heights()
+      .into_iter()
+      .rev()
+      .filter_map(|height| {
+        let path = self.path(height).join("manifest");
+        fs::read(path)
+          .ok()
+          .and_then(|bytes| bincode::deserialize(&bytes).ok())
+      })
+      .collect()
+  }
+
+  /// Reads a chunk of a stored snapshot as it is
+  /// transmitted over the wire.
+  pub fn chunk(&self, height: u64, index: usize) -> Option<Vec<u8>> {
+    fs::read(self.path(height).join(format!("{index}.chunk"))).ok()
+  }
+
+  /// Starts writing a snapshot of all accounts in the state along with
+  /// the output of the finalized block on a background thread.
+  ///
+  /// Finalized blocks keep being applied while the snapshot is written,
+  /// the accounts they change are recorded by [`Snapshots::record`] so
+  /// the snapshot still holds the state at its height. The snapshot is
+  /// written to a staging directory first, so only complete snapshots
+  /// are ever served to other validators.
+  pub(super) fn create(
+    &self,
+    db: &Db,
+    height: u64,
+    block: Multihash,
+    output: &BlockOutput,
+  ) -> Result<(), Error> {
+    let output = bincode::serialize(output)?;
+
+    // snapshots normally take less time to write than the interval
+    // between them, otherwise finalization waits for the previous one.
+    self.wait();
+    *self.preimages.lock().expect("poisoned lock") = Some(Preimages::new());
+
+    let snapshots = self.clone();
+    let db = db.clone();
+    let writer = thread::spawn(move || {
+      let staging = snapshots.directory.join(format!("{height}.partial"));
+      let result = snapshots.write(&staging, &db, height, block, &output);
+      *snapshots.preimages.lock().expect("poisoned lock") = None;
+      if let Err(e) = result {
+        warn!("storing state snapshot at height {height} failed: {e}");
+        let _ = fs::remove_dir_all(&staging);
+      }
+    });
+
+    *self.writer.lock().expect("poisoned lock") = Some(writer);
+    Ok(())
+  }
+
+  /// Blocks until the snapshot that is being written, if any, is stored.
+  pub fn wait(&self) {
+    let writer = self.writer.lock().expect("poisoned lock").take();
+    if let Some(writer) = writer {
+      let _ = writer.join();
+    }
+  }
+
+  /// Records the contents of accounts that are about to be changed by a
+  /// finalized block while a snapshot is being written. Only the first
+  /// change of an account is recorded, that is its state at the height
+  /// of the snapshot.
+  pub(super) fn record(&self, db: &Db, diff: &StateDiff) -> Result<(), Error> {
+    let mut preimages = self.preimages.lock().expect("poisoned lock");
+    if let Some(ref mut preimages) = *preimages {
+      for (address, _) in diff.iter() {
+        if !preimages.contains_key(address) {
+          let account = match db.get(address)? {
+            Some(value) => Some(bincode::deserialize(&value)?),
+            None => None,
+          };
+          preimages.insert(*address, account);
+        }
+      }
+    }
+    Ok(())
+  }
+
+  fn write(
+    &self,
+    staging: &Path,
+    db: &Db,
+    height: u64,
+    block: Multihash,
+    output: &[u8],
+  ) -> Result<Manifest, Error> {
+    fs::create_dir_all(staging)?;
+
+    let mut chunks = vec![];
+    let mut accounts: Vec<(Pubkey, Account)> = vec![];
+    let mut size = 0;
+
+    let mut flush = |accounts: &mut Vec<(Pubkey, Account)>| {
+      let chunk = bincode::serialize(accounts)?;
+      let path = staging.join(format!("{}.chunk", chunks.len()));
+      chunks.push(MultihashCode::Sha3_256.digest(&chunk));
+      fs::write(path, chunk)?;
+      accounts.clear();
+      Ok::<_, Error>(())
+    };
+
+    self.scan(db, |address, account| {
+      size += address.len() + bincode::serialized_size(&account)? as usize;
+      accounts.push((address, account));
+      if size >= CHUNK_SIZE {
+        flush(&mut accounts)?;
+        size = 0;
+      }
+      Ok(())
+    })?;
+
+    if !accounts.is_empty() {
+      flush(&mut accounts)?;
+    }
+
+    let output_chunks = output.chunks(CHUNK_SIZE).len();
+    for chunk in output.chunks(CHUNK_SIZE) {
+      let path = staging.join(format!("{}.chunk", chunks.len()));
+      chunks.push(MultihashCode::Sha3_256.digest(chunk));
+      fs::write(path, chunk)?;
+    }
+
+    let manifest = Manifest {
+      height,
+      block,
+      chunks,
+      output_chunks,
+    };
+
+    fs::write(staging.join("manifest"), bincode::serialize(&manifest)?)?;
+    fs::rename(staging, self.path(height))?;
+
+    info!(
+      "stored state snapshot at height {height} in {} chunks",
+      manifest.chunks.len()
+    );
+
+    // keep only the most recent snapshots
+    let heights = self.heights();
+    let stale = heights.len().saturating_sub(SNAPSHOTS_KEPT);
+    for height in &heights[..stale] {
+      fs::remove_dir_all(self.path(*height))?;
+    }
+
+    Ok(manifest)
+  }
+
+  /// Visits all accounts as they were at the height of the snapshot
+  /// in the order of their addresses.
+  ///
+  /// Accounts changed since then are visited with their recorded
+  /// contents. Accounts removed since then are no longer in the
+  /// database, they are visited between the stored accounts that
+  /// surround them.
+  fn scan(
+    &self,
+    db: &Db,
+    mut visit: impl FnMut(Pubkey, Account) -> Result<(), Error>,
+  ) -> Result<(), Error> {
+    // an account is always recorded before it is changed, so an
+    // account that is not recorded yet was read unchanged.
+    let recorded = |after: Bound<Pubkey>, until: Bound<Pubkey>| {
+      let preimages = self.preimages.lock().expect("poisoned lock");
+      preimages
+        .as_ref()
+        .map(|p| {
+          p.range((after, until))
+            .map(|(address, account)| (*address, account.clone()))
+            .collect::<Vec<_>>()
+        })
+        .unwrap_or_default()
+    };
+
+    let mut after = Bound::Unbounded;
+    for entry in db.iter() {
+      let (key, value) = entry?;
+      let address: [u8; 32] = key.as_ref().try_into().expect("invalid key");
+      let address = Pubkey::from(address);
+
+      let mut accounts = recorded(after, Bound::Included(address));
+      if accounts.last().map(|(a, _)| a) != Some(&address) {
+        accounts.push((address, Some(bincode::deserialize(&value)?)));
+      }
+
+      for (address, account) in accounts {
+        if let Some(account) = account {
+          visit(address, account)?;
+        }
+      }
+      after = Bound::Excluded(address);
+    }
+
+    for (address, account) in recorded(after, Bound::Unbounded) {
+      if let Some(account) = account {
+        visit(address, account)?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Heights of all stored snapshots in ascending order.
+  fn heights(&self) -> Vec<u64> {
+    let mut heights: Vec<u64> = fs::read_dir(&self.directory)
+      .map(|entries| {
+        entries
+          .filter_map(|e| e.ok()?.file_name().to_str()?.parse().ok())
+          .collect()
+      })
+      .unwrap_or_default();
+    heights.sort_unstable();
+    heights
+  }
+
+  fn path(&self, height: u64) -> PathBuf {
+    self.directory.join(height.to_string())
+  }
+}
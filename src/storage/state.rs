@@ -1,12 +1,13 @@
 use {
-  super::Error,
+  super::{Error, Manifest, Snapshots},
   crate::{
-    consensus::{BlockData, Genesis},
+    consensus::{BlockData, Genesis, Produced},
     primitives::{Account, Pubkey},
-    vm::{State, StateDiff, StateError, StateStore},
+    vm::{BlockOutput, State, StateDiff, StateError, StateStore},
   },
+  multihash::Multihash,
   sled::{Batch, Db},
-  std::{path::PathBuf, sync::Arc},
+  std::{collections::HashMap, path::PathBuf, sync::Arc},
 };
 
 /// This type represents a storage that is persisted on disk and survives node
@@ -27,6 +28,7 @@ use {
 #[derive(Debug)]
 pub struct PersistentState {
   db: Arc<Db>,
+  snapshots: Option<Snapshots>,
 }
 
 impl PersistentState {
@@ -34,26 +36,145 @@ impl PersistentState {
     genesis: &Genesis<D>,
     directory: PathBuf,
   ) -> Result<Self, Error> {
-    let mut directory = directory;
-    directory.push("state");
-    std::fs::create_dir_all(directory.clone())?;
-
-    let db = sled::open(directory)?;
+    let db = open(genesis, directory)?;
     if db.is_empty() {
       for (addr, account) in &genesis.state {
         if db.get(addr).unwrap().is_none() {
           db.insert(addr, bincode::serialize(account)?)?;
         }
       }
+      db.open_tree("genesis")?
+        .insert("hash", genesis.hash()?.to_bytes())?;
+    }
+
+    Ok(Self {
+      db: Arc::new(db),
+      snapshots: None,
+    })
+  }
+
+  /// Recreates the state from the chunks of a snapshot instead of the
+  /// genesis. Any accounts already stored in the data directory are
+  /// replaced by the accounts from the snapshot.
+  ///
+  /// The snapshot must be taken at the given finalized block. The
+  /// output of the block in the snapshot must match the state hash
+  /// of the block and the accounts it changed must match the state.
+  pub fn restore<D: BlockData>(
+    genesis: &Genesis<D>,
+    directory: PathBuf,
+    block: &Produced<D>,
+    manifest: &Manifest,
+    chunks: &[Vec<u8>],
+  ) -> Result<Self, Error> {
+    if chunks.len() != manifest.chunks.len()
+      || manifest.output_chunks > chunks.len()
+    {
+      return Err(Error::InvalidSnapshot(chunks.len()));
+    }
+
+    if let Some(index) = (0..chunks.len())
+      .find(|index| !manifest.verify_chunk(*index, &chunks[*index]))
+    {
+      return Err(Error::InvalidSnapshot(index));
+    }
+
+    if manifest.height != block.height || manifest.block != block.hash()? {
+      return Err(Error::SnapshotMismatch(block.to_string()));
+    }
+
+    let (accounts, output) =
+      chunks.split_at(chunks.len() - manifest.output_chunks);
+    let output: BlockOutput = bincode::deserialize(&output.concat())?;
+    if output.hash() != &block.state_hash {
+      return Err(Error::SnapshotMismatch(block.to_string()));
+    }
+
+    // accounts changed by the block must have the same contents
+    // in the snapshot, accounts removed by it must be absent.
+    let mut changed: HashMap<Pubkey, Option<Account>> = output
+      .state
+      .iter()
+      .map(|(addr, account)| (*addr, account.cloned()))
+      .collect();
+
+    let mut batches = Vec::with_capacity(accounts.len());
+    for chunk in accounts {
+      let mut batch = Batch::default();
+      let accounts: Vec<(Pubkey, Account)> = bincode::deserialize(chunk)?;
+      for (addr, account) in accounts {
+        if let Some(expected) = changed.remove(&addr) {
+          if expected.as_ref() != Some(&account) {
+            return Err(Error::SnapshotMismatch(block.to_string()));
+          }
+        }
+        batch.insert(addr.as_ref(), bincode::serialize(&account)?);
+      }
+      batches.push(batch);
+    }
+
+    if changed.values().any(Option::is_some) {
+      return Err(Error::SnapshotMismatch(block.to_string()));
+    }
+
+    let db = open(genesis, directory)?;
+    db.open_tree("genesis")?
+      .insert("hash", genesis.hash()?.to_bytes())?;
+    db.clear()?;
+
+    for batch in batches {
+      db.apply_batch(batch)?;
+    }
+
+    db.flush()?;
+
+    Ok(Self {
+      db: Arc::new(db),
+      snapshots: None,
+    })
+  }
+
+  /// Stores snapshots of the state at finalized
+  /// heights for other validators to start from.
+  pub fn with_snapshots(self, snapshots: Snapshots) -> Self {
+    Self {
+      snapshots: Some(snapshots),
+      ..self
     }
+  }
+}
+
+/// Opens the state database in the data directory, states that were
+/// initialized by a different genesis can't be reused.
+fn open<D: BlockData>(
+  genesis: &Genesis<D>,
+  directory: PathBuf,
+) -> Result<Db, Error> {
+  let mut directory = directory;
+  directory.push("state");
+  std::fs::create_dir_all(directory.clone())?;
 
-    Ok(Self { db: Arc::new(db) })
+  let db = sled::open(directory)?;
+
+  // the hash of the genesis that initialized this state, states that
+  // were initialized by a different genesis or by an earlier version
+  // of the genesis hash can't be reused.
+  let hash = genesis.hash()?.to_bytes();
+  let meta = db.open_tree("genesis")?;
+  match meta.get("hash")? {
+    Some(existing) if existing != hash => Err(Error::GenesisMismatch),
+    None if !db.is_empty() => Err(Error::GenesisMismatch),
+    _ => Ok(db),
   }
 }
 
 impl StateStore for PersistentState {
   /// Applies a state diff from a finalized block
   fn apply(&self, diff: &StateDiff) -> Result<(), Error> {
+    if let Some(ref snapshots) = self.snapshots {
+      snapshots.record(&self.db, diff)?;
+    }
+
     let mut batch = Batch::default();
     for (addr, account) in diff.iter() {
       match account {
@@ -65,6 +186,22 @@ impl StateStore for PersistentState {
     }
     self.db.apply_batch(batch).map_err(Error::StorageEngine)
   }
+
+  /// Starts taking a snapshot of the state if the
+  /// finalized block is at a snapshot height.
+  fn finalized(
+    &self,
+    height: u64,
+    block: &Multihash,
+    output: &BlockOutput,
+  ) -> Result<(), Error> {
+    match self.snapshots {
+      Some(ref snapshots) if snapshots.is_due(height) => {
+        snapshots.create(&self.db, height, *block, output)
+      }
+      _ => Ok(()),
+    }
+  }
 }
 
 impl State for PersistentState {
@@ -106,6 +243,7 @@ impl Clone for PersistentState {
   fn clone(&self) -> Self {
     Self {
       db: Arc::clone(&self.db),
+      snapshots: self.snapshots.clone(),
     }
   }
 }
@@ -2,9 +2,19 @@ use {
   crate::{
     primitives::{Keypair, Pubkey},
     test::utils::CURRENCY_CONTRACT_ADDR,
-    vm::{AccountRef, Transaction},
+    vm::{
+      builtin::currency::{
+        Attribute,
+        Creator,
+        Instruction as CurrencyInstruction,
+      },
+      AccountRef,
+      Instruction,
+      Transaction,
+    },
   },
   borsh::BorshSerialize,
+  multihash::Multihash,
 };
 
 /// Abstract class to generate a currency
@@ -16,7 +26,9 @@ pub struct Currency {
 // TODO(bmaas): finish implementing
 
 impl Currency {
+  #[allow(clippy::too_many_arguments)]
   pub fn create(
+    chain: &Multihash,
     payer: Keypair,
     nonce: u64,
     seed: &[u8; 32],
@@ -25,12 +37,13 @@ impl Currency {
     name: Option<String>,
     symbol: Option<String>,
   ) -> Transaction {
-    let ix = crate::vm::builtin::currency::Instruction::Create {
+    let ix = CurrencyInstruction::Create {
       seed: *seed,
       authority,
       decimals,
       name,
       symbol,
+      freeze_authority: None,
     };
 
     let params = ix.try_to_vec().unwrap();
@@ -45,20 +58,23 @@ impl Currency {
 
     Transaction::new(
       *CURRENCY_CONTRACT_ADDR,
+      chain,
       nonce,
       &payer,
       accounts,
       params,
       &[&payer],
     )
+    .unwrap()
   }
 
   // fn mint(&self, authority: Keypair, payer: Keypair, amount: u64) {}
 }
 
 /// Helper to quickly create the PQ Token
-pub fn create_pq_token_tx(payer: &Keypair) -> Transaction {
+pub fn create_pq_token_tx(chain: &Multihash, payer: &Keypair) -> Transaction {
   Currency::create(
+    chain,
     payer.clone(),
     1,
     &[0; 32],
@@ -68,3 +84,1054 @@ pub fn create_pq_token_tx(payer: &Keypair) -> Transaction {
     Some(String::from("PQ")),
   )
 }
+
+/// Creates a new coin with the payer as its mint and freeze authority
+/// and mints an initial supply to a wallet within one atomic transaction.
+pub fn create_and_mint_tx(
+  chain: &Multihash,
+  payer: &Keypair,
+  nonce: u64,
+  seed: &[u8; 32],
+  wallet: Pubkey,
+  amount: u64,
+) -> Transaction {
+  let mint_address = CURRENCY_CONTRACT_ADDR.derive(&[seed]);
+  let coin_address = CURRENCY_CONTRACT_ADDR.derive(&[&mint_address, &wallet]);
+
+  let accounts = vec![
+    AccountRef::writable(mint_address, false).unwrap(),
+    AccountRef::readonly(payer.public(), true).unwrap(),
+    AccountRef::readonly(wallet, false).unwrap(),
+    AccountRef::writable(coin_address, false).unwrap(),
+  ];
+
+  let instructions = vec![
+    Instruction {
+      contract: *CURRENCY_CONTRACT_ADDR,
+      accounts: vec![0],
+      params: CurrencyInstruction::Create {
+        seed: *seed,
+        authority: payer.public(),
+        decimals: 9,
+        name: None,
+        symbol: None,
+        freeze_authority: Some(payer.public()),
+      }
+      .try_to_vec()
+      .unwrap(),
+    },
+    Instruction {
+      contract: *CURRENCY_CONTRACT_ADDR,
+      accounts: vec![0, 1, 2, 3],
+      params: CurrencyInstruction::Mint(amount).try_to_vec().unwrap(),
+    },
+  ];
+
+  Transaction::with_instructions(
+    chain,
+    nonce,
+    None,
+    payer,
+    accounts,
+    instructions,
+    &[payer],
+  )
+}
+
+/// The owner wallet approves a delegate to spend up to `amount` coins.
+pub fn approve_tx(
+  chain: &Multihash,
+  owner: &Keypair,
+  nonce: u64,
+  mint: Pubkey,
+  delegate: Pubkey,
+  amount: u64,
+) -> Transaction {
+  let allowance =
+    CURRENCY_CONTRACT_ADDR.derive(&[&mint, &owner.public(), &delegate]);
+
+  Transaction::new(
+    *CURRENCY_CONTRACT_ADDR,
+    chain,
+    nonce,
+    owner,
+    vec![
+      AccountRef::readonly(mint, false).unwrap(),
+      AccountRef::readonly(owner.public(), true).unwrap(),
+      AccountRef::readonly(delegate, false).unwrap(),
+      AccountRef::writable(allowance, false).unwrap(),
+    ],
+    CurrencyInstruction::Approve(amount).try_to_vec().unwrap(),
+    &[owner],
+  )
+  .unwrap()
+}
+
+/// The owner wallet revokes the allowance of a delegate.
+pub fn revoke_tx(
+  chain: &Multihash,
+  owner: &Keypair,
+  nonce: u64,
+  mint: Pubkey,
+  delegate: Pubkey,
+) -> Transaction {
+  let allowance =
+    CURRENCY_CONTRACT_ADDR.derive(&[&mint, &owner.public(), &delegate]);
+
+  Transaction::new(
+    *CURRENCY_CONTRACT_ADDR,
+    chain,
+    nonce,
+    owner,
+    vec![
+      AccountRef::readonly(mint, false).unwrap(),
+      AccountRef::readonly(owner.public(), true).unwrap(),
+      AccountRef::readonly(delegate, false).unwrap(),
+      AccountRef::writable(allowance, false).unwrap(),
+    ],
+    CurrencyInstruction::Revoke.try_to_vec().unwrap(),
+    &[owner],
+  )
+  .unwrap()
+}
+
+/// The delegate transfers coins of the owner wallet to a recipient.
+pub fn transfer_from_tx(
+  chain: &Multihash,
+  delegate: &Keypair,
+  nonce: u64,
+  mint: Pubkey,
+  owner: Pubkey,
+  recipient: Pubkey,
+  amount: u64,
+) -> Transaction {
+  let allowance =
+    CURRENCY_CONTRACT_ADDR.derive(&[&mint, &owner, &delegate.public()]);
+  let owner_coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &owner]);
+  let recipient_coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &recipient]);
+
+  Transaction::new(
+    *CURRENCY_CONTRACT_ADDR,
+    chain,
+    nonce,
+    delegate,
+    vec![
+      AccountRef::readonly(mint, false).unwrap(),
+      AccountRef::readonly(delegate.public(), true).unwrap(),
+      AccountRef::readonly(owner, false).unwrap(),
+      AccountRef::writable(allowance, false).unwrap(),
+      AccountRef::writable(owner_coin, false).unwrap(),
+      AccountRef::readonly(recipient, false).unwrap(),
+      AccountRef::writable(recipient_coin, false).unwrap(),
+    ],
+    CurrencyInstruction::TransferFrom(amount)
+      .try_to_vec()
+      .unwrap(),
+    &[delegate],
+  )
+  .unwrap()
+}
+
+/// The freeze authority freezes or thaws the coin account of a wallet.
+pub fn set_frozen_tx(
+  chain: &Multihash,
+  authority: &Keypair,
+  nonce: u64,
+  mint: Pubkey,
+  wallet: Pubkey,
+  frozen: bool,
+) -> Transaction {
+  let coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &wallet]);
+  let instruction = match frozen {
+    true => CurrencyInstruction::Freeze,
+    false => CurrencyInstruction::Thaw,
+  };
+
+  Transaction::new(
+    *CURRENCY_CONTRACT_ADDR,
+    chain,
+    nonce,
+    authority,
+    vec![
+      AccountRef::writable(mint, false).unwrap(),
+      AccountRef::readonly(authority.public(), true).unwrap(),
+      AccountRef::readonly(wallet, false).unwrap(),
+      AccountRef::writable(coin, false).unwrap(),
+    ],
+    instruction.try_to_vec().unwrap(),
+    &[authority],
+  )
+  .unwrap()
+}
+
+/// The freeze authority hands over or removes its authority.
+pub fn set_freeze_authority_tx(
+  chain: &Multihash,
+  authority: &Keypair,
+  nonce: u64,
+  mint: Pubkey,
+  new_authority: Option<Pubkey>,
+) -> Transaction {
+  Transaction::new(
+    *CURRENCY_CONTRACT_ADDR,
+    chain,
+    nonce,
+    authority,
+    vec![
+      AccountRef::writable(mint, false).unwrap(),
+      AccountRef::readonly(authority.public(), true).unwrap(),
+    ],
+    CurrencyInstruction::SetFreezeAuthority(new_authority)
+      .try_to_vec()
+      .unwrap(),
+    &[authority],
+  )
+  .unwrap()
+}
+
+/// The wallet owner transfers coins to a recipient.
+pub fn transfer_tx(
+  chain: &Multihash,
+  owner: &Keypair,
+  nonce: u64,
+  mint: Pubkey,
+  recipient: Pubkey,
+  amount: u64,
+) -> Transaction {
+  let owner_coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &owner.public()]);
+  let recipient_coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &recipient]);
+
+  Transaction::new(
+    *CURRENCY_CONTRACT_ADDR,
+    chain,
+    nonce,
+    owner,
+    vec![
+      AccountRef::readonly(mint, false).unwrap(),
+      AccountRef::readonly(owner.public(), true).unwrap(),
+      AccountRef::writable(owner_coin, false).unwrap(),
+      AccountRef::readonly(recipient, false).unwrap(),
+      AccountRef::writable(recipient_coin, false).unwrap(),
+    ],
+    CurrencyInstruction::Transfer(amount).try_to_vec().unwrap(),
+    &[owner],
+  )
+  .unwrap()
+}
+
+/// The wallet owner burns coins from its coin account.
+pub fn burn_tx(
+  chain: &Multihash,
+  owner: &Keypair,
+  nonce: u64,
+  mint: Pubkey,
+  amount: u64,
+) -> Transaction {
+  let coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &owner.public()]);
+
+  Transaction::new(
+    *CURRENCY_CONTRACT_ADDR,
+    chain,
+    nonce,
+    owner,
+    vec![
+      AccountRef::writable(mint, false).unwrap(),
+      AccountRef::readonly(owner.public(), true).unwrap(),
+      AccountRef::writable(coin, false).unwrap(),
+    ],
+    CurrencyInstruction::Burn(amount).try_to_vec().unwrap(),
+    &[owner],
+  )
+  .unwrap()
+}
+
+/// Creates a new NFT with the payer as its authority, optionally
+/// claiming membership in a collection.
+pub fn create_nft_tx(
+  chain: &Multihash,
+  payer: &Keypair,
+  nonce: u64,
+  seed: &[u8; 32],
+  collection: Option<Pubkey>,
+) -> Transaction {
+  let mint = CURRENCY_CONTRACT_ADDR.derive(&[seed]);
+  let metadata = CURRENCY_CONTRACT_ADDR.derive(&[&mint, b"metadata"]);
+
+  Transaction::new(
+    *CURRENCY_CONTRACT_ADDR,
+    chain,
+    nonce,
+    payer,
+    vec![
+      AccountRef::writable(mint, false).unwrap(),
+      AccountRef::writable(metadata, false).unwrap(),
+    ],
+    CurrencyInstruction::CreateNft {
+      seed: *seed,
+      authority: payer.public(),
+      name: Some("Rensa Punk".to_owned()),
+      symbol: Some("RPUNK".to_owned()),
+      uri: "ipfs://QmRensaPunk".to_owned(),
+      attributes: vec![Attribute {
+        key: "background".to_owned(),
+        value: "blue".to_owned(),
+      }],
+      creators: vec![Creator {
+        address: payer.public(),
+        share: 100,
+      }],
+      collection,
+    }
+    .try_to_vec()
+    .unwrap(),
+    &[payer],
+  )
+  .unwrap()
+}
+
+/// The mint authority mints new coins to a wallet.
+pub fn mint_tx(
+  chain: &Multihash,
+  authority: &Keypair,
+  nonce: u64,
+  mint: Pubkey,
+  wallet: Pubkey,
+  amount: u64,
+) -> Transaction {
+  let coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &wallet]);
+
+  Transaction::new(
+    *CURRENCY_CONTRACT_ADDR,
+    chain,
+    nonce,
+    authority,
+    vec![
+      AccountRef::writable(mint, false).unwrap(),
+      AccountRef::readonly(authority.public(), true).unwrap(),
+      AccountRef::readonly(wallet, false).unwrap(),
+      AccountRef::writable(coin, false).unwrap(),
+    ],
+    CurrencyInstruction::Mint(amount).try_to_vec().unwrap(),
+    &[authority],
+  )
+  .unwrap()
+}
+
+/// The collection authority verifies that an NFT is a member
+/// of the collection.
+pub fn verify_collection_tx(
+  chain: &Multihash,
+  authority: &Keypair,
+  nonce: u64,
+  nft: Pubkey,
+  collection: Pubkey,
+) -> Transaction {
+  Transaction::new(
+    *CURRENCY_CONTRACT_ADDR,
+    chain,
+    nonce,
+    authority,
+    vec![
+      AccountRef::writable(
+        CURRENCY_CONTRACT_ADDR.derive(&[&nft, b"metadata"]),
+        false,
+      )
+      .unwrap(),
+      AccountRef::readonly(
+        CURRENCY_CONTRACT_ADDR.derive(&[&collection, b"metadata"]),
+        false,
+      )
+      .unwrap(),
+      AccountRef::readonly(authority.public(), true).unwrap(),
+    ],
+    CurrencyInstruction::VerifyCollection.try_to_vec().unwrap(),
+    &[authority],
+  )
+  .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{
+      test::{
+        test_validator::{TestCtx, TestValidator},
+        utils::keypair_default,
+      },
+      vm::{
+        builtin::currency::{
+          metadata_address,
+          Allowance,
+          CoinAccount,
+          Collection,
+          Event as CurrencyEvent,
+          Metadata,
+          Mint,
+          APPROVE_TOPIC,
+          FREEZE_TOPIC,
+          TRANSFER_TOPIC,
+        },
+        ContractError,
+        State,
+        StateDiff,
+      },
+    },
+    borsh::BorshDeserialize,
+  };
+
+  struct Setup {
+    mint: Pubkey,
+    owner: Keypair,
+    delegate: Keypair,
+    recipient: Pubkey,
+  }
+
+  /// Creates a new coin and mints 1000 coins to the owner wallet.
+  fn setup(validator: &mut TestValidator<Vec<Transaction>>) -> Setup {
+    let payer = keypair_default();
+    let owner = Keypair::unique();
+    let seed = [7u8; 32];
+
+    let tx = create_and_mint_tx(
+      validator.chain_hash(),
+      &payer,
+      1,
+      &seed,
+      owner.public(),
+      1000,
+    );
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    Setup {
+      mint: CURRENCY_CONTRACT_ADDR.derive(&[&seed]),
+      owner,
+      delegate: Keypair::unique(),
+      recipient: Pubkey::unique(),
+    }
+  }
+
+  fn balance(validator: &TestValidator<Vec<Transaction>>, coin: Pubkey) -> u64 {
+    validator
+      .get_account(coin)
+      .and_then(|acc| acc.data)
+      .map(|data| CoinAccount::try_from_slice(&data).unwrap().balance)
+      .unwrap_or(0)
+  }
+
+  fn mint_account(
+    validator: &TestValidator<Vec<Transaction>>,
+    mint: &Pubkey,
+  ) -> Mint {
+    validator
+      .get_account(*mint)
+      .and_then(|acc| acc.data)
+      .map(|data| Mint::try_from_slice(&data).unwrap())
+      .unwrap()
+  }
+
+  fn frozen(validator: &TestValidator<Vec<Transaction>>, coin: Pubkey) -> bool {
+    validator
+      .get_account(coin)
+      .and_then(|acc| acc.data)
+      .map(|data| CoinAccount::try_from_slice(&data).unwrap().frozen)
+      .unwrap()
+  }
+
+  fn allowance(state: &StateDiff, address: &Pubkey) -> Option<u64> {
+    state
+      .get(address)
+      .and_then(|acc| acc.data)
+      .map(|data| Allowance::try_from_slice(&data).unwrap().amount)
+  }
+
+  #[test]
+  fn approve_and_transfer_from_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup {
+      mint,
+      owner,
+      delegate,
+      recipient,
+    } = setup(&mut validator);
+    let chain = *validator.chain_hash();
+
+    let allowance_addr = CURRENCY_CONTRACT_ADDR.derive(&[
+      &mint,
+      &owner.public(),
+      &delegate.public(),
+    ]);
+    let owner_coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &owner.public()]);
+    let recipient_coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &recipient]);
+
+    let tx = approve_tx(&chain, &owner, 1, mint, delegate.public(), 300);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(allowance(result.state(), &allowance_addr), Some(300));
+
+    let events = result.events().unwrap();
+    assert_eq!(events[0].topics[0], *APPROVE_TOPIC);
+    assert_eq!(&events[0].topics[3][..], delegate.public().as_ref());
+
+    // the delegate spends part of the allowance
+    let tx = transfer_from_tx(
+      &chain,
+      &delegate,
+      1,
+      mint,
+      owner.public(),
+      recipient,
+      200,
+    );
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(allowance(result.state(), &allowance_addr), Some(100));
+    assert_eq!(balance(&validator, owner_coin), 800);
+    assert_eq!(balance(&validator, recipient_coin), 200);
+
+    let events = result.events().unwrap();
+    assert_eq!(events[0].topics[0], *TRANSFER_TOPIC);
+    assert_eq!(
+      CurrencyEvent::try_from_slice(&events[0].data).unwrap(),
+      CurrencyEvent::Transfer {
+        mint,
+        from: owner.public(),
+        to: recipient,
+        amount: 200,
+      }
+    );
+
+    // spending more than the remaining allowance fails
+    let tx = transfer_from_tx(
+      &chain,
+      &delegate,
+      2,
+      mint,
+      owner.public(),
+      recipient,
+      150,
+    );
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+    assert_eq!(balance(&validator, owner_coin), 800);
+
+    // spending the rest of the allowance removes it
+    let tx = transfer_from_tx(
+      &chain,
+      &delegate,
+      3,
+      mint,
+      owner.public(),
+      recipient,
+      100,
+    );
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert!(validator.get_account(allowance_addr).is_none());
+    assert!(result.receipt().unwrap().deleted.contains(&allowance_addr));
+    assert_eq!(balance(&validator, owner_coin), 700);
+    assert_eq!(balance(&validator, recipient_coin), 300);
+  }
+
+  #[test]
+  fn revoke_allowance_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup {
+      mint,
+      owner,
+      delegate,
+      recipient,
+    } = setup(&mut validator);
+    let chain = *validator.chain_hash();
+
+    let tx = approve_tx(&chain, &owner, 1, mint, delegate.public(), 500);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let tx = revoke_tx(&chain, &owner, 2, mint, delegate.public());
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let allowance_addr = CURRENCY_CONTRACT_ADDR.derive(&[
+      &mint,
+      &owner.public(),
+      &delegate.public(),
+    ]);
+    assert!(validator.get_account(allowance_addr).is_none());
+
+    // revoked allowances can't be spent
+    let tx = transfer_from_tx(
+      &chain,
+      &delegate,
+      1,
+      mint,
+      owner.public(),
+      recipient,
+      10,
+    );
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::AccountDoesNotExist)
+    ));
+
+    // revoking a non-existing allowance fails
+    let tx = revoke_tx(&chain, &owner, 3, mint, delegate.public());
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::AccountDoesNotExist)
+    ));
+  }
+
+  #[test]
+  fn unsigned_delegate_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup {
+      mint,
+      owner,
+      delegate,
+      recipient,
+    } = setup(&mut validator);
+    let chain = *validator.chain_hash();
+
+    let tx = approve_tx(&chain, &owner, 1, mint, delegate.public(), 300);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    // the delegate must sign, referencing it is not enough
+    let allowance = CURRENCY_CONTRACT_ADDR.derive(&[
+      &mint,
+      &owner.public(),
+      &delegate.public(),
+    ]);
+    let owner_coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &owner.public()]);
+    let recipient_coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &recipient]);
+    let tx = Transaction::new(
+      *CURRENCY_CONTRACT_ADDR,
+      &chain,
+      2,
+      &owner,
+      vec![
+        AccountRef::readonly(mint, false).unwrap(),
+        AccountRef::readonly(delegate.public(), false).unwrap(),
+        AccountRef::readonly(owner.public(), false).unwrap(),
+        AccountRef::writable(allowance, false).unwrap(),
+        AccountRef::writable(owner_coin, false).unwrap(),
+        AccountRef::readonly(recipient, false).unwrap(),
+        AccountRef::writable(recipient_coin, false).unwrap(),
+      ],
+      CurrencyInstruction::TransferFrom(100).try_to_vec().unwrap(),
+      &[&owner],
+    )
+    .unwrap();
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::SignatureError(_))
+    ));
+    assert_eq!(balance(&validator, owner_coin), 1000);
+  }
+
+  #[test]
+  fn transfer_from_requires_delegate_signature_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup {
+      mint,
+      owner,
+      delegate,
+      recipient,
+    } = setup(&mut validator);
+    let chain = *validator.chain_hash();
+
+    let tx = approve_tx(&chain, &owner, 1, mint, delegate.public(), 500);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    // the recipient tries to pull the approved coins
+    // while pretending to be the delegate.
+    let thief = Keypair::unique();
+    let mut tx = transfer_from_tx(
+      &chain,
+      &thief,
+      1,
+      mint,
+      owner.public(),
+      thief.public(),
+      500,
+    );
+    let allowance_addr = CURRENCY_CONTRACT_ADDR.derive(&[
+      &mint,
+      &owner.public(),
+      &delegate.public(),
+    ]);
+    tx.accounts[1] = AccountRef::readonly(delegate.public(), false).unwrap();
+    tx.accounts[3] = AccountRef::writable(allowance_addr, false).unwrap();
+    let tx = Transaction::with_instructions(
+      &chain,
+      1,
+      None,
+      &thief,
+      tx.accounts,
+      tx.instructions,
+      &[&thief],
+    );
+
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::SignatureError(_))
+    ));
+    assert_eq!(
+      balance(
+        &validator,
+        CURRENCY_CONTRACT_ADDR.derive(&[&mint, &recipient])
+      ),
+      0
+    );
+  }
+
+  #[test]
+  fn transfer_to_same_coin_account_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup {
+      mint,
+      owner,
+      delegate,
+      ..
+    } = setup(&mut validator);
+    let chain = *validator.chain_hash();
+    let owner_coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &owner.public()]);
+
+    let tx = transfer_tx(&chain, &owner, 1, mint, owner.public(), 100);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::InvalidInputAccounts)
+    ));
+    assert_eq!(balance(&validator, owner_coin), 1000);
+
+    let tx = approve_tx(&chain, &owner, 2, mint, delegate.public(), 500);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    // the delegate can't credit the debited coins back to the owner
+    let tx = transfer_from_tx(
+      &chain,
+      &delegate,
+      1,
+      mint,
+      owner.public(),
+      owner.public(),
+      500,
+    );
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::InvalidInputAccounts)
+    ));
+    assert_eq!(balance(&validator, owner_coin), 1000);
+  }
+
+  #[test]
+  fn freeze_and_thaw_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup {
+      mint,
+      owner,
+      recipient,
+      ..
+    } = setup(&mut validator);
+    let chain = *validator.chain_hash();
+    let authority = keypair_default();
+    let owner_coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &owner.public()]);
+
+    // the freeze authority freezes the owner coin account
+    let tx = set_frozen_tx(&chain, &authority, 2, mint, owner.public(), true);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert!(frozen(&validator, owner_coin));
+
+    let events = result.events().unwrap();
+    assert_eq!(events[0].topics[0], *FREEZE_TOPIC);
+    assert_eq!(
+      CurrencyEvent::try_from_slice(&events[0].data).unwrap(),
+      CurrencyEvent::Freeze {
+        mint,
+        wallet: owner.public(),
+        frozen: true,
+      }
+    );
+
+    // frozen accounts can't send or burn coins
+    let tx = transfer_tx(&chain, &owner, 1, mint, recipient, 10);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    let tx = burn_tx(&chain, &owner, 2, mint, 10);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+    assert_eq!(balance(&validator, owner_coin), 1000);
+
+    // only existing coin accounts can be frozen
+    let tx = set_frozen_tx(&chain, &authority, 3, mint, recipient, true);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::AccountDoesNotExist)
+    ));
+
+    // once thawed, the account is usable again
+    let tx = set_frozen_tx(&chain, &authority, 4, mint, owner.public(), false);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert!(!frozen(&validator, owner_coin));
+
+    let tx = transfer_tx(&chain, &owner, 3, mint, recipient, 10);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(balance(&validator, owner_coin), 990);
+  }
+
+  #[test]
+  fn frozen_recipient_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup {
+      mint,
+      owner,
+      recipient,
+      ..
+    } = setup(&mut validator);
+    let chain = *validator.chain_hash();
+    let authority = keypair_default();
+    let recipient_coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &recipient]);
+
+    let tx = transfer_tx(&chain, &owner, 1, mint, recipient, 10);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let tx = set_frozen_tx(&chain, &authority, 2, mint, recipient, true);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let tx = transfer_tx(&chain, &owner, 2, mint, recipient, 10);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+    assert_eq!(balance(&validator, recipient_coin), 10);
+  }
+
+  #[test]
+  fn freeze_authority_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup { mint, owner, .. } = setup(&mut validator);
+    let chain = *validator.chain_hash();
+    let authority = keypair_default();
+
+    // only the freeze authority can freeze accounts
+    let tx = set_frozen_tx(&chain, &owner, 1, mint, owner.public(), true);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    // thawing an account that is not frozen fails
+    let tx = set_frozen_tx(&chain, &authority, 2, mint, owner.public(), false);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    // the freeze authority can't be removed while accounts are frozen,
+    // otherwise nobody could ever thaw them
+    let tx = set_frozen_tx(&chain, &authority, 3, mint, owner.public(), true);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(mint_account(&validator, &mint).frozen_accounts, 1);
+
+    let tx = set_freeze_authority_tx(&chain, &authority, 4, mint, None);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    let tx = set_frozen_tx(&chain, &authority, 5, mint, owner.public(), false);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(mint_account(&validator, &mint).frozen_accounts, 0);
+
+    // removing the freeze authority disables freezing forever
+    let tx = set_freeze_authority_tx(&chain, &authority, 6, mint, None);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let tx = set_frozen_tx(&chain, &authority, 7, mint, owner.public(), true);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+  }
+
+  #[test]
+  fn legacy_layouts_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let payer = keypair_default();
+
+    // accounts stored before freezing and NFTs were added
+    let (mint, owner) = (Pubkey::unique(), Pubkey::unique());
+    let legacy = (Some(owner), 10u64, 9u8, None::<String>, None::<String>);
+    let decoded = Mint::try_from_slice(&legacy.try_to_vec().unwrap()).unwrap();
+    assert_eq!(decoded.supply, 10);
+    assert_eq!(decoded.freeze_authority, None);
+    assert!(!decoded.nft);
+
+    let legacy = (mint, owner, 5u64).try_to_vec().unwrap();
+    let decoded = CoinAccount::try_from_slice(&legacy).unwrap();
+    assert_eq!(decoded.balance, 5);
+    assert!(!decoded.frozen);
+
+    // clients that predate freezing create mints without a freeze authority
+    let seed = [9u8; 32];
+    let symbol = Some(String::from("OLD"));
+    let legacy = (seed, payer.public(), 2u8, None::<String>, symbol);
+    let params = [&[0u8][..], &legacy.try_to_vec().unwrap()].concat();
+    let mint = CURRENCY_CONTRACT_ADDR.derive(&[&seed]);
+    let tx = Transaction::new(
+      *CURRENCY_CONTRACT_ADDR,
+      &chain,
+      1,
+      &payer,
+      vec![AccountRef::writable(mint, false).unwrap()],
+      params,
+      &[&payer],
+    )
+    .unwrap();
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let created = mint_account(&validator, &mint);
+    assert_eq!(created.decimals, 2);
+    assert_eq!(created.symbol.as_deref(), Some("OLD"));
+    assert_eq!(created.freeze_authority, None);
+  }
+
+  fn metadata(
+    validator: &TestValidator<Vec<Transaction>>,
+    mint: &Pubkey,
+  ) -> Metadata {
+    validator
+      .get_account(metadata_address(mint))
+      .and_then(|acc| acc.data)
+      .map(|data| Metadata::try_from_slice(&data).unwrap())
+      .unwrap()
+  }
+
+  #[test]
+  fn nft_supply_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let authority = keypair_default();
+    let wallet = Pubkey::unique();
+    let seed = [4u8; 32];
+    let mint = CURRENCY_CONTRACT_ADDR.derive(&[&seed]);
+
+    let tx = create_nft_tx(&chain, &authority, 1, &seed, None);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(metadata(&validator, &mint), Metadata {
+      mint,
+      authority: authority.public(),
+      uri: "ipfs://QmRensaPunk".to_owned(),
+      attributes: vec![Attribute {
+        key: "background".to_owned(),
+        value: "blue".to_owned(),
+      }],
+      creators: vec![Creator {
+        address: authority.public(),
+        share: 100,
+      }],
+      collection: None,
+    });
+
+    // NFTs can't be minted in quantities other than one
+    let tx = mint_tx(&chain, &authority, 2, mint, wallet, 2);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::InvalidInputParameters)
+    ));
+
+    let tx = mint_tx(&chain, &authority, 3, mint, wallet, 1);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(
+      balance(&validator, CURRENCY_CONTRACT_ADDR.derive(&[&mint, &wallet])),
+      1
+    );
+
+    // the mint authority is dropped after the first mint
+    let tx = mint_tx(&chain, &authority, 4, mint, wallet, 1);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    // the same seed can't be reused for another NFT
+    let tx = create_nft_tx(&chain, &authority, 5, &seed, None);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::AccountAlreadyExists)
+    ));
+  }
+
+  #[test]
+  fn nft_collection_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let authority = keypair_default();
+    let creator = Keypair::unique();
+
+    let collection = CURRENCY_CONTRACT_ADDR.derive(&[&[5u8; 32]]);
+    let nft = CURRENCY_CONTRACT_ADDR.derive(&[&[6u8; 32]]);
+
+    let tx = create_nft_tx(&chain, &authority, 1, &[5u8; 32], None);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    // anyone can claim membership in a collection
+    let tx = create_nft_tx(&chain, &creator, 1, &[6u8; 32], Some(collection));
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(
+      metadata(&validator, &nft).collection,
+      Some(Collection {
+        mint: collection,
+        verified: false
+      })
+    );
+
+    // but only the collection authority can verify it
+    let tx = verify_collection_tx(&chain, &creator, 2, nft, collection);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    let tx = verify_collection_tx(&chain, &authority, 2, nft, collection);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(
+      metadata(&validator, &nft).collection,
+      Some(Collection {
+        mint: collection,
+        verified: true
+      })
+    );
+
+    // the collection itself is not a member of any collection
+    let tx = verify_collection_tx(&chain, &authority, 3, collection, nft);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+  }
+}
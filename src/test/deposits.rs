@@ -0,0 +1,215 @@
+#[cfg(test)]
+mod tests {
+  use {
+    crate::{
+      primitives::{Account, Keypair, Pubkey},
+      test::{
+        system::{allocate_tx, close_tx},
+        test_validator::{TestCtx, TestValidator},
+        utils::{genesis_default, keypair_default},
+      },
+      vm::{
+        builtin::currency::{coin_address, CoinAccount, CURRENCY_BUILTIN_ADDR},
+        deposit_address,
+        ContractError,
+        Deposit,
+        Transaction,
+        DEPOSIT_VAULT_ADDR,
+      },
+    },
+    borsh::{BorshDeserialize, BorshSerialize},
+  };
+
+  /// A test chain that locks 100 system coins for every account
+  /// and one system coin for every byte of account data.
+  fn deposits_ctx() -> TestCtx<Vec<Transaction>> {
+    let keypair = keypair_default();
+    let mut genesis = genesis_default(&keypair);
+    genesis.limits.storage_deposit_per_account = 100;
+    genesis.limits.storage_deposit_per_byte = 1;
+    TestCtx::with_genesis(genesis, keypair)
+  }
+
+  fn system_coin() -> Pubkey {
+    "RensaToken1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
+      .parse()
+      .unwrap()
+  }
+
+  fn fund(
+    validator: &TestValidator<Vec<Transaction>>,
+    wallet: &Pubkey,
+    balance: u64,
+  ) {
+    let coin = CoinAccount {
+      mint: system_coin(),
+      owner: *wallet,
+      balance,
+      frozen: false,
+    };
+    validator.add_account(coin_address(&system_coin(), wallet), Account {
+      nonce: 0,
+      executable: false,
+      owner: Some(*CURRENCY_BUILTIN_ADDR),
+      data: Some(coin.try_to_vec().unwrap()),
+    });
+  }
+
+  fn balance(
+    validator: &TestValidator<Vec<Transaction>>,
+    wallet: &Pubkey,
+  ) -> Option<u64> {
+    validator
+      .get_account(coin_address(&system_coin(), wallet))
+      .and_then(|acc| acc.data)
+      .map(|data| CoinAccount::try_from_slice(&data).unwrap().balance)
+  }
+
+  fn record(
+    validator: &TestValidator<Vec<Transaction>>,
+    account: &Pubkey,
+  ) -> Option<Deposit> {
+    validator
+      .get_account(deposit_address(account))
+      .and_then(|acc| acc.data)
+      .map(|data| Deposit::try_from_slice(&data).unwrap())
+  }
+
+  #[test]
+  fn lock_and_refund_deposit_test() {
+    let ctx = deposits_ctx();
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let payer = Keypair::unique();
+    let account = Keypair::unique();
+    fund(&validator, &payer.public(), 1000);
+
+    // 100 for the payer account created by its nonce and 140 for its
+    // deposit record, 173 for the new coin account of the vault, then
+    // 100 for the account, 200 for its data and 140 for its record.
+    let tx = allocate_tx(&chain, &payer, 1, &account, 200);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(balance(&validator, &payer.public()), Some(147));
+    assert_eq!(balance(&validator, &DEPOSIT_VAULT_ADDR), Some(853));
+    assert_eq!(
+      record(&validator, &account.public()),
+      Some(Deposit {
+        depositor: payer.public(),
+        amount: 440,
+      })
+    );
+
+    // closing the account refunds its deposit
+    let tx = close_tx(&chain, &payer, 2, &account);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert!(validator.get_account(account.public()).is_none());
+    assert!(record(&validator, &account.public()).is_none());
+    assert_eq!(balance(&validator, &payer.public()), Some(587));
+    assert_eq!(balance(&validator, &DEPOSIT_VAULT_ADDR), Some(413));
+  }
+
+  #[test]
+  fn insufficient_deposit_test() {
+    let ctx = deposits_ctx();
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let payer = Keypair::unique();
+    let account = Keypair::unique();
+    fund(&validator, &payer.public(), 1000);
+
+    let tx = allocate_tx(&chain, &payer, 1, &account, 2000);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::InsufficientStorageDeposit(2240))
+    ));
+    assert!(validator.get_account(account.public()).is_none());
+    assert_eq!(balance(&validator, &payer.public()), Some(587));
+
+    // payers without system coins can't pay for their own account,
+    // so their transactions have no effect at all
+    let unfunded = Keypair::unique();
+    let tx = allocate_tx(&chain, &unfunded, 1, &account, 0);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::InsufficientStorageDeposit(240))
+    ));
+    assert!(validator.get_account(unfunded.public()).is_none());
+    assert!(validator.get_account(account.public()).is_none());
+  }
+
+  #[test]
+  fn refund_depositor_test() {
+    let ctx = deposits_ctx();
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let (payer, closer) = (Keypair::unique(), Keypair::unique());
+    let (first, second) = (Keypair::unique(), Keypair::unique());
+    fund(&validator, &payer.public(), 1000);
+    fund(&validator, &closer.public(), 1000);
+    fund(&validator, &DEPOSIT_VAULT_ADDR, 0);
+
+    let tx = allocate_tx(&chain, &payer, 1, &first, 200);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let tx = allocate_tx(&chain, &payer, 2, &second, 0);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(balance(&validator, &payer.public()), Some(80));
+    assert_eq!(balance(&validator, &DEPOSIT_VAULT_ADDR), Some(920));
+
+    // the deposit is refunded to the depositor,
+    // not to the payer of the closing transaction
+    let tx = close_tx(&chain, &closer, 1, &first);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(balance(&validator, &payer.public()), Some(520));
+    assert_eq!(balance(&validator, &closer.public()), Some(760));
+    assert_eq!(balance(&validator, &DEPOSIT_VAULT_ADDR), Some(480));
+
+    // a depositor without a coin account is refunded the
+    // deposit minus 173 for its new coin account
+    validator.delete_account(coin_address(&system_coin(), &payer.public()));
+    let tx = close_tx(&chain, &closer, 2, &second);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert!(validator.get_account(second.public()).is_none());
+    assert_eq!(balance(&validator, &payer.public()), Some(67));
+    assert_eq!(balance(&validator, &DEPOSIT_VAULT_ADDR), Some(413));
+  }
+
+  #[test]
+  fn take_over_deposit_test() {
+    let ctx = deposits_ctx();
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let (first, second) = (Keypair::unique(), Keypair::unique());
+    fund(&validator, &first.public(), 1000);
+    fund(&validator, &second.public(), 1000);
+    fund(&validator, &DEPOSIT_VAULT_ADDR, 0);
+
+    let tx = allocate_tx(&chain, &first, 1, &Keypair::unique(), 0);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(balance(&validator, &first.public()), Some(520));
+
+    // growing an account deposited by someone else refunds the earlier
+    // depositor and locks the whole deposit of the account again
+    let tx = allocate_tx(&chain, &second, 1, &first, 200);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(balance(&validator, &first.public()), Some(760));
+    assert_eq!(balance(&validator, &second.public()), Some(320));
+    assert_eq!(
+      record(&validator, &first.public()),
+      Some(Deposit {
+        depositor: second.public(),
+        amount: 440,
+      })
+    );
+  }
+}
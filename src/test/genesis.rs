@@ -0,0 +1,353 @@
+#[cfg(test)]
+mod tests {
+  use {
+    crate::{
+      cli::CreateGenesisOpts,
+      consensus::{
+        block::Block,
+        validator::Validator,
+        Genesis,
+        GenesisError,
+        Limits,
+        GENESIS_ENCODING_VERSION,
+      },
+      primitives::{Account, Keypair, Pubkey, ToBase58String},
+      storage::PersistentState,
+      test::utils::genesis_default,
+      vm::{
+        builtin::{
+          currency::{coin_address, CoinAccount, CURRENCY_BUILTIN_ADDR},
+          governance::GOVERNANCE_STATE_ADDR,
+        },
+        State,
+        Transaction,
+        SYSTEM_BUILTIN_ADDR,
+        WASM_VM_BUILTIN_ADDR,
+      },
+    },
+    borsh::{BorshDeserialize, BorshSerialize},
+    clap::Parser,
+    std::{
+      collections::BTreeMap,
+      marker::PhantomData,
+      path::{Path, PathBuf},
+      time::Duration,
+    },
+  };
+
+  /// The smallest valid WASM module, a header without any sections.
+  const EMPTY_MODULE: &[u8] = b"\0asm\x01\0\0\0";
+
+  fn genesis_with_contract(
+    dir: &std::path::Path,
+    address: Pubkey,
+  ) -> Genesis<Vec<Transaction>> {
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(dir.join("contract.wasm"), EMPTY_MODULE).unwrap();
+
+    let mut genesis = genesis_default(&Keypair::unique());
+    genesis
+      .contracts
+      .insert(address, PathBuf::from("contract.wasm"));
+    genesis
+  }
+
+  #[test]
+  fn genesis_contracts_test() {
+    let mut dir = std::env::temp_dir();
+    dir.push("genesis_contracts_test");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let address = Pubkey::unique();
+    let mut genesis = genesis_with_contract(&dir, address);
+    genesis.load_contracts(&dir).unwrap();
+    assert!(genesis.contracts.is_empty());
+
+    let account = genesis.state.get(&address).unwrap();
+    assert!(account.executable);
+    assert_eq!(account.owner, Some(*WASM_VM_BUILTIN_ADDR));
+    assert_eq!(account.data.as_deref(), Some(EMPTY_MODULE));
+
+    // the executable flag survives the round trip through storage
+    let storage = PersistentState::new(&genesis, dir.clone()).unwrap();
+    assert_eq!(storage.get(&address), Some(account.clone()));
+    drop(storage);
+
+    // and is part of the genesis identity
+    let hash = genesis.hash().unwrap();
+    genesis.state.get_mut(&address).unwrap().executable = false;
+    assert_ne!(hash, genesis.hash().unwrap());
+
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn genesis_contracts_limits_test() {
+    let mut dir = std::env::temp_dir();
+    dir.push("genesis_contracts_limits_test");
+
+    // contract addresses can't collide with other genesis accounts
+    let address = Pubkey::unique();
+    let mut genesis = genesis_with_contract(&dir, address);
+    genesis.state.insert(address, Default::default());
+    assert!(genesis.load_contracts(&dir).is_err());
+
+    // contracts are subject to the same size limit as deployments
+    let mut genesis = genesis_with_contract(&dir, address);
+    genesis.limits.max_contract_size = EMPTY_MODULE.len() - 1;
+    assert!(genesis.load_contracts(&dir).is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+
+  fn created_genesis(args: &[&str]) -> Genesis<Vec<Transaction>> {
+    let opts = CreateGenesisOpts::parse_from(
+      ["create", "--chain-id", "test"].iter().chain(args).copied(),
+    );
+    opts.build(&[Pubkey::unique(), Pubkey::unique()]).unwrap()
+  }
+
+  #[test]
+  fn validate_test_genesis() {
+    let genesis =
+      Genesis::<Vec<Transaction>>::load(Path::new("test/genesis.json"))
+        .unwrap();
+    assert!(genesis.validate().is_ok());
+  }
+
+  #[test]
+  fn legacy_limits_test() {
+    // limits of a genesis file created before multi-instruction
+    // transactions and storage deposits existed
+    let limits: Limits = serde_json::from_str(
+      r#"{
+        "maxJustificationAge": 4,
+        "maxBlockSize": 1000000,
+        "minimumStake": 1000,
+        "maxInputAccounts": 32,
+        "maxAccountSize": 65536,
+        "maxLogSize": 256,
+        "maxLogsCount": 32,
+        "maxTransactionParamsSize": 2048,
+        "maxBlockTransactions": 2000,
+        "maxContractSize": 614400
+      }"#,
+    )
+    .unwrap();
+    assert_eq!(limits.max_transaction_instructions, 1);
+    assert_eq!(limits.storage_deposit_per_account, 0);
+    assert_eq!(limits.storage_deposit_per_byte, 0);
+    assert!(limits.validate().is_ok());
+  }
+
+  #[test]
+  fn create_genesis_test() {
+    let wallet = Pubkey::unique();
+    let balance = format!("{wallet}=5000");
+    let genesis = created_genesis(&["--balance", &balance]);
+    assert!(genesis.validate().is_ok());
+    assert_eq!(genesis.validators.len(), 2);
+
+    let account = genesis
+      .state
+      .get(&coin_address(&genesis.system_coin, &wallet))
+      .unwrap();
+    let coin =
+      CoinAccount::try_from_slice(account.data.as_ref().unwrap()).unwrap();
+    assert_eq!(coin.owner, wallet);
+    assert_eq!(coin.balance, 5000);
+  }
+
+  #[test]
+  fn invalid_genesis_test() {
+    let mut genesis = created_genesis(&[]);
+    genesis.builtins.push(Pubkey::unique());
+    assert!(matches!(
+      genesis.validate(),
+      Err(GenesisError::UnknownBuiltin(_))
+    ));
+
+    let mut genesis = created_genesis(&[]);
+    genesis.validators[0].stake = 999;
+    assert!(matches!(
+      genesis.validate(),
+      Err(GenesisError::InsufficientStake(_, 999))
+    ));
+
+    // coins that don't come from the mint supply
+    let mut genesis = created_genesis(&[]);
+    let wallet = Pubkey::unique();
+    let coin = CoinAccount {
+      mint: genesis.system_coin,
+      owner: wallet,
+      balance: 10,
+      frozen: false,
+    };
+    let address = coin_address(&genesis.system_coin, &wallet);
+    let mut account = genesis.state[&genesis.system_coin].clone();
+    account.data = Some(coin.try_to_vec().unwrap());
+    genesis.state.insert(address, account);
+    assert!(matches!(
+      genesis.validate(),
+      Err(GenesisError::SupplyMismatch(0, 10))
+    ));
+
+    let mut genesis = created_genesis(&[]);
+    genesis.state.remove(&genesis.system_coin);
+    assert!(matches!(
+      genesis.validate(),
+      Err(GenesisError::InvalidSystemCoin(_))
+    ));
+
+    // governance can't be enabled without its state account
+    let mut genesis = created_genesis(&[]);
+    genesis.state.remove(&GOVERNANCE_STATE_ADDR);
+    assert!(matches!(
+      genesis.validate(),
+      Err(GenesisError::MissingGovernanceState(_))
+    ));
+  }
+
+  /// A genesis with fixed values for all fields, its hash must never
+  /// change for a given [`GENESIS_ENCODING_VERSION`].
+  fn golden_genesis() -> Genesis<Vec<Transaction>> {
+    let mut state = BTreeMap::new();
+    state.insert(Pubkey::from([3; 32]), Account {
+      nonce: 5,
+      executable: false,
+      owner: None,
+      data: None,
+    });
+    state.insert(Pubkey::from([4; 32]), Account {
+      nonce: 0,
+      executable: true,
+      owner: Some(*WASM_VM_BUILTIN_ADDR),
+      data: Some(EMPTY_MODULE.to_vec()),
+    });
+    state.insert(Pubkey::from([5; 32]), Account {
+      nonce: 0,
+      executable: false,
+      owner: Some(*CURRENCY_BUILTIN_ADDR),
+      data: Some(vec![0]),
+    });
+
+    Genesis {
+      chain_id: "golden".to_owned(),
+      genesis_time: "2022-01-01T00:00:00Z".parse().unwrap(),
+      slot_interval: Duration::from_secs(2),
+      epoch_blocks: 32,
+      limits: Limits {
+        max_justification_age: 100,
+        minimum_stake: 1000,
+        max_input_accounts: 32,
+        max_account_size: 65536,
+        max_log_size: 512,
+        max_logs_count: 32,
+        max_block_transactions: 2000,
+        max_contract_size: 614400,
+        max_block_size: 1_000_000,
+        max_transaction_params_size: 2048,
+        max_transaction_instructions: 16,
+        storage_deposit_per_account: 100,
+        storage_deposit_per_byte: 1,
+      },
+      builtins: vec![*CURRENCY_BUILTIN_ADDR, *SYSTEM_BUILTIN_ADDR],
+      validators: vec![
+        Validator {
+          pubkey: Pubkey::from([1; 32]),
+          stake: 1000,
+        },
+        Validator {
+          pubkey: Pubkey::from([2; 32]),
+          stake: 2000,
+        },
+      ],
+      system_coin: "RensaToken1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
+        .parse()
+        .unwrap(),
+      state,
+      contracts: BTreeMap::new(),
+      upgrades: vec![],
+      _marker: PhantomData,
+    }
+  }
+
+  fn hash_b58(genesis: &Genesis<Vec<Transaction>>) -> String {
+    genesis.hash().unwrap().to_b58()
+  }
+
+  #[test]
+  fn genesis_hash_vectors_test() {
+    let genesis = golden_genesis();
+    assert_eq!(genesis.encode().unwrap()[0], GENESIS_ENCODING_VERSION);
+    assert_eq!(
+      hash_b58(&genesis),
+      "W1ZhmyrZEhGMn15cZGj443WkQYGDfoBmDxg3JchXLZnpED"
+    );
+
+    // no data is different from a single zero byte of data
+    let mut genesis = golden_genesis();
+    genesis.state.get_mut(&Pubkey::from([5; 32])).unwrap().data = None;
+    assert_eq!(
+      hash_b58(&genesis),
+      "W1o9ptvAkrnkMwZuuTMepYtaBZcNCEg9uDneBVASkisXy8"
+    );
+
+    let mut genesis = golden_genesis();
+    genesis.state.clear();
+    assert_eq!(
+      hash_b58(&genesis),
+      "W1mF5cQyDTpiq9pa5QzeAakKrFSUm2rBfxvoLP1z5htwud"
+    );
+  }
+
+  #[test]
+  fn genesis_hash_fields_test() {
+    let golden = hash_b58(&golden_genesis());
+    let changed = |change: fn(&mut Genesis<Vec<Transaction>>)| {
+      let mut genesis = golden_genesis();
+      change(&mut genesis);
+      hash_b58(&genesis) != golden
+    };
+
+    assert!(changed(|g| g.limits.max_transaction_params_size += 1));
+    assert!(changed(|g| g.limits.storage_deposit_per_byte += 1));
+    assert!(changed(|g| g.system_coin = Pubkey::from([6; 32])));
+    assert!(changed(|g| g.validators[1].stake += 1));
+    assert!(changed(|g| {
+      g.state.get_mut(&Pubkey::from([3; 32])).unwrap().nonce += 1
+    }));
+    assert!(changed(|g| {
+      g.state.get_mut(&Pubkey::from([4; 32])).unwrap().executable = false
+    }));
+    assert!(changed(|g| {
+      g.state.get_mut(&Pubkey::from([3; 32])).unwrap().owner =
+        Some(Pubkey::from([0; 32]))
+    }));
+
+    // unloaded contracts would be left out of the chain identity
+    let mut genesis = golden_genesis();
+    genesis
+      .contracts
+      .insert(Pubkey::unique(), PathBuf::from("contract.wasm"));
+    assert!(genesis.hash().is_err());
+  }
+
+  #[test]
+  fn genesis_mismatch_test() {
+    let mut dir = std::env::temp_dir();
+    dir.push("genesis_mismatch_test");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let genesis = golden_genesis();
+    drop(PersistentState::new(&genesis, dir.clone()).unwrap());
+    drop(PersistentState::new(&genesis, dir.clone()).unwrap());
+
+    // a data directory can't be reused by a different chain
+    let mut other = golden_genesis();
+    other.limits.max_transaction_params_size += 1;
+    assert!(PersistentState::new(&other, dir.clone()).is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+}
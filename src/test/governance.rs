@@ -0,0 +1,334 @@
+use {
+  crate::{
+    primitives::Keypair,
+    test::utils::GOVERNANCE_CONTRACT_ADDR,
+    vm::{
+      builtin::governance::{
+        proposal_address,
+        Change,
+        Instruction as GovernanceInstruction,
+        Limit,
+        GOVERNANCE_STATE_ADDR,
+      },
+      AccountRef,
+      Transaction,
+    },
+  },
+  borsh::BorshSerialize,
+  multihash::Multihash,
+};
+
+/// A validator proposes a change of chain parameters.
+pub fn propose_tx(
+  chain: &Multihash,
+  validator: &Keypair,
+  nonce: u64,
+  id: u64,
+  change: Change,
+) -> Transaction {
+  Transaction::new(
+    *GOVERNANCE_CONTRACT_ADDR,
+    chain,
+    nonce,
+    validator,
+    vec![
+      AccountRef::writable(*GOVERNANCE_STATE_ADDR, false).unwrap(),
+      AccountRef::writable(proposal_address(id), false).unwrap(),
+      AccountRef::readonly(validator.public(), true).unwrap(),
+    ],
+    GovernanceInstruction::Propose(change).try_to_vec().unwrap(),
+    &[validator],
+  )
+  .unwrap()
+}
+
+/// A validator approves or rejects a pending proposal.
+pub fn vote_tx(
+  chain: &Multihash,
+  validator: &Keypair,
+  nonce: u64,
+  id: u64,
+  approve: bool,
+) -> Transaction {
+  Transaction::new(
+    *GOVERNANCE_CONTRACT_ADDR,
+    chain,
+    nonce,
+    validator,
+    vec![
+      AccountRef::writable(*GOVERNANCE_STATE_ADDR, false).unwrap(),
+      AccountRef::writable(proposal_address(id), false).unwrap(),
+      AccountRef::readonly(validator.public(), true).unwrap(),
+    ],
+    GovernanceInstruction::Vote(approve).try_to_vec().unwrap(),
+    &[validator],
+  )
+  .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{
+      consensus::{validator::Validator, Genesis, Upgrade},
+      test::{
+        test_validator::{TestCtx, TestValidator},
+        utils::{genesis_default, keypair_default, SHA3_CONTRACT_ADDR},
+      },
+      vm::{
+        builtin::governance::{self, Proposal, Status},
+        ContractError,
+      },
+    },
+    borsh::BorshDeserialize,
+  };
+
+  /// A chain with the governance builtin and three validators staking
+  /// 200000, 300000 and 100000, so the default validator needs the
+  /// approval of the second validator to pass a proposal, while the
+  /// second validator alone can reject it.
+  fn governed_genesis(validators: &[Keypair]) -> Genesis<Vec<Transaction>> {
+    let mut genesis = genesis_default(&validators[0]);
+    genesis.builtins.push(*GOVERNANCE_CONTRACT_ADDR);
+    genesis
+      .state
+      .insert(*GOVERNANCE_STATE_ADDR, governance::genesis_account());
+    genesis.validators = validators
+      .iter()
+      .zip([200000, 300000, 100000])
+      .map(|(v, stake)| Validator {
+        pubkey: v.public(),
+        stake,
+      })
+      .collect();
+    genesis
+  }
+
+  fn validators() -> Vec<Keypair> {
+    vec![keypair_default(), Keypair::unique(), Keypair::unique()]
+  }
+
+  fn proposal(
+    validator: &TestValidator<Vec<Transaction>>,
+    id: u64,
+  ) -> Proposal {
+    let account = validator.get_account(proposal_address(id)).unwrap();
+    Proposal::try_from_slice(&account.data.unwrap()).unwrap()
+  }
+
+  #[test]
+  fn enact_limits_test() {
+    let validators = validators();
+    let genesis = governed_genesis(&validators);
+    let epoch_blocks = genesis.epoch_blocks;
+    let previous = genesis.limits.max_block_transactions;
+    let ctx = TestCtx::with_genesis(genesis, validators[0].clone());
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+
+    let limit = Limit::MaxBlockTransactions(previous * 2);
+    let change = Change::Limits(vec![limit]);
+    let tx = propose_tx(&chain, &validators[0], 1, 0, change);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(proposal(&validator, 0).status, Status::Voting);
+    assert_eq!(proposal(&validator, 0).approvals, 200000);
+
+    let tx = vote_tx(&chain, &validators[1], 1, 0, true);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(proposal(&validator, 0).status, Status::Passed);
+
+    // voting is closed once the proposal passed
+    let tx = vote_tx(&chain, &validators[2], 1, 0, true);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    // the change is enacted at the next epoch boundary
+    validator.advance_to(epoch_blocks - 2);
+    assert_eq!(validator.limits().max_block_transactions, previous);
+    validator.advance_to(epoch_blocks - 1);
+    assert_eq!(validator.limits().max_block_transactions, previous * 2);
+
+    // later changes are layered on top of enacted changes
+    let change = Change::StorageDeposits {
+      per_account: 10,
+      per_byte: 1,
+    };
+    let tx = propose_tx(&chain, &validators[0], 2, 1, change);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    let tx = vote_tx(&chain, &validators[1], 2, 1, true);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    validator.advance_to(epoch_blocks * 2 - 1);
+    let limits = validator.limits();
+    assert_eq!(limits.storage_deposit_per_account, 10);
+    assert_eq!(limits.storage_deposit_per_byte, 1);
+    assert_eq!(limits.max_block_transactions, previous * 2);
+  }
+
+  #[test]
+  fn limits_follow_schedule_test() {
+    let validators = validators();
+    let mut genesis = governed_genesis(&validators);
+    let epoch_blocks = genesis.epoch_blocks;
+    let mut upgraded = genesis.limits.clone();
+    upgraded.max_block_transactions *= 2;
+    genesis.upgrades.push(Upgrade {
+      epoch: 2,
+      limits: Some(upgraded.clone()),
+      enable_builtins: vec![],
+      disable_builtins: vec![],
+    });
+    let ctx = TestCtx::with_genesis(genesis, validators[0].clone());
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+
+    let logs = upgraded.max_logs_count + 1;
+    let change = Change::Limits(vec![Limit::MaxLogsCount(logs)]);
+    let tx = propose_tx(&chain, &validators[0], 1, 0, change);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    let tx = vote_tx(&chain, &validators[1], 1, 0, true);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    // only the changed limit overrides the scheduled upgrade
+    validator.advance_to(epoch_blocks * 2 - 1);
+    let limits = validator.limits();
+    assert_eq!(limits.max_logs_count, logs);
+    assert_eq!(
+      limits.max_block_transactions,
+      upgraded.max_block_transactions
+    );
+  }
+
+  #[test]
+  fn reject_proposal_test() {
+    let validators = validators();
+    let genesis = governed_genesis(&validators);
+    let epoch_blocks = genesis.epoch_blocks;
+    let ctx = TestCtx::with_genesis(genesis, validators[0].clone());
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+
+    let change = Change::EnableBuiltin(*SHA3_CONTRACT_ADDR);
+    let tx = propose_tx(&chain, &validators[0], 1, 0, change.clone());
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    // validators vote once
+    let tx = vote_tx(&chain, &validators[0], 2, 0, true);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    let tx = vote_tx(&chain, &validators[1], 1, 0, false);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(proposal(&validator, 0).status, Status::Rejected);
+    assert_eq!(proposal(&validator, 0).rejections, 300000);
+
+    // proposals that are not decided within
+    // the voting period stop accepting votes
+    let tx = propose_tx(&chain, &validators[0], 3, 1, change);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    validator.advance_to(epoch_blocks * 2 - 1);
+    let tx = vote_tx(&chain, &validators[2], 1, 1, true);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+    assert_eq!(proposal(&validator, 1).status, Status::Voting);
+  }
+
+  #[test]
+  fn recount_votes_test() {
+    let validators = validators();
+    let genesis = governed_genesis(&validators);
+    let epoch_blocks = genesis.epoch_blocks;
+    let ctx = TestCtx::with_genesis(genesis, validators[0].clone());
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+
+    let change = Change::EnableBuiltin(*SHA3_CONTRACT_ADDR);
+    let tx = propose_tx(&chain, &validators[0], 1, 0, change);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let tx = vote_tx(&chain, &validators[2], 1, 0, true);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(proposal(&validator, 0).approvals, 300000);
+
+    // raise the minimum stake above the stake of the third validator
+    let change = Change::Limits(vec![Limit::MinimumStake(150000)]);
+    let tx = propose_tx(&chain, &validators[0], 2, 1, change);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    let tx = vote_tx(&chain, &validators[1], 1, 1, true);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(proposal(&validator, 1).status, Status::Passed);
+
+    // validators below the minimum stake no longer take part
+    validator.advance_to(epoch_blocks - 1);
+    let change = Change::EnableBuiltin(*SHA3_CONTRACT_ADDR);
+    let tx = propose_tx(&chain, &validators[2], 2, 2, change);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    // and their earlier votes lose their weight
+    let tx = vote_tx(&chain, &validators[1], 2, 0, false);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(proposal(&validator, 0).approvals, 200000);
+    assert_eq!(proposal(&validator, 0).rejections, 300000);
+    assert_eq!(proposal(&validator, 0).status, Status::Rejected);
+  }
+
+  #[test]
+  fn invalid_proposal_test() {
+    let validators = validators();
+    let genesis = governed_genesis(&validators);
+    let max_block_size = genesis.limits.max_block_size;
+    let ctx = TestCtx::with_genesis(genesis, validators[0].clone());
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+
+    // only staking validators may propose
+    let change = Change::EnableBuiltin(*SHA3_CONTRACT_ADDR);
+    let tx = propose_tx(&chain, &Keypair::unique(), 1, 0, change);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    // changes that would halt the chain are refused, blocks can't
+    // outgrow the transport limits of the network either
+    let invalid = vec![
+      Change::Limits(vec![Limit::MaxBlockSize(0)]),
+      Change::Limits(vec![Limit::MaxBlockSize(max_block_size + 1)]),
+      Change::EnableBuiltin(*GOVERNANCE_CONTRACT_ADDR),
+      Change::DisableBuiltin(*GOVERNANCE_CONTRACT_ADDR),
+      Change::DisableBuiltin(*SHA3_CONTRACT_ADDR),
+    ];
+    for (nonce, change) in invalid.into_iter().enumerate() {
+      let tx = propose_tx(&chain, &validators[0], nonce as u64 + 1, 0, change);
+      let result = validator.process_transaction(vec![tx]).unwrap();
+      assert!(matches!(
+        result.error(),
+        Some(ContractError::InvalidInputParameters)
+      ));
+    }
+
+    // proposals are created at sequential addresses
+    let change = Change::EnableBuiltin(*SHA3_CONTRACT_ADDR);
+    let tx = propose_tx(&chain, &validators[0], 6, 1, change);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::InvalidInputAccounts)
+    ));
+  }
+}
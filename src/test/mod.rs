@@ -1,3 +1,11 @@
 mod currency;
+mod deposits;
+mod genesis;
+mod governance;
+mod multisig;
+mod signatures;
+mod system;
 mod test_validator;
+mod upgrades;
 mod utils;
+mod vesting;
@@ -0,0 +1,395 @@
+use {
+  crate::{
+    primitives::{Keypair, Pubkey},
+    test::utils::MULTISIG_CONTRACT_ADDR,
+    vm::{
+      builtin::multisig::Instruction as MultisigInstruction,
+      AccountRef,
+      Transaction,
+    },
+  },
+  borsh::BorshSerialize,
+  multihash::Multihash,
+};
+
+/// Creates a new M-of-N multisig account.
+pub fn create_multisig_tx(
+  chain: &Multihash,
+  payer: &Keypair,
+  nonce: u64,
+  seed: &[u8; 32],
+  signers: Vec<Pubkey>,
+  threshold: u8,
+) -> Transaction {
+  Transaction::new(
+    *MULTISIG_CONTRACT_ADDR,
+    chain,
+    nonce,
+    payer,
+    vec![
+      AccountRef::writable(MULTISIG_CONTRACT_ADDR.derive(&[seed]), false)
+        .unwrap(),
+    ],
+    MultisigInstruction::Create {
+      seed: *seed,
+      signers,
+      threshold,
+    }
+    .try_to_vec()
+    .unwrap(),
+    &[],
+  )
+  .unwrap()
+}
+
+/// One of the multisig signers proposes an instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn propose_tx(
+  chain: &Multihash,
+  proposer: &Keypair,
+  nonce: u64,
+  multisig: Pubkey,
+  id: u64,
+  contract: Pubkey,
+  accounts: Vec<AccountRef>,
+  params: Vec<u8>,
+) -> Transaction {
+  Transaction::new(
+    *MULTISIG_CONTRACT_ADDR,
+    chain,
+    nonce,
+    proposer,
+    vec![
+      AccountRef::writable(multisig, false).unwrap(),
+      AccountRef::readonly(proposer.public(), true).unwrap(),
+      AccountRef::writable(proposal_address(&multisig, id), false).unwrap(),
+    ],
+    MultisigInstruction::Propose {
+      contract,
+      accounts,
+      params,
+    }
+    .try_to_vec()
+    .unwrap(),
+    &[proposer],
+  )
+  .unwrap()
+}
+
+/// One of the multisig signers approves a pending proposal.
+pub fn approve_tx(
+  chain: &Multihash,
+  signer: &Keypair,
+  nonce: u64,
+  multisig: Pubkey,
+  id: u64,
+) -> Transaction {
+  Transaction::new(
+    *MULTISIG_CONTRACT_ADDR,
+    chain,
+    nonce,
+    signer,
+    vec![
+      AccountRef::readonly(multisig, false).unwrap(),
+      AccountRef::readonly(signer.public(), true).unwrap(),
+      AccountRef::writable(proposal_address(&multisig, id), false).unwrap(),
+    ],
+    MultisigInstruction::Approve.try_to_vec().unwrap(),
+    &[signer],
+  )
+  .unwrap()
+}
+
+/// Executes an approved proposal, the accounts of the proposed
+/// instruction are passed in the same order as proposed.
+pub fn execute_tx(
+  chain: &Multihash,
+  payer: &Keypair,
+  nonce: u64,
+  multisig: Pubkey,
+  id: u64,
+  accounts: &[AccountRef],
+) -> Transaction {
+  let mut inputs = vec![
+    AccountRef::readonly(multisig, false).unwrap(),
+    AccountRef::writable(proposal_address(&multisig, id), false).unwrap(),
+  ];
+
+  // signatures of the proposed instruction are
+  // provided by the multisig contract itself.
+  inputs.extend(accounts.iter().map(|account| AccountRef {
+    signer: false,
+    ..account.clone()
+  }));
+
+  Transaction::new(
+    *MULTISIG_CONTRACT_ADDR,
+    chain,
+    nonce,
+    payer,
+    inputs,
+    MultisigInstruction::Execute.try_to_vec().unwrap(),
+    &[],
+  )
+  .unwrap()
+}
+
+pub fn proposal_address(multisig: &Pubkey, id: u64) -> Pubkey {
+  MULTISIG_CONTRACT_ADDR.derive(&[multisig.as_ref(), &id.to_le_bytes()])
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{
+      test::{
+        currency::Currency,
+        test_validator::{TestCtx, TestValidator},
+        utils::{keypair_default, CURRENCY_CONTRACT_ADDR},
+      },
+      vm::{
+        builtin::{
+          currency::{CoinAccount, Instruction as CurrencyInstruction},
+          multisig::{Multisig, Proposal},
+        },
+        ContractError,
+      },
+    },
+    borsh::BorshDeserialize,
+  };
+
+  struct Setup {
+    multisig: Pubkey,
+    mint: Pubkey,
+    signers: Vec<Keypair>,
+  }
+
+  /// Creates a 2-of-3 multisig and a coin with the
+  /// multisig address as its mint authority.
+  fn setup(validator: &mut TestValidator<Vec<Transaction>>) -> Setup {
+    let payer = keypair_default();
+    let chain = *validator.chain_hash();
+    let signers = vec![Keypair::unique(), Keypair::unique(), Keypair::unique()];
+    let multisig = MULTISIG_CONTRACT_ADDR.derive(&[&[1u8; 32]]);
+
+    let tx = create_multisig_tx(
+      &chain,
+      &payer,
+      1,
+      &[1u8; 32],
+      signers.iter().map(|s| s.public()).collect(),
+      2,
+    );
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let tx =
+      Currency::create(&chain, payer, 2, &[2u8; 32], multisig, 9, None, None);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    Setup {
+      multisig,
+      mint: CURRENCY_CONTRACT_ADDR.derive(&[&[2u8; 32]]),
+      signers,
+    }
+  }
+
+  fn mint_accounts(
+    mint: Pubkey,
+    multisig: Pubkey,
+    wallet: Pubkey,
+  ) -> Vec<AccountRef> {
+    vec![
+      AccountRef::writable(mint, false).unwrap(),
+      AccountRef::readonly(multisig, true).unwrap(),
+      AccountRef::readonly(wallet, false).unwrap(),
+      AccountRef::writable(
+        CURRENCY_CONTRACT_ADDR.derive(&[&mint, &wallet]),
+        false,
+      )
+      .unwrap(),
+    ]
+  }
+
+  #[test]
+  fn multisig_controlled_mint_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup {
+      multisig,
+      mint,
+      signers,
+    } = setup(&mut validator);
+    let chain = *validator.chain_hash();
+
+    let wallet = Pubkey::unique();
+    let accounts = mint_accounts(mint, multisig, wallet);
+
+    let tx = propose_tx(
+      &chain,
+      &signers[0],
+      1,
+      multisig,
+      0,
+      *CURRENCY_CONTRACT_ADDR,
+      accounts.clone(),
+      CurrencyInstruction::Mint(100).try_to_vec().unwrap(),
+    );
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let state = validator.get_account(multisig).unwrap();
+    let account = Multisig::try_from_slice(&state.data.unwrap()).unwrap();
+    assert_eq!(account.proposals, 1);
+
+    // one approval of the proposer is not enough
+    let tx = execute_tx(&chain, &signers[2], 1, multisig, 0, &accounts);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    // the proposer can't approve twice
+    let tx = approve_tx(&chain, &signers[0], 2, multisig, 0);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    let tx = approve_tx(&chain, &signers[1], 1, multisig, 0);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let proposal = validator
+      .get_account(proposal_address(&multisig, 0))
+      .and_then(|acc| acc.data)
+      .map(|data| Proposal::try_from_slice(&data).unwrap())
+      .unwrap();
+    assert_eq!(proposal.approvals, vec![true, true, false]);
+
+    // anyone can execute an approved proposal
+    let executor = Keypair::unique();
+    let tx = execute_tx(&chain, &executor, 1, multisig, 0, &accounts);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let receipt = result.receipt().unwrap();
+    assert!(receipt.deleted.contains(&proposal_address(&multisig, 0)));
+    assert_eq!(receipt.invocations.len(), 2);
+    assert_eq!(receipt.invocations[1].caller, Some(*MULTISIG_CONTRACT_ADDR));
+
+    let coin = validator
+      .get_account(CURRENCY_CONTRACT_ADDR.derive(&[&mint, &wallet]))
+      .and_then(|acc| acc.data)
+      .map(|data| CoinAccount::try_from_slice(&data).unwrap())
+      .unwrap();
+    assert_eq!(coin.balance, 100);
+  }
+
+  #[test]
+  fn non_signer_proposal_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup { multisig, mint, .. } = setup(&mut validator);
+    let chain = *validator.chain_hash();
+
+    let outsider = Keypair::unique();
+    let tx = propose_tx(
+      &chain,
+      &outsider,
+      1,
+      multisig,
+      0,
+      *CURRENCY_CONTRACT_ADDR,
+      mint_accounts(mint, multisig, outsider.public()),
+      CurrencyInstruction::Mint(100).try_to_vec().unwrap(),
+    );
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+  }
+
+  #[test]
+  fn nested_signatures_cant_be_forged_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup {
+      multisig,
+      mint,
+      signers,
+    } = setup(&mut validator);
+    let chain = *validator.chain_hash();
+
+    // a wallet that is neither owned by the multisig
+    // contract nor signing the transaction.
+    let victim = Keypair::unique().public();
+    let mut accounts = mint_accounts(mint, multisig, victim);
+    accounts[2].signer = true;
+
+    let tx = propose_tx(
+      &chain,
+      &signers[0],
+      1,
+      multisig,
+      0,
+      *CURRENCY_CONTRACT_ADDR,
+      accounts.clone(),
+      CurrencyInstruction::Mint(100).try_to_vec().unwrap(),
+    );
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let tx = approve_tx(&chain, &signers[1], 1, multisig, 0);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let tx = execute_tx(&chain, &signers[2], 1, multisig, 0, &accounts);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::SignatureError(_))
+    ));
+  }
+
+  #[test]
+  fn multisig_cant_sign_for_another_multisig_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup { multisig, mint, .. } = setup(&mut validator);
+    let chain = *validator.chain_hash();
+
+    // a 1-of-1 multisig controlled by the attacker, owned by the
+    // same contract as the multisig that is the mint authority.
+    let attacker = Keypair::unique();
+    let tx = create_multisig_tx(
+      &chain,
+      &keypair_default(),
+      3,
+      &[3u8; 32],
+      vec![attacker.public()],
+      1,
+    );
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    let attacker_multisig = MULTISIG_CONTRACT_ADDR.derive(&[&[3u8; 32]]);
+
+    let accounts = mint_accounts(mint, multisig, attacker.public());
+    let tx = propose_tx(
+      &chain,
+      &attacker,
+      1,
+      attacker_multisig,
+      0,
+      *CURRENCY_CONTRACT_ADDR,
+      accounts.clone(),
+      CurrencyInstruction::Mint(100).try_to_vec().unwrap(),
+    );
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let tx =
+      execute_tx(&chain, &attacker, 2, attacker_multisig, 0, &accounts);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::SignatureError(_))
+    ));
+  }
+}
@@ -0,0 +1,216 @@
+use {
+  crate::{
+    primitives::Keypair,
+    test::utils::{ED25519_CONTRACT_ADDR, SECP256K1_CONTRACT_ADDR},
+    vm::{
+      builtin::{ed25519, secp256k1},
+      Transaction,
+    },
+  },
+  borsh::BorshSerialize,
+  multihash::Multihash,
+};
+
+/// Verifies a batch of Ed25519 signatures.
+pub fn verify_ed25519_tx(
+  chain: &Multihash,
+  payer: &Keypair,
+  nonce: u64,
+  messages: Vec<ed25519::SignedMessage>,
+) -> Transaction {
+  Transaction::new(
+    *ED25519_CONTRACT_ADDR,
+    chain,
+    nonce,
+    payer,
+    vec![],
+    ed25519::Instruction::Verify(messages).try_to_vec().unwrap(),
+    &[],
+  )
+  .unwrap()
+}
+
+/// Verifies a batch of secp256k1 signatures.
+pub fn verify_secp256k1_tx(
+  chain: &Multihash,
+  payer: &Keypair,
+  nonce: u64,
+  messages: Vec<secp256k1::SignedMessage>,
+) -> Transaction {
+  Transaction::new(
+    *SECP256K1_CONTRACT_ADDR,
+    chain,
+    nonce,
+    payer,
+    vec![],
+    secp256k1::Instruction::Verify(messages)
+      .try_to_vec()
+      .unwrap(),
+    &[],
+  )
+  .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{
+      test::test_validator::{TestCtx, TestValidator},
+      vm::{
+        builtin::secp256k1::{eth_address, Signer},
+        ContractError,
+      },
+    },
+    ed25519_dalek::Signer as _,
+    libsecp256k1::{Message, PublicKey, SecretKey},
+    multihash::{Hasher, Keccak256},
+  };
+
+  fn ed25519_signed(
+    keypair: &Keypair,
+    message: &[u8],
+  ) -> ed25519::SignedMessage {
+    ed25519::SignedMessage {
+      pubkey: keypair.public(),
+      message: message.to_vec(),
+      signature: keypair.sign(message).to_bytes(),
+    }
+  }
+
+  fn secp256k1_signed(
+    secret: &SecretKey,
+    signer: Signer,
+    message: &[u8],
+  ) -> secp256k1::SignedMessage {
+    let mut hasher = Keccak256::default();
+    hasher.update(message);
+    let hash = Message::parse(&hasher.finalize().try_into().unwrap());
+    let (signature, recovery_id) = libsecp256k1::sign(&hash, secret);
+
+    let mut bytes = [0u8; 65];
+    bytes[..64].copy_from_slice(&signature.serialize());
+    bytes[64] = recovery_id.serialize() + 27;
+
+    secp256k1::SignedMessage {
+      signer,
+      message: message.to_vec(),
+      signature: bytes,
+    }
+  }
+
+  #[test]
+  fn ed25519_batch_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let payer = Keypair::unique();
+    let (alice, bob) = (Keypair::unique(), Keypair::unique());
+
+    let messages = vec![
+      ed25519_signed(&alice, b"message one"),
+      ed25519_signed(&bob, b"message two"),
+    ];
+
+    let tx = verify_ed25519_tx(&chain, &payer, 1, messages.clone());
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    // one altered message fails the entire batch
+    let mut tampered = messages.clone();
+    tampered[1].message = b"message three".to_vec();
+    let tx = verify_ed25519_tx(&chain, &payer, 2, tampered);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::SignatureError(_))
+    ));
+
+    // a valid signature by a different key
+    let mut tampered = messages;
+    tampered[0].pubkey = bob.public();
+    let tx = verify_ed25519_tx(&chain, &payer, 3, tampered);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::SignatureError(_))
+    ));
+
+    // empty batches are rejected
+    let tx = verify_ed25519_tx(&chain, &payer, 4, vec![]);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::InvalidInputParameters)
+    ));
+  }
+
+  #[test]
+  fn eth_address_test() {
+    // the well-known address of the private key 0x00..01
+    let mut secret = [0u8; 32];
+    secret[31] = 1;
+    let secret = SecretKey::parse(&secret).unwrap();
+    let pubkey = PublicKey::from_secret_key(&secret);
+
+    assert_eq!(
+      eth_address(&pubkey).to_vec(),
+      vec![
+        0x7e, 0x5f, 0x45, 0x52, 0x09, 0x1a, 0x69, 0x12, 0x5d, 0x5d, 0xfc, 0xb7,
+        0xb8, 0xc2, 0x65, 0x90, 0x29, 0x39, 0x5b, 0xdf
+      ]
+    );
+  }
+
+  #[test]
+  fn secp256k1_batch_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let payer = Keypair::unique();
+
+    let secret = SecretKey::parse(&[0x42; 32]).unwrap();
+    let pubkey = PublicKey::from_secret_key(&secret);
+    let address = eth_address(&pubkey);
+
+    let messages = vec![
+      secp256k1_signed(
+        &secret,
+        Signer::PublicKey(pubkey.serialize_compressed()),
+        b"message one",
+      ),
+      secp256k1_signed(&secret, Signer::EthAddress(address), b"message two"),
+    ];
+
+    let tx = verify_secp256k1_tx(&chain, &payer, 1, messages.clone());
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    // recovery ids are also accepted without the 27 offset
+    let mut unprefixed = messages.clone();
+    unprefixed[1].signature[64] -= 27;
+    let tx = verify_secp256k1_tx(&chain, &payer, 2, unprefixed);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    // recovered signer does not match the expected address
+    let mut tampered = messages.clone();
+    tampered[1].signer = Signer::EthAddress([1; 20]);
+    let tx = verify_secp256k1_tx(&chain, &payer, 3, tampered);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::SignatureError(_))
+    ));
+
+    // altered message
+    let mut tampered = messages;
+    tampered[0].message = b"message three".to_vec();
+    let tx = verify_secp256k1_tx(&chain, &payer, 4, tampered);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::SignatureError(_))
+    ));
+  }
+}
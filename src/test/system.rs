@@ -0,0 +1,290 @@
+use {
+  crate::{
+    primitives::{Keypair, Pubkey},
+    test::utils::SYSTEM_CONTRACT_ADDR,
+    vm::{
+      builtin::system::Instruction as SystemInstruction,
+      AccountRef,
+      Transaction,
+    },
+  },
+  borsh::BorshSerialize,
+  multihash::Multihash,
+};
+
+/// Creates a new account with zeroed data, the transaction is paid
+/// by the payer and signed by the new account.
+pub fn create_account_tx(
+  chain: &Multihash,
+  payer: &Keypair,
+  nonce: u64,
+  account: &Keypair,
+  owner: Option<Pubkey>,
+  space: u64,
+) -> Transaction {
+  system_tx(
+    chain,
+    payer,
+    nonce,
+    account,
+    SystemInstruction::CreateAccount { owner, space },
+  )
+}
+
+/// Assigns a plain account to a contract.
+pub fn assign_owner_tx(
+  chain: &Multihash,
+  payer: &Keypair,
+  nonce: u64,
+  account: &Keypair,
+  owner: Pubkey,
+) -> Transaction {
+  system_tx(
+    chain,
+    payer,
+    nonce,
+    account,
+    SystemInstruction::AssignOwner(owner),
+  )
+}
+
+/// Allocates zeroed data for a plain account.
+pub fn allocate_tx(
+  chain: &Multihash,
+  payer: &Keypair,
+  nonce: u64,
+  account: &Keypair,
+  space: u64,
+) -> Transaction {
+  system_tx(
+    chain,
+    payer,
+    nonce,
+    account,
+    SystemInstruction::Allocate(space),
+  )
+}
+
+/// Deletes a plain account.
+pub fn close_tx(
+  chain: &Multihash,
+  payer: &Keypair,
+  nonce: u64,
+  account: &Keypair,
+) -> Transaction {
+  system_tx(chain, payer, nonce, account, SystemInstruction::Close)
+}
+
+fn system_tx(
+  chain: &Multihash,
+  payer: &Keypair,
+  nonce: u64,
+  account: &Keypair,
+  instruction: SystemInstruction,
+) -> Transaction {
+  Transaction::new(
+    *SYSTEM_CONTRACT_ADDR,
+    chain,
+    nonce,
+    payer,
+    vec![AccountRef::writable(account.public(), true).unwrap()],
+    instruction.try_to_vec().unwrap(),
+    &[account],
+  )
+  .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{
+      primitives::Account,
+      test::{
+        test_validator::{TestCtx, TestValidator},
+        utils::CURRENCY_CONTRACT_ADDR,
+      },
+      vm::ContractError,
+    },
+  };
+
+  #[test]
+  fn create_account_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let payer = Keypair::unique();
+    let account = Keypair::unique();
+
+    let tx = create_account_tx(
+      &chain,
+      &payer,
+      1,
+      &account,
+      Some(*CURRENCY_CONTRACT_ADDR),
+      16,
+    );
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(
+      validator.get_account(account.public()),
+      Some(Account {
+        nonce: 0,
+        executable: false,
+        owner: Some(*CURRENCY_CONTRACT_ADDR),
+        data: Some(vec![0; 16]),
+      })
+    );
+
+    // owned accounts are no longer managed by the system contract
+    let tx = close_tx(&chain, &payer, 2, &account);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::InvalidAccountOwner)
+    ));
+  }
+
+  #[test]
+  fn allocate_and_assign_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let payer = Keypair::unique();
+    let account = Keypair::unique();
+
+    let tx = allocate_tx(&chain, &payer, 1, &account, 8);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    // accounts with data can't be allocated again
+    let tx = allocate_tx(&chain, &payer, 2, &account, 8);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::AccountAlreadyExists)
+    ));
+
+    // the data is kept when the account is assigned
+    let tx =
+      assign_owner_tx(&chain, &payer, 3, &account, *CURRENCY_CONTRACT_ADDR);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let state = validator.get_account(account.public()).unwrap();
+    assert_eq!(state.owner, Some(*CURRENCY_CONTRACT_ADDR));
+    assert_eq!(state.data, Some(vec![0; 8]));
+
+    // data size is limited by genesis
+    let other = Keypair::unique();
+    let tx = allocate_tx(&chain, &payer, 4, &other, 1_000_000);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::AccountTooLarge)
+    ));
+  }
+
+  #[test]
+  fn missing_signature_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let payer = Keypair::unique();
+    let account = Keypair::unique();
+
+    let tx = Transaction::new(
+      *SYSTEM_CONTRACT_ADDR,
+      &chain,
+      1,
+      &payer,
+      vec![AccountRef::writable(account.public(), false).unwrap()],
+      SystemInstruction::AssignOwner(payer.public())
+        .try_to_vec()
+        .unwrap(),
+      &[],
+    )
+    .unwrap();
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::SignatureError(_))
+    ));
+    assert!(validator.get_account(account.public()).is_none());
+
+    // derived addresses have no private key to sign with
+    let derived = SYSTEM_CONTRACT_ADDR.derive(&[b"seed"]);
+    let tx = Transaction::new(
+      *SYSTEM_CONTRACT_ADDR,
+      &chain,
+      2,
+      &payer,
+      vec![AccountRef::writable(derived, false).unwrap()],
+      SystemInstruction::Allocate(8).try_to_vec().unwrap(),
+      &[],
+    )
+    .unwrap();
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::SignatureError(_))
+    ));
+  }
+
+  #[test]
+  fn close_account_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let payer = Keypair::unique();
+    let account = Keypair::unique();
+
+    let tx = allocate_tx(&chain, &payer, 1, &account, 8);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    // accounts that never paid for transactions are deleted
+    let tx = close_tx(&chain, &payer, 2, &account);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert!(result
+      .receipt()
+      .unwrap()
+      .deleted
+      .contains(&account.public()));
+    assert!(validator.get_account(account.public()).is_none());
+
+    // closing an account that does not exist fails
+    let tx = close_tx(&chain, &payer, 3, &account);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::AccountDoesNotExist)
+    ));
+  }
+
+  #[test]
+  fn close_payer_keeps_nonce_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let payer = Keypair::unique();
+
+    let tx = allocate_tx(&chain, &payer, 1, &payer, 8);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let tx = close_tx(&chain, &payer, 2, &payer);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    // the nonce survives, so past transactions can't be replayed
+    assert_eq!(
+      validator.get_account(payer.public()),
+      Some(Account {
+        nonce: 2,
+        ..Account::default()
+      })
+    );
+  }
+}
@@ -4,20 +4,23 @@ use {
       block::{Block, BlockData, Produced},
       Chain,
       Genesis,
+      Limits,
       Vote,
     },
     primitives::{b58::ToBase58String, Account, Keypair, Pubkey},
     storage::Error as StorageError,
     test::{
-      currency::create_pq_token_tx,
-      utils::{genesis_default, keypair_default},
+      currency::{create_and_mint_tx, create_pq_token_tx},
+      utils::{genesis_default, keypair_default, CURRENCY_CONTRACT_ADDR},
     },
     vm::{
       self,
       BlockOutput,
       ContractError,
+      Event,
       Finalized,
       MachineError,
+      Receipt,
       State,
       StateDiff,
       StateError,
@@ -86,7 +89,12 @@ impl<D: BlockData> TestCtx<D> {
   pub fn new() -> Self {
     let keypair = keypair_default();
     let genesis = genesis_default::<D>(&keypair);
+    Self::with_genesis(genesis, keypair)
+  }
 
+  /// Construct a TestCtx based on a custom genesis, for
+  /// example one with different limits.
+  pub fn with_genesis(genesis: Genesis<D>, keypair: Keypair) -> Self {
     let store = InMemState::default();
 
     let vm = vm::Machine::new(&genesis).unwrap();
@@ -132,19 +140,29 @@ pub struct ProcessTransactionResult<D: BlockData> {
 
 impl<D: BlockData> ProcessTransactionResult<D> {
   /// returns the resulting logs after processing the transaction
-  fn log(&self) -> Option<&Vec<(String, String)>> {
+  pub fn log(&self) -> Option<&Vec<(String, String)>> {
     self.inner.logs().values().next() // as there is only one
   }
 
   /// returns the resulting errors after executing transactions
-  fn error(&self) -> Option<&ContractError> {
+  pub fn error(&self) -> Option<&ContractError> {
     self.inner.errors().values().next()
   }
 
   /// returns the resulting StateDiff after processing the transactions
-  fn state(&self) -> &StateDiff {
+  pub fn state(&self) -> &StateDiff {
     self.inner.state()
   }
+
+  /// returns the receipt of the processed transaction
+  pub fn receipt(&self) -> Option<&Receipt> {
+    self.inner.block_output.receipts.values().next()
+  }
+
+  /// returns the events emitted by the processed transaction
+  pub fn events(&self) -> Option<&Vec<Event>> {
+    self.inner.block_output.events.values().next()
+  }
 }
 
 /// Implements a TestValidator
@@ -172,10 +190,23 @@ impl<'g, D: BlockData> TestValidator<'g, D> {
     self.height
   }
 
+  /// The chain id transactions are signed against.
+  pub fn chain_hash(&self) -> &Multihash {
+    self.ctx.vm.chain()
+  }
+
   pub fn get_account(&self, pubkey: Pubkey) -> Option<Account> {
     self.chain.with_head(|s, _| s.get(&pubkey))
   }
 
+  /// The limits that apply to the next processed transaction,
+  /// including changes enacted by governance.
+  pub fn limits(&self) -> Limits {
+    self.chain.with_head(|s, _| {
+      self.ctx.vm.at_block(s, self.height + 1).limits().clone()
+    })
+  }
+
   pub fn add_account(&self, pubkey: Pubkey, account: Account) {
     let mut diff = StateDiff::default();
     diff.set(pubkey, account).unwrap();
@@ -188,6 +219,14 @@ impl<'g, D: BlockData> TestValidator<'g, D> {
     self.ctx.store.apply(&diff).unwrap();
   }
 
+  /// Produces empty blocks until the chain reaches the given height,
+  /// the next processed transaction is executed at `height + 1`.
+  pub fn advance_to(&mut self, height: u64) {
+    while self.height < height {
+      self.process_transactions(D::default()).unwrap();
+    }
+  }
+
   pub fn process_transaction(
     &mut self,
     transaction: D,
@@ -203,8 +242,9 @@ impl<'g, D: BlockData> TestValidator<'g, D> {
   ) -> Result<ProcessTransactionsResult<D>, MachineError> {
     // execute our transaction on the head state and return the
     // parents hash
+    let height = self.height + 1;
     let (parent, execution_result) = self.chain.with_head(|s, b| {
-      (b.hash().unwrap(), transactions.execute(&self.ctx.vm, s))
+      (b.hash().unwrap(), transactions.execute(&self.ctx.vm, s, height))
     });
 
     let block_output = execution_result?;
@@ -228,7 +268,7 @@ impl<'g, D: BlockData> TestValidator<'g, D> {
     // the previous block
     let _produced_vote_block = Produced::new(
       &self.ctx.keypair,
-      self.inc_height(),
+      self.height + 1, // not included, so the next block reuses this height
       produced.hash().unwrap(),
       D::default(), // StateDiff::default(),
       statehash,
@@ -273,7 +313,11 @@ impl<'g, D: BlockData> TestValidator<'g, D> {
 
 #[cfg(test)]
 mod tests {
-  use super::*;
+  use {
+    super::*,
+    crate::vm::builtin::currency::{Event as CurrencyEvent, MINT_TOPIC},
+    borsh::BorshDeserialize,
+  };
 
   #[test]
   fn initialize_test_validator_test() {
@@ -290,7 +334,7 @@ mod tests {
 
     let payer = keypair_default();
 
-    let tx_create = create_pq_token_tx(&payer);
+    let tx_create = create_pq_token_tx(ctx.vm.chain(), &payer);
 
     let result = validator.process_transactions(vec![tx_create]).unwrap();
 
@@ -298,7 +342,7 @@ mod tests {
     assert_eq!(result.errors().values().len(), 0);
     assert_eq!(
       &result.state().hash().to_b58(),
-      "W1biVagiHrDbkdaP2qQ5ktft82yppTfpL1kbGek7xGUiKS"
+      "W1aT2mmV3rawHUFxHqmMm7WSqmV1DnKCoMaqPMpGeB6SfV"
     );
   }
 
@@ -309,7 +353,7 @@ mod tests {
 
     let payer = keypair_default();
 
-    let tx_create = create_pq_token_tx(&payer);
+    let tx_create = create_pq_token_tx(ctx.vm.chain(), &payer);
 
     let result = validator.process_transaction(vec![tx_create]).unwrap();
 
@@ -318,8 +362,131 @@ mod tests {
 
     assert_eq!(
       &result.state().hash().to_b58(),
-      "W1biVagiHrDbkdaP2qQ5ktft82yppTfpL1kbGek7xGUiKS"
+      "W1aT2mmV3rawHUFxHqmMm7WSqmV1DnKCoMaqPMpGeB6SfV"
+    );
+  }
+
+  #[test]
+  fn process_atomic_instructions_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+
+    let payer = keypair_default();
+    let wallet = Pubkey::unique();
+    let seed = [1u8; 32];
+
+    let tx = create_and_mint_tx(ctx.vm.chain(), &payer, 1, &seed, wallet, 1000);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+
+    assert!(result.error().is_none());
+
+    let mint = CURRENCY_CONTRACT_ADDR.derive(&[&seed]);
+    let coin = CURRENCY_CONTRACT_ADDR.derive(&[&mint, &wallet]);
+    assert!(result.state().get(&mint).is_some());
+    assert!(result.state().get(&coin).is_some());
+
+    let receipt = result.receipt().unwrap();
+    assert!(receipt.created.contains(&mint));
+    assert!(receipt.created.contains(&coin));
+    assert!(receipt.deleted.is_empty());
+    assert_eq!(receipt.invocations.len(), 2);
+    assert!(receipt.invocations.iter().all(|i| i.caller.is_none()));
+  }
+
+  #[test]
+  fn currency_mint_event_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+
+    let payer = keypair_default();
+    let wallet = Pubkey::unique();
+    let seed = [3u8; 32];
+
+    let tx = create_and_mint_tx(ctx.vm.chain(), &payer, 1, &seed, wallet, 50);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let mint = CURRENCY_CONTRACT_ADDR.derive(&[&seed]);
+    let events = result.events().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].contract, *CURRENCY_CONTRACT_ADDR);
+    assert_eq!(events[0].topics[0], *MINT_TOPIC);
+    assert_eq!(&events[0].topics[2][..], wallet.as_ref());
+
+    let event: CurrencyEvent =
+      BorshDeserialize::try_from_slice(&events[0].data).unwrap();
+    assert_eq!(event, CurrencyEvent::Mint {
+      mint,
+      to: wallet,
+      amount: 50
+    });
+  }
+
+  #[test]
+  fn failed_instruction_reverts_transaction_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+
+    let payer = keypair_default();
+    let wallet = Pubkey::unique();
+    let seed = [2u8; 32];
+
+    // minting zero coins fails, so the mint creation
+    // in the first instruction must not be persisted.
+    let tx = create_and_mint_tx(ctx.vm.chain(), &payer, 1, &seed, wallet, 0);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+
+    assert!(result.error().is_some());
+
+    let mint = CURRENCY_CONTRACT_ADDR.derive(&[&seed]);
+    assert!(result.state().get(&mint).is_none());
+
+    // only the payer nonce increment is recorded
+    let receipt = result.receipt().unwrap();
+    assert!(receipt.state.get(&mint).is_none());
+    assert_eq!(receipt.state.get(&payer.public()).unwrap().nonce, 1);
+    assert!(receipt.invocations.is_empty());
+  }
+
+  #[test]
+  fn expired_transaction_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+
+    let payer = keypair_default();
+    let tx_create = create_pq_token_tx(ctx.vm.chain(), &payer);
+
+    // the same transaction, but valid only until the genesis block
+    let tx_expired = Transaction::with_instructions(
+      ctx.vm.chain(),
+      tx_create.nonce,
+      Some(0),
+      &payer,
+      tx_create.accounts.clone(),
+      tx_create.instructions.clone(),
+      &[&payer],
     );
+
+    let result = validator.process_transaction(vec![tx_expired]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::TransactionExpired)
+    ));
+  }
+
+  #[test]
+  fn foreign_chain_transaction_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+
+    let payer = keypair_default();
+    let tx_create = create_pq_token_tx(&Multihash::default(), &payer);
+
+    let result = validator.process_transaction(vec![tx_create]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::SignatureError(_))
+    ));
   }
 
   #[test]
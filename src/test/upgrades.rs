@@ -0,0 +1,146 @@
+#[cfg(test)]
+mod tests {
+  use {
+    crate::{
+      consensus::{
+        block::Block,
+        Genesis,
+        GenesisError,
+        ProtocolSchedule,
+        Upgrade,
+      },
+      primitives::Keypair,
+      test::{
+        signatures::verify_ed25519_tx,
+        test_validator::{TestCtx, TestValidator},
+        utils::{
+          genesis_default,
+          keypair_default,
+          ED25519_CONTRACT_ADDR,
+          SHA3_CONTRACT_ADDR,
+        },
+      },
+      vm::{builtin::ed25519, ContractError, Machine, Transaction},
+    },
+    ed25519_dalek::Signer,
+  };
+
+  /// A chain that swaps the Ed25519 builtin for Sha3 and doubles the
+  /// maximum number of transactions in a block at its second epoch.
+  fn upgraded_genesis(keypair: &Keypair) -> Genesis<Vec<Transaction>> {
+    let mut genesis = genesis_default(keypair);
+    let mut limits = genesis.limits.clone();
+    limits.max_block_transactions *= 2;
+    genesis.upgrades.push(Upgrade {
+      epoch: 1,
+      limits: Some(limits),
+      enable_builtins: vec![*SHA3_CONTRACT_ADDR],
+      disable_builtins: vec![*ED25519_CONTRACT_ADDR],
+    });
+    genesis
+  }
+
+  #[test]
+  fn protocol_schedule_test() {
+    let genesis = upgraded_genesis(&Keypair::unique());
+    let schedule = ProtocolSchedule::new(&genesis);
+    assert_eq!(schedule.versions().len(), 2);
+
+    let last = genesis.epoch_blocks - 1;
+    assert_eq!(schedule.at(0).version, 0);
+    assert_eq!(schedule.at(last).version, 0);
+    assert_eq!(schedule.at(last + 1).version, 1);
+    assert_eq!(schedule.at(u64::MAX).version, 1);
+
+    let vm = Machine::new(&genesis).unwrap();
+    assert_eq!(vm.protocol().version, 0);
+    assert!(vm.builtin(&ED25519_CONTRACT_ADDR).is_some());
+    assert!(vm.builtin(&SHA3_CONTRACT_ADDR).is_none());
+
+    let upgraded = vm.at(genesis.epoch_blocks);
+    assert_eq!(upgraded.protocol().version, 1);
+    assert!(upgraded.builtin(&ED25519_CONTRACT_ADDR).is_none());
+    assert!(upgraded.builtin(&SHA3_CONTRACT_ADDR).is_some());
+    assert_eq!(
+      upgraded.limits().max_block_transactions,
+      genesis.limits.max_block_transactions * 2
+    );
+    assert_eq!(
+      vm.at(last).limits().max_block_transactions,
+      genesis.limits.max_block_transactions
+    );
+  }
+
+  fn signed(keypair: &Keypair) -> Vec<ed25519::SignedMessage> {
+    vec![ed25519::SignedMessage {
+      pubkey: keypair.public(),
+      message: b"upgrade".to_vec(),
+      signature: keypair.sign(b"upgrade").to_bytes(),
+    }]
+  }
+
+  #[test]
+  fn upgrade_activation_test() {
+    let keypair = keypair_default();
+    let genesis = upgraded_genesis(&keypair);
+    let epoch_blocks = genesis.epoch_blocks;
+    let ctx = TestCtx::with_genesis(genesis, keypair);
+    let mut validator = TestValidator::new(&ctx);
+    let chain = *validator.chain_hash();
+    let payer = Keypair::unique();
+
+    // the last block of the first epoch runs the genesis rules
+    validator.advance_to(epoch_blocks - 2);
+    let tx = verify_ed25519_tx(&chain, &payer, 1, signed(&payer));
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    // and the first block of the next epoch the upgraded rules
+    let tx = verify_ed25519_tx(&chain, &payer, 2, signed(&payer));
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::ContractDoesNotExit)
+    ));
+  }
+
+  #[test]
+  fn invalid_upgrades_test() {
+    let keypair = Keypair::unique();
+
+    let mut genesis = upgraded_genesis(&keypair);
+    genesis.upgrades[0].epoch = 0;
+    assert!(matches!(
+      genesis.validate(),
+      Err(GenesisError::UnorderedUpgrade(0))
+    ));
+
+    let mut genesis = upgraded_genesis(&keypair);
+    genesis.upgrades.push(genesis.upgrades[0].clone());
+    assert!(matches!(
+      genesis.validate(),
+      Err(GenesisError::UnorderedUpgrade(1))
+    ));
+
+    let mut genesis = upgraded_genesis(&keypair);
+    genesis.upgrades[0].disable_builtins = vec![*SHA3_CONTRACT_ADDR];
+    assert!(matches!(
+      genesis.validate(),
+      Err(GenesisError::DisabledBuiltin(_))
+    ));
+
+    // upgraded limits are held to the same rules as genesis limits
+    let mut genesis = upgraded_genesis(&keypair);
+    genesis.upgrades[0].limits.as_mut().unwrap().max_block_size = 0;
+    assert!(matches!(
+      genesis.validate(),
+      Err(GenesisError::InvalidLimits(_))
+    ));
+
+    // and upgrades are part of the chain identity
+    let genesis = upgraded_genesis(&keypair);
+    let mut other = genesis.clone();
+    other.upgrades[0].epoch = 2;
+    assert_ne!(genesis.hash().unwrap(), other.hash().unwrap());
+  }
+}
@@ -17,6 +17,13 @@ use {
 // get these from somewhere deeper in the system
 lazy_static::lazy_static! {
     pub static ref CURRENCY_CONTRACT_ADDR: Pubkey = "Currency1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap();
+    pub static ref MULTISIG_CONTRACT_ADDR: Pubkey = "Msig1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap();
+    pub static ref VESTING_CONTRACT_ADDR: Pubkey = "Vesting1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap();
+    pub static ref ED25519_CONTRACT_ADDR: Pubkey = "Ed25519xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap();
+    pub static ref SECP256K1_CONTRACT_ADDR: Pubkey = "Secp256k1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap();
+    pub static ref SYSTEM_CONTRACT_ADDR: Pubkey = "System1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap();
+    pub static ref SHA3_CONTRACT_ADDR: Pubkey = "Sha3xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap();
+    pub static ref GOVERNANCE_CONTRACT_ADDR: Pubkey = "Governance1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap();
 }
 
 pub fn genesis_default<D: BlockData>(keypair: &Keypair) -> Genesis<D> {
@@ -26,7 +33,16 @@ pub fn genesis_default<D: BlockData>(keypair: &Keypair) -> Genesis<D> {
     genesis_time: Utc::now(),
     slot_interval: Duration::from_secs(2),
     state: BTreeMap::new(),
-    builtins: vec![*CURRENCY_CONTRACT_ADDR],
+    contracts: BTreeMap::new(),
+    upgrades: vec![],
+    builtins: vec![
+      *CURRENCY_CONTRACT_ADDR,
+      *MULTISIG_CONTRACT_ADDR,
+      *VESTING_CONTRACT_ADDR,
+      *ED25519_CONTRACT_ADDR,
+      *SECP256K1_CONTRACT_ADDR,
+      *SYSTEM_CONTRACT_ADDR,
+    ],
     limits: Limits {
       max_block_size: 100_000,
       max_justification_age: 100,
@@ -38,6 +54,9 @@ pub fn genesis_default<D: BlockData>(keypair: &Keypair) -> Genesis<D> {
       max_block_transactions: 2000,
       max_contract_size: 614400,
       max_transaction_params_size: 2048,
+      max_transaction_instructions: 16,
+      storage_deposit_per_account: 0,
+      storage_deposit_per_byte: 0,
     },
     system_coin: "RensaToken1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
       .parse()
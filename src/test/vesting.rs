@@ -0,0 +1,399 @@
+use {
+  crate::{
+    primitives::{Keypair, Pubkey},
+    test::utils::VESTING_CONTRACT_ADDR,
+    vm::{
+      builtin::{
+        currency::coin_address,
+        vesting::{Instruction as VestingInstruction, Schedule},
+      },
+      AccountRef,
+      Transaction,
+    },
+  },
+  borsh::BorshSerialize,
+  multihash::Multihash,
+};
+
+/// Escrows tokens of the funder in a new vesting account.
+#[allow(clippy::too_many_arguments)]
+pub fn create_vesting_tx(
+  chain: &Multihash,
+  funder: &Keypair,
+  nonce: u64,
+  seed: &[u8; 32],
+  mint: Pubkey,
+  beneficiary: Pubkey,
+  admin: Option<Pubkey>,
+  amount: u64,
+  schedule: Schedule,
+) -> Transaction {
+  let vesting = VESTING_CONTRACT_ADDR.derive(&[seed]);
+  Transaction::new(
+    *VESTING_CONTRACT_ADDR,
+    chain,
+    nonce,
+    funder,
+    vec![
+      AccountRef::writable(vesting, false).unwrap(),
+      AccountRef::readonly(mint, false).unwrap(),
+      AccountRef::readonly(funder.public(), true).unwrap(),
+      AccountRef::writable(coin_address(&mint, &funder.public()), false)
+        .unwrap(),
+      AccountRef::writable(coin_address(&mint, &vesting), false).unwrap(),
+    ],
+    VestingInstruction::Create {
+      seed: *seed,
+      beneficiary,
+      admin,
+      amount,
+      schedule,
+    }
+    .try_to_vec()
+    .unwrap(),
+    &[funder],
+  )
+  .unwrap()
+}
+
+/// The beneficiary claims all vested tokens.
+pub fn claim_tx(
+  chain: &Multihash,
+  beneficiary: &Keypair,
+  nonce: u64,
+  vesting: Pubkey,
+  mint: Pubkey,
+) -> Transaction {
+  vesting_tx(
+    chain,
+    beneficiary,
+    nonce,
+    vesting,
+    mint,
+    VestingInstruction::Claim,
+  )
+}
+
+/// The admin revokes all unvested tokens.
+pub fn revoke_tx(
+  chain: &Multihash,
+  admin: &Keypair,
+  nonce: u64,
+  vesting: Pubkey,
+  mint: Pubkey,
+) -> Transaction {
+  vesting_tx(
+    chain,
+    admin,
+    nonce,
+    vesting,
+    mint,
+    VestingInstruction::Revoke,
+  )
+}
+
+fn vesting_tx(
+  chain: &Multihash,
+  signer: &Keypair,
+  nonce: u64,
+  vesting: Pubkey,
+  mint: Pubkey,
+  instruction: VestingInstruction,
+) -> Transaction {
+  Transaction::new(
+    *VESTING_CONTRACT_ADDR,
+    chain,
+    nonce,
+    signer,
+    vec![
+      AccountRef::writable(vesting, false).unwrap(),
+      AccountRef::readonly(mint, false).unwrap(),
+      AccountRef::readonly(signer.public(), true).unwrap(),
+      AccountRef::writable(coin_address(&mint, &vesting), false).unwrap(),
+      AccountRef::writable(coin_address(&mint, &signer.public()), false)
+        .unwrap(),
+    ],
+    instruction.try_to_vec().unwrap(),
+    &[signer],
+  )
+  .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{
+      test::{
+        currency::{create_and_mint_tx, mint_tx},
+        test_validator::{TestCtx, TestValidator},
+        utils::{keypair_default, CURRENCY_CONTRACT_ADDR},
+      },
+      vm::{
+        builtin::{
+          currency::CoinAccount,
+          vesting::{TimeUnit, Vesting},
+        },
+        ContractError,
+        Invocation,
+      },
+    },
+    borsh::BorshDeserialize,
+  };
+
+  struct Setup {
+    mint: Pubkey,
+    vesting: Pubkey,
+    beneficiary: Keypair,
+  }
+
+  /// Mints 1000 coins to the default keypair and escrows all of them
+  /// in a vesting account that vests between slots 10 and 30 with a
+  /// cliff at slot 20.
+  fn setup(
+    validator: &mut TestValidator<Vec<Transaction>>,
+    admin: Option<Pubkey>,
+  ) -> Setup {
+    let funder = keypair_default();
+    let chain = *validator.chain_hash();
+    let beneficiary = Keypair::unique();
+    let mint = CURRENCY_CONTRACT_ADDR.derive(&[&[7u8; 32]]);
+
+    let tx =
+      create_and_mint_tx(&chain, &funder, 1, &[7u8; 32], funder.public(), 1000);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    let tx = create_vesting_tx(
+      &chain,
+      &funder,
+      2,
+      &[8u8; 32],
+      mint,
+      beneficiary.public(),
+      admin,
+      1000,
+      Schedule {
+        unit: TimeUnit::Slot,
+        start: 10,
+        cliff: 20,
+        end: 30,
+      },
+    );
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+
+    Setup {
+      mint,
+      vesting: VESTING_CONTRACT_ADDR.derive(&[&[8u8; 32]]),
+      beneficiary,
+    }
+  }
+
+  fn balance(
+    validator: &TestValidator<Vec<Transaction>>,
+    mint: &Pubkey,
+    wallet: &Pubkey,
+  ) -> Option<u64> {
+    validator
+      .get_account(coin_address(mint, wallet))
+      .and_then(|acc| acc.data)
+      .map(|data| CoinAccount::try_from_slice(&data).unwrap().balance)
+  }
+
+  #[test]
+  fn linear_vesting_with_cliff_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup {
+      mint,
+      vesting,
+      beneficiary,
+    } = setup(&mut validator, None);
+    let chain = *validator.chain_hash();
+
+    // all tokens are escrowed
+    assert_eq!(balance(&validator, &mint, &vesting), Some(1000));
+    assert_eq!(
+      balance(&validator, &mint, &keypair_default().public()),
+      None
+    );
+
+    // tokens vest since slot 10, but nothing is claimable before the cliff
+    validator.advance_to(14);
+    let tx = claim_tx(&chain, &beneficiary, 1, vesting, mint);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    // executed at slot 25, 15 out of 20 slots vested
+    validator.advance_to(24);
+    let tx = claim_tx(&chain, &beneficiary, 2, vesting, mint);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(balance(&validator, &mint, &beneficiary.public()), Some(750));
+
+    let state = validator
+      .get_account(vesting)
+      .and_then(|acc| acc.data)
+      .map(|data| Vesting::try_from_slice(&data).unwrap())
+      .unwrap();
+    assert_eq!(state.claimed, 750);
+
+    // executed at slot 26, one more slot vested
+    let tx = claim_tx(&chain, &beneficiary, 3, vesting, mint);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(balance(&validator, &mint, &beneficiary.public()), Some(800));
+
+    // after the end of the schedule everything is claimable
+    validator.advance_to(40);
+    let tx = claim_tx(&chain, &beneficiary, 4, vesting, mint);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(
+      balance(&validator, &mint, &beneficiary.public()),
+      Some(1000)
+    );
+
+    // fully claimed vesting and its escrow are deleted
+    let receipt = result.receipt().unwrap();
+    assert!(receipt.deleted.contains(&vesting));
+    assert!(receipt.deleted.contains(&coin_address(&mint, &vesting)));
+  }
+
+  #[test]
+  fn vesting_signs_for_escrow_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup {
+      mint,
+      vesting,
+      beneficiary,
+    } = setup(&mut validator, None);
+    let chain = *validator.chain_hash();
+
+    // the vesting contract signs only for Vesting.derive([seed]),
+    // which is the wallet of the escrow it transfers tokens from.
+    validator.advance_to(24);
+    let tx = claim_tx(&chain, &beneficiary, 1, vesting, mint);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(balance(&validator, &mint, &vesting), Some(250));
+    assert_eq!(result.receipt().unwrap().invocations, vec![
+      Invocation {
+        contract: *VESTING_CONTRACT_ADDR,
+        caller: None,
+      },
+      Invocation {
+        contract: *CURRENCY_CONTRACT_ADDR,
+        caller: Some(*VESTING_CONTRACT_ADDR),
+      },
+    ]);
+  }
+
+  #[test]
+  fn close_escrow_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup {
+      mint,
+      vesting,
+      beneficiary,
+    } = setup(&mut validator, None);
+    let chain = *validator.chain_hash();
+
+    // tokens sent to the escrow directly don't vest
+    let tx = mint_tx(&chain, &keypair_default(), 3, mint, vesting, 50);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(balance(&validator, &mint, &vesting), Some(1050));
+
+    validator.advance_to(24);
+    let tx = claim_tx(&chain, &beneficiary, 1, vesting, mint);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(balance(&validator, &mint, &beneficiary.public()), Some(750));
+
+    // but they go out with the last claim, so the escrow
+    // is closed along with the vesting account.
+    validator.advance_to(40);
+    let tx = claim_tx(&chain, &beneficiary, 2, vesting, mint);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(
+      balance(&validator, &mint, &beneficiary.public()),
+      Some(1050)
+    );
+    let escrow = coin_address(&mint, &vesting);
+    assert!(validator.get_account(vesting).is_none());
+    assert!(validator.get_account(escrow).is_none());
+  }
+
+  #[test]
+  fn only_beneficiary_can_claim_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup { mint, vesting, .. } = setup(&mut validator, None);
+    let chain = *validator.chain_hash();
+
+    validator.advance_to(40);
+    let tx = claim_tx(&chain, &Keypair::unique(), 1, vesting, mint);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(
+      result.error(),
+      Some(ContractError::InvalidInputAccounts)
+    ));
+    assert_eq!(balance(&validator, &mint, &vesting), Some(1000));
+  }
+
+  #[test]
+  fn revoke_vesting_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let admin = keypair_default();
+    let Setup {
+      mint,
+      vesting,
+      beneficiary,
+    } = setup(&mut validator, Some(admin.public()));
+    let chain = *validator.chain_hash();
+
+    // the beneficiary can't revoke
+    let tx = revoke_tx(&chain, &beneficiary, 1, vesting, mint);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    // executed at slot 20, half of the tokens vested
+    validator.advance_to(19);
+    let tx = revoke_tx(&chain, &admin, 3, vesting, mint);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(balance(&validator, &mint, &admin.public()), Some(500));
+    assert_eq!(balance(&validator, &mint, &vesting), Some(500));
+
+    // nothing vests after revocation
+    validator.advance_to(40);
+    let tx = revoke_tx(&chain, &admin, 4, vesting, mint);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+
+    let tx = claim_tx(&chain, &beneficiary, 2, vesting, mint);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(result.error().is_none());
+    assert_eq!(balance(&validator, &mint, &beneficiary.public()), Some(500));
+    assert!(validator.get_account(vesting).is_none());
+  }
+
+  #[test]
+  fn irrevocable_vesting_test() {
+    let ctx: TestCtx<Vec<Transaction>> = TestCtx::new();
+    let mut validator = TestValidator::new(&ctx);
+    let Setup { mint, vesting, .. } = setup(&mut validator, None);
+    let chain = *validator.chain_hash();
+
+    let tx = revoke_tx(&chain, &keypair_default(), 3, vesting, mint);
+    let result = validator.process_transaction(vec![tx]).unwrap();
+    assert!(matches!(result.error(), Some(ContractError::Other(_))));
+    assert_eq!(balance(&validator, &mint, &vesting), Some(1000));
+  }
+}
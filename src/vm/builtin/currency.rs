@@ -7,13 +7,13 @@ use {
   crate::{
     primitives::Pubkey,
     vm::{
-      contract::{self, AccountView, ContractError, Environment},
+      contract::{self, AccountView, Clock, ContractError, Environment, Topic},
       transaction::SignatureError,
       Machine,
     },
   },
   borsh::{BorshDeserialize, BorshSerialize},
-  serde::Deserialize,
+  serde::{Deserialize, Serialize},
 };
 
 /// Represents a single token metadata on the chain.
@@ -21,8 +21,11 @@ use {
 /// The mint account is always owned by the Currency native contract and its
 /// address doesn't have a corresponding private key. It can be manipulated
 /// only through instructions to the Currency contract.
-#[derive(Debug, Deserialize, BorshSerialize, BorshDeserialize)]
-struct Mint {
+///
+/// Fields were appended to the mint over time, mints stored before a field
+/// existed end before it and decode with its default value.
+#[derive(Debug, Deserialize, BorshSerialize)]
+pub struct Mint {
   /// Optional authority specifies the pubkey that is allowed to mint
   /// new tokens for this token. If set to None, then no more tokens
   /// of this type can be ever minted.
@@ -44,6 +47,35 @@ struct Mint {
   /// The short ticker symbol of the token.
   /// Limited to 9 alphanumeric symbols.
   pub symbol: Option<String>,
+
+  /// Optional authority that is allowed to freeze and thaw coin
+  /// accounts of this token. If set to None, then coin accounts
+  /// of this token can never be frozen.
+  pub freeze_authority: Option<Pubkey>,
+
+  /// Non-fungible tokens have a supply capped at 1, their mint
+  /// authority is dropped automatically once the token is minted.
+  pub nft: bool,
+
+  /// The number of coin accounts of this token that are frozen.
+  /// The freeze authority can't be removed while any are frozen.
+  #[serde(default)]
+  pub frozen_accounts: u64,
+}
+
+impl BorshDeserialize for Mint {
+  fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+    Ok(Self {
+      authority: BorshDeserialize::deserialize(buf)?,
+      supply: BorshDeserialize::deserialize(buf)?,
+      decimals: BorshDeserialize::deserialize(buf)?,
+      name: BorshDeserialize::deserialize(buf)?,
+      symbol: BorshDeserialize::deserialize(buf)?,
+      freeze_authority: trailing(buf)?,
+      nft: trailing(buf)?,
+      frozen_accounts: trailing(buf)?,
+    })
+  }
 }
 
 /// Represents a Coin account associated with a user wallet.
@@ -58,8 +90,8 @@ struct Mint {
 ///   CoinAccount = Currency.derive([mint_pubkey,wallet_pubkey])
 ///
 /// The owner of the token acconut is always the currency module
-#[derive(Debug, Deserialize, BorshSerialize, BorshDeserialize)]
-struct CoinAccount {
+#[derive(Debug, Deserialize, BorshSerialize)]
+pub struct CoinAccount {
   /// The token mint associated with this account
   pub mint: Pubkey,
 
@@ -68,6 +100,265 @@ struct CoinAccount {
 
   /// The amount of tokens in this account.
   pub balance: u64,
+
+  /// Frozen accounts can't send, receive or burn tokens until
+  /// they are thawed by the freeze authority of the mint.
+  pub frozen: bool,
+}
+
+impl BorshDeserialize for CoinAccount {
+  fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+    Ok(Self {
+      mint: BorshDeserialize::deserialize(buf)?,
+      owner: BorshDeserialize::deserialize(buf)?,
+      balance: BorshDeserialize::deserialize(buf)?,
+      frozen: trailing(buf)?,
+    })
+  }
+}
+
+/// Decodes a field that was appended to an existing layout,
+/// data stored in the earlier layout ends before it.
+fn trailing<T: BorshDeserialize + Default>(
+  buf: &mut &[u8],
+) -> std::io::Result<T> {
+  match buf.is_empty() {
+    true => Ok(T::default()),
+    false => T::deserialize(buf),
+  }
+}
+
+/// Describes a non-fungible token.
+///
+/// Metadata accounts are created along with NFT mints and are derived
+/// from the mint address:
+///
+///   Metadata = Currency.derive([mint_pubkey, "metadata"])
+///
+/// The metadata account is owned by the currency module.
+#[derive(
+  Debug,
+  Clone,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+  BorshSerialize,
+  BorshDeserialize,
+)]
+pub struct Metadata {
+  /// The NFT mint described by this metadata.
+  pub mint: Pubkey,
+
+  /// The account that is allowed to verify other NFTs as members
+  /// of the collection represented by this NFT.
+  pub authority: Pubkey,
+
+  /// Location of the off-chain token content (256 bytes max).
+  pub uri: String,
+
+  /// Traits of the token, in the order they were specified.
+  pub attributes: Vec<Attribute>,
+
+  /// Creators of the token and their share of royalties.
+  pub creators: Vec<Creator>,
+
+  /// The collection this NFT claims to be a member of.
+  pub collection: Option<Collection>,
+}
+
+/// A single trait of a non-fungible token.
+#[derive(
+  Debug,
+  Clone,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+  BorshSerialize,
+  BorshDeserialize,
+)]
+pub struct Attribute {
+  pub key: String,
+  pub value: String,
+}
+
+/// A creator of a non-fungible token.
+#[derive(
+  Debug,
+  Clone,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+  BorshSerialize,
+  BorshDeserialize,
+)]
+pub struct Creator {
+  pub address: Pubkey,
+
+  /// Percentage of royalties owed to this creator,
+  /// shares of all creators add up to 100.
+  pub share: u8,
+}
+
+/// Membership of a non-fungible token in a collection.
+///
+/// A collection is itself an NFT, membership is claimed by the member
+/// NFT when it is created and then confirmed by the authority stored
+/// in the metadata of the collection NFT.
+#[derive(
+  Debug,
+  Clone,
+  PartialEq,
+  Eq,
+  Serialize,
+  Deserialize,
+  BorshSerialize,
+  BorshDeserialize,
+)]
+pub struct Collection {
+  /// The mint address of the collection NFT.
+  pub mint: Pubkey,
+
+  /// Set by the collection authority using the
+  /// [`Instruction::VerifyCollection`] instruction.
+  pub verified: bool,
+}
+
+/// Represents an allowance granted by a wallet owner to a delegate.
+///
+/// The delegate is allowed to transfer up to `amount` tokens from the
+/// owner's coin account. Allowance accounts are derived from the mint,
+/// the owner wallet and the delegate addresses:
+///
+///   Allowance = Currency.derive([mint_pubkey, owner_pubkey, delegate_pubkey])
+///
+/// The allowance account is deleted once it is fully spent or revoked.
+#[derive(Debug, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct Allowance {
+  /// The token mint associated with this allowance
+  pub mint: Pubkey,
+
+  /// The wallet address that owns the tokens.
+  pub owner: Pubkey,
+
+  /// The account that is allowed to spend the owner's tokens.
+  pub delegate: Pubkey,
+
+  /// The remaining amount of tokens the delegate is allowed to spend.
+  pub amount: u64,
+}
+
+lazy_static::lazy_static! {
+  /// Address of the currency builtin contract.
+  pub static ref CURRENCY_BUILTIN_ADDR: Pubkey =
+    "Currency1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap();
+
+  /// The first topic of all [`Event::Transfer`] events.
+  pub static ref TRANSFER_TOPIC: Topic = contract::topic("Transfer");
+
+  /// The first topic of all [`Event::Mint`] events.
+  pub static ref MINT_TOPIC: Topic = contract::topic("Mint");
+
+  /// The first topic of all [`Event::Burn`] events.
+  pub static ref BURN_TOPIC: Topic = contract::topic("Burn");
+
+  /// The first topic of all [`Event::Approve`] events.
+  pub static ref APPROVE_TOPIC: Topic = contract::topic("Approve");
+
+  /// The first topic of all [`Event::Freeze`] events.
+  pub static ref FREEZE_TOPIC: Topic = contract::topic("Freeze");
+}
+
+/// Structured events emitted by the currency contract.
+///
+/// The event payload is the borsh encoding of this type. Events are
+/// indexed under their type topic followed by the mint address and
+/// the wallets involved, so clients can query for example all transfers
+/// involving a given wallet.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum Event {
+  /// Topics: [TRANSFER_TOPIC, mint, from wallet, to wallet]
+  Transfer {
+    mint: Pubkey,
+    from: Pubkey,
+    to: Pubkey,
+    amount: u64,
+  },
+
+  /// Topics: [MINT_TOPIC, mint, recipient wallet]
+  Mint {
+    mint: Pubkey,
+    to: Pubkey,
+    amount: u64,
+  },
+
+  /// Topics: [BURN_TOPIC, mint, wallet]
+  Burn {
+    mint: Pubkey,
+    wallet: Pubkey,
+    amount: u64,
+  },
+
+  /// Emitted whenever an allowance changes, revoked allowances
+  /// and fully spent allowances have an amount of zero.
+  ///
+  /// Topics: [APPROVE_TOPIC, mint, owner wallet, delegate]
+  Approve {
+    mint: Pubkey,
+    owner: Pubkey,
+    delegate: Pubkey,
+    amount: u64,
+  },
+
+  /// Emitted when a coin account is frozen or thawed.
+  ///
+  /// Topics: [FREEZE_TOPIC, mint, wallet]
+  Freeze {
+    mint: Pubkey,
+    wallet: Pubkey,
+    frozen: bool,
+  },
+}
+
+impl Event {
+  /// The indexed topics of this event.
+  pub fn topics(&self) -> Vec<Topic> {
+    let account = |addr: &Pubkey| -> Topic { addr.as_ref().try_into().unwrap() };
+    match self {
+      Event::Transfer { mint, from, to, .. } => {
+        vec![*TRANSFER_TOPIC, account(mint), account(from), account(to)]
+      }
+      Event::Mint { mint, to, .. } => {
+        vec![*MINT_TOPIC, account(mint), account(to)]
+      }
+      Event::Burn { mint, wallet, .. } => {
+        vec![*BURN_TOPIC, account(mint), account(wallet)]
+      }
+      Event::Approve {
+        mint,
+        owner,
+        delegate,
+        ..
+      } => vec![
+        *APPROVE_TOPIC,
+        account(mint),
+        account(owner),
+        account(delegate),
+      ],
+      Event::Freeze { mint, wallet, .. } => {
+        vec![*FREEZE_TOPIC, account(mint), account(wallet)]
+      }
+    }
+  }
+
+  fn into_output(self) -> Result<contract::Output, ContractError> {
+    Ok(contract::Output::Event {
+      topics: self.topics(),
+      data: self.try_to_vec()?,
+    })
+  }
 }
 
 /// This is the instruction param to the currency contract
@@ -98,14 +389,19 @@ pub enum Instruction {
     /// The short ticker symbol of the token.
     /// between 1-9 bytes.
     symbol: Option<String>,
+
+    /// Optional authority that is allowed to freeze and thaw
+    /// coin accounts of this token.
+    ///
+    /// Clients that predate freezing don't encode it, which
+    /// is the same as no freeze authority.
+    freeze_authority: Option<Pubkey>,
   },
 
   /// Mints new tokens
   ///
   /// Enabled only when the authority is not None and when
-  /// the transaction is signed by the authority private key
-  /// or invoked by a contract with address equal to the mint
-  /// authority.
+  /// the authority is a signer of this instruction.
   ///
   /// Accounts expected by this instruction:
   ///  0. [drw-] The mint address
@@ -116,9 +412,8 @@ pub enum Instruction {
 
   /// Transfers tokens between wallets.
   ///
-  /// Must be signed by the private key of the wallet
-  /// owner of the sender or the contract that owns the
-  /// tokens.
+  /// The wallet owner of the sender must be a signer
+  /// of this instruction.
   ///
   /// Accounts expected by this instruction:
   //  0. [d-r--] The mint address
@@ -130,8 +425,7 @@ pub enum Instruction {
 
   /// Remove tokens from circulation.
   ///
-  /// Must be signed by the private key of the wallet
-  /// owner or called by the owning contract.
+  /// The wallet owner must be a signer of this instruction.
   ///
   /// Accounts expected by this instruction:
   ///  0. [drw-] The mint address
@@ -141,10 +435,7 @@ pub enum Instruction {
 
   /// Changes the mint authority of the token.
   ///
-  /// The transaction invoking this instruction must be
-  /// signed by the private key of the current authority
-  /// or invoked by the contract with address equal to the
-  /// current authority.
+  /// The current authority must be a signer of this instruction.
   ///
   /// Setting the authority to None is an irreversible operation
   /// and forever foregoes the ability to mint new tokens of this
@@ -154,16 +445,188 @@ pub enum Instruction {
   ///  0. [drw-] The mint address
   ///  1. [---s] The the current authority wallet as signer
   SetAuthority(Option<Pubkey>),
+
+  /// Allows a delegate to transfer up to the given amount of tokens
+  /// from the owner's coin account using [`Instruction::TransferFrom`].
+  ///
+  /// Approving replaces any previous allowance of the same delegate.
+  /// Allowances do not lock tokens, the owner may still spend them.
+  ///
+  /// Accounts expected by this instruction:
+  ///  0. [d---] The mint address
+  ///  1. [---s] The owner wallet address as signer
+  ///  2. [----] The delegate address
+  ///  3. [drw-] The allowance address
+  ///       (Currency.derive([mint, owner, delegate]))
+  Approve(u64),
+
+  /// Removes the allowance of a delegate.
+  ///
+  /// Accounts expected by this instruction:
+  ///  0. [d---] The mint address
+  ///  1. [---s] The owner wallet address as signer
+  ///  2. [----] The delegate address
+  ///  3. [drw-] The allowance address
+  ///       (Currency.derive([mint, owner, delegate]))
+  Revoke,
+
+  /// Transfers tokens from the owner's coin account on behalf of
+  /// the owner and decreases the allowance by the transferred amount.
+  ///
+  /// The delegate must be a signer of this instruction. Contracts
+  /// like exchanges or escrows move approved tokens by approving an
+  /// account they own as the delegate and signing for it when they
+  /// invoke this instruction.
+  ///
+  /// Accounts expected by this instruction:
+  ///  0. [d---] The mint address
+  ///  1. [---s] The delegate address as signer
+  ///  2. [----] The owner wallet address
+  ///  3. [drw-] The allowance address
+  ///       (Currency.derive([mint, owner, delegate]))
+  ///  4. [drw-] The owner coin address
+  ///  5. [----] The recipient wallet owner address
+  ///  6. [drw-] The recipient coin address
+  TransferFrom(u64),
+
+  /// Changes the freeze authority of the token.
+  ///
+  /// The current freeze authority must be a signer
+  /// of this instruction.
+  ///
+  /// Setting the freeze authority to None is an irreversible
+  /// operation and forever foregoes the ability to freeze coin
+  /// accounts of this token. It fails while any coin account
+  /// of this token is frozen, so none is left frozen forever.
+  ///
+  /// Accounts expected by this instruction:
+  ///  0. [drw-] The mint address
+  ///  1. [---s] The current freeze authority as signer
+  SetFreezeAuthority(Option<Pubkey>),
+
+  /// Freezes a coin account, frozen accounts can't send,
+  /// receive or burn tokens until they are thawed.
+  ///
+  /// Must be signed by the freeze authority of the mint.
+  ///
+  /// Accounts expected by this instruction:
+  ///  0. [drw-] The mint address
+  ///  1. [---s] The freeze authority as signer
+  ///  2. [----] The wallet owner address
+  ///  3. [drw-] The coin address (Currency.derive([mint, wallet]))
+  Freeze,
+
+  /// Thaws a frozen coin account.
+  ///
+  /// Must be signed by the freeze authority of the mint.
+  ///
+  /// Accounts expected by this instruction:
+  ///  0. [drw-] The mint address
+  ///  1. [---s] The freeze authority as signer
+  ///  2. [----] The wallet owner address
+  ///  3. [drw-] The coin address (Currency.derive([mint, wallet]))
+  Thaw,
+
+  /// Creates new non-fungible token mint along with its metadata.
+  ///
+  /// NFT mints have no decimals and a supply capped at one token,
+  /// the mint authority is dropped once the token is minted. The
+  /// mint authority is also the authority of the collection
+  /// represented by this NFT.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [drw-] Mint address
+  ///   1. [drw-] Metadata address (Currency.derive([mint, "metadata"]))
+  CreateNft {
+    /// A unique seed that is used to generate the mint address,
+    /// the same way as in [`Instruction::Create`].
+    seed: [u8; 32],
+
+    /// The account allowed to mint the token and to verify
+    /// members of the collection represented by this NFT.
+    authority: Pubkey,
+
+    /// The long version of the token name (64 bytes max)
+    name: Option<String>,
+
+    /// The short ticker symbol of the token.
+    /// between 1-9 bytes.
+    symbol: Option<String>,
+
+    /// Location of the off-chain token content (256 bytes max).
+    uri: String,
+
+    /// Traits of the token (32 attributes max).
+    attributes: Vec<Attribute>,
+
+    /// Creators of the token (5 creators max), their
+    /// shares must add up to 100 if any are specified.
+    creators: Vec<Creator>,
+
+    /// The mint address of the collection this NFT is a member of,
+    /// membership stays unverified until the collection authority
+    /// confirms it with [`Instruction::VerifyCollection`].
+    collection: Option<Pubkey>,
+  },
+
+  /// Confirms that an NFT is a member of the collection it claims.
+  ///
+  /// The authority of the collection NFT must be a signer
+  /// of this instruction.
+  ///
+  /// Accounts expected by this instruction:
+  ///  0. [drw-] The NFT metadata address
+  ///  1. [d---] The collection NFT metadata address
+  ///  2. [---s] The collection authority as signer
+  VerifyCollection,
+}
+
+/// The layout of [`Instruction::Create`] before freezing was added.
+#[derive(BorshDeserialize)]
+struct LegacyCreate {
+  seed: [u8; 32],
+  authority: Pubkey,
+  decimals: u8,
+  name: Option<String>,
+  symbol: Option<String>,
+}
+
+impl From<LegacyCreate> for Instruction {
+  fn from(create: LegacyCreate) -> Self {
+    Instruction::Create {
+      seed: create.seed,
+      authority: create.authority,
+      decimals: create.decimals,
+      name: create.name,
+      symbol: create.symbol,
+      freeze_authority: None,
+    }
+  }
+}
+
+/// Decodes the instruction params, accepting the earlier
+/// layout of [`Instruction::Create`] as well.
+fn decode_instruction(params: &[u8]) -> Result<Instruction, ContractError> {
+  let mut buf = params;
+  if let Ok(instruction) = BorshDeserialize::deserialize(&mut buf) {
+    return Ok(instruction);
+  }
+
+  match params.split_first() {
+    Some((0, mut create)) => LegacyCreate::deserialize(&mut create)
+      .map(Instruction::from)
+      .map_err(|_| ContractError::InvalidInputParameters),
+    _ => Err(ContractError::InvalidInputParameters),
+  }
 }
 
 pub fn contract(
   env: &Environment,
   params: &[u8],
   _: &Machine,
+  _: &Clock,
 ) -> contract::Result {
-  let mut params = params;
-  let instruction: Instruction = BorshDeserialize::deserialize(&mut params)
-    .map_err(|_| ContractError::InvalidInputParameters)?;
+  let instruction = decode_instruction(params)?;
 
   match instruction {
     Instruction::Create {
@@ -172,11 +635,56 @@ pub fn contract(
       decimals,
       name,
       symbol,
-    } => process_create(env, &seed, authority, decimals, name, symbol),
+      freeze_authority,
+    } => process_create(
+      env,
+      &seed,
+      authority,
+      decimals,
+      name,
+      symbol,
+      freeze_authority,
+    ),
     Instruction::Mint(amount) => process_mint(env, amount),
     Instruction::Transfer(amount) => process_transfer(env, amount),
     Instruction::Burn(amount) => process_burn(env, amount),
     Instruction::SetAuthority(account) => process_set_authority(env, account),
+    Instruction::Approve(amount) => process_approve(env, amount),
+    Instruction::Revoke => process_revoke(env),
+    Instruction::TransferFrom(amount) => process_transfer_from(env, amount),
+    Instruction::SetFreezeAuthority(account) => {
+      process_set_freeze_authority(env, account)
+    }
+    Instruction::Freeze => process_set_frozen(env, true),
+    Instruction::Thaw => process_set_frozen(env, false),
+    Instruction::CreateNft {
+      seed,
+      authority,
+      name,
+      symbol,
+      uri,
+      attributes,
+      creators,
+      collection,
+    } => process_create_nft(
+      env,
+      &seed,
+      authority,
+      name,
+      symbol,
+      Metadata {
+        mint: env.address.derive(&[&seed]),
+        authority,
+        uri,
+        attributes,
+        creators,
+        collection: collection.map(|mint| Collection {
+          mint,
+          verified: false,
+        }),
+      },
+    ),
+    Instruction::VerifyCollection => process_verify_collection(env),
   }
 }
 
@@ -189,6 +697,7 @@ fn process_create(
   decimals: u8,
   name: Option<String>,
   symbol: Option<String>,
+  freeze_authority: Option<Pubkey>,
 ) -> contract::Result {
   if env.accounts.len() != 1 {
     return Err(ContractError::InvalidInputAccounts);
@@ -210,17 +719,7 @@ fn process_create(
   }
 
   // validate mint specs
-  if let Some(ref symbol) = symbol {
-    if symbol.is_empty() || symbol.len() > 10 {
-      return Err(ContractError::InvalidInputParameters);
-    }
-  }
-
-  if let Some(ref name) = name {
-    if name.is_empty() || name.len() > 64 {
-      return Err(ContractError::InvalidInputParameters);
-    }
-  }
+  validate_name_and_symbol(&name, &symbol)?;
 
   if decimals > 20 {
     // won't fit in u64
@@ -233,6 +732,9 @@ fn process_create(
     decimals,
     name,
     symbol,
+    freeze_authority,
+    nft: false,
+    frozen_accounts: 0,
   };
 
   Ok(vec![
@@ -280,6 +782,11 @@ fn process_mint(env: &Environment, amount: u64) -> contract::Result {
   // validate and read coin mint data
   let mut mint = read_coin_mint(mint_addr, mint_data, env)?;
 
+  // NFTs have a supply of exactly one token
+  if mint.nft && amount != 1 {
+    return Err(ContractError::InvalidInputParameters);
+  }
+
   // make sure that the caller is authorized to mint new tokens
   if let Some(ref mint_authority) = mint.authority {
     // fail if accounts do not match
@@ -306,6 +813,12 @@ fn process_mint(env: &Environment, amount: u64) -> contract::Result {
     contract::Output::LogEntry("action".into(), "mint".into()),
     contract::Output::LogEntry("to".into(), wallet_addr.to_string()),
     contract::Output::LogEntry("amount".into(), amount.to_string()),
+    Event::Mint {
+      mint: *mint_addr,
+      to: *wallet_addr,
+      amount,
+    }
+    .into_output()?,
   ];
 
   // all checks passed, now the coin account either has to be created because
@@ -313,6 +826,7 @@ fn process_mint(env: &Environment, amount: u64) -> contract::Result {
   // by increasing its balance of this coin
   match read_coin_account(coin_addr, coin_acc, mint_addr, wallet_addr, env)? {
     Some(mut coin) => {
+      ensure_not_frozen(&coin)?;
       coin.balance = coin.balance.saturating_add(amount);
       outputs.push(contract::Output::WriteAccountData(
         *coin_addr,
@@ -325,6 +839,7 @@ fn process_mint(env: &Environment, amount: u64) -> contract::Result {
         mint: *mint_addr,
         balance: amount,
         owner: *wallet_addr,
+        frozen: false,
       };
       outputs.push(contract::Output::CreateOwnedAccount(
         *coin_addr,
@@ -335,6 +850,13 @@ fn process_mint(env: &Environment, amount: u64) -> contract::Result {
 
   // update the global supply value
   mint.supply = mint.supply.saturating_add(amount);
+
+  if mint.nft {
+    // the only token of this NFT was minted,
+    // no more tokens can ever be minted.
+    mint.authority = None;
+  }
+
   outputs.push(contract::Output::WriteAccountData(
     *mint_addr,
     Some(mint.try_to_vec()?),
@@ -363,6 +885,12 @@ fn process_transfer(env: &Environment, amount: u64) -> contract::Result {
   let (recipient_wallet_addr, _) = &env.accounts[3];
   let (recipient_coin_addr, recipient_coin_acc) = &env.accounts[4];
 
+  // the recipient is credited using its account contents from before
+  // the sender was debited, so they can't be the same coin account.
+  if sender_coin_addr == recipient_coin_addr {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
   // make sure that the mint address points to a valid coin mint.
   read_coin_mint(mint_addr, mint_acc, env)?;
 
@@ -383,6 +911,8 @@ fn process_transfer(env: &Environment, amount: u64) -> contract::Result {
       ));
     }
 
+    ensure_not_frozen(&sender_coin)?;
+
     // make sure that the sender has enough balance
     if sender_coin.balance < amount {
       return Err(ContractError::Other(
@@ -403,6 +933,13 @@ fn process_transfer(env: &Environment, amount: u64) -> contract::Result {
         recipient_wallet_addr.to_string(),
       ),
       contract::Output::LogEntry("amount".into(), amount.to_string()),
+      Event::Transfer {
+        mint: *mint_addr,
+        from: *sender_wallet_addr,
+        to: *recipient_wallet_addr,
+        amount,
+      }
+      .into_output()?,
       contract::Output::WriteAccountData(
         *sender_coin_addr,
         Some(sender_coin.try_to_vec()?),
@@ -415,37 +952,14 @@ fn process_transfer(env: &Environment, amount: u64) -> contract::Result {
       outputs.push(contract::Output::DeleteOwnedAccount(*sender_coin_addr));
     }
 
-    match read_coin_account(
+    outputs.push(credit_coin_account(
       recipient_coin_addr,
       recipient_coin_acc,
       mint_addr,
       recipient_wallet_addr,
+      amount,
       env,
-    )? {
-      // the recipient already has a coin account for this coin type
-      Some(mut recipient_coin) => {
-        recipient_coin.balance = recipient_coin.balance.saturating_add(amount);
-        outputs.push(contract::Output::WriteAccountData(
-          *recipient_coin_addr,
-          Some(recipient_coin.try_to_vec()?),
-        ));
-      }
-      None => {
-        // this is the first time a coin of this type is
-        // transferred to this wallet, create a coin account
-        let coin = CoinAccount {
-          mint: *mint_addr,
-          balance: amount,
-          owner: *recipient_wallet_addr,
-        };
-
-        // create coin account for recipient wallet
-        outputs.push(contract::Output::CreateOwnedAccount(
-          *recipient_coin_addr,
-          Some(coin.try_to_vec()?),
-        ));
-      }
-    }
+    )?);
 
     // success, coins transfer completed
     Ok(outputs)
@@ -482,6 +996,8 @@ fn process_burn(env: &Environment, amount: u64) -> contract::Result {
       ));
     }
 
+    ensure_not_frozen(&coin)?;
+
     // make sure the wallet coin account owns enough coins to burn
     if coin.balance < amount {
       return Err(ContractError::Other(
@@ -499,6 +1015,12 @@ fn process_burn(env: &Environment, amount: u64) -> contract::Result {
       contract::Output::LogEntry("wallet".into(), wallet_addr.to_string()),
       contract::Output::LogEntry("amount".into(), amount.to_string()),
       contract::Output::LogEntry("coin".into(), mint_addr.to_string()),
+      Event::Burn {
+        mint: *mint_addr,
+        wallet: *wallet_addr,
+        amount,
+      }
+      .into_output()?,
       // store updated accounts
       contract::Output::WriteAccountData(*mint_addr, Some(mint.try_to_vec()?)),
       contract::Output::WriteAccountData(*coin_addr, Some(coin.try_to_vec()?)),
@@ -572,6 +1094,535 @@ fn process_set_authority(
   }
 }
 
+fn process_approve(env: &Environment, amount: u64) -> contract::Result {
+  if env.accounts.len() != 4 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  // zero allowances are removed using the revoke instruction
+  if amount == 0 {
+    return Err(ContractError::InvalidInputParameters);
+  }
+
+  // Accounts expected by this instruction:
+  //  0. [d---] The mint address
+  //  1. [---s] The owner wallet address as signer
+  //  2. [----] The delegate address
+  //  3. [drw-] The allowance address
+  let (mint_addr, mint_acc) = &env.accounts[0];
+  let (owner_addr, owner_acc) = &env.accounts[1];
+  let (delegate_addr, _) = &env.accounts[2];
+  let (allowance_addr, allowance_acc) = &env.accounts[3];
+
+  // make sure that the mint address points to a valid coin mint.
+  read_coin_mint(mint_addr, mint_acc, env)?;
+
+  // only the owner of the tokens may delegate spending them
+  if !owner_acc.signer {
+    return Err(ContractError::SignatureError(
+      SignatureError::MissingSigners,
+    ));
+  }
+
+  let existing = read_allowance(
+    allowance_addr,
+    allowance_acc,
+    mint_addr,
+    owner_addr,
+    delegate_addr,
+    env,
+  )?;
+
+  let allowance = Allowance {
+    mint: *mint_addr,
+    owner: *owner_addr,
+    delegate: *delegate_addr,
+    amount,
+  };
+
+  Ok(vec![
+    // logs for explorers and dApps
+    contract::Output::LogEntry("action".into(), "approve".into()),
+    contract::Output::LogEntry("owner".into(), owner_addr.to_string()),
+    contract::Output::LogEntry("delegate".into(), delegate_addr.to_string()),
+    contract::Output::LogEntry("coin".into(), mint_addr.to_string()),
+    contract::Output::LogEntry("amount".into(), amount.to_string()),
+    Event::Approve {
+      mint: *mint_addr,
+      owner: *owner_addr,
+      delegate: *delegate_addr,
+      amount,
+    }
+    .into_output()?,
+    match existing {
+      Some(_) => contract::Output::WriteAccountData(
+        *allowance_addr,
+        Some(allowance.try_to_vec()?),
+      ),
+      None => contract::Output::CreateOwnedAccount(
+        *allowance_addr,
+        Some(allowance.try_to_vec()?),
+      ),
+    },
+  ])
+}
+
+fn process_revoke(env: &Environment) -> contract::Result {
+  if env.accounts.len() != 4 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  // Accounts expected by this instruction:
+  //  0. [d---] The mint address
+  //  1. [---s] The owner wallet address as signer
+  //  2. [----] The delegate address
+  //  3. [drw-] The allowance address
+  let (mint_addr, mint_acc) = &env.accounts[0];
+  let (owner_addr, owner_acc) = &env.accounts[1];
+  let (delegate_addr, _) = &env.accounts[2];
+  let (allowance_addr, allowance_acc) = &env.accounts[3];
+
+  read_coin_mint(mint_addr, mint_acc, env)?;
+
+  if !owner_acc.signer {
+    return Err(ContractError::SignatureError(
+      SignatureError::MissingSigners,
+    ));
+  }
+
+  if read_allowance(
+    allowance_addr,
+    allowance_acc,
+    mint_addr,
+    owner_addr,
+    delegate_addr,
+    env,
+  )?
+  .is_none()
+  {
+    return Err(ContractError::AccountDoesNotExist);
+  }
+
+  Ok(vec![
+    // logs for explorers and dApps
+    contract::Output::LogEntry("action".into(), "revoke".into()),
+    contract::Output::LogEntry("owner".into(), owner_addr.to_string()),
+    contract::Output::LogEntry("delegate".into(), delegate_addr.to_string()),
+    contract::Output::LogEntry("coin".into(), mint_addr.to_string()),
+    Event::Approve {
+      mint: *mint_addr,
+      owner: *owner_addr,
+      delegate: *delegate_addr,
+      amount: 0,
+    }
+    .into_output()?,
+    contract::Output::DeleteOwnedAccount(*allowance_addr),
+  ])
+}
+
+fn process_transfer_from(env: &Environment, amount: u64) -> contract::Result {
+  if env.accounts.len() != 7 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  if amount == 0 {
+    return Err(ContractError::InvalidInputParameters);
+  }
+
+  // Accounts expected by this instruction:
+  //  0. [d---] The mint address
+  //  1. [---s] The delegate address as signer
+  //  2. [----] The owner wallet address
+  //  3. [drw-] The allowance address
+  //  4. [drw-] The owner coin address
+  //  5. [----] The recipient wallet owner address
+  //  6. [drw-] The recipient coin address
+  let (mint_addr, mint_acc) = &env.accounts[0];
+  let (delegate_addr, delegate_acc) = &env.accounts[1];
+  let (owner_addr, _) = &env.accounts[2];
+  let (allowance_addr, allowance_acc) = &env.accounts[3];
+  let (owner_coin_addr, owner_coin_acc) = &env.accounts[4];
+  let (recipient_wallet_addr, _) = &env.accounts[5];
+  let (recipient_coin_addr, recipient_coin_acc) = &env.accounts[6];
+
+  // the recipient is credited using its account contents from before
+  // the owner was debited, so they can't be the same coin account.
+  if owner_coin_addr == recipient_coin_addr {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  read_coin_mint(mint_addr, mint_acc, env)?;
+
+  // fail if the delegate is not a signer of this instruction
+  if !delegate_acc.signer {
+    return Err(ContractError::SignatureError(
+      SignatureError::MissingSigners,
+    ));
+  }
+
+  let mut allowance = read_allowance(
+    allowance_addr,
+    allowance_acc,
+    mint_addr,
+    owner_addr,
+    delegate_addr,
+    env,
+  )?
+  .ok_or(ContractError::AccountDoesNotExist)?;
+
+  if allowance.amount < amount {
+    return Err(ContractError::Other(
+      "Transfer amount exceeds the delegate allowance".to_owned(),
+    ));
+  }
+
+  let mut owner_coin = read_coin_account(
+    owner_coin_addr,
+    owner_coin_acc,
+    mint_addr,
+    owner_addr,
+    env,
+  )?
+  .ok_or(ContractError::AccountDoesNotExist)?;
+
+  ensure_not_frozen(&owner_coin)?;
+
+  if owner_coin.balance < amount {
+    return Err(ContractError::Other(
+      "Not enough balance in sender's account".to_owned(),
+    ));
+  }
+
+  // all checks passed, debit the owner and spend the allowance
+  owner_coin.balance = owner_coin.balance.saturating_sub(amount);
+  allowance.amount = allowance.amount.saturating_sub(amount);
+
+  let mut outputs = vec![
+    // logs for explorers and dApps
+    contract::Output::LogEntry("action".into(), "transfer-from".into()),
+    contract::Output::LogEntry("from".into(), owner_addr.to_string()),
+    contract::Output::LogEntry("delegate".into(), delegate_addr.to_string()),
+    contract::Output::LogEntry("coin".into(), mint_addr.to_string()),
+    contract::Output::LogEntry("to".into(), recipient_wallet_addr.to_string()),
+    contract::Output::LogEntry("amount".into(), amount.to_string()),
+    Event::Transfer {
+      mint: *mint_addr,
+      from: *owner_addr,
+      to: *recipient_wallet_addr,
+      amount,
+    }
+    .into_output()?,
+    Event::Approve {
+      mint: *mint_addr,
+      owner: *owner_addr,
+      delegate: *delegate_addr,
+      amount: allowance.amount,
+    }
+    .into_output()?,
+    contract::Output::WriteAccountData(
+      *owner_coin_addr,
+      Some(owner_coin.try_to_vec()?),
+    ),
+  ];
+
+  if owner_coin.balance == 0 {
+    // collect dust coin account, that have zero balance after the
+    // transaction.
+    outputs.push(contract::Output::DeleteOwnedAccount(*owner_coin_addr));
+  }
+
+  if allowance.amount == 0 {
+    // fully spent allowances are removed
+    outputs.push(contract::Output::DeleteOwnedAccount(*allowance_addr));
+  } else {
+    outputs.push(contract::Output::WriteAccountData(
+      *allowance_addr,
+      Some(allowance.try_to_vec()?),
+    ));
+  }
+
+  outputs.push(credit_coin_account(
+    recipient_coin_addr,
+    recipient_coin_acc,
+    mint_addr,
+    recipient_wallet_addr,
+    amount,
+    env,
+  )?);
+
+  Ok(outputs)
+}
+
+fn process_set_freeze_authority(
+  env: &Environment,
+  authority: Option<Pubkey>,
+) -> contract::Result {
+  if env.accounts.len() != 2 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  // Accounts expected by this instruction:
+  //  0. [drw-] The mint address
+  //  1. [---s] The current freeze authority as signer
+  let (mint_addr, mint_acc) = &env.accounts[0];
+  let (current_auth_addr, current_auth_acc) = &env.accounts[1];
+
+  let mut mint = read_coin_mint(mint_addr, mint_acc, env)?;
+  authorize_freeze(&mint, current_auth_addr, current_auth_acc)?;
+
+  // nobody could ever thaw accounts that are still frozen
+  if authority.is_none() && mint.frozen_accounts != 0 {
+    return Err(ContractError::Other(
+      "Coin accounts of this currency are still frozen".to_owned(),
+    ));
+  }
+
+  // update freeze authority on mint metadata
+  mint.freeze_authority = authority;
+
+  Ok(vec![
+    // logs for dApps and explorers
+    contract::Output::LogEntry("action".into(), "set-freeze-authority".into()),
+    contract::Output::LogEntry("coin.address".into(), mint_addr.to_string()),
+    contract::Output::LogEntry(
+      "to".into(),
+      authority.map(|a| a.to_string()).unwrap_or_default(),
+    ),
+    // update mint storage value
+    contract::Output::WriteAccountData(*mint_addr, Some(mint.try_to_vec()?)),
+  ])
+}
+
+/// Freezes or thaws a coin account.
+fn process_set_frozen(env: &Environment, frozen: bool) -> contract::Result {
+  if env.accounts.len() != 4 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  // Accounts expected by this instruction:
+  //  0. [drw-] The mint address
+  //  1. [---s] The freeze authority as signer
+  //  2. [----] The wallet owner address
+  //  3. [drw-] The coin address
+  let (mint_addr, mint_acc) = &env.accounts[0];
+  let (authority_addr, authority_acc) = &env.accounts[1];
+  let (wallet_addr, _) = &env.accounts[2];
+  let (coin_addr, coin_acc) = &env.accounts[3];
+
+  let mut mint = read_coin_mint(mint_addr, mint_acc, env)?;
+  authorize_freeze(&mint, authority_addr, authority_acc)?;
+
+  let mut coin =
+    read_coin_account(coin_addr, coin_acc, mint_addr, wallet_addr, env)?
+      .ok_or(ContractError::AccountDoesNotExist)?;
+
+  if coin.frozen == frozen {
+    return Err(ContractError::Other(match frozen {
+      true => "Coin account is already frozen".to_owned(),
+      false => "Coin account is not frozen".to_owned(),
+    }));
+  }
+
+  coin.frozen = frozen;
+  mint.frozen_accounts = match frozen {
+    true => mint.frozen_accounts + 1,
+    false => mint.frozen_accounts.saturating_sub(1),
+  };
+
+  let action = match frozen {
+    true => "freeze",
+    false => "thaw",
+  };
+
+  Ok(vec![
+    // logs for explorers and dApps
+    contract::Output::LogEntry("action".into(), action.into()),
+    contract::Output::LogEntry("wallet".into(), wallet_addr.to_string()),
+    contract::Output::LogEntry("coin".into(), mint_addr.to_string()),
+    Event::Freeze {
+      mint: *mint_addr,
+      wallet: *wallet_addr,
+      frozen,
+    }
+    .into_output()?,
+    contract::Output::WriteAccountData(*mint_addr, Some(mint.try_to_vec()?)),
+    contract::Output::WriteAccountData(*coin_addr, Some(coin.try_to_vec()?)),
+  ])
+}
+
+/// Creates a new NFT mint and its metadata account.
+fn process_create_nft(
+  env: &Environment,
+  seed: &[u8],
+  authority: Pubkey,
+  name: Option<String>,
+  symbol: Option<String>,
+  metadata: Metadata,
+) -> contract::Result {
+  if env.accounts.len() != 2 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  //   0. [drw-] Mint address
+  //   1. [drw-] Metadata address
+  let (mint_addr, mint_acc) = &env.accounts[0];
+  let (metadata_addr, metadata_acc) = &env.accounts[1];
+
+  if &env.address.derive(&[seed]) != mint_addr
+    || &env.address.derive(&[mint_addr.as_ref(), b"metadata"]) != metadata_addr
+  {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  // are those already in use by some other mint?
+  for acc in [mint_acc, metadata_acc] {
+    if acc.data.is_some() || acc.owner.is_some() {
+      return Err(ContractError::AccountAlreadyExists);
+    }
+  }
+
+  validate_name_and_symbol(&name, &symbol)?;
+
+  if metadata.uri.is_empty()
+    || metadata.uri.len() > 256
+    || metadata.attributes.len() > 32
+    || metadata.creators.len() > 5
+  {
+    return Err(ContractError::InvalidInputParameters);
+  }
+
+  // royalty shares are split among all creators
+  let shares: u32 = metadata.creators.iter().map(|c| c.share as u32).sum();
+  if !metadata.creators.is_empty() && shares != 100 {
+    return Err(ContractError::InvalidInputParameters);
+  }
+
+  // an NFT can't be a member of its own collection
+  if let Some(ref collection) = metadata.collection {
+    if &collection.mint == mint_addr {
+      return Err(ContractError::InvalidInputParameters);
+    }
+  }
+
+  let spec = Mint {
+    authority: Some(authority),
+    supply: 0,
+    decimals: 0,
+    name,
+    symbol,
+    freeze_authority: None,
+    nft: true,
+    frozen_accounts: 0,
+  };
+
+  Ok(vec![
+    // initialize the mint and metadata accounts
+    contract::Output::CreateOwnedAccount(*mint_addr, Some(spec.try_to_vec()?)),
+    contract::Output::CreateOwnedAccount(
+      *metadata_addr,
+      Some(metadata.try_to_vec()?),
+    ),
+    // generate logs
+    contract::Output::LogEntry("action".into(), "create-nft".into()),
+    contract::Output::LogEntry("address".into(), mint_addr.to_string()),
+    contract::Output::LogEntry("name".into(), spec.name.unwrap_or_default()),
+    contract::Output::LogEntry(
+      "symbol".into(),
+      spec.symbol.unwrap_or_default(),
+    ),
+    contract::Output::LogEntry("uri".into(), metadata.uri),
+  ])
+}
+
+fn process_verify_collection(env: &Environment) -> contract::Result {
+  if env.accounts.len() != 3 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  // Accounts expected by this instruction:
+  //  0. [drw-] The NFT metadata address
+  //  1. [d---] The collection NFT metadata address
+  //  2. [---s] The collection authority as signer
+  let (metadata_addr, metadata_acc) = &env.accounts[0];
+  let (collection_addr, collection_acc) = &env.accounts[1];
+  let (authority_addr, authority_acc) = &env.accounts[2];
+
+  let mut metadata = read_metadata(metadata_addr, metadata_acc, env)?;
+  let collection = read_metadata(collection_addr, collection_acc, env)?;
+
+  // the NFT must claim membership in this collection
+  match metadata.collection {
+    Some(ref mut membership) if membership.mint == collection.mint => {
+      if membership.verified {
+        return Err(ContractError::Other(
+          "Collection membership is already verified".to_owned(),
+        ));
+      }
+      membership.verified = true;
+    }
+    _ => {
+      return Err(ContractError::Other(
+        "NFT is not a member of this collection".to_owned(),
+      ))
+    }
+  }
+
+  if &collection.authority != authority_addr {
+    return Err(ContractError::Other(
+      "account not authorized to verify members of this collection".to_owned(),
+    ));
+  }
+
+  if !authority_acc.signer {
+    return Err(ContractError::SignatureError(
+      SignatureError::MissingSigners,
+    ));
+  }
+
+  Ok(vec![
+    // logs for explorers and dApps
+    contract::Output::LogEntry("action".into(), "verify-collection".into()),
+    contract::Output::LogEntry("nft".into(), metadata.mint.to_string()),
+    contract::Output::LogEntry(
+      "collection".into(),
+      collection.mint.to_string(),
+    ),
+    contract::Output::WriteAccountData(
+      *metadata_addr,
+      Some(metadata.try_to_vec()?),
+    ),
+  ])
+}
+
+/// Verifies that the freeze authority of a mint is authorizing
+/// the current instruction by being one of its signers.
+fn authorize_freeze(
+  mint: &Mint,
+  authority_addr: &Pubkey,
+  authority_acc: &AccountView,
+) -> Result<(), ContractError> {
+  match mint.freeze_authority {
+    Some(ref freeze_authority) => {
+      if freeze_authority != authority_addr {
+        return Err(ContractError::Other(
+          "account not authorized to freeze coins of this currency".to_owned(),
+        ));
+      }
+
+      if !authority_acc.signer {
+        return Err(ContractError::SignatureError(
+          SignatureError::MissingSigners,
+        ));
+      }
+
+      Ok(())
+    }
+    None => Err(ContractError::Other(
+      "Freezing coin accounts is disabled for this currency".to_owned(),
+    )),
+  }
+}
+
 /// Verifies that the given account is a valid
 /// coin mint account and returns its deserialized
 /// representation.
@@ -649,3 +1700,137 @@ fn read_coin_account(
     Ok(None)
   }
 }
+
+/// Increases the balance of a wallet coin account, creating
+/// the coin account if this is the first time a coin of this
+/// type is transferred to the wallet.
+fn credit_coin_account(
+  coin_addr: &Pubkey,
+  coin_acc: &AccountView,
+  mint_addr: &Pubkey,
+  wallet_addr: &Pubkey,
+  amount: u64,
+  env: &Environment,
+) -> Result<contract::Output, ContractError> {
+  match read_coin_account(coin_addr, coin_acc, mint_addr, wallet_addr, env)? {
+    // the recipient already has a coin account for this coin type
+    Some(mut coin) => {
+      ensure_not_frozen(&coin)?;
+      coin.balance = coin.balance.saturating_add(amount);
+      Ok(contract::Output::WriteAccountData(
+        *coin_addr,
+        Some(coin.try_to_vec()?),
+      ))
+    }
+    None => {
+      // this is the first time a coin of this type is
+      // transferred to this wallet, create a coin account
+      let coin = CoinAccount {
+        mint: *mint_addr,
+        balance: amount,
+        owner: *wallet_addr,
+        frozen: false,
+      };
+      Ok(contract::Output::CreateOwnedAccount(
+        *coin_addr,
+        Some(coin.try_to_vec()?),
+      ))
+    }
+  }
+}
+
+/// Verifies that the given account is a valid NFT metadata
+/// account and returns its deserialized representation.
+fn read_metadata(
+  addr: &Pubkey,
+  acc: &AccountView,
+  env: &Environment,
+) -> Result<Metadata, ContractError> {
+  if acc.owner.as_ref() != Some(&env.address) {
+    return Err(ContractError::InvalidAccountOwner);
+  }
+
+  let metadata: Metadata = match acc.data {
+    Some(ref data) => BorshDeserialize::try_from_slice(data)
+      .map_err(|_| ContractError::InvalidInputAccounts)?,
+    None => return Err(ContractError::InvalidInputAccounts),
+  };
+
+  // the metadata account must be derived from its mint
+  if addr != &metadata_address(&metadata.mint) {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  Ok(metadata)
+}
+
+/// Validates the optional name and symbol of a new mint.
+fn validate_name_and_symbol(
+  name: &Option<String>,
+  symbol: &Option<String>,
+) -> Result<(), ContractError> {
+  if let Some(ref symbol) = symbol {
+    if symbol.is_empty() || symbol.len() > 10 {
+      return Err(ContractError::InvalidInputParameters);
+    }
+  }
+
+  if let Some(ref name) = name {
+    if name.is_empty() || name.len() > 64 {
+      return Err(ContractError::InvalidInputParameters);
+    }
+  }
+
+  Ok(())
+}
+
+/// The address of the metadata account of an NFT mint.
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+  CURRENCY_BUILTIN_ADDR.derive(&[mint.as_ref(), b"metadata"])
+}
+
+/// The address of the coin account of a wallet for a given mint.
+pub fn coin_address(mint: &Pubkey, wallet: &Pubkey) -> Pubkey {
+  CURRENCY_BUILTIN_ADDR.derive(&[mint.as_ref(), wallet.as_ref()])
+}
+
+/// Frozen coin accounts can't send, receive or burn tokens.
+fn ensure_not_frozen(coin: &CoinAccount) -> Result<(), ContractError> {
+  match coin.frozen {
+    true => Err(ContractError::Other("Coin account is frozen".to_owned())),
+    false => Ok(()),
+  }
+}
+
+/// Verifies that the allowance account is derived from the mint,
+/// owner and delegate addresses and returns the allowance if it
+/// exists, otherwise None is returned.
+fn read_allowance(
+  allowance_addr: &Pubkey,
+  allowance_acc: &AccountView,
+  mint_addr: &Pubkey,
+  owner_addr: &Pubkey,
+  delegate_addr: &Pubkey,
+  env: &Environment,
+) -> Result<Option<Allowance>, ContractError> {
+  if allowance_addr
+    != &env.address.derive(&[mint_addr, owner_addr, delegate_addr])
+  {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  if let Some(ref owner) = allowance_acc.owner {
+    if owner != &env.address {
+      return Err(ContractError::InvalidAccountOwner);
+    }
+  }
+
+  match allowance_acc.data {
+    Some(ref data) => {
+      let allowance: Allowance = BorshDeserialize::try_from_slice(data)
+        .map_err(|_| ContractError::InvalidInputAccounts)?;
+      Ok(Some(allowance))
+    }
+    None => Ok(None),
+  }
+}
@@ -0,0 +1,86 @@
+//! Native Ed25519 Signature Verification Contract
+//!
+//! Verifies a batch of Ed25519 signatures over arbitrary messages and
+//! fails the transaction if any of them is invalid. Contracts that need
+//! to verify signatures, such as bridges, invoke this contract through
+//! [`Output::ContractInvoke`](contract::Output::ContractInvoke) and rely
+//! on the failure reverting the entire transaction.
+
+use {
+  crate::{
+    primitives::Pubkey,
+    vm::{
+      contract::{self, Clock, ContractError, Environment, Output},
+      transaction::SignatureError,
+      Machine,
+    },
+  },
+  borsh::{BorshDeserialize, BorshSerialize},
+  ed25519_dalek::{PublicKey, Signature},
+};
+
+/// A message signed by an Ed25519 private key.
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct SignedMessage {
+  /// The public key of the signer.
+  pub pubkey: Pubkey,
+
+  /// The signed message bytes.
+  pub message: Vec<u8>,
+
+  /// The signature over the message bytes.
+  pub signature: [u8; 64],
+}
+
+/// This is the instruction param to the ed25519 contract
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub enum Instruction {
+  /// Verifies all signatures in the batch.
+  ///
+  /// This instruction takes no accounts.
+  Verify(Vec<SignedMessage>),
+}
+
+pub fn contract(
+  env: &Environment,
+  params: &[u8],
+  _: &Machine,
+  _: &Clock,
+) -> contract::Result {
+  let instruction = Instruction::try_from_slice(params)
+    .map_err(|_| ContractError::InvalidInputParameters)?;
+
+  if !env.accounts.is_empty() {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  match instruction {
+    Instruction::Verify(messages) => {
+      if messages.is_empty() {
+        return Err(ContractError::InvalidInputParameters);
+      }
+
+      for message in &messages {
+        verify(message)?;
+      }
+
+      Ok(vec![
+        Output::LogEntry("action".into(), "verify".into()),
+        Output::LogEntry("count".into(), messages.len().to_string()),
+      ])
+    }
+  }
+}
+
+fn verify(message: &SignedMessage) -> Result<(), ContractError> {
+  let pubkey = PublicKey::from_bytes(message.pubkey.as_ref())
+    .map_err(|_| SignatureError::InvalidSignature)?;
+  let signature = Signature::from_bytes(&message.signature)
+    .map_err(|_| SignatureError::InvalidSignature)?;
+
+  // strict verification rejects small order keys and malleable
+  // signatures, so a signature can't be reused in altered form.
+  pubkey
+    .verify_strict(&message.message, &signature)
+    .map_err(|_| SignatureError::InvalidSignature.into())
+}
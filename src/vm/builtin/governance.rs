@@ -0,0 +1,551 @@
+//! Native Governance Contract
+//!
+//! This builtin contract lets validators change parameters of the chain
+//! without scheduling a protocol upgrade in genesis. Any validator may
+//! propose a change, validators vote on it with their stake and once
+//! more than 2/3 of the total stake approves a proposal, its change is
+//! enacted at the next epoch boundary.
+//!
+//! Votes are weighed by the stakes the consensus uses in the epoch of
+//! the block that counts them. All votes of a proposal are recounted
+//! whenever a new vote arrives, so validators that no longer take part
+//! in the consensus lose the weight of their earlier votes.
+//!
+//! Enacted changes are recorded in the governance state account and
+//! applied by the virtual machine on top of the protocol rules that are
+//! in effect at the height of every executed block, see [`apply`]. The
+//! state account is part of the genesis state of every chain that ever
+//! enables this contract, so no other contract can create it first.
+
+use {
+  crate::{
+    consensus::{Limits, Protocol},
+    primitives::{Account, Pubkey},
+    vm::{
+      builtin::{currency::CURRENCY_BUILTIN_ADDR, BUILTIN_CONTRACTS},
+      contract::{self, AccountView, Clock, ContractError, Environment},
+      transaction::SignatureError,
+      Machine,
+      State,
+    },
+  },
+  borsh::{BorshDeserialize, BorshSerialize},
+};
+
+/// The number of epochs a proposal accepts votes, including
+/// the epoch in which it was created.
+pub const VOTING_EPOCHS: u64 = 2;
+
+lazy_static::lazy_static! {
+  /// Address of the governance builtin contract.
+  pub static ref GOVERNANCE_BUILTIN_ADDR: Pubkey =
+    "Governance1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap();
+
+  /// Address of the governance state account.
+  pub static ref GOVERNANCE_STATE_ADDR: Pubkey =
+    GOVERNANCE_BUILTIN_ADDR.derive(&[b"state"]);
+}
+
+/// A change of chain parameters voted on by validators.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum Change {
+  /// Changes the listed limits of the chain, all other limits
+  /// keep following the protocol schedule.
+  Limits(Vec<Limit>),
+
+  /// Makes a builtin contract available to transactions.
+  EnableBuiltin(Pubkey),
+
+  /// Stops a builtin contract from being available to transactions.
+  /// The Currency and Governance builtins can't be disabled.
+  DisableBuiltin(Pubkey),
+
+  /// Changes the storage deposit rates in system coins.
+  StorageDeposits { per_account: u64, per_byte: u64 },
+}
+
+/// A new value of a single field of [`Limits`].
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum Limit {
+  MaxJustificationAge(u64),
+  MinimumStake(u64),
+  MaxInputAccounts(usize),
+  MaxAccountSize(usize),
+  MaxLogSize(usize),
+  MaxLogsCount(usize),
+  MaxBlockTransactions(usize),
+  MaxContractSize(usize),
+  MaxBlockSize(usize),
+  MaxTransactionParamsSize(usize),
+  MaxTransactionInstructions(usize),
+  StorageDepositPerAccount(u64),
+  StorageDepositPerByte(u64),
+}
+
+impl Limit {
+  /// Overwrites the corresponding field of the given limits.
+  pub fn apply(&self, limits: &mut Limits) {
+    match *self {
+      Limit::MaxJustificationAge(v) => limits.max_justification_age = v,
+      Limit::MinimumStake(v) => limits.minimum_stake = v,
+      Limit::MaxInputAccounts(v) => limits.max_input_accounts = v,
+      Limit::MaxAccountSize(v) => limits.max_account_size = v,
+      Limit::MaxLogSize(v) => limits.max_log_size = v,
+      Limit::MaxLogsCount(v) => limits.max_logs_count = v,
+      Limit::MaxBlockTransactions(v) => limits.max_block_transactions = v,
+      Limit::MaxContractSize(v) => limits.max_contract_size = v,
+      Limit::MaxBlockSize(v) => limits.max_block_size = v,
+      Limit::MaxTransactionParamsSize(v) => {
+        limits.max_transaction_params_size = v
+      }
+      Limit::MaxTransactionInstructions(v) => {
+        limits.max_transaction_instructions = v
+      }
+      Limit::StorageDepositPerAccount(v) => {
+        limits.storage_deposit_per_account = v
+      }
+      Limit::StorageDepositPerByte(v) => limits.storage_deposit_per_byte = v,
+    }
+  }
+}
+
+/// A change that passed the vote and the epoch it takes effect.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Enactment {
+  /// The first epoch that follows the changed parameters.
+  pub epoch: u64,
+
+  /// The enacted change.
+  pub change: Change,
+}
+
+/// The state of the governance contract, stored in the account
+/// at [`GOVERNANCE_STATE_ADDR`].
+#[derive(
+  Debug,
+  Clone,
+  Default,
+  PartialEq,
+  Eq,
+  BorshSerialize,
+  BorshDeserialize,
+)]
+pub struct Governance {
+  /// The number of proposals created so far, used to derive
+  /// the address of the next proposal.
+  pub proposals: u64,
+
+  /// All passed changes in the order they were enacted.
+  pub enacted: Vec<Enactment>,
+}
+
+/// The voting status of a proposal.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  PartialEq,
+  Eq,
+  BorshSerialize,
+  BorshDeserialize,
+)]
+pub enum Status {
+  /// The proposal accepts votes until the end of its voting period.
+  Voting,
+
+  /// More than 2/3 of the stake approved the proposal.
+  Passed,
+
+  /// Enough stake rejected the proposal that it can't pass anymore.
+  Rejected,
+}
+
+/// A change proposed by a validator.
+///
+/// Proposal accounts are derived from the sequential proposal id:
+///
+///   Proposal = Governance.derive([b"proposal", id.to_le_bytes()])
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Proposal {
+  /// Sequential id of the proposal.
+  pub id: u64,
+
+  /// The validator that created the proposal.
+  pub proposer: Pubkey,
+
+  /// The proposed change.
+  pub change: Change,
+
+  /// The first epoch that no longer accepts votes.
+  pub deadline: u64,
+
+  /// Validators that voted so far along with their vote.
+  pub votes: Vec<(Pubkey, bool)>,
+
+  /// The stake that approved the proposal as of the last vote.
+  pub approvals: u64,
+
+  /// The stake that rejected the proposal as of the last vote.
+  pub rejections: u64,
+
+  /// The outcome of the vote.
+  pub status: Status,
+}
+
+/// This is the instruction param to the governance contract
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub enum Instruction {
+  /// Proposes a change of chain parameters, the stake of the
+  /// proposer counts as an approval.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [drw-] The governance state (Governance.derive([b"state"]))
+  ///   1. [drw-] The new proposal
+  ///        (Governance.derive([b"proposal", proposals.to_le_bytes()]))
+  ///   2. [---s] The proposing validator as signer
+  Propose(Change),
+
+  /// Approves or rejects a proposal with the stake of the voter.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [drw-] The governance state
+  ///   1. [drw-] The proposal
+  ///   2. [---s] The voting validator as signer
+  Vote(bool),
+}
+
+pub fn contract(
+  env: &Environment,
+  params: &[u8],
+  vm: &Machine,
+  clock: &Clock,
+) -> contract::Result {
+  let instruction = Instruction::try_from_slice(params)
+    .map_err(|_| ContractError::InvalidInputParameters)?;
+
+  if env.accounts.len() != 3 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  //   0. [drw-] The governance state
+  //   1. [drw-] The proposal
+  //   2. [---s] The validator as signer
+  let (state_addr, state_acc) = &env.accounts[0];
+  let (proposal_addr, proposal_acc) = &env.accounts[1];
+  let (validator_addr, validator_acc) = &env.accounts[2];
+
+  if state_addr != &*GOVERNANCE_STATE_ADDR {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  verify_validator(vm, validator_addr, validator_acc)?;
+  let governance = read_governance(state_acc, env)?;
+
+  match instruction {
+    Instruction::Propose(change) => process_propose(
+      env,
+      vm,
+      clock,
+      governance,
+      (proposal_addr, proposal_acc),
+      validator_addr,
+      change,
+    ),
+    Instruction::Vote(approve) => process_vote(
+      env,
+      vm,
+      clock,
+      governance,
+      (proposal_addr, proposal_acc),
+      validator_addr,
+      approve,
+    ),
+  }
+}
+
+fn process_propose(
+  env: &Environment,
+  vm: &Machine,
+  clock: &Clock,
+  mut governance: Governance,
+  proposal: (&Pubkey, &AccountView),
+  proposer: &Pubkey,
+  change: Change,
+) -> contract::Result {
+  let (proposal_addr, proposal_acc) = proposal;
+  if proposal_addr != &proposal_address(governance.proposals) {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  if proposal_acc.data.is_some() || proposal_acc.owner.is_some() {
+    return Err(ContractError::AccountAlreadyExists);
+  }
+
+  validate_change(vm, &change)?;
+
+  let mut proposal = Proposal {
+    id: governance.proposals,
+    proposer: *proposer,
+    change,
+    deadline: clock.epoch + VOTING_EPOCHS,
+    votes: vec![],
+    approvals: 0,
+    rejections: 0,
+    status: Status::Voting,
+  };
+  governance.proposals += 1;
+  count_vote(vm, clock, &mut governance, &mut proposal, proposer, true);
+
+  Ok(vec![
+    contract::Output::LogEntry("action".into(), "propose".into()),
+    contract::Output::LogEntry("proposal".into(), proposal.id.to_string()),
+    contract::Output::LogEntry("status".into(), status_name(proposal.status)),
+    write_governance(&governance)?,
+    contract::Output::CreateOwnedAccount(
+      *proposal_addr,
+      Some(proposal.try_to_vec()?),
+    ),
+  ])
+}
+
+fn process_vote(
+  env: &Environment,
+  vm: &Machine,
+  clock: &Clock,
+  mut governance: Governance,
+  proposal: (&Pubkey, &AccountView),
+  voter: &Pubkey,
+  approve: bool,
+) -> contract::Result {
+  let (proposal_addr, proposal_acc) = proposal;
+  let mut proposal = read_proposal(proposal_addr, proposal_acc, env)?;
+
+  if proposal.status != Status::Voting {
+    return Err(ContractError::Other(
+      "Voting on this proposal is closed".to_owned(),
+    ));
+  }
+
+  if clock.epoch >= proposal.deadline {
+    return Err(ContractError::Other(
+      "Voting period of this proposal has ended".to_owned(),
+    ));
+  }
+
+  if proposal.votes.iter().any(|(v, _)| v == voter) {
+    return Err(ContractError::Other(
+      "Proposal is already voted on by this validator".to_owned(),
+    ));
+  }
+
+  count_vote(vm, clock, &mut governance, &mut proposal, voter, approve);
+
+  Ok(vec![
+    contract::Output::LogEntry("action".into(), "vote".into()),
+    contract::Output::LogEntry("proposal".into(), proposal.id.to_string()),
+    contract::Output::LogEntry("status".into(), status_name(proposal.status)),
+    write_governance(&governance)?,
+    contract::Output::WriteAccountData(
+      *proposal_addr,
+      Some(proposal.try_to_vec()?),
+    ),
+  ])
+}
+
+/// Records the vote of a validator and recounts all votes of the
+/// proposal with the current stakes of the voters. The change is
+/// enacted at the next epoch once more than 2/3 of the stake
+/// approves it.
+fn count_vote(
+  vm: &Machine,
+  clock: &Clock,
+  governance: &mut Governance,
+  proposal: &mut Proposal,
+  voter: &Pubkey,
+  approve: bool,
+) {
+  proposal.votes.push((*voter, approve));
+  proposal.approvals = 0;
+  proposal.rejections = 0;
+  for (voter, approve) in &proposal.votes {
+    match approve {
+      true => proposal.approvals += vm.stake(voter),
+      false => proposal.rejections += vm.stake(voter),
+    }
+  }
+
+  // a proposal can't pass anymore once a third of the stake rejects it
+  let total = vm.total_stake();
+  if proposal.approvals * 3 > total * 2 {
+    proposal.status = Status::Passed;
+    governance.enacted.push(Enactment {
+      epoch: clock.epoch + 1,
+      change: proposal.change.clone(),
+    });
+  } else if proposal.rejections * 3 >= total {
+    proposal.status = Status::Rejected;
+  }
+}
+
+/// Rejects changes that would leave the chain unable to make progress.
+fn validate_change(vm: &Machine, change: &Change) -> Result<(), ContractError> {
+  let valid = match change {
+    Change::Limits(changes) => {
+      let mut limits = vm.limits().clone();
+      changes.iter().for_each(|c| c.apply(&mut limits));
+      limits.validate().is_ok()
+        && limits.max_block_size <= vm.protocols().max_block_size()
+    }
+    Change::EnableBuiltin(builtin) => {
+      BUILTIN_CONTRACTS.contains_key(builtin) && vm.builtin(builtin).is_none()
+    }
+    Change::DisableBuiltin(builtin) => {
+      vm.builtin(builtin).is_some()
+        && builtin != &*CURRENCY_BUILTIN_ADDR
+        && builtin != &*GOVERNANCE_BUILTIN_ADDR
+    }
+    Change::StorageDeposits { .. } => true,
+  };
+
+  match valid {
+    true => Ok(()),
+    false => Err(ContractError::InvalidInputParameters),
+  }
+}
+
+/// Verifies that the signer of the transaction is a validator
+/// that takes part in the consensus in the current epoch.
+fn verify_validator(
+  vm: &Machine,
+  addr: &Pubkey,
+  acc: &AccountView,
+) -> Result<(), ContractError> {
+  if !acc.signer {
+    return Err(ContractError::SignatureError(
+      SignatureError::MissingSigners,
+    ));
+  }
+
+  match vm.stake(addr) {
+    0 => Err(ContractError::Other(
+      "account is not a staking validator".to_owned(),
+    )),
+    _ => Ok(()),
+  }
+}
+
+/// Reads the governance state, the state account is created
+/// in genesis, see [`genesis_account`].
+fn read_governance(
+  acc: &AccountView,
+  env: &Environment,
+) -> Result<Governance, ContractError> {
+  if acc.owner.as_ref() != Some(&env.address) {
+    return Err(ContractError::InvalidAccountOwner);
+  }
+
+  match acc.data {
+    Some(ref data) => Governance::try_from_slice(data)
+      .map_err(|_| ContractError::InvalidInputAccounts),
+    None => Err(ContractError::AccountDoesNotExist),
+  }
+}
+
+fn write_governance(
+  governance: &Governance,
+) -> Result<contract::Output, ContractError> {
+  Ok(contract::Output::WriteAccountData(
+    *GOVERNANCE_STATE_ADDR,
+    Some(governance.try_to_vec()?),
+  ))
+}
+
+/// The governance state account at [`GOVERNANCE_STATE_ADDR`] that
+/// every chain which enables this contract starts with.
+pub fn genesis_account() -> Account {
+  Account {
+    nonce: 0,
+    executable: false,
+    owner: Some(*GOVERNANCE_BUILTIN_ADDR),
+    data: Some(Governance::default().try_to_vec().expect("infallible")),
+  }
+}
+
+/// Verifies that the given account is a proposal account owned
+/// by this contract and returns its deserialized representation.
+fn read_proposal(
+  addr: &Pubkey,
+  acc: &AccountView,
+  env: &Environment,
+) -> Result<Proposal, ContractError> {
+  if acc.owner.as_ref() != Some(&env.address) {
+    return Err(ContractError::InvalidAccountOwner);
+  }
+
+  let proposal = match acc.data {
+    Some(ref data) => Proposal::try_from_slice(data)
+      .map_err(|_| ContractError::InvalidInputAccounts)?,
+    None => return Err(ContractError::AccountDoesNotExist),
+  };
+
+  if addr != &proposal_address(proposal.id) {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  Ok(proposal)
+}
+
+fn status_name(status: Status) -> String {
+  match status {
+    Status::Voting => "voting",
+    Status::Passed => "passed",
+    Status::Rejected => "rejected",
+  }
+  .to_owned()
+}
+
+/// The address of the proposal with the given id.
+pub fn proposal_address(id: u64) -> Pubkey {
+  GOVERNANCE_BUILTIN_ADDR.derive(&[b"proposal", &id.to_le_bytes()])
+}
+
+/// Applies all changes enacted by governance up to the given epoch
+/// on top of the protocol rules scheduled for that epoch.
+///
+/// Enacted changes take precedence over the same limits of later
+/// scheduled upgrades, a change stays in effect until governance changes
+/// the same parameter again. Limits that governance never changed follow
+/// the protocol schedule.
+pub fn apply(protocol: &Protocol, state: &dyn State, epoch: u64) -> Protocol {
+  let mut protocol = protocol.clone();
+  let governance = state
+    .get(&GOVERNANCE_STATE_ADDR)
+    .filter(|acc| acc.owner == Some(*GOVERNANCE_BUILTIN_ADDR))
+    .and_then(|acc| acc.data)
+    .and_then(|data| Governance::try_from_slice(&data).ok());
+
+  let enacted = governance.iter().flat_map(|g| g.enacted.iter());
+  for enactment in enacted.filter(|e| e.epoch <= epoch) {
+    match &enactment.change {
+      Change::Limits(changes) => {
+        changes.iter().for_each(|c| c.apply(&mut protocol.limits))
+      }
+      Change::EnableBuiltin(builtin) => {
+        if !protocol.builtins.contains(builtin) {
+          protocol.builtins.push(*builtin);
+        }
+      }
+      Change::DisableBuiltin(builtin) => {
+        protocol.builtins.retain(|b| b != builtin);
+      }
+      Change::StorageDeposits {
+        per_account,
+        per_byte,
+      } => {
+        protocol.limits.storage_deposit_per_account = *per_account;
+        protocol.limits.storage_deposit_per_byte = *per_byte;
+      }
+    }
+  }
+  protocol
+}
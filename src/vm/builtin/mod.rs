@@ -5,9 +5,15 @@
 //! identical to invoking a regular user-uploaded contract.
 
 pub mod currency;
+pub mod ed25519;
+pub mod governance;
+pub mod multisig;
+pub mod secp256k1;
 mod sha3;
 mod staking;
-mod wasm;
+pub mod system;
+pub mod vesting;
+pub mod wasm;
 
 use {
   crate::{primitives::Pubkey, vm::contract::NativeContractEntrypoint},
@@ -18,8 +24,14 @@ lazy_static::lazy_static! {
   pub static ref BUILTIN_CONTRACTS: HashMap<Pubkey, NativeContractEntrypoint> = {
     let mut funcs = HashMap::<Pubkey, NativeContractEntrypoint>::new();
     funcs.insert("Sha3xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap(), sha3::contract);
+    funcs.insert("Ed25519xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap(), ed25519::contract);
+    funcs.insert("Secp256k1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap(), secp256k1::contract);
     funcs.insert("Currency1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap(), currency::contract);
+    funcs.insert("Msig1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap(), multisig::contract);
+    funcs.insert("Vesting1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap(), vesting::contract);
+    funcs.insert("System1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap(), system::contract);
     funcs.insert("Staking1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap(), staking::contract);
+    funcs.insert("Governance1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap(), governance::contract);
     funcs.insert("WasmVM1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".parse().unwrap(), wasm::contract);
     funcs
   };
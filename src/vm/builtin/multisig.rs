@@ -0,0 +1,453 @@
+//! Native Multisig Contract
+//!
+//! This builtin contract manages M-of-N multisig accounts. Any of the
+//! multisig signers may propose an arbitrary instruction, the proposal
+//! collects approvals from other signers across transactions and once
+//! the threshold is reached the instruction is invoked with the multisig
+//! address as one of its signers.
+//!
+//! This lets authorities of other contracts, for example the mint
+//! authority of a Currency coin, be controlled by a group of keys.
+
+use {
+  crate::{
+    primitives::Pubkey,
+    vm::{
+      contract::{self, AccountView, Clock, ContractError, Environment},
+      transaction::SignatureError,
+      AccountRef,
+      Machine,
+    },
+  },
+  borsh::{BorshDeserialize, BorshSerialize},
+};
+
+/// The maximum number of signers of a single multisig account.
+pub const MAX_SIGNERS: usize = 16;
+
+/// Represents a M-of-N multisig account.
+///
+/// Multisig accounts are derived from a unique seed:
+///
+///   Multisig = Msig.derive([seed])
+///
+/// The multisig account is owned by the multisig contract, so it
+/// acts as a signer only in instructions invoked by this contract.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Multisig {
+  /// Keys that are allowed to propose and approve instructions.
+  pub signers: Vec<Pubkey>,
+
+  /// The number of approvals required to execute a proposal.
+  pub threshold: u8,
+
+  /// The number of proposals created so far, used to derive
+  /// the address of the next proposal.
+  pub proposals: u64,
+}
+
+/// An instruction proposed for execution by a multisig.
+///
+/// Proposal accounts are derived from the multisig address and
+/// the sequential proposal id:
+///
+///   Proposal = Msig.derive([multisig, id.to_le_bytes()])
+///
+/// The proposal account is deleted once it is executed.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Proposal {
+  /// The multisig account that is executing this proposal.
+  pub multisig: Pubkey,
+
+  /// Sequential id of the proposal within its multisig.
+  pub id: u64,
+
+  /// The contract to be invoked.
+  pub contract: Pubkey,
+
+  /// Input accounts of the invoked contract, the multisig
+  /// address is the only account that may be referenced
+  /// as a signer.
+  pub accounts: Vec<AccountRef>,
+
+  /// Input bytes to the invoked contract.
+  pub params: Vec<u8>,
+
+  /// Approvals of the proposal, indexed by signer position
+  /// in [`Multisig::signers`].
+  pub approvals: Vec<bool>,
+}
+
+impl Proposal {
+  fn approvals_count(&self) -> usize {
+    self.approvals.iter().filter(|a| **a).count()
+  }
+}
+
+/// This is the instruction param to the multisig contract
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub enum Instruction {
+  /// Creates new multisig account.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [drw-] Multisig address (Msig.derive([seed]))
+  Create {
+    /// A unique seed that is used to generate the multisig address.
+    seed: [u8; 32],
+
+    /// Keys allowed to propose and approve instructions,
+    /// between 1 and [`MAX_SIGNERS`] unique keys.
+    signers: Vec<Pubkey>,
+
+    /// The number of approvals required to execute a proposal,
+    /// between 1 and the number of signers.
+    threshold: u8,
+  },
+
+  /// Proposes an instruction for execution by the multisig,
+  /// the proposal is approved by its proposer.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [drw-] The multisig address
+  ///   1. [---s] The proposer, one of the multisig signers
+  ///   2. [drw-] The proposal address
+  ///        (Msig.derive([multisig, proposals.to_le_bytes()]))
+  Propose {
+    /// The contract to be invoked.
+    contract: Pubkey,
+
+    /// Input accounts of the invoked contract.
+    accounts: Vec<AccountRef>,
+
+    /// Input bytes to the invoked contract.
+    params: Vec<u8>,
+  },
+
+  /// Approves a pending proposal.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [d---] The multisig address
+  ///   1. [---s] One of the multisig signers
+  ///   2. [drw-] The proposal address
+  Approve,
+
+  /// Invokes the proposed instruction once it collected enough
+  /// approvals and deletes the proposal. Anyone can execute an
+  /// approved proposal.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [d---] The multisig address
+  ///   1. [drw-] The proposal address
+  ///   2.. All input accounts of the proposed instruction in order
+  Execute,
+}
+
+pub fn contract(
+  env: &Environment,
+  params: &[u8],
+  _: &Machine,
+  _: &Clock,
+) -> contract::Result {
+  let instruction = Instruction::try_from_slice(params)
+    .map_err(|_| ContractError::InvalidInputParameters)?;
+
+  match instruction {
+    Instruction::Create {
+      seed,
+      signers,
+      threshold,
+    } => process_create(env, &seed, signers, threshold),
+    Instruction::Propose {
+      contract,
+      accounts,
+      params,
+    } => process_propose(env, contract, accounts, params),
+    Instruction::Approve => process_approve(env),
+    Instruction::Execute => process_execute(env),
+  }
+}
+
+fn process_create(
+  env: &Environment,
+  seed: &[u8],
+  signers: Vec<Pubkey>,
+  threshold: u8,
+) -> contract::Result {
+  if env.accounts.len() != 1 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  let (addr, acc) = &env.accounts[0];
+
+  if &env.address.derive(&[seed]) != addr {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  if acc.data.is_some() || acc.owner.is_some() {
+    return Err(ContractError::AccountAlreadyExists);
+  }
+
+  if signers.is_empty()
+    || signers.len() > MAX_SIGNERS
+    || threshold == 0
+    || threshold as usize > signers.len()
+  {
+    return Err(ContractError::InvalidInputParameters);
+  }
+
+  // every signer may approve a proposal only once
+  for (i, signer) in signers.iter().enumerate() {
+    if signers[..i].contains(signer) {
+      return Err(ContractError::InvalidInputParameters);
+    }
+  }
+
+  let multisig = Multisig {
+    signers,
+    threshold,
+    proposals: 0,
+  };
+
+  Ok(vec![
+    contract::Output::LogEntry("action".into(), "create".into()),
+    contract::Output::LogEntry("address".into(), addr.to_string()),
+    contract::Output::LogEntry(
+      "threshold".into(),
+      format!("{threshold}/{}", multisig.signers.len()),
+    ),
+    contract::Output::CreateOwnedAccount(*addr, Some(multisig.try_to_vec()?)),
+  ])
+}
+
+fn process_propose(
+  env: &Environment,
+  contract: Pubkey,
+  accounts: Vec<AccountRef>,
+  params: Vec<u8>,
+) -> contract::Result {
+  if env.accounts.len() != 3 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  //   0. [drw-] The multisig address
+  //   1. [---s] The proposer, one of the multisig signers
+  //   2. [drw-] The proposal address
+  let (multisig_addr, multisig_acc) = &env.accounts[0];
+  let (proposer_addr, proposer_acc) = &env.accounts[1];
+  let (proposal_addr, proposal_acc) = &env.accounts[2];
+
+  let mut multisig = read_multisig(multisig_addr, multisig_acc, env)?;
+  let position = signer_position(&multisig, proposer_addr, proposer_acc)?;
+
+  let id = multisig.proposals;
+  if proposal_addr != &proposal_address(env, multisig_addr, id) {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  if proposal_acc.data.is_some() || proposal_acc.owner.is_some() {
+    return Err(ContractError::AccountAlreadyExists);
+  }
+
+  let mut proposal = Proposal {
+    multisig: *multisig_addr,
+    id,
+    contract,
+    accounts,
+    params,
+    approvals: vec![false; multisig.signers.len()],
+  };
+  proposal.approvals[position] = true;
+  multisig.proposals += 1;
+
+  Ok(vec![
+    contract::Output::LogEntry("action".into(), "propose".into()),
+    contract::Output::LogEntry("multisig".into(), multisig_addr.to_string()),
+    contract::Output::LogEntry("proposal".into(), id.to_string()),
+    contract::Output::LogEntry("contract".into(), contract.to_string()),
+    contract::Output::WriteAccountData(
+      *multisig_addr,
+      Some(multisig.try_to_vec()?),
+    ),
+    contract::Output::CreateOwnedAccount(
+      *proposal_addr,
+      Some(proposal.try_to_vec()?),
+    ),
+  ])
+}
+
+fn process_approve(env: &Environment) -> contract::Result {
+  if env.accounts.len() != 3 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  //   0. [d---] The multisig address
+  //   1. [---s] One of the multisig signers
+  //   2. [drw-] The proposal address
+  let (multisig_addr, multisig_acc) = &env.accounts[0];
+  let (signer_addr, signer_acc) = &env.accounts[1];
+  let (proposal_addr, proposal_acc) = &env.accounts[2];
+
+  let multisig = read_multisig(multisig_addr, multisig_acc, env)?;
+  let position = signer_position(&multisig, signer_addr, signer_acc)?;
+  let mut proposal =
+    read_proposal(proposal_addr, proposal_acc, multisig_addr, env)?;
+
+  if proposal.approvals[position] {
+    return Err(ContractError::Other(
+      "Proposal is already approved by this signer".to_owned(),
+    ));
+  }
+
+  proposal.approvals[position] = true;
+
+  Ok(vec![
+    contract::Output::LogEntry("action".into(), "approve".into()),
+    contract::Output::LogEntry("multisig".into(), multisig_addr.to_string()),
+    contract::Output::LogEntry("proposal".into(), proposal.id.to_string()),
+    contract::Output::LogEntry("signer".into(), signer_addr.to_string()),
+    contract::Output::LogEntry(
+      "approvals".into(),
+      proposal.approvals_count().to_string(),
+    ),
+    contract::Output::WriteAccountData(
+      *proposal_addr,
+      Some(proposal.try_to_vec()?),
+    ),
+  ])
+}
+
+fn process_execute(env: &Environment) -> contract::Result {
+  if env.accounts.len() < 2 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  //   0. [d---] The multisig address
+  //   1. [drw-] The proposal address
+  //   2.. All input accounts of the proposed instruction
+  let (multisig_addr, multisig_acc) = &env.accounts[0];
+  let (proposal_addr, proposal_acc) = &env.accounts[1];
+
+  let multisig = read_multisig(multisig_addr, multisig_acc, env)?;
+  let proposal =
+    read_proposal(proposal_addr, proposal_acc, multisig_addr, env)?;
+
+  if proposal.approvals_count() < multisig.threshold as usize {
+    return Err(ContractError::Other(
+      "Proposal does not have enough approvals".to_owned(),
+    ));
+  }
+
+  // the invoked contract accounts must be passed in the proposed order
+  let inner = &env.accounts[2..];
+  if inner.len() != proposal.accounts.len()
+    || inner
+      .iter()
+      .zip(proposal.accounts.iter())
+      .any(|((addr, _), proposed)| addr != &proposed.address)
+  {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  // the multisig signs only for itself, any other account proposed
+  // as a signer would otherwise be signed for by this contract.
+  if proposal
+    .accounts
+    .iter()
+    .any(|a| a.signer && &a.address != multisig_addr)
+  {
+    return Err(ContractError::SignatureError(
+      SignatureError::MissingSigners,
+    ));
+  }
+
+  Ok(vec![
+    contract::Output::LogEntry("action".into(), "execute".into()),
+    contract::Output::LogEntry("multisig".into(), multisig_addr.to_string()),
+    contract::Output::LogEntry("proposal".into(), proposal.id.to_string()),
+    contract::Output::DeleteOwnedAccount(*proposal_addr),
+    contract::Output::SignedContractInvoke {
+      contract: proposal.contract,
+      accounts: proposal.accounts,
+      params: proposal.params,
+      signer: *multisig_addr,
+    },
+  ])
+}
+
+/// Verifies that the given account is a valid multisig
+/// account and returns its deserialized representation.
+fn read_multisig(
+  addr: &Pubkey,
+  acc: &AccountView,
+  env: &Environment,
+) -> Result<Multisig, ContractError> {
+  if addr.has_private_key() {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  if acc.owner.as_ref() != Some(&env.address) {
+    return Err(ContractError::InvalidAccountOwner);
+  }
+
+  match acc.data {
+    Some(ref data) => Multisig::try_from_slice(data)
+      .map_err(|_| ContractError::InvalidInputAccounts),
+    None => Err(ContractError::InvalidInputAccounts),
+  }
+}
+
+/// Verifies that the proposal account belongs to the given
+/// multisig and returns its deserialized representation.
+fn read_proposal(
+  addr: &Pubkey,
+  acc: &AccountView,
+  multisig: &Pubkey,
+  env: &Environment,
+) -> Result<Proposal, ContractError> {
+  if acc.owner.as_ref() != Some(&env.address) {
+    return Err(ContractError::InvalidAccountOwner);
+  }
+
+  let proposal = match acc.data {
+    Some(ref data) => Proposal::try_from_slice(data)
+      .map_err(|_| ContractError::InvalidInputAccounts)?,
+    None => return Err(ContractError::AccountDoesNotExist),
+  };
+
+  if &proposal.multisig != multisig
+    || addr != &proposal_address(env, multisig, proposal.id)
+  {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  Ok(proposal)
+}
+
+/// Returns the position of a multisig signer, if it
+/// is one of the signers and signed the transaction.
+fn signer_position(
+  multisig: &Multisig,
+  addr: &Pubkey,
+  acc: &AccountView,
+) -> Result<usize, ContractError> {
+  let position = match multisig.signers.iter().position(|s| s == addr) {
+    Some(position) => position,
+    None => {
+      return Err(ContractError::Other(
+        "account is not a signer of this multisig".to_owned(),
+      ))
+    }
+  };
+
+  if !acc.signer {
+    return Err(ContractError::SignatureError(
+      SignatureError::MissingSigners,
+    ));
+  }
+
+  Ok(position)
+}
+
+fn proposal_address(env: &Environment, multisig: &Pubkey, id: u64) -> Pubkey {
+  env.address.derive(&[multisig.as_ref(), &id.to_le_bytes()])
+}
@@ -0,0 +1,139 @@
+//! Native Secp256k1 Signature Verification Contract
+//!
+//! Verifies a batch of secp256k1 ECDSA signatures over arbitrary messages
+//! and fails the transaction if any of them is invalid. Messages are
+//! hashed with Keccak256 before signing, the same way Ethereum does, so
+//! signatures produced by Ethereum wallets and contracts can be verified
+//! against their signer address using recoverable signatures.
+//!
+//! Contracts invoke this contract through
+//! [`Output::ContractInvoke`](contract::Output::ContractInvoke) and rely
+//! on the failure reverting the entire transaction.
+
+use {
+  crate::vm::{
+    contract::{self, Clock, ContractError, Environment, Output},
+    transaction::SignatureError,
+    Machine,
+  },
+  borsh::{BorshDeserialize, BorshSerialize},
+  libsecp256k1::{Message, PublicKey, RecoveryId, Signature},
+  multihash::{Hasher, Keccak256},
+};
+
+/// Identifies the expected signer of a message.
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub enum Signer {
+  /// A SEC1 compressed public key.
+  PublicKey([u8; 33]),
+
+  /// An Ethereum address, the last 20 bytes of the Keccak256 hash
+  /// of the uncompressed public key. Signatures verified against
+  /// an address must be recoverable.
+  EthAddress([u8; 20]),
+}
+
+/// A message signed by a secp256k1 private key.
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct SignedMessage {
+  /// The expected signer of the message.
+  pub signer: Signer,
+
+  /// The signed message bytes, they are hashed with Keccak256.
+  pub message: Vec<u8>,
+
+  /// The `r || s || v` signature over the message hash. The recovery
+  /// id `v` is either 0, 1 or 27, 28 and is ignored when the signer
+  /// is a public key. Only signatures with low `s` values are valid.
+  pub signature: [u8; 65],
+}
+
+/// This is the instruction param to the secp256k1 contract
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub enum Instruction {
+  /// Verifies all signatures in the batch.
+  ///
+  /// This instruction takes no accounts.
+  Verify(Vec<SignedMessage>),
+}
+
+pub fn contract(
+  env: &Environment,
+  params: &[u8],
+  _: &Machine,
+  _: &Clock,
+) -> contract::Result {
+  let instruction = Instruction::try_from_slice(params)
+    .map_err(|_| ContractError::InvalidInputParameters)?;
+
+  if !env.accounts.is_empty() {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  match instruction {
+    Instruction::Verify(messages) => {
+      if messages.is_empty() {
+        return Err(ContractError::InvalidInputParameters);
+      }
+
+      for message in &messages {
+        verify(message)?;
+      }
+
+      Ok(vec![
+        Output::LogEntry("action".into(), "verify".into()),
+        Output::LogEntry("count".into(), messages.len().to_string()),
+      ])
+    }
+  }
+}
+
+/// The Ethereum address of a secp256k1 public key.
+pub fn eth_address(pubkey: &PublicKey) -> [u8; 20] {
+  // skip the 0x04 prefix of the uncompressed encoding
+  let hash = keccak256(&pubkey.serialize()[1..]);
+  hash[12..].try_into().unwrap()
+}
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+  let mut hasher = Keccak256::default();
+  hasher.update(data);
+  hasher.finalize().try_into().unwrap()
+}
+
+fn verify(message: &SignedMessage) -> Result<(), ContractError> {
+  let hash = Message::parse(&keccak256(&message.message));
+  let signature = Signature::parse_standard_slice(&message.signature[..64])
+    .map_err(|_| SignatureError::InvalidSignature)?;
+
+  // reject malleable signatures, for every valid signature (r, s)
+  // the signature (r, -s) is also valid for the same message.
+  if signature.s.is_high() {
+    return Err(SignatureError::InvalidSignature.into());
+  }
+
+  let valid = match message.signer {
+    Signer::PublicKey(ref pubkey) => {
+      let pubkey = PublicKey::parse_compressed(pubkey)
+        .map_err(|_| SignatureError::InvalidSignature)?;
+      libsecp256k1::verify(&hash, &signature, &pubkey)
+    }
+    Signer::EthAddress(ref address) => {
+      let recovery_id = match message.signature[64] {
+        v @ 27..=28 => v - 27,
+        v => v,
+      };
+      let recovery_id = RecoveryId::parse(recovery_id)
+        .map_err(|_| SignatureError::InvalidSignature)?;
+      match libsecp256k1::recover(&hash, &signature, &recovery_id) {
+        Ok(pubkey) => &eth_address(&pubkey) == address,
+        Err(_) => false,
+      }
+    }
+  };
+
+  match valid {
+    true => Ok(()),
+    false => Err(SignatureError::InvalidSignature.into()),
+  }
+}
@@ -1,6 +1,6 @@
 use {
   crate::vm::{
-    contract::{self, ContractError, Environment, Output},
+    contract::{self, Clock, ContractError, Environment, Output},
     Machine,
   },
   multihash::{Hasher, Sha3_256},
@@ -10,6 +10,7 @@ pub fn contract(
   env: &Environment,
   params: &[u8],
   _: &Machine,
+  _: &Clock,
 ) -> contract::Result {
   let mut sha = Sha3_256::default();
 
@@ -1,5 +1,5 @@
 use crate::vm::{
-  contract::{self, Environment},
+  contract::{self, Clock, Environment},
   Machine,
 };
 
@@ -10,6 +10,7 @@ pub fn contract(
   _env: &Environment,
   _params: &[u8],
   _: &Machine,
+  _: &Clock,
 ) -> contract::Result {
   todo!();
 }
@@ -0,0 +1,165 @@
+//! Native System Contract
+//!
+//! This builtin contract manages plain accounts, those that are not
+//! owned by any contract. It is the only contract allowed to emit the
+//! [`Output::AssignAccount`] and [`Output::CloseAccount`] outputs.
+//!
+//! Every instruction must be signed by the private key of the account
+//! it modifies, so derived accounts can't be managed by this contract
+//! and addresses of other contracts can't be squatted.
+
+use {
+  crate::{
+    primitives::Pubkey,
+    vm::{
+      contract::{
+        self,
+        AccountView,
+        Clock,
+        ContractError,
+        Environment,
+        Output,
+      },
+      transaction::SignatureError,
+      Machine,
+    },
+  },
+  borsh::{BorshDeserialize, BorshSerialize},
+};
+
+/// This is the instruction param to the system contract
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub enum Instruction {
+  /// Creates a new account with zeroed data, optionally owned by
+  /// a contract. The account must not have an owner or data.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [-rws] The new account
+  CreateAccount {
+    /// The contract that owns the new account, None keeps it plain.
+    owner: Option<Pubkey>,
+
+    /// The size of the account data in bytes.
+    space: u64,
+  },
+
+  /// Assigns a plain account to a contract, the account data is
+  /// left unchanged.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [-rws] The plain account
+  AssignOwner(Pubkey),
+
+  /// Allocates zeroed data for a plain account without data.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [-rws] The plain account
+  Allocate(u64),
+
+  /// Deletes a plain account, the address can be reused afterwards.
+  /// Accounts that paid for transactions keep their nonce.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [drws] The plain account
+  Close,
+}
+
+pub fn contract(
+  env: &Environment,
+  params: &[u8],
+  vm: &Machine,
+  _: &Clock,
+) -> contract::Result {
+  let instruction = Instruction::try_from_slice(params)
+    .map_err(|_| ContractError::InvalidInputParameters)?;
+
+  if env.accounts.len() != 1 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  let (addr, acc) = &env.accounts[0];
+  ensure_plain_signer(addr, acc)?;
+
+  match instruction {
+    Instruction::CreateAccount { owner, space } => {
+      if acc.data.is_some() {
+        return Err(ContractError::AccountAlreadyExists);
+      }
+
+      Ok(vec![
+        Output::LogEntry("action".into(), "create".into()),
+        Output::LogEntry("account".into(), addr.to_string()),
+        Output::LogEntry("space".into(), space.to_string()),
+        Output::AssignAccount {
+          address: *addr,
+          owner,
+          data: allocate(vm, space)?,
+        },
+      ])
+    }
+    Instruction::AssignOwner(owner) => Ok(vec![
+      Output::LogEntry("action".into(), "assign".into()),
+      Output::LogEntry("account".into(), addr.to_string()),
+      Output::LogEntry("owner".into(), owner.to_string()),
+      Output::AssignAccount {
+        address: *addr,
+        owner: Some(owner),
+        data: acc.data.clone(),
+      },
+    ]),
+    Instruction::Allocate(space) => {
+      if acc.data.is_some() {
+        return Err(ContractError::AccountAlreadyExists);
+      }
+
+      Ok(vec![
+        Output::LogEntry("action".into(), "allocate".into()),
+        Output::LogEntry("account".into(), addr.to_string()),
+        Output::LogEntry("space".into(), space.to_string()),
+        Output::AssignAccount {
+          address: *addr,
+          owner: None,
+          data: allocate(vm, space)?,
+        },
+      ])
+    }
+    Instruction::Close => Ok(vec![
+      Output::LogEntry("action".into(), "close".into()),
+      Output::LogEntry("account".into(), addr.to_string()),
+      Output::CloseAccount(*addr),
+    ]),
+  }
+}
+
+/// Only plain accounts that signed the transaction with their private
+/// key are managed by this contract.
+fn ensure_plain_signer(
+  addr: &Pubkey,
+  acc: &AccountView,
+) -> Result<(), ContractError> {
+  if !acc.signer || !addr.has_private_key() {
+    return Err(ContractError::SignatureError(
+      SignatureError::MissingSigners,
+    ));
+  }
+
+  if acc.owner.is_some() || acc.executable {
+    return Err(ContractError::InvalidAccountOwner);
+  }
+
+  Ok(())
+}
+
+fn allocate(
+  vm: &Machine,
+  space: u64,
+) -> Result<Option<Vec<u8>>, ContractError> {
+  if space > vm.limits().max_account_size as u64 {
+    return Err(ContractError::AccountTooLarge);
+  }
+
+  Ok(match space {
+    0 => None,
+    space => Some(vec![0; space as usize]),
+  })
+}
@@ -0,0 +1,509 @@
+//! Native Vesting Contract
+//!
+//! This builtin contract escrows Currency tokens and releases them to
+//! a beneficiary over time. Tokens vest linearly between the start and
+//! the end of a schedule, nothing is released before the cliff. A pure
+//! cliff schedule is one where the cliff is equal to its end.
+//!
+//! Escrowed tokens are held in a regular Currency coin account whose
+//! wallet is the vesting account itself. The vesting account is owned
+//! by this contract, so all token movements are Currency transfers
+//! invoked by this contract on behalf of the vesting account.
+
+use {
+  crate::{
+    primitives::Pubkey,
+    vm::{
+      builtin::currency::{
+        coin_address,
+        CoinAccount,
+        Instruction as CurrencyInstruction,
+        CURRENCY_BUILTIN_ADDR,
+      },
+      contract::{self, AccountView, Clock, ContractError, Environment},
+      transaction::SignatureError,
+      AccountRef,
+      Machine,
+    },
+  },
+  borsh::{BorshDeserialize, BorshSerialize},
+};
+
+/// The unit of time in which a vesting schedule is measured.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  PartialEq,
+  Eq,
+  BorshSerialize,
+  BorshDeserialize,
+)]
+pub enum TimeUnit {
+  /// Block heights.
+  Slot,
+
+  /// Epochs of `epoch_blocks` blocks, as defined in genesis.
+  Epoch,
+}
+
+impl TimeUnit {
+  fn now(&self, clock: &Clock) -> u64 {
+    match self {
+      TimeUnit::Slot => clock.slot,
+      TimeUnit::Epoch => clock.epoch,
+    }
+  }
+}
+
+/// Defines when escrowed tokens become available to the beneficiary.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Schedule {
+  /// The unit of all points in time of this schedule.
+  pub unit: TimeUnit,
+
+  /// The point in time at which tokens start vesting.
+  pub start: u64,
+
+  /// Nothing can be claimed before this point in time, at the
+  /// cliff all tokens that vested since the start are released.
+  pub cliff: u64,
+
+  /// The point in time at which all tokens are vested.
+  pub end: u64,
+}
+
+impl Schedule {
+  /// Returns the amount of tokens out of the total
+  /// that are vested at the given point in time.
+  pub fn vested(&self, total: u64, clock: &Clock) -> u64 {
+    let now = self.unit.now(clock);
+    if now < self.cliff {
+      0
+    } else if now >= self.end {
+      total
+    } else {
+      // start <= cliff <= now < end
+      let elapsed = (now - self.start) as u128;
+      let duration = (self.end - self.start) as u128;
+      (total as u128 * elapsed / duration) as u64
+    }
+  }
+}
+
+/// Represents a vesting account that escrows tokens of a single mint.
+///
+/// Vesting accounts are derived from a unique seed:
+///
+///   Vesting = Vesting.derive([seed])
+///
+/// and their escrowed tokens are held in the coin account:
+///
+///   Escrow = Currency.derive([mint, Vesting])
+///
+/// The vesting account and its escrow are deleted once all its tokens
+/// are claimed.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Vesting {
+  /// The token mint of escrowed tokens.
+  pub mint: Pubkey,
+
+  /// The wallet that is allowed to claim vested tokens.
+  pub beneficiary: Pubkey,
+
+  /// The wallet that is allowed to revoke unvested tokens,
+  /// None if the vesting is irrevocable.
+  pub admin: Option<Pubkey>,
+
+  /// The vesting schedule of escrowed tokens.
+  pub schedule: Schedule,
+
+  /// The total amount of tokens that vest over the schedule.
+  pub total: u64,
+
+  /// The amount of tokens that was already claimed.
+  pub claimed: u64,
+
+  /// Revoked vestings have their total capped to the amount
+  /// that was vested at the time of revocation.
+  pub revoked: bool,
+}
+
+impl Vesting {
+  /// The amount of tokens that vested so far, including claimed ones.
+  pub fn vested(&self, clock: &Clock) -> u64 {
+    if self.revoked {
+      self.total
+    } else {
+      self.schedule.vested(self.total, clock)
+    }
+  }
+}
+
+/// This is the instruction param to the vesting contract
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub enum Instruction {
+  /// Creates a new vesting account and escrows the given amount
+  /// of tokens from the funder's coin account.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [drw-] Vesting address (Vesting.derive([seed]))
+  ///   1. [d---] The mint address
+  ///   2. [---s] The funder wallet as signer
+  ///   3. [drw-] The funder coin address (Currency.derive([mint, funder]))
+  ///   4. [drw-] The escrow coin address (Currency.derive([mint, vesting]))
+  Create {
+    /// A unique seed that is used to generate the vesting address.
+    seed: [u8; 32],
+
+    /// The wallet that is allowed to claim vested tokens.
+    beneficiary: Pubkey,
+
+    /// The wallet that is allowed to revoke unvested tokens,
+    /// None makes the vesting irrevocable.
+    admin: Option<Pubkey>,
+
+    /// The amount of tokens to escrow.
+    amount: u64,
+
+    /// The vesting schedule, start <= cliff <= end.
+    schedule: Schedule,
+  },
+
+  /// Transfers all vested and not yet claimed tokens to the
+  /// beneficiary's coin account.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [drw-] The vesting address
+  ///   1. [d---] The mint address
+  ///   2. [---s] The beneficiary wallet as signer
+  ///   3. [drw-] The escrow coin address
+  ///   4. [drw-] The beneficiary coin address
+  ///        (Currency.derive([mint, beneficiary]))
+  Claim,
+
+  /// Returns all unvested tokens to the admin's coin account.
+  ///
+  /// Tokens that are vested at the time of revocation remain
+  /// claimable by the beneficiary, nothing vests afterwards.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [drw-] The vesting address
+  ///   1. [d---] The mint address
+  ///   2. [---s] The admin wallet as signer
+  ///   3. [drw-] The escrow coin address
+  ///   4. [drw-] The admin coin address (Currency.derive([mint, admin]))
+  Revoke,
+}
+
+pub fn contract(
+  env: &Environment,
+  params: &[u8],
+  _: &Machine,
+  clock: &Clock,
+) -> contract::Result {
+  let instruction = Instruction::try_from_slice(params)
+    .map_err(|_| ContractError::InvalidInputParameters)?;
+
+  match instruction {
+    Instruction::Create {
+      seed,
+      beneficiary,
+      admin,
+      amount,
+      schedule,
+    } => process_create(env, &seed, beneficiary, admin, amount, schedule),
+    Instruction::Claim => process_claim(env, clock),
+    Instruction::Revoke => process_revoke(env, clock),
+  }
+}
+
+fn process_create(
+  env: &Environment,
+  seed: &[u8],
+  beneficiary: Pubkey,
+  admin: Option<Pubkey>,
+  amount: u64,
+  schedule: Schedule,
+) -> contract::Result {
+  if env.accounts.len() != 5 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  //   0. [drw-] Vesting address (Vesting.derive([seed]))
+  //   1. [d---] The mint address
+  //   2. [---s] The funder wallet as signer
+  //   3. [drw-] The funder coin address
+  //   4. [drw-] The escrow coin address
+  let (vesting_addr, vesting_acc) = &env.accounts[0];
+  let (mint_addr, _) = &env.accounts[1];
+  let (funder_addr, funder_acc) = &env.accounts[2];
+  let (funder_coin_addr, _) = &env.accounts[3];
+  let (escrow_addr, _) = &env.accounts[4];
+
+  if &env.address.derive(&[seed]) != vesting_addr {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  if vesting_acc.data.is_some() || vesting_acc.owner.is_some() {
+    return Err(ContractError::AccountAlreadyExists);
+  }
+
+  if escrow_addr != &coin_address(mint_addr, vesting_addr) {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  if !funder_acc.signer {
+    return Err(ContractError::SignatureError(
+      SignatureError::MissingSigners,
+    ));
+  }
+
+  if amount == 0
+    || schedule.start > schedule.cliff
+    || schedule.cliff > schedule.end
+  {
+    return Err(ContractError::InvalidInputParameters);
+  }
+
+  let vesting = Vesting {
+    mint: *mint_addr,
+    beneficiary,
+    admin,
+    schedule,
+    total: amount,
+    claimed: 0,
+    revoked: false,
+  };
+
+  Ok(vec![
+    contract::Output::LogEntry("action".into(), "create".into()),
+    contract::Output::LogEntry("address".into(), vesting_addr.to_string()),
+    contract::Output::LogEntry("beneficiary".into(), beneficiary.to_string()),
+    contract::Output::LogEntry("amount".into(), amount.to_string()),
+    contract::Output::CreateOwnedAccount(
+      *vesting_addr,
+      Some(vesting.try_to_vec()?),
+    ),
+    transfer(
+      mint_addr,
+      (funder_addr, funder_coin_addr),
+      (vesting_addr, escrow_addr),
+      amount,
+    )?,
+  ])
+}
+
+fn process_claim(env: &Environment, clock: &Clock) -> contract::Result {
+  if env.accounts.len() != 5 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  //   0. [drw-] The vesting address
+  //   1. [d---] The mint address
+  //   2. [---s] The beneficiary wallet as signer
+  //   3. [drw-] The escrow coin address
+  //   4. [drw-] The beneficiary coin address
+  let (vesting_addr, vesting_acc) = &env.accounts[0];
+  let (mint_addr, _) = &env.accounts[1];
+  let (beneficiary_addr, beneficiary_acc) = &env.accounts[2];
+  let (escrow_addr, escrow_acc) = &env.accounts[3];
+  let (beneficiary_coin_addr, _) = &env.accounts[4];
+
+  let mut vesting =
+    read_vesting(vesting_addr, vesting_acc, mint_addr, escrow_addr, env)?;
+
+  if beneficiary_addr != &vesting.beneficiary {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  if !beneficiary_acc.signer {
+    return Err(ContractError::SignatureError(
+      SignatureError::MissingSigners,
+    ));
+  }
+
+  let amount = vesting.vested(clock) - vesting.claimed;
+  if amount == 0 {
+    return Err(ContractError::Other("No vested tokens to claim".to_owned()));
+  }
+
+  vesting.claimed += amount;
+
+  let mut outputs = vec![
+    contract::Output::LogEntry("action".into(), "claim".into()),
+    contract::Output::LogEntry("address".into(), vesting_addr.to_string()),
+    contract::Output::LogEntry("amount".into(), amount.to_string()),
+  ];
+  outputs.extend(update_vesting(
+    vesting_addr,
+    &vesting,
+    (escrow_addr, escrow_acc),
+    (beneficiary_addr, beneficiary_coin_addr),
+    amount,
+  )?);
+  Ok(outputs)
+}
+
+fn process_revoke(env: &Environment, clock: &Clock) -> contract::Result {
+  if env.accounts.len() != 5 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  //   0. [drw-] The vesting address
+  //   1. [d---] The mint address
+  //   2. [---s] The admin wallet as signer
+  //   3. [drw-] The escrow coin address
+  //   4. [drw-] The admin coin address
+  let (vesting_addr, vesting_acc) = &env.accounts[0];
+  let (mint_addr, _) = &env.accounts[1];
+  let (admin_addr, admin_acc) = &env.accounts[2];
+  let (escrow_addr, escrow_acc) = &env.accounts[3];
+  let (admin_coin_addr, _) = &env.accounts[4];
+
+  let mut vesting =
+    read_vesting(vesting_addr, vesting_acc, mint_addr, escrow_addr, env)?;
+
+  if vesting.admin.as_ref() != Some(admin_addr) {
+    return Err(ContractError::Other(
+      "Vesting is not revocable by this account".to_owned(),
+    ));
+  }
+
+  if !admin_acc.signer {
+    return Err(ContractError::SignatureError(
+      SignatureError::MissingSigners,
+    ));
+  }
+
+  let vested = vesting.vested(clock);
+  let amount = vesting.total - vested;
+  if amount == 0 {
+    return Err(ContractError::Other(
+      "No unvested tokens to revoke".to_owned(),
+    ));
+  }
+
+  vesting.total = vested;
+  vesting.revoked = true;
+
+  let mut outputs = vec![
+    contract::Output::LogEntry("action".into(), "revoke".into()),
+    contract::Output::LogEntry("address".into(), vesting_addr.to_string()),
+    contract::Output::LogEntry("amount".into(), amount.to_string()),
+  ];
+  outputs.extend(update_vesting(
+    vesting_addr,
+    &vesting,
+    (escrow_addr, escrow_acc),
+    (admin_addr, admin_coin_addr),
+    amount,
+  )?);
+  Ok(outputs)
+}
+
+/// Verifies that the given account is a valid vesting account of
+/// the given mint and escrow and returns its deserialized representation.
+fn read_vesting(
+  addr: &Pubkey,
+  acc: &AccountView,
+  mint: &Pubkey,
+  escrow: &Pubkey,
+  env: &Environment,
+) -> Result<Vesting, ContractError> {
+  if addr.has_private_key() {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  if acc.owner.as_ref() != Some(&env.address) {
+    return Err(ContractError::InvalidAccountOwner);
+  }
+
+  let vesting = match acc.data {
+    Some(ref data) => Vesting::try_from_slice(data)
+      .map_err(|_| ContractError::InvalidInputAccounts)?,
+    None => return Err(ContractError::AccountDoesNotExist),
+  };
+
+  if &vesting.mint != mint || escrow != &coin_address(mint, addr) {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  Ok(vesting)
+}
+
+/// Writes the updated vesting account and transfers the released amount
+/// of tokens out of its escrow to the given wallet and coin addresses.
+///
+/// Once all tokens are claimed the vesting account is deleted and the
+/// transfer empties the escrow, including any tokens that were sent to
+/// it directly. Currency deletes coin accounts left without a balance,
+/// so the escrow is closed along with its vesting account.
+fn update_vesting(
+  addr: &Pubkey,
+  vesting: &Vesting,
+  escrow: (&Pubkey, &AccountView),
+  to: (&Pubkey, &Pubkey),
+  amount: u64,
+) -> Result<Vec<contract::Output>, ContractError> {
+  if vesting.claimed != vesting.total {
+    return Ok(vec![
+      contract::Output::WriteAccountData(*addr, Some(vesting.try_to_vec()?)),
+      transfer(&vesting.mint, (addr, escrow.0), to, amount)?,
+    ]);
+  }
+
+  let balance = match escrow.1.data {
+    Some(ref data) => CoinAccount::try_from_slice(data)?.balance,
+    None => 0,
+  };
+
+  Ok(vec![
+    contract::Output::DeleteOwnedAccount(*addr),
+    transfer(&vesting.mint, (addr, escrow.0), to, balance.max(amount))?,
+  ])
+}
+
+/// Invokes a Currency transfer between two wallets given as pairs of
+/// wallet and coin addresses. The sender wallet has to be a signer of
+/// this invocation or the vesting account, which this contract signs
+/// for.
+fn transfer(
+  mint: &Pubkey,
+  from: (&Pubkey, &Pubkey),
+  to: (&Pubkey, &Pubkey),
+  amount: u64,
+) -> Result<contract::Output, ContractError> {
+  Ok(contract::Output::SignedContractInvoke {
+    contract: *CURRENCY_BUILTIN_ADDR,
+    accounts: vec![
+      AccountRef {
+        address: *mint,
+        writable: false,
+        signer: false,
+      },
+      AccountRef {
+        address: *from.0,
+        writable: false,
+        signer: true,
+      },
+      AccountRef {
+        address: *from.1,
+        writable: true,
+        signer: false,
+      },
+      AccountRef {
+        address: *to.0,
+        writable: false,
+        signer: false,
+      },
+      AccountRef {
+        address: *to.1,
+        writable: true,
+        signer: false,
+      },
+    ],
+    params: CurrencyInstruction::Transfer(amount).try_to_vec()?,
+    signer: *from.0,
+  })
+}
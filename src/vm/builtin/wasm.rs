@@ -1,15 +1,29 @@
 //! Wasm VM Contract
 //!
 //! This builtin contract implements WASM smart contract deployment and update.
+//!
+//! Contracts may also be deployed along with a description of their interface,
+//! which is stored at Wasm.derive(contract, b"interface") and served by the
+//! RPC, so that explorers can decode transactions invoking the contract.
 
 use {
   crate::{
     primitives::{Pubkey, ToBase58String},
     vm::{
-      contract::{self, AccountView, ContractError, Environment},
+      contract::{
+        self,
+        AccountView,
+        Clock,
+        ContractError,
+        Environment,
+        Interface,
+        ABI_VERSION,
+      },
+      runtime::Runtime,
       transaction::SignatureError,
       AccountRef,
       Machine,
+      WASM_VM_BUILTIN_ADDR,
     },
   },
   borsh::{BorshDeserialize, BorshSerialize},
@@ -92,6 +106,8 @@ enum Instruction {
   /// This instruction will fail if:
   ///   - not all parts of the bytecode were uploaded.
   ///   - The uploaded bytecode is not a valid WASM.
+  ///   - The contract was built against a different ABI version
+  ///     than [`ABI_VERSION`].
   ///   - The init instruction fails.
   ///
   /// Once the bytecode is installed as an executable, the bytecode
@@ -108,6 +124,30 @@ enum Instruction {
     /// it is deployed as an executable.
     init: Option<Vec<u8>>,
   },
+
+  /// Uploads the interface description of a contract that is being
+  /// deployed. It must be uploaded before the contract is installed
+  /// and may be uploaded again to replace a previous description.
+  ///
+  /// Accounts expected by this instruction:
+  ///   0. [d---] Contract destination address [Wasm.derive(seed)]
+  ///   1. [d---] Contract bytecode storage address
+  ///       [Wasm.derive(seed, b"bytecode")]
+  ///   2. [---s] Signature of the authority account specified during
+  ///       [`Allocate`]
+  ///   3. [drw-] Contract interface address
+  ///       [Wasm.derive(Wasm.derive(seed), b"interface")]
+  ///
+  /// This instruction will fail if the description was generated
+  /// for a different ABI version than [`ABI_VERSION`].
+  Describe {
+    /// A seed value used to generate the contract address.
+    /// The contract will be deployed at Wasm.derive(seed).
+    seed: ContractSeed,
+
+    /// The interface description generated by the SDK.
+    interface: Interface,
+  },
 }
 
 /// An account that stores the wasm bytecode while it is being
@@ -142,6 +182,7 @@ pub fn contract(
   env: &Environment,
   params: &[u8],
   vm: &Machine,
+  _: &Clock,
 ) -> contract::Result {
   let mut params = params;
   let instruction: Instruction = BorshDeserialize::deserialize(&mut params)
@@ -158,6 +199,9 @@ pub fn contract(
       process_upload(env, seed, index, bytes)
     }
     Instruction::Install { seed, init } => process_install(env, seed, init, vm),
+    Instruction::Describe { seed, interface } => {
+      process_describe(env, seed, interface)
+    }
   }
 }
 
@@ -412,6 +456,13 @@ fn process_install(
     ));
   }
 
+  // refuse contracts built against an incompatible ABI, otherwise
+  // the contract inputs and outputs would be silently misinterpreted.
+  let abi_version = Runtime::new(&content.bytecode)?.abi_version()?;
+  if abi_version != ABI_VERSION {
+    return Err(ContractError::UnsupportedAbiVersion(abi_version));
+  }
+
   let mut output = vec![
     contract::Output::LogEntry("action".to_owned(), "install".to_owned()),
     contract::Output::LogEntry("contract".to_owned(), c_addr.to_string()),
@@ -441,6 +492,89 @@ fn process_install(
   Ok(output)
 }
 
+/// Accounts expected by this instruction:
+///   0. [d---] Contract destination address [Wasm.derive(seed)]
+///   1. [d---] Contract bytecode storage address
+///       [Wasm.derive(seed, b"bytecode")]
+///   2. [---s] Signature of the authority account specified during
+///       [`Allocate`]
+///   3. [drw-] Contract interface address
+///       [Wasm.derive(Wasm.derive(seed), b"interface")]
+fn process_describe(
+  env: &Environment,
+  seed: ContractSeed,
+  interface: Interface,
+) -> contract::Result {
+  if env.accounts.len() != 4 {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  // validate and get the destination account for the contract
+  let (c_addr, c_acc) = contract_account(seed, env)?;
+
+  // interfaces are immutable once the contract is installed
+  if c_acc.executable {
+    return Err(ContractError::AccountAlreadyExists);
+  }
+
+  // the bytecode storage account
+  let (_, b_acc) = bytecode_account(seed, env)?;
+
+  // read the accumulated bytecode account
+  let content: BytecodeAccount = match b_acc.data {
+    Some(ref data) => BorshDeserialize::try_from_slice(data.as_slice())
+      .map_err(|_| ContractError::InvalidInputAccounts)?,
+    None => return Err(ContractError::AccountDoesNotExist),
+  };
+
+  // verify authority
+  let (a_addr, a_acc) = &env.accounts[2];
+
+  if content.authority != *a_addr {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  if !a_acc.signer {
+    return Err(ContractError::SignatureError(
+      SignatureError::MissingSigners,
+    ));
+  }
+
+  // a description of a different ABI would misdescribe the layouts
+  if interface.abi_version != ABI_VERSION {
+    return Err(ContractError::UnsupportedAbiVersion(interface.abi_version));
+  }
+
+  let (i_addr, i_acc) = &env.accounts[3];
+  if *i_addr != interface_address(c_addr) {
+    return Err(ContractError::InvalidInputAccounts);
+  }
+
+  if !i_acc.writable {
+    return Err(ContractError::AccountNotWritable);
+  }
+
+  let data = interface
+    .try_to_vec()
+    .map_err(|e| ContractError::Other(e.to_string()))?;
+
+  Ok(vec![
+    contract::Output::LogEntry("action".to_owned(), "describe".to_owned()),
+    contract::Output::LogEntry("contract".to_owned(), c_addr.to_string()),
+    contract::Output::LogEntry("name".to_owned(), interface.name),
+    match i_acc.owner {
+      Some(_) => contract::Output::WriteAccountData(*i_addr, Some(data)),
+      None => contract::Output::CreateOwnedAccount(*i_addr, Some(data)),
+    },
+  ])
+}
+
+/// The address of the account that stores the interface description
+/// of a contract deployed through this builtin.
+pub fn interface_address(contract: &Pubkey) -> Pubkey {
+  WASM_VM_BUILTIN_ADDR.derive(&[contract.as_ref(), b"interface"])
+}
+
 fn contract_account(
   seed: ContractSeed,
   env: &Environment,
@@ -3,145 +3,51 @@
 //! This module defines the basic types that are used to
 //! invoke smart contracts by the virtual machine and carry
 //! input and output data into and from the contract.
+//!
+//! The binary layout of those types is defined in the `rensa_abi`
+//! crate, which is shared with the contracts SDK.
 
 use {
-  super::{transaction::SignatureError, AccountRef, Machine},
+  super::Machine,
   crate::primitives::Pubkey,
-  borsh::{BorshDeserialize, BorshSerialize},
+  multihash::{Hasher, Sha3_256},
   serde::{Deserialize, Serialize},
-  thiserror::Error,
+  std::sync::Arc,
 };
 
-#[derive(Debug, Error, Clone, Serialize, Deserialize, BorshDeserialize)]
-pub enum ContractError {
-  #[error("Invalid transaction nonce value for this payer")]
-  InvalidTransactionNonce,
-
-  #[error("Account already exists")]
-  AccountAlreadyExists,
-
-  #[error("Account does not exist")]
-  AccountDoesNotExist,
-
-  #[error("The number of input accounts exceeds the maximum limit")]
-  TooManyInputAccounts,
-
-  #[error("Account is exceeding the maximum size limit")]
-  AccountTooLarge,
-
-  #[error("Log is exceeding the maximum size limit")]
-  LogTooLarge,
-
-  #[error("Logs count for this transaction is exceeding the maximum limit")]
-  TooManyLogs,
-
-  #[error("Invalid contract input accounts")]
-  InvalidInputAccounts,
-
-  #[error("Attempt to modify an account not owned by the contract")]
-  InvalidAccountOwner,
-
-  #[error(
-    "Contract attempting to write to an account not in the transaction \
-     accounts list"
-  )]
-  InvalidOutputAccount,
-
-  #[error("The specified account is not writable")]
-  AccountNotWritable,
-
-  #[error("Contract does not exit")]
-  ContractDoesNotExit,
-
-  #[error("The called account is not executable")]
-  AccountIsNotExecutable,
-
-  #[error("Signature Error: {0}")]
-  SignatureError(#[from] SignatureError),
-
-  #[error("Invalid contract input paramters data")]
-  InvalidInputParameters,
-
-  #[error("This contract is not allowed to perform this operation")]
-  UnauthorizedOperation,
-
-  #[error("Runtime Error: {0}")]
-  Runtime(String),
-
-  #[error("Contract error: {0}")]
-  Other(String),
-
-  #[error("The transaction has used up all compute units before completing")]
-  _ComputationalBudgetExhausted,
-}
-
-impl From<std::io::Error> for ContractError {
-  fn from(e: std::io::Error) -> Self {
-    ContractError::Other(e.to_string())
-  }
-}
+pub use rensa_abi::{
+  ContractError,
+  Interface,
+  Topic,
+  ABI_VERSION,
+  MAX_EVENT_TOPICS,
+};
 
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
-pub struct AccountView {
-  pub signer: bool,
-  pub writable: bool,
-  pub executable: bool,
-  pub owner: Option<Pubkey>,
-  pub data: Option<Vec<u8>>,
+/// Creates an event topic from an event name.
+pub fn topic(name: &str) -> Topic {
+  let mut hasher = Sha3_256::default();
+  hasher.update(name.as_bytes());
+  hasher.finalize().try_into().unwrap()
 }
 
-#[derive(Debug, BorshDeserialize)]
-pub enum Output {
-  /// This type represents a log entry emitted by a smart contract.
-  ///
-  /// Log entries are key-value pairs that are emitted by a contract and
-  /// visible to external observers through the RPC interface.
-  LogEntry(String, String),
-
-  /// Represents creation of a new account that is owned by a calling
-  /// contract.
-  ///
-  /// The modified account should be set as writable in the transaction
-  /// inputs, otherwise the transaction will fail.
-  CreateOwnedAccount(Pubkey, Option<Vec<u8>>),
+/// A view of an input account as seen by the invoked contract.
+pub type AccountView = rensa_abi::AccountView<Pubkey>;
 
-  /// Represents an overrwrite to the contents of an account owned
-  /// by the contract.
-  ///
-  /// The modified account should be set as writable in the transaction
-  /// inputs, otherwise the transaction will fail.
-  ///
-  /// To delete the data contents of an account without deleting the
-  /// account itself (for example to reset it to some initial state),
-  /// use [`None`] as the second parameter to this constructor.
-  WriteAccountData(Pubkey, Option<Vec<u8>>),
+/// Outputs emitted by contracts, either state changes, logs, events
+/// or requests for cross-contract invocations.
+pub type Output = rensa_abi::Output<Pubkey>;
 
-  /// Represents a deletion of an account that is owned by the contract.
-  ///
-  /// The modified account should be set as writable in the transaction
-  /// inputs, and its owner should match the executing contract.
-  DeleteOwnedAccount(Pubkey),
+/// An event emitted by a contract during transaction execution.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+  /// The contract that emitted this event.
+  pub contract: Pubkey,
 
-  /// Represents a request for cross contract invocation to another contract.
-  ContractInvoke {
-    /// Address of the contract to be invoked
-    contract: Pubkey,
-    /// Input accounts to the contract to be invoked.
-    ///
-    /// Those accounts must already be referenced by the calling
-    /// contract, with the same or higher writability flags.
-    accounts: Vec<AccountRef>,
+  /// Up to [`MAX_EVENT_TOPICS`] indexed values.
+  pub topics: Vec<Topic>,
 
-    /// Input bytes to the invoked contract
-    params: Vec<u8>,
-  },
-
-  /// Represents creation of a new contract account.
-  ///
-  /// This output is only allowed to be emitted by the WASM_VM contract
-  /// during contract installation. Returning this value from any other
-  /// contract will fail the entire transaction.
-  CreateExecutableAccount(Pubkey, Vec<u8>),
+  /// Opaque event payload.
+  pub data: Vec<u8>,
 }
 
 /// Represents the output of invocing a smart contract by a transaction.
@@ -152,33 +58,39 @@ pub type Result = std::result::Result<Vec<Output>, ContractError>;
 /// This is the self-cointained input type that is passed to the
 /// contract code containing all accounts data referenced by the
 /// transaction.
-#[derive(Debug, BorshSerialize)]
-pub struct Environment {
-  /// Address of the contract invoking this contract.
-  ///
-  /// For top-level contracts, that are invoked by the transaction
-  /// directly this value is None.
-  ///
-  /// This value is used in contracts to ensure that certain
-  /// instructions are internal and not permitted to be called
-  /// by external contracts, or explicitly specify access policy
-  /// to those instructions based on the caller address.
-  pub caller: Option<Pubkey>,
-
-  /// Address of the contract that is being invoked
-  pub address: Pubkey,
+pub type Environment = rensa_abi::Environment<Pubkey>;
 
-  /// A list of all input accounts specified by the transaction
-  pub accounts: Vec<(Pubkey, AccountView)>,
+/// The position in the chain of the block that is executing a contract.
+///
+/// Only builtin contracts observe the clock, WASM contracts are not
+/// given any notion of time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clock {
+  /// Height of the block that includes the executing transaction.
+  pub slot: u64,
+
+  /// The epoch of that block, `slot / epoch_blocks` in genesis.
+  pub epoch: u64,
 }
 
 /// This is the signature of a builtin contract entrypoint.
 ///
-/// Builtin contracts have direct access to the virtual machine instance.
-pub type NativeContractEntrypoint = fn(&Environment, &[u8], &Machine) -> Result;
+/// Builtin contracts have direct access to the virtual machine instance
+/// and to the clock of the block that is being executed.
+pub type NativeContractEntrypoint =
+  fn(&Environment, &[u8], &Machine, &Clock) -> Result;
 
 /// This is the signature of a contract entrypoint.
 ///
 /// WASM contracts run in an isolated environment and have no direct access
 /// to any runtime facilities.
 pub type ContractEntrypoint = Box<dyn Fn(&Environment, &[u8]) -> Result>;
+
+/// This is the signature of a contract that is compiled into the host
+/// process instead of running in the WASM runtime.
+///
+/// Validators never execute host contracts, they exist so contracts
+/// can be tested natively with ordinary tooling while observing the
+/// same inputs and producing the same outputs as their WASM builds.
+pub type HostContractEntrypoint =
+  Arc<dyn Fn(&Environment, &[u8]) -> Result + Send + Sync>;
@@ -0,0 +1,291 @@
+//! Storage deposits
+//!
+//! Every account kept in the state costs validators storage for as
+//! long as it exists. To keep the state growth bounded, transactions
+//! that create accounts or grow their data lock system coins of their
+//! payer in proportion to the stored bytes. The rates are configured
+//! in genesis [`Limits`].
+//!
+//! Locked coins are moved from the payer's system coin account to the
+//! coin account of [`DEPOSIT_VAULT_ADDR`]. Who paid the deposit of an
+//! account is recorded in a [`Deposit`] kept at [`deposit_address`] of
+//! that account. Transactions that shrink or delete accounts get the
+//! freed deposit refunded to the recorded depositor, regardless of who
+//! pays for those transactions. A payer that grows an account that was
+//! deposited by someone else refunds the earlier depositor and takes
+//! over the whole deposit.
+//!
+//! Deposit records are kept in the state too, so they lock a deposit
+//! that is refunded along with the deposit of their account.
+//!
+//! Settling deposits may create the coin account of the vault or of a
+//! depositor. Those coin accounts lock a deposit like any other account,
+//! it is paid on top of the locked amount or subtracted from the refund
+//! and kept by the vault.
+
+use {
+  super::{
+    builtin::currency::{coin_address, CoinAccount, CURRENCY_BUILTIN_ADDR},
+    ContractError,
+    Machine,
+    State,
+    StateDiff,
+    DEPOSIT_VAULT_ADDR,
+  },
+  crate::{
+    consensus::Limits,
+    primitives::{Account, Pubkey},
+  },
+  borsh::{BorshDeserialize, BorshSerialize},
+  std::collections::BTreeMap,
+};
+
+/// The storage deposit locked for an account.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Deposit {
+  /// The payer that gets the deposit refunded once it is freed.
+  pub depositor: Pubkey,
+
+  /// The amount of system coins locked for the account and
+  /// for this record.
+  pub amount: u64,
+}
+
+/// The address of the deposit record of an account.
+pub fn deposit_address(account: &Pubkey) -> Pubkey {
+  DEPOSIT_VAULT_ADDR.derive(&[account.as_ref()])
+}
+
+/// Locks or refunds storage deposits of all accounts changed by a
+/// transaction, in the system coin of the chain.
+///
+/// The state is the state before the transaction outputs were
+/// applied and the diff holds all changes made by the transaction.
+/// Returns the changes to deposit records and to the coin accounts
+/// of the payer, the refunded depositors and the deposits vault.
+pub fn settle(
+  vm: &Machine,
+  state: &dyn State,
+  payer: &Pubkey,
+  diff: &StateDiff,
+) -> Result<StateDiff, ContractError> {
+  let limits = vm.limits();
+  let mut output = StateDiff::default();
+
+  if limits.storage_deposit_per_account == 0
+    && limits.storage_deposit_per_byte == 0
+  {
+    return Ok(output);
+  }
+
+  // accounts that were written to and then deleted by the same
+  // transaction show up twice, the deletion always comes last.
+  let changes: BTreeMap<_, _> = diff.iter().collect();
+
+  // records have a fixed size, so they all lock the same deposit
+  let record_deposit = deposit(
+    limits,
+    Some(&record_account(&Deposit {
+      depositor: *payer,
+      amount: 0,
+    })?),
+  );
+
+  // coins paid by the payer and coins refunded to depositors
+  let mut locked = 0u64;
+  let mut refunds: BTreeMap<Pubkey, u64> = BTreeMap::new();
+  for (addr, account) in changes {
+    let before = deposit(limits, state.get(addr).as_ref());
+    let after = deposit(limits, account);
+    let record_addr = deposit_address(addr);
+    let record = read_record(state, &record_addr);
+
+    if after > before {
+      let mut amount = after - before;
+      let held = match record {
+        Some(record) if &record.depositor == payer => record.amount,
+        Some(record) => {
+          // the payer takes over the deposit of the earlier depositor
+          *refunds.entry(record.depositor).or_default() += record.amount;
+          amount = amount.saturating_add(record.amount);
+          0
+        }
+        None => {
+          amount = amount.saturating_add(record_deposit);
+          0
+        }
+      };
+
+      let record = Deposit {
+        depositor: *payer,
+        amount: held.saturating_add(amount),
+      };
+      locked = locked.saturating_add(amount);
+      output.set(record_addr, record_account(&record)?).unwrap();
+    } else if before > after {
+      // accounts that never locked a deposit, like those
+      // created in genesis, have nothing to refund.
+      let mut record = match record {
+        Some(record) => record,
+        None => continue,
+      };
+
+      let refund = match account {
+        Some(_) => (before - after).min(record.amount),
+        None => record.amount,
+      };
+      *refunds.entry(record.depositor).or_default() += refund;
+      record.amount -= refund;
+
+      match (account, record.amount) {
+        (Some(_), amount) if amount != 0 => {
+          output.set(record_addr, record_account(&record)?).unwrap();
+        }
+        _ => output.remove(record_addr).unwrap(),
+      }
+    }
+  }
+
+  let system_coin = vm.system_coin();
+  let coin_deposit = deposit(
+    limits,
+    Some(&coin_account(&empty_coin(system_coin, payer))?),
+  );
+
+  // the payer refunded to itself only pays the difference
+  let refunded = refunds.remove(payer).unwrap_or(0);
+  let (charged, refunded) = match locked > refunded {
+    true => (locked - refunded, 0),
+    false => (0, refunded - locked),
+  };
+  if refunded != 0 {
+    refunds.insert(*payer, refunded);
+  }
+
+  let vault_addr = coin_address(system_coin, &DEPOSIT_VAULT_ADDR);
+  let mut vault_coin = read_coin(state, diff, &vault_addr)?;
+
+  // coin accounts created by settling the deposits are kept in
+  // the state like any other account, so they need a deposit too.
+  // Their deposit is kept by the vault along with all others.
+  let mut settled = charged != 0;
+  if charged != 0 {
+    let amount = match vault_coin {
+      Some(_) => charged,
+      None => charged.saturating_add(coin_deposit),
+    };
+    let payer_addr = coin_address(system_coin, payer);
+    let mut payer_coin = match read_coin(state, diff, &payer_addr)? {
+      Some(coin) if !coin.frozen && coin.balance >= amount => coin,
+      _ => return Err(ContractError::InsufficientStorageDeposit(amount)),
+    };
+    let vault = vault_coin
+      .get_or_insert_with(|| empty_coin(system_coin, &DEPOSIT_VAULT_ADDR));
+    payer_coin.balance -= amount;
+    vault.balance += amount;
+    output.set(payer_addr, coin_account(&payer_coin)?).unwrap();
+  }
+
+  for (depositor, amount) in refunds {
+    // refunds are paid from deposits that were locked
+    // before, so the vault always holds enough coins.
+    let vault = match vault_coin {
+      Some(ref mut vault) if vault.balance >= amount => vault,
+      _ => return Err(ContractError::InsufficientStorageDeposit(amount)),
+    };
+
+    // refunds that don't cover the deposit of a new depositor
+    // coin account are kept by the vault.
+    let depositor_addr = coin_address(system_coin, &depositor);
+    let (mut depositor_coin, amount) =
+      match read_coin(state, diff, &depositor_addr)? {
+        Some(coin) => (coin, amount),
+        None if amount > coin_deposit => {
+          (empty_coin(system_coin, &depositor), amount - coin_deposit)
+        }
+        None => continue,
+      };
+
+    vault.balance -= amount;
+    depositor_coin.balance += amount;
+    output
+      .set(depositor_addr, coin_account(&depositor_coin)?)
+      .unwrap();
+    settled = true;
+  }
+
+  if let (true, Some(vault_coin)) = (settled, vault_coin) {
+    output.set(vault_addr, coin_account(&vault_coin)?).unwrap();
+  }
+  Ok(output)
+}
+
+/// The amount of system coins locked for keeping an account in the state.
+fn deposit(limits: &Limits, account: Option<&Account>) -> u64 {
+  match account {
+    Some(account) => {
+      let bytes = account.data.as_ref().map(|d| d.len()).unwrap_or(0);
+      limits
+        .storage_deposit_per_byte
+        .saturating_mul(bytes as u64)
+        .saturating_add(limits.storage_deposit_per_account)
+    }
+    None => 0,
+  }
+}
+
+/// Reads the deposit record of an account, records are kept by the vault
+/// and anything else stored at their address is not a deposit record.
+fn read_record(state: &dyn State, address: &Pubkey) -> Option<Deposit> {
+  state
+    .get(address)
+    .filter(|acc| acc.owner == Some(*DEPOSIT_VAULT_ADDR))
+    .and_then(|acc| acc.data)
+    .and_then(|data| Deposit::try_from_slice(&data).ok())
+}
+
+/// Reads a system coin account as it is after the transaction,
+/// returns None if the coin account doesn't exist.
+fn read_coin(
+  state: &dyn State,
+  diff: &StateDiff,
+  address: &Pubkey,
+) -> Result<Option<CoinAccount>, ContractError> {
+  let deleted = diff.iter().any(|(a, acc)| a == address && acc.is_none());
+  let account = match deleted {
+    true => None,
+    false => diff.get(address).or_else(|| state.get(address)),
+  };
+
+  match account.and_then(|acc| acc.data) {
+    Some(data) => Ok(Some(CoinAccount::try_from_slice(&data)?)),
+    None => Ok(None),
+  }
+}
+
+fn empty_coin(mint: &Pubkey, wallet: &Pubkey) -> CoinAccount {
+  CoinAccount {
+    mint: *mint,
+    owner: *wallet,
+    balance: 0,
+    frozen: false,
+  }
+}
+
+fn coin_account(coin: &CoinAccount) -> Result<Account, ContractError> {
+  Ok(Account {
+    nonce: 0,
+    executable: false,
+    owner: Some(*CURRENCY_BUILTIN_ADDR),
+    data: Some(coin.try_to_vec()?),
+  })
+}
+
+fn record_account(record: &Deposit) -> Result<Account, ContractError> {
+  Ok(Account {
+    nonce: 0,
+    executable: false,
+    owner: Some(*DEPOSIT_VAULT_ADDR),
+    data: Some(record.try_to_vec()?),
+  })
+}
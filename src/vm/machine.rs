@@ -1,8 +1,15 @@
 use {
   super::{
     builtin::BUILTIN_CONTRACTS,
-    contract::{ContractEntrypoint, ContractError, NativeContractEntrypoint},
-    output::{BlockOutput, ErrorsMap, LogsMap},
+    contract::{
+      Clock,
+      ContractEntrypoint,
+      ContractError,
+      HostContractEntrypoint,
+      NativeContractEntrypoint,
+    },
+    deposit,
+    output::{BlockOutput, ErrorsMap, EventsMap, LogsMap, Receipt},
     unit::ExecutionUnit,
     Overlayed,
     State,
@@ -10,11 +17,27 @@ use {
     Transaction,
   },
   crate::{
-    consensus::{BlockData, Genesis, Limits, Produced},
+    consensus::{
+      Block,
+      BlockData,
+      Genesis,
+      Limits,
+      Produced,
+      Protocol,
+      ProtocolSchedule,
+      Stakes,
+    },
     primitives::{Account, Pubkey, ToBase58String},
     vm::{contract::Environment, runtime::Runtime, WASM_VM_BUILTIN_ADDR},
   },
-  std::{cmp::Ordering, collections::HashMap},
+  indexmap::IndexMap,
+  multihash::Multihash,
+  std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::HashMap,
+    io::Error as StdIoError,
+  },
   thiserror::Error,
   tracing::debug,
 };
@@ -36,43 +59,119 @@ pub enum MachineError {
   #[error("Invalid block height, expected a monotonically increasing value")]
   InvalidBlockHeight,
 
+  #[error("Block producer {0} does not have enough stake")]
+  InsufficientStake(Pubkey),
+
   #[error("Transactions are not correctly ordered in this block")]
   InvalidTransactionsOrder,
 
   #[error("Undefined builtin in genesis: {0}")]
   UndefinedBuiltin(Pubkey),
+
+  #[error("Failed to compute the genesis hash: {0}")]
+  InvalidGenesis(#[from] StdIoError),
 }
 
 pub trait Executable {
+  /// Executes the block data on top of the given state,
+  /// the height is the height of the block being executed.
   fn execute(
     &self,
     vm: &Machine,
     state: &dyn State,
+    height: u64,
   ) -> Result<BlockOutput, MachineError>;
 }
 
 /// Represents a state machine that takes as an input a state
 /// and a block and outputs a new state. This is the API
 /// entry point to the virtual machine that runs contracts.
+///
+/// A machine follows the rules of one protocol version at a time,
+/// blocks are always executed by the machine returned from
+/// [`Machine::at_block`] for their height.
+#[derive(Clone)]
 pub struct Machine {
-  limits: Limits,
+  chain: Multihash,
+  epoch_blocks: u64,
+  system_coin: Pubkey,
+  stakes: Stakes,
+  protocols: ProtocolSchedule,
+  protocol: Protocol,
   builtins: HashMap<Pubkey, NativeContractEntrypoint>,
+  hosted: HashMap<Pubkey, HostContractEntrypoint>,
 }
 
 impl Machine {
   pub fn new<D: BlockData>(genesis: &Genesis<D>) -> Result<Self, MachineError> {
-    let mut builtins = HashMap::new();
-    for addr in &genesis.builtins {
-      if let Some(entrypoint) = BUILTIN_CONTRACTS.get(addr) {
-        builtins.insert(*addr, *entrypoint);
-      } else {
-        return Err(MachineError::UndefinedBuiltin(*addr));
+    let protocols = ProtocolSchedule::new(genesis);
+    for protocol in protocols.versions() {
+      for addr in &protocol.builtins {
+        if !BUILTIN_CONTRACTS.contains_key(addr) {
+          return Err(MachineError::UndefinedBuiltin(*addr));
+        }
       }
     }
-    Ok(Self {
-      builtins,
-      limits: genesis.limits.clone(),
-    })
+
+    let protocol = protocols.at(0).clone();
+    let mut machine = Self {
+      builtins: HashMap::new(),
+      hosted: HashMap::new(),
+      chain: genesis.hash()?,
+      epoch_blocks: genesis.epoch_blocks,
+      system_coin: genesis.system_coin,
+      stakes: Stakes::new(genesis),
+      protocols,
+      protocol: protocol.clone(),
+    };
+    machine.activate(protocol);
+    Ok(machine)
+  }
+
+  /// Switches the machine to the given protocol rules.
+  fn activate(&mut self, protocol: Protocol) {
+    self.builtins = protocol
+      .builtins
+      .iter()
+      .filter_map(|addr| BUILTIN_CONTRACTS.get(addr).map(|e| (*addr, *e)))
+      .collect();
+    self.protocol = protocol;
+  }
+
+  /// Returns a machine that follows the given rules, borrowing
+  /// this machine if it is already following them.
+  fn with_protocol(&self, protocol: Protocol) -> Cow<'_, Machine> {
+    if protocol == self.protocol {
+      Cow::Borrowed(self)
+    } else {
+      let mut machine = self.clone();
+      machine.activate(protocol);
+      Cow::Owned(machine)
+    }
+  }
+
+  /// The machine that follows the scheduled protocol rules
+  /// in effect for the block at the given height.
+  pub fn at(&self, height: u64) -> Cow<'_, Machine> {
+    self.with_protocol(self.protocols.at(height).clone())
+  }
+
+  /// The machine that executes the block at the given height on top
+  /// of the given state. It follows the scheduled protocol rules in
+  /// effect at that height along with all parameter changes enacted
+  /// by governance up to the epoch of the block.
+  pub fn at_block(&self, state: &dyn State, height: u64) -> Cow<'_, Machine> {
+    self.with_protocol(self.protocols.effective(state, height))
+  }
+
+  /// The protocol rules this machine is following.
+  pub fn protocol(&self) -> &Protocol {
+    &self.protocol
+  }
+
+  /// All protocol versions of the chain.
+  pub fn protocols(&self) -> &ProtocolSchedule {
+    &self.protocols
   }
 
   /// Gets a VM-native builtin contract.
@@ -89,14 +188,19 @@ impl Machine {
   ) -> Result<ContractEntrypoint, ContractError> {
     if let Some(account) = state.get(addr) {
       if let Some(owner) = account.owner {
-        if owner == *WASM_VM_BUILTIN_ADDR
-          && account.executable
-          && account.data.is_some()
-        {
-          let runtime = Runtime::new(account.data.unwrap().as_ref())?;
-          return Ok(Box::new(move |env: &Environment, params: &[u8]| {
-            runtime.invoke(env, params)
-          }));
+        if owner == *WASM_VM_BUILTIN_ADDR && account.executable {
+          if let Some(hosted) = self.hosted.get(addr).cloned() {
+            return Ok(Box::new(move |env: &Environment, params: &[u8]| {
+              hosted(env, params)
+            }));
+          }
+
+          if let Some(bytecode) = account.data {
+            let runtime = Runtime::new(&bytecode)?;
+            return Ok(Box::new(move |env: &Environment, params: &[u8]| {
+              runtime.invoke(env, params)
+            }));
+          }
         }
       }
 
@@ -106,9 +210,56 @@ impl Machine {
     }
   }
 
-  /// Configured execution contraints.
+  /// Runs the contract deployed at a given address natively in the
+  /// host process instead of the WASM runtime.
+  ///
+  /// The contract account still has to exist in the state as an
+  /// executable account owned by the WasmVM builtin, only the code
+  /// it runs is replaced. This is used by the contracts test harness
+  /// and never by validators.
+  pub fn host_contract(
+    &mut self,
+    address: Pubkey,
+    entrypoint: HostContractEntrypoint,
+  ) {
+    self.hosted.insert(address, entrypoint);
+  }
+
+  /// Configured execution contraints of the active protocol rules.
   pub fn limits(&self) -> &Limits {
-    &self.limits
+    &self.protocol.limits
+  }
+
+  /// The stake of a validator in the epoch of the executed block,
+  /// weighed the same way as by the consensus.
+  pub fn stake(&self, validator: &Pubkey) -> u64 {
+    self.stakes.stake(validator, self.limits())
+  }
+
+  /// The sum of stakes of all validators that take
+  /// part in the epoch of the executed block.
+  pub fn total_stake(&self) -> u64 {
+    self.stakes.total(self.limits())
+  }
+
+  /// The genesis hash of the chain this machine is executing.
+  /// All transactions must be signed for this chain.
+  pub fn chain(&self) -> &Multihash {
+    &self.chain
+  }
+
+  /// The currency used for storage deposits.
+  pub fn system_coin(&self) -> &Pubkey {
+    &self.system_coin
+  }
+
+  /// The clock observed by builtin contracts while
+  /// executing a block at the given height.
+  pub fn clock(&self, height: u64) -> Clock {
+    Clock {
+      slot: height,
+      epoch: height / self.epoch_blocks,
+    }
   }
 
   pub fn execute<D: BlockData>(
@@ -116,7 +267,7 @@ impl Machine {
     state: &impl State,
     block: &Produced<D>,
   ) -> Result<BlockOutput, MachineError> {
-    block.data.execute(self, state)
+    block.data.execute(self, state, block.height)
   }
 }
 
@@ -126,7 +277,13 @@ impl Executable for Vec<Transaction> {
     &self,
     vm: &Machine,
     state: &dyn State,
+    height: u64,
   ) -> Result<BlockOutput, MachineError> {
+    // all transactions are executed under the
+    // protocol rules in effect at this height.
+    let vm = vm.at_block(state, height);
+    let vm = vm.as_ref();
+
     // transactions order within a block must follow a known
     // ordering algorithm described in more detail in the block
     // producer module. Reject all blocks that don't follow that
@@ -138,46 +295,76 @@ impl Executable for Vec<Transaction> {
     // accumulates state across all txs
     let mut acclogs = LogsMap::new();
     let mut accerrors = ErrorsMap::new();
+    let mut accevents = EventsMap::new();
+    let mut accreceipts = IndexMap::new();
     let mut accstate = StateDiff::default();
 
     for transaction in self {
+      // the state as it was before this transaction, used
+      // to tell apart created and modified accounts.
+      let prior = Overlayed::new(state, &accstate);
+
       // on execution of a tranasction, increment payer's nonce value
       // so the same transaction could not be replayed in the future,
       // regardless of its execution outcome.
-      // TODO(karim): why has is this an Overlayed and not just directly the
-      // state?
-      match Overlayed::new(state, &accstate).get(&transaction.payer) {
+      let mut txstate = StateDiff::default();
+      match prior.get(&transaction.payer) {
         Some(mut payer) => {
           payer.nonce += 1;
-          accstate.set(transaction.payer, payer).unwrap();
+          txstate.set(transaction.payer, payer).unwrap();
         }
         None => {
-          accstate
+          txstate
             .set(transaction.payer, Account {
               nonce: 1,
               ..Account::default()
             })
             .unwrap();
+
+          // the payer account created by the nonce change locks a
+          // storage deposit like any other account. Payers that can't
+          // pay for it are not created and their transaction is void.
+          match deposit::settle(vm, &prior, &transaction.payer, &txstate) {
+            Ok(deposits) => txstate = txstate.merge(deposits),
+            Err(error) => {
+              accerrors.insert(*transaction.hash(), error);
+              accreceipts.insert(
+                *transaction.hash(),
+                Receipt::new(&prior, StateDiff::default(), vec![]),
+              );
+              continue;
+            }
+          }
         }
       };
 
-      // Create a view of the state that encompasses the global state
-      // and the state accumulated so far by the block.
-      let state = Overlayed::new(state, &accstate);
+      // Create a view of the state that encompasses the global state,
+      // the state accumulated so far by the block and the payer nonce.
+      let state = Overlayed::new(&prior, &txstate);
 
-      // try instantiating the contract, construct its
-      // isolated environment and execute it then ingest
-      // all its outputs if ran successfully to completion.
-      match ExecutionUnit::new(transaction, &state, vm)
-        .and_then(|exec_unit| exec_unit.execute())
-      {
+      // try instantiating the contracts of all instructions, construct
+      // their isolated environments and execute them then ingest
+      // all their outputs if all ran successfully to completion.
+      let receipt = match ExecutionUnit::execute_transaction(
+        transaction,
+        &state,
+        vm,
+        height,
+      ) {
         Ok(txout) => {
           // transaction execution successfully ran to completion.
           // merge and accumulate state changes in this block.
-          accstate = accstate.merge(txout.state_diff);
+          txstate = txstate.merge(txout.state_diff);
 
           // append all generated logs
           acclogs.insert(*transaction.hash(), txout.log_entries);
+
+          // and all emitted events, if any
+          if !txout.events.is_empty() {
+            accevents.insert(*transaction.hash(), txout.events);
+          }
+
+          Receipt::new(&prior, txstate.clone(), txout.invocations)
         }
         Err(error) => {
           // on error, don't apply any of transaction changes
@@ -188,13 +375,25 @@ impl Executable for Vec<Transaction> {
 
           // store the error output of the failed transaction
           accerrors.insert(*transaction.hash(), error);
+
+          // only the payer nonce change is persisted
+          Receipt::new(&prior, txstate.clone(), vec![])
         }
       };
+
+      accstate = accstate.merge(txstate);
+      accreceipts.insert(*transaction.hash(), receipt);
     }
 
     // NOTE(bmaas): this blockoutput would be perfect
     // to ease our testing
-    Ok(BlockOutput::new(accstate, acclogs, accerrors))
+    Ok(BlockOutput::new(
+      accstate,
+      acclogs,
+      accerrors,
+      accevents,
+      accreceipts,
+    ))
   }
 }
 
@@ -205,6 +404,7 @@ impl Executable for String {
     &self,
     _vm: &Machine,
     _state: &dyn State,
+    _height: u64,
   ) -> Result<BlockOutput, MachineError> {
     Ok(BlockOutput::default())
   }
@@ -217,6 +417,7 @@ impl Executable for u8 {
     &self,
     _vm: &Machine,
     _state: &dyn State,
+    _height: u64,
   ) -> Result<BlockOutput, MachineError> {
     Ok(BlockOutput::default())
   }
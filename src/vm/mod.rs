@@ -1,5 +1,6 @@
 pub mod builtin;
 mod contract;
+mod deposit;
 mod executed;
 mod machine;
 mod output;
@@ -9,12 +10,32 @@ mod transaction;
 mod unit;
 
 pub use {
-  contract::ContractError,
+  contract::{
+    topic,
+    AccountView,
+    Clock,
+    ContractError,
+    Environment,
+    Event,
+    HostContractEntrypoint,
+    Interface,
+    Output,
+    Topic,
+    ABI_VERSION,
+  },
+  deposit::{deposit_address, Deposit},
   executed::Executed,
   machine::{Executable, Machine, MachineError},
-  output::BlockOutput,
+  output::{BlockOutput, EventsMap, Invocation, Receipt},
   state::{Finalized, Overlayed, State, StateDiff, StateError, StateStore},
-  transaction::{AccountRef, ExecutedTransaction, Transaction},
+  transaction::{
+    AccountRef,
+    ExecutedTransaction,
+    Instruction,
+    LegacyTransaction,
+    Transaction,
+    TransactionError,
+  },
 };
 
 lazy_static::lazy_static! {
@@ -23,4 +44,16 @@ lazy_static::lazy_static! {
     "WasmVM1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
       .parse()
       .unwrap();
+
+  /// Address of the only contract that is allowed to assign and close
+  /// plain accounts.
+  pub static ref SYSTEM_BUILTIN_ADDR: crate::primitives::Pubkey =
+    "System1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
+      .parse()
+      .unwrap();
+
+  /// The wallet that holds storage deposits locked by transactions,
+  /// it has no private key and is not owned by any contract.
+  pub static ref DEPOSIT_VAULT_ADDR: crate::primitives::Pubkey =
+    SYSTEM_BUILTIN_ADDR.derive(&[b"deposits"]);
 }
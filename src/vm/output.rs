@@ -1,6 +1,10 @@
 use {
-  super::{contract::ContractError, State, StateDiff},
-  crate::primitives::ToBase58String,
+  super::{
+    contract::{ContractError, Event},
+    State,
+    StateDiff,
+  },
+  crate::primitives::{Pubkey, ToBase58String},
   indexmap::IndexMap,
   multihash::{
     Code as MultihashCode,
@@ -36,6 +40,12 @@ impl ErrorsMap {
       for (k, v) in self.inner.iter() {
         hasher.update(&k.to_bytes());
         hasher.update(v.to_string().as_bytes());
+        if let ContractError::Custom {
+          data: Some(data), ..
+        } = v
+        {
+          hasher.update(data);
+        }
       }
       MultihashCode::Sha3_256.wrap(hasher.finalize()).unwrap()
     })
@@ -102,6 +112,69 @@ impl std::ops::DerefMut for LogsMap {
   }
 }
 
+/// mapping tx_hash -> list of events emitted by tx
+#[derive(Default, Serialize, Deserialize)]
+pub struct EventsMap {
+  inner: IndexMap<Multihash, Vec<Event>>,
+
+  #[serde(skip)]
+  hashcache: OnceCell<Multihash>,
+}
+
+impl EventsMap {
+  pub fn new() -> Self {
+    Self {
+      inner: IndexMap::new(),
+      hashcache: OnceCell::new(),
+    }
+  }
+
+  pub fn hash(&self) -> &Multihash {
+    self.hashcache.get_or_init(|| {
+      let mut hasher = Sha3_256::default();
+      for (k, v) in self.inner.iter() {
+        hasher.update(&k.to_bytes());
+        for event in v.iter() {
+          hasher.update(&event.contract);
+          hasher.update(&(event.topics.len() as u64).to_le_bytes());
+          for topic in &event.topics {
+            hasher.update(topic);
+          }
+          hasher.update(&(event.data.len() as u64).to_le_bytes());
+          hasher.update(&event.data);
+        }
+      }
+      MultihashCode::Sha3_256.wrap(hasher.finalize()).unwrap()
+    })
+  }
+}
+
+impl std::ops::Deref for EventsMap {
+  type Target = IndexMap<Multihash, Vec<Event>>;
+
+  fn deref(&self) -> &Self::Target {
+    &self.inner
+  }
+}
+
+impl std::ops::DerefMut for EventsMap {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    &mut self.inner
+  }
+}
+
+/// A single contract invocation that happened while
+/// executing a transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invocation {
+  /// The invoked contract.
+  pub contract: Pubkey,
+
+  /// The contract that invoked this contract,
+  /// None for top-level transaction instructions.
+  pub caller: Option<Pubkey>,
+}
+
 /// The result of executing a transaction.
 #[derive(Default)]
 pub struct TransactionOutput {
@@ -110,6 +183,12 @@ pub struct TransactionOutput {
 
   /// The generated log entries for explorers and chain clients.
   pub log_entries: Vec<(String, String)>,
+
+  /// Structured events emitted by contracts.
+  pub events: Vec<Event>,
+
+  /// All contracts invoked by the transaction, in invocation order.
+  pub invocations: Vec<Invocation>,
 }
 
 impl TransactionOutput {
@@ -117,13 +196,72 @@ impl TransactionOutput {
     let mut newer = newer;
     let mut state = self.state_diff;
     let mut logs = self.log_entries;
+    let mut events = self.events;
+    let mut invocations = self.invocations;
 
     state = state.merge(newer.state_diff);
     logs.append(&mut newer.log_entries);
+    events.append(&mut newer.events);
+    invocations.append(&mut newer.invocations);
 
     Self {
       state_diff: state,
       log_entries: logs,
+      events,
+      invocations,
+    }
+  }
+}
+
+/// Describes the effects of a single transaction on the global
+/// state. Receipts are created for both successful and failed
+/// transactions, as failed transactions still increment the
+/// nonce of their payer.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Receipt {
+  /// Changes to the global state made by this transaction only.
+  pub state: StateDiff,
+
+  /// Accounts that did not exist before this transaction.
+  pub created: Vec<Pubkey>,
+
+  /// Existing accounts that had their contents changed.
+  pub written: Vec<Pubkey>,
+
+  /// Accounts removed by this transaction.
+  pub deleted: Vec<Pubkey>,
+
+  /// All contract invocations, including nested ones,
+  /// in the order they were invoked.
+  pub invocations: Vec<Invocation>,
+}
+
+impl Receipt {
+  /// Creates a receipt for a transaction from its state changes
+  /// and the state as it was right before the transaction.
+  pub fn new(
+    prior: &dyn State,
+    state: StateDiff,
+    invocations: Vec<Invocation>,
+  ) -> Self {
+    let mut created = vec![];
+    let mut written = vec![];
+    let mut deleted = vec![];
+
+    for (addr, account) in state.iter() {
+      match (account, prior.get(addr)) {
+        (Some(_), None) => created.push(*addr),
+        (Some(_), Some(_)) => written.push(*addr),
+        (None, _) => deleted.push(*addr),
+      }
+    }
+
+    Self {
+      state,
+      created,
+      written,
+      deleted,
+      invocations,
     }
   }
 }
@@ -141,17 +279,34 @@ pub struct BlockOutput {
   /// Failed transactions and their failure error message
   pub errors: ErrorsMap,
 
+  /// Structured events emitted by successful transactions
+  pub events: EventsMap,
+
+  /// Per-transaction changes to the ledger state.
+  ///
+  /// Receipts are derived from the execution of transactions, so
+  /// they are not part of the block output hash.
+  pub receipts: IndexMap<Multihash, Receipt>,
+
   /// Hash of the state logs of transactions
   #[serde(skip)]
   hashcache: OnceCell<Multihash>,
 }
 
 impl BlockOutput {
-  pub fn new(state: StateDiff, logs: LogsMap, errors: ErrorsMap) -> Self {
+  pub fn new(
+    state: StateDiff,
+    logs: LogsMap,
+    errors: ErrorsMap,
+    events: EventsMap,
+    receipts: IndexMap<Multihash, Receipt>,
+  ) -> Self {
     Self {
       state,
       logs,
       errors,
+      events,
+      receipts,
       hashcache: OnceCell::new(),
     }
   }
@@ -162,6 +317,7 @@ impl BlockOutput {
       hasher.update(&self.state.hash().to_bytes());
       hasher.update(&self.logs.hash().to_bytes());
       hasher.update(&self.errors.hash().to_bytes());
+      hasher.update(&self.events.hash().to_bytes());
       MultihashCode::Sha3_256.wrap(hasher.finalize()).unwrap()
     })
   }
@@ -173,6 +329,7 @@ impl std::fmt::Debug for BlockOutput {
       .field("state", &self.state.hash().to_bytes().to_b58())
       .field("logs", &self.logs.hash().to_bytes().to_b58())
       .field("errors", &self.errors.hash().to_bytes().to_b58())
+      .field("events", &self.events.hash().to_bytes().to_b58())
       .field("hash", &self.hash().to_b58())
       .finish()
   }
@@ -69,6 +69,21 @@ impl Runtime {
     Ok(Self { instance })
   }
 
+  /// Returns the ABI version the contract was built against.
+  ///
+  /// Contracts that do not export their ABI version predate
+  /// versioning and are reported as version 0.
+  pub fn abi_version(&self) -> Result<u32, ContractError> {
+    match self.instance.exports.get_function("abi_version") {
+      Ok(func) => func
+        .native::<(), u32>()
+        .map_err(|e| ContractError::Runtime(e.to_string()))?
+        .call()
+        .map_err(|e| ContractError::Runtime(e.to_string())),
+      Err(_) => Ok(0),
+    }
+  }
+
   pub fn invoke(&self, env: &Environment, params: &[u8]) -> contract::Result {
     // get a function pointer to contract's exported entrypoint
     let main_func = self
@@ -1,5 +1,5 @@
 use {
-  super::Executed,
+  super::{BlockOutput, Executed},
   crate::{
     consensus::{Block, BlockData},
     primitives::{Account, Pubkey},
@@ -20,6 +20,7 @@ use {
     sync::Arc,
   },
   thiserror::Error,
+  tracing::warn,
 };
 
 #[derive(Debug, Error)]
@@ -62,6 +63,19 @@ pub trait State {
 pub trait StateStore: State {
   /// Applies a state diff from a finalized block
   fn apply(&self, diff: &StateDiff) -> StorageResult<()>;
+
+  /// Called once the state diff of a finalized block was applied.
+  ///
+  /// The state of the block is already stored at this point, so
+  /// errors are logged and don't stop the chain.
+  fn finalized(
+    &self,
+    _height: u64,
+    _block: &Multihash,
+    _output: &BlockOutput,
+  ) -> StorageResult<()> {
+    Ok(())
+  }
 }
 
 /// Represents a view of two overlayed states without modifying any of them.
@@ -127,6 +141,12 @@ impl<'f, D: BlockData, S: State + StateStore> Finalized<'f, D, S> {
       .state
       .apply(&block.output.state)
       .expect("unrecoverable storage engine error"); // most likely disk is full
+
+    let height = self.underlying.height();
+    let hash = self.underlying.hash().unwrap();
+    if let Err(e) = self.state.finalized(height, &hash, &block.output) {
+      warn!("finalizing the state at height {height} failed: {e}");
+    }
   }
 
   pub fn state(&self) -> &'f impl State {
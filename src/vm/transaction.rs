@@ -1,10 +1,12 @@
 use {
-  super::contract::ContractError,
+  super::{
+    contract::{ContractError, Event},
+    output::Receipt,
+  },
   crate::{
     consensus::Limits,
     primitives::{Keypair, Pubkey, ToBase58String},
   },
-  borsh::BorshDeserialize,
   ed25519_dalek::{PublicKey, Signature, Signer, Verifier},
   multihash::{
     Code as MultihashCode,
@@ -15,50 +17,16 @@ use {
   },
   once_cell::sync::OnceCell,
   serde::{Deserialize, Serialize},
-  std::io::{Error as StdError, ErrorKind},
   thiserror::Error,
 };
 
+pub use rensa_abi::SignatureError;
+
 /// This is a parameter on the transaction that indicates that
 /// a contract is going to touch this account. Only accounts
 /// speciefied in the accounts list in a transaction can be
 /// accessed by smart contracts.
-#[derive(
-  Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshDeserialize,
-)]
-pub struct AccountRef {
-  pub address: Pubkey,
-  pub writable: bool,
-  pub signer: bool,
-}
-
-impl AccountRef {
-  pub fn readonly(
-    address: impl TryInto<Pubkey>,
-    signer: bool,
-  ) -> Result<AccountRef, StdError> {
-    Ok(Self {
-      address: address.try_into().map_err(|_| {
-        StdError::new(ErrorKind::InvalidInput, "invalid pubkey")
-      })?,
-      writable: false,
-      signer,
-    })
-  }
-
-  pub fn writable(
-    address: impl TryInto<Pubkey>,
-    signer: bool,
-  ) -> Result<AccountRef, StdError> {
-    Ok(Self {
-      address: address.try_into().map_err(|_| {
-        StdError::new(ErrorKind::InvalidInput, "invalid pubkey")
-      })?,
-      writable: true,
-      signer,
-    })
-  }
-}
+pub type AccountRef = rensa_abi::AccountRef<Pubkey>;
 
 #[derive(Debug, Clone, Error, Serialize, Deserialize)]
 pub enum TransactionError {
@@ -67,28 +35,65 @@ pub enum TransactionError {
 
   #[error("The transaction's params blob is too big")]
   ParamsTooBig,
+
+  #[error("The transaction has no instructions")]
+  NoInstructions,
+
+  #[error("The transaction has too many instructions")]
+  TooManyInstructions,
+
+  #[error("An instruction is referencing an account not in the transaction")]
+  InvalidAccountIndex,
+
+  #[error("The transaction has expired")]
+  Expired,
 }
 
-#[derive(Debug, Clone, Error, Serialize, Deserialize, BorshDeserialize)]
-pub enum SignatureError {
-  #[error("Signature verification failed")]
-  InvalidSignature,
+/// Represents a single contract invocation within a transaction.
+///
+/// Instructions don't carry their own accounts, instead they reference
+/// accounts from the transaction accounts list by their position in
+/// that list. This way accounts shared between instructions are listed
+/// and signed only once per transaction.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Instruction {
+  pub contract: Pubkey,
+  pub accounts: Vec<u8>,
+
+  #[serde(with = "crate::primitives::b58::serde")]
+  pub params: Vec<u8>,
+}
 
-  #[error("Missing Signers")]
-  MissingSigners,
+impl std::fmt::Debug for Instruction {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Instruction")
+      .field("contract", &self.contract)
+      .field("accounts", &self.accounts)
+      .field("params", &format!("{:02x?}", &self.params.as_slice()))
+      .finish()
+  }
 }
 
 /// Represents a single invocation of the state machine.
 /// This is the smallest unit of computation on the blockchain.
+///
+/// A transaction carries one or more instructions that are executed
+/// in order and atomically. If any of the instructions fails, then
+/// none of the changes made by the transaction are persisted, except
+/// the payer nonce increment.
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Transaction {
-  pub contract: Pubkey,
   pub nonce: u64,
   pub payer: Pubkey,
-  pub accounts: Vec<AccountRef>,
 
-  #[serde(with = "crate::primitives::b58::serde")]
-  pub params: Vec<u8>,
+  /// The last block height at which this transaction can be included
+  /// in a block. Transactions without an expiry are valid until their
+  /// nonce is used.
+  #[serde(default)]
+  pub expiry: Option<u64>,
+
+  pub accounts: Vec<AccountRef>,
+  pub instructions: Vec<Instruction>,
 
   #[serde(with = "crate::primitives::b58::serde::signatures")]
   pub signatures: Vec<Signature>,
@@ -98,16 +103,58 @@ pub struct Transaction {
 }
 
 impl Transaction {
+  /// Creates a transaction with a single instruction that invokes a
+  /// contract with all the transaction accounts and never expires.
+  ///
+  /// The chain parameter is the hash of the genesis of the chain this
+  /// transaction is meant for. It is part of the signed message, so the
+  /// transaction is not valid on any other chain.
+  ///
+  /// Fails if there are more accounts than an instruction can reference.
   pub fn new(
     contract: Pubkey,
+    chain: &Multihash,
     nonce: u64,
     payer: &Keypair,
     accounts: Vec<AccountRef>,
     params: Vec<u8>,
     signers: &[&Keypair],
+  ) -> Result<Self, TransactionError> {
+    let instruction = Instruction {
+      contract,
+      accounts: all_accounts(accounts.len())?,
+      params,
+    };
+    Ok(Self::with_instructions(
+      chain,
+      nonce,
+      None,
+      payer,
+      accounts,
+      vec![instruction],
+      signers,
+    ))
+  }
+
+  /// Creates a transaction that executes many instructions atomically
+  /// and optionally expires after a given block height.
+  pub fn with_instructions(
+    chain: &Multihash,
+    nonce: u64,
+    expiry: Option<u64>,
+    payer: &Keypair,
+    accounts: Vec<AccountRef>,
+    instructions: Vec<Instruction>,
+    signers: &[&Keypair],
   ) -> Self {
-    let fields_hash =
-      Self::hash_fields(&contract, nonce, &payer.public(), &accounts, &params);
+    let fields_hash = Self::hash_fields(
+      chain,
+      nonce,
+      expiry,
+      &payer.public(),
+      &accounts,
+      &instructions,
+    );
 
     // payers signature alwyas goes first
     let mut signatures = vec![payer.sign(fields_hash.as_ref())];
@@ -118,11 +165,11 @@ impl Transaction {
     }
 
     Self {
-      contract,
       nonce,
       payer: payer.public(),
+      expiry,
       accounts,
-      params,
+      instructions,
       signatures,
       hashcache: OnceCell::new(),
     }
@@ -133,20 +180,57 @@ impl Transaction {
       return Err(TransactionError::TooManyAccounts);
     }
 
-    if self.params.len() > limits.max_transaction_params_size {
+    if self.instructions.is_empty() {
+      return Err(TransactionError::NoInstructions);
+    }
+
+    if self.instructions.len() > limits.max_transaction_instructions {
+      return Err(TransactionError::TooManyInstructions);
+    }
+
+    // the params limit applies to all instructions combined
+    let params_size: usize =
+      self.instructions.iter().map(|ix| ix.params.len()).sum();
+    if params_size > limits.max_transaction_params_size {
       return Err(TransactionError::ParamsTooBig);
     }
 
+    for instruction in &self.instructions {
+      if instruction.accounts.len() > limits.max_input_accounts {
+        return Err(TransactionError::TooManyAccounts);
+      }
+
+      if instruction
+        .accounts
+        .iter()
+        .any(|i| *i as usize >= self.accounts.len())
+      {
+        return Err(TransactionError::InvalidAccountIndex);
+      }
+    }
+
     Ok(())
   }
 
-  pub fn verify_signatures(&self) -> Result<(), SignatureError> {
+  /// Returns true if this transaction can no longer be
+  /// included in a block at the given height.
+  pub fn is_expired(&self, height: u64) -> bool {
+    self.expiry.map(|expiry| height > expiry).unwrap_or(false)
+  }
+
+  /// Verifies that the transaction is signed by the payer and all signing
+  /// accounts for the chain with the given genesis hash.
+  pub fn verify_signatures(
+    &self,
+    chain: &Multihash,
+  ) -> Result<(), SignatureError> {
     let fields_hash = Self::hash_fields(
-      &self.contract,
+      chain,
       self.nonce,
+      self.expiry,
       &self.payer,
       &self.accounts,
-      &self.params,
+      &self.instructions,
     );
 
     // first verify the payer
@@ -193,17 +277,49 @@ impl Transaction {
     Ok(())
   }
 
+  /// Resolves the account indices of an instruction into account
+  /// references from the transaction accounts list.
+  ///
+  /// Returns None if any of the indices is out of range.
+  pub fn instruction_accounts(
+    &self,
+    instruction: &Instruction,
+  ) -> Option<Vec<AccountRef>> {
+    instruction
+      .accounts
+      .iter()
+      .map(|i| self.accounts.get(*i as usize).cloned())
+      .collect()
+  }
+
+  /// The transaction id.
+  ///
+  /// It covers all transaction fields and its signatures, but not
+  /// the chain it was signed for, which is implied by the signatures.
   pub fn hash(&self) -> &Multihash {
     self.hashcache.get_or_init(|| {
       let mut hasher = Sha3_256::default();
-      hasher.update(&self.contract);
       hasher.update(&self.nonce.to_le_bytes());
       hasher.update(&self.payer);
+      match self.expiry {
+        Some(expiry) => {
+          hasher.update(&[1]);
+          hasher.update(&expiry.to_le_bytes());
+        }
+        None => hasher.update(&[0]),
+      }
       for accref in &self.accounts {
         hasher.update(&accref.address);
         hasher.update(&[accref.writable as u8]);
+        hasher.update(&[accref.signer as u8]);
+      }
+      for instruction in &self.instructions {
+        hasher.update(&instruction.contract);
+        hasher.update(&(instruction.accounts.len() as u64).to_le_bytes());
+        hasher.update(&instruction.accounts);
+        hasher.update(&(instruction.params.len() as u64).to_le_bytes());
+        hasher.update(&instruction.params);
       }
-      hasher.update(&self.params);
       for sig in &self.signatures {
         hasher.update(sig.as_ref());
       }
@@ -211,36 +327,115 @@ impl Transaction {
     })
   }
 
+  /// The message that is signed by the payer and all signers.
   fn hash_fields(
-    contract: &Pubkey,
+    chain: &Multihash,
     nonce: u64,
+    expiry: Option<u64>,
     payer: &Pubkey,
     accounts: &[AccountRef],
-    params: &[u8],
+    instructions: &[Instruction],
   ) -> Vec<u8> {
     let mut hasher = Sha3_256::default();
-    hasher.update(contract);
+    hasher.update(&chain.to_bytes());
     hasher.update(&nonce.to_le_bytes());
     hasher.update(payer);
 
+    // a presence byte followed by the last valid height, if any
+    match expiry {
+      Some(expiry) => {
+        hasher.update(&[1]);
+        hasher.update(&expiry.to_le_bytes());
+      }
+      None => hasher.update(&[0]),
+    }
+
+    hasher.update(&(accounts.len() as u64).to_le_bytes());
     for accref in accounts {
       hasher.update(&accref.address);
       hasher.update(&[accref.writable as u8]);
       hasher.update(&[accref.signer as u8]);
     }
-    hasher.update(params);
+
+    hasher.update(&(instructions.len() as u64).to_le_bytes());
+    for instruction in instructions {
+      hasher.update(&instruction.contract);
+      hasher.update(&(instruction.accounts.len() as u64).to_le_bytes());
+      hasher.update(&instruction.accounts);
+      hasher.update(&(instruction.params.len() as u64).to_le_bytes());
+      hasher.update(&instruction.params);
+    }
+
     hasher.finalize().to_vec()
   }
 }
 
+/// The transaction format used by clients before transactions were able
+/// to carry more than one instruction.
+///
+/// It is still accepted by the RPC interface and converted to a transaction
+/// with a single instruction that references all transaction accounts, so
+/// its signatures must be made over the message of the converted
+/// transaction. That message includes the chain, so signatures made by
+/// clients that predate chain binding are not valid, see `MIGRATION.md`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LegacyTransaction {
+  pub contract: Pubkey,
+  pub nonce: u64,
+  pub payer: Pubkey,
+
+  #[serde(default)]
+  pub expiry: Option<u64>,
+
+  pub accounts: Vec<AccountRef>,
+
+  #[serde(with = "crate::primitives::b58::serde")]
+  pub params: Vec<u8>,
+
+  #[serde(with = "crate::primitives::b58::serde::signatures")]
+  pub signatures: Vec<Signature>,
+}
+
+impl TryFrom<LegacyTransaction> for Transaction {
+  type Error = TransactionError;
+
+  fn try_from(legacy: LegacyTransaction) -> Result<Self, Self::Error> {
+    Ok(Self {
+      nonce: legacy.nonce,
+      payer: legacy.payer,
+      expiry: legacy.expiry,
+      instructions: vec![Instruction {
+        contract: legacy.contract,
+        accounts: all_accounts(legacy.accounts.len())?,
+        params: legacy.params,
+      }],
+      accounts: legacy.accounts,
+      signatures: legacy.signatures,
+      hashcache: OnceCell::new(),
+    })
+  }
+}
+
+/// Indices of the first `count` transaction accounts.
+///
+/// Instructions reference accounts by a single byte, so
+/// at most 256 accounts can be referenced.
+fn all_accounts(count: usize) -> Result<Vec<u8>, TransactionError> {
+  (0..count)
+    .map(|index| {
+      u8::try_from(index).map_err(|_| TransactionError::TooManyAccounts)
+    })
+    .collect()
+}
+
 impl std::fmt::Debug for Transaction {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     f.debug_struct("Transaction")
       .field("nonce", &self.nonce)
-      .field("contract", &self.contract)
       .field("payer", &self.payer)
+      .field("expiry", &self.expiry)
       .field("accounts", &self.accounts)
-      .field("params", &format!("{:02x?}", &self.params.as_slice()))
+      .field("instructions", &self.instructions)
       .field(
         "signatures",
         &self
@@ -265,4 +460,6 @@ pub struct ExecutedTransaction {
   pub block: u64,
   pub transaction: Transaction,
   pub output: Result<Vec<(String, String)>, ContractError>,
+  pub events: Vec<Event>,
+  pub receipt: Receipt,
 }
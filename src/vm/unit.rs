@@ -2,18 +2,25 @@ use {
   super::{
     contract::{
       AccountView,
+      Clock,
       ContractEntrypoint,
       ContractError,
       Environment,
+      Event,
       NativeContractEntrypoint,
       Output,
+      MAX_EVENT_TOPICS,
     },
-    output::TransactionOutput,
+    deposit,
+    output::{Invocation, TransactionOutput},
+    transaction::SignatureError,
     AccountRef,
     Machine,
+    Overlayed,
     State,
     StateDiff,
     Transaction,
+    SYSTEM_BUILTIN_ADDR,
     WASM_VM_BUILTIN_ADDR,
   },
   crate::primitives::{Account, Pubkey},
@@ -24,9 +31,9 @@ enum Entrypoint {
   External(ContractEntrypoint),
 }
 
-/// Represents the execution context of a single transaction.
+/// Represents the execution context of a single contract invocation.
 ///
-/// This type is responsible for running the transaction logic
+/// This type is responsible for running the contract logic
 /// and then processing all its outputs that affect the external
 /// blockchain state. Any failure in the contract logic or in
 /// any of the outputs will cause the entire transaction to fail
@@ -35,24 +42,48 @@ pub struct ExecutionUnit<'s, 'm> {
   vm: &'m Machine,
   entrypoint: Entrypoint,
   env: Environment,
+  clock: Clock,
   state: &'s dyn State,
   contract: Pubkey,
   params: Vec<u8>,
 }
 
 impl<'s, 'm> ExecutionUnit<'s, 'm> {
-  pub fn new(
+  /// Executes all instructions of a transaction in order and returns
+  /// the combined state difference and logs of all of them.
+  ///
+  /// Instructions are executed atomically, every instruction observes
+  /// the changes made by the instructions preceding it, and a failure
+  /// in any of them fails the entire transaction.
+  ///
+  /// The height is the height of the block that includes
+  /// this transaction.
+  pub fn execute_transaction(
     transaction: &Transaction,
-    state: &'s impl State,
-    vm: &'m Machine,
-  ) -> Result<Self, ContractError> {
+    state: &impl State,
+    vm: &Machine,
+    height: u64,
+  ) -> Result<TransactionOutput, ContractError> {
     // this value is defined in genesis
     if transaction.accounts.len() > vm.limits().max_input_accounts {
       return Err(ContractError::TooManyInputAccounts);
     }
 
-    // don't proceed unless all tx signatures are valid.
-    transaction.verify_signatures()?;
+    if transaction.instructions.is_empty()
+      || transaction.instructions.len()
+        > vm.limits().max_transaction_instructions
+    {
+      return Err(ContractError::InvalidInstructionsCount);
+    }
+
+    // transactions may not be included after their expiry height
+    if transaction.is_expired(height) {
+      return Err(ContractError::TransactionExpired);
+    }
+
+    // don't proceed unless all tx signatures are valid
+    // and were made for this chain.
+    transaction.verify_signatures(vm.chain())?;
 
     // to prevent transaction reply, the payer account has a
     // nonce field that is incremented with every transaction
@@ -65,75 +96,142 @@ impl<'s, 'm> ExecutionUnit<'s, 'm> {
       return Err(ContractError::InvalidTransactionNonce);
     }
 
-    // construct an execution unit for a native or external contract
-    Ok(Self {
-      entrypoint: {
-        if let Some(entrypoint) = vm.builtin(&transaction.contract) {
-          Entrypoint::Native(entrypoint)
-        } else {
-          Entrypoint::External(vm.contract(&transaction.contract, state)?)
-        }
-      },
-      env: Self::create_environment(
-        state,
-        &transaction.accounts,
-        transaction.contract,
+    let mut txoutput = TransactionOutput::default();
+    for instruction in &transaction.instructions {
+      // a view of the state that includes changes made
+      // by previous instructions of this transaction.
+      let state = Overlayed::new(state, &txoutput.state_diff);
+
+      let accounts = transaction
+        .instruction_accounts(instruction)
+        .ok_or(ContractError::InvalidInputAccounts)?;
+
+      let output = ExecutionUnit::new(
+        instruction.contract,
+        &accounts,
+        instruction.params.clone(),
         None, // top-level contract, caller is None
-      )?,
-      state,
-      contract: transaction.contract,
-      params: transaction.params.clone(),
-      vm,
-    })
+        vm.clock(height),
+        &state,
+        vm,
+      )?
+      .execute()?;
+
+      txoutput = txoutput.merge(output);
+
+      // logs limit applies to the entire transaction
+      if txoutput.log_entries.len() + txoutput.events.len()
+        > vm.limits().max_logs_count
+      {
+        return Err(ContractError::TooManyLogs);
+      }
+    }
+
+    // lock or refund storage deposits of accounts
+    // created, resized or deleted by this transaction.
+    let deposits =
+      deposit::settle(vm, state, &transaction.payer, &txoutput.state_diff)?;
+    txoutput.state_diff = txoutput.state_diff.merge(deposits);
+
+    Ok(txoutput)
   }
 
-  fn new_nested(
-    &self,
+  fn new(
     contract: Pubkey,
-    accounts: Vec<AccountRef>,
+    accounts: &[AccountRef],
     params: Vec<u8>,
+    caller: Option<Pubkey>,
+    clock: Clock,
+    state: &'s dyn State,
+    vm: &'m Machine,
   ) -> Result<Self, ContractError> {
     // tx signatures are already validated by the top-level
-    // execution unit, no need to verify them again for
-    // nested calls. Nonce verification also applies only
+    // transaction execution, no need to verify them again for
+    // instructions and nested calls. Nonce verification also
+    // applies only to the transaction as a whole.
 
     // this value is defined in genesis
-    if accounts.len() > self.vm.limits().max_input_accounts {
+    if accounts.len() > vm.limits().max_input_accounts {
       return Err(ContractError::TooManyInputAccounts);
     }
 
     // construct an execution unit for a native or external contract
     Ok(Self {
       entrypoint: {
-        if let Some(entrypoint) = self.vm.builtin(&contract) {
+        if let Some(entrypoint) = vm.builtin(&contract) {
           Entrypoint::Native(entrypoint)
         } else {
-          Entrypoint::External(self.vm.contract(&contract, self.state)?)
+          Entrypoint::External(vm.contract(&contract, state)?)
         }
       },
-      env: Self::create_environment(
-        self.state,
-        &accounts,
-        contract,
-        Some(self.contract), // caller is incoking contract
-      )?,
-      state: self.state,
+      env: Self::create_environment(state, accounts, contract, caller)?,
+      clock,
+      state,
       contract,
       params,
-      vm: self.vm,
+      vm,
     })
   }
 
+  fn new_nested(
+    &self,
+    contract: Pubkey,
+    accounts: Vec<AccountRef>,
+    params: Vec<u8>,
+    signer: Option<Pubkey>,
+  ) -> Result<Self, ContractError> {
+    // signatures can't be forged by nested invocations. An account
+    // is a signer of a nested invocation only if it signed the calling
+    // invocation or if it is the one account the calling contract
+    // explicitly signs for, which must be a derived account it owns.
+    for account in accounts.iter().filter(|a| a.signer) {
+      let signed = self
+        .env
+        .accounts
+        .iter()
+        .any(|(addr, view)| addr == &account.address && view.signer);
+      let owned = signer == Some(account.address)
+        && !account.address.has_private_key()
+        && self.state.get(&account.address).and_then(|a| a.owner)
+          == Some(self.contract);
+      if !signed && !owned {
+        return Err(ContractError::SignatureError(
+          SignatureError::MissingSigners,
+        ));
+      }
+    }
+
+    Self::new(
+      contract,
+      &accounts,
+      params,
+      Some(self.contract), // caller is invoking contract
+      self.clock,
+      self.state,
+      self.vm,
+    )
+  }
+
   /// Consumes the execution unit and returns the state difference
   /// that is caused by running this transaction and all its outputs.
   pub fn execute(self) -> Result<TransactionOutput, ContractError> {
     let outputs = match self.entrypoint {
-      Entrypoint::Native(native) => native(&self.env, &self.params, self.vm),
+      Entrypoint::Native(native) => {
+        native(&self.env, &self.params, self.vm, &self.clock)
+      }
       Entrypoint::External(ref external) => external(&self.env, &self.params),
     };
     match outputs {
       Ok(outputs) => {
-        let mut txoutputs = TransactionOutput::default();
+        // the invocation itself is recorded before any nested
+        // invocations that may be caused by its outputs.
+        let mut txoutputs = TransactionOutput {
+          invocations: vec![Invocation {
+            contract: self.contract,
+            caller: self.env.caller,
+          }],
+          ..Default::default()
+        };
         // if the transaction execution successfully ran to
         // completion, then process all its outputs that
         // modify global state. Those outputs may still
@@ -143,12 +241,22 @@ impl<'s, 'm> ExecutionUnit<'s, 'm> {
           txoutputs = txoutputs.merge(self.process_output(output)?);
 
           // ensure its bounded
-          if txoutputs.log_entries.len() > self.vm.limits().max_logs_count {
+          if txoutputs.log_entries.len() + txoutputs.events.len()
+            > self.vm.limits().max_logs_count
+          {
             return Err(ContractError::TooManyLogs);
           }
         }
         Ok(txoutputs)
       }
+      Err(ContractError::Custom {
+        data: Some(ref data),
+        ..
+      }) if data.len() > self.vm.limits().max_log_size => {
+        Err(ContractError::Runtime(
+          "custom error data is exceeding the maximum log size".into(),
+        ))
+      }
       Err(err) => Err(err),
     }
   }
@@ -232,8 +340,23 @@ impl<'s, 'm> ExecutionUnit<'s, 'm> {
           return Err(ContractError::LogTooLarge);
         }
         Ok(TransactionOutput {
-          state_diff: StateDiff::default(),
           log_entries: vec![(key, value)],
+          ..Default::default()
+        })
+      }
+      Output::Event { topics, data } => {
+        if topics.len() > MAX_EVENT_TOPICS
+          || topics.len() * 32 + data.len() > self.vm.limits().max_log_size
+        {
+          return Err(ContractError::InvalidEvent);
+        }
+        Ok(TransactionOutput {
+          events: vec![Event {
+            contract: self.contract,
+            topics,
+            data,
+          }],
+          ..Default::default()
         })
       }
       Output::CreateOwnedAccount(addr, data) => {
@@ -266,7 +389,15 @@ impl<'s, 'm> ExecutionUnit<'s, 'm> {
         contract,
         accounts,
         params,
-      } => self.new_nested(contract, accounts, params)?.execute(),
+      } => self.new_nested(contract, accounts, params, None)?.execute(),
+      Output::SignedContractInvoke {
+        contract,
+        accounts,
+        params,
+        signer,
+      } => self
+        .new_nested(contract, accounts, params, Some(signer))?
+        .execute(),
       Output::CreateExecutableAccount(address, bytecode) => {
         if bytecode.len() > self.vm.limits().max_contract_size {
           return Err(ContractError::AccountTooLarge);
@@ -276,6 +407,25 @@ impl<'s, 'm> ExecutionUnit<'s, 'm> {
           ..Default::default()
         })
       }
+      Output::AssignAccount {
+        address,
+        owner,
+        data,
+      } => {
+        if let Some(ref data) = data {
+          if data.len() > self.vm.limits().max_account_size {
+            return Err(ContractError::AccountTooLarge);
+          }
+        }
+        Ok(TransactionOutput {
+          state_diff: self.assign_account(address, owner, data)?,
+          ..Default::default()
+        })
+      }
+      Output::CloseAccount(address) => Ok(TransactionOutput {
+        state_diff: self.close_account(address)?,
+        ..Default::default()
+      }),
     }
   }
 
@@ -322,6 +472,59 @@ impl<'s, 'm> ExecutionUnit<'s, 'm> {
     }
   }
 
+  /// Changes the owner and contents of a plain account that is not owned
+  /// by any contract, the account is created if it does not exist.
+  /// This operation is only permitted when emitted by the builtin
+  /// contract address: System1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx
+  fn assign_account(
+    &self,
+    address: Pubkey,
+    owner: Option<Pubkey>,
+    data: Option<Vec<u8>>,
+  ) -> Result<StateDiff, ContractError> {
+    // special privilage gateway
+    if self.env.address != *SYSTEM_BUILTIN_ADDR {
+      return Err(ContractError::UnauthorizedOperation);
+    }
+
+    let nonce = match self.state.get(&address) {
+      Some(existing) => {
+        if existing.owner.is_some() || existing.executable {
+          return Err(ContractError::InvalidAccountOwner);
+        }
+        existing.nonce
+      }
+      None => 0,
+    };
+
+    self.set_account(address, Account {
+      nonce,
+      executable: false,
+      owner,
+      data,
+    })
+  }
+
+  /// Deletes a plain account that is not owned by any contract.
+  /// This operation is only permitted when emitted by the builtin
+  /// contract address: System1xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx
+  fn close_account(&self, address: Pubkey) -> Result<StateDiff, ContractError> {
+    // special privilage gateway
+    if self.env.address != *SYSTEM_BUILTIN_ADDR {
+      return Err(ContractError::UnauthorizedOperation);
+    }
+
+    match self.state.get(&address) {
+      Some(existing) => {
+        if existing.owner.is_some() || existing.executable {
+          return Err(ContractError::InvalidAccountOwner);
+        }
+        self.remove_account(address, existing)
+      }
+      None => Err(ContractError::AccountDoesNotExist),
+    }
+  }
+
   /// Process an output that modifies an existing
   /// contract owned account.
   fn modify_account(
@@ -356,6 +559,39 @@ impl<'s, 'm> ExecutionUnit<'s, 'm> {
           return Err(ContractError::AccountNotWritable);
         }
 
+        return match self.state.get(&address) {
+          Some(existing) => self.remove_account(address, existing),
+          None => Err(ContractError::AccountDoesNotExist),
+        };
+      }
+    }
+    Err(ContractError::InvalidOutputAccount)
+  }
+
+  /// Removes an account from the state.
+  ///
+  /// Accounts that paid for transactions are never removed, they are
+  /// reset to an empty account that keeps its nonce. Otherwise, once
+  /// the nonce starts over, past transactions of the account could be
+  /// replayed.
+  fn remove_account(
+    &self,
+    address: Pubkey,
+    existing: Account,
+  ) -> Result<StateDiff, ContractError> {
+    if existing.nonce != 0 {
+      return self.set_account(address, Account {
+        nonce: existing.nonce,
+        ..Account::default()
+      });
+    }
+
+    for (addr, view) in &self.env.accounts {
+      if addr == &address {
+        if !view.writable {
+          return Err(ContractError::AccountNotWritable);
+        }
+
         let mut output = StateDiff::default();
         output.remove(address).unwrap();
         return Ok(output);
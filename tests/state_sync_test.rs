@@ -0,0 +1,323 @@
+//! A new node starts from a snapshot of the finalized state taken by
+//! other validators instead of replaying all blocks since genesis.
+
+use {
+  futures::{FutureExt, StreamExt},
+  libp2p::{identity, PeerId},
+  multihash::{Code as MultihashCode, MultihashDigest},
+  rensa::{
+    consensus::{validator::Validator, Block, Genesis, Produced},
+    network::snapshot::{SnapshotRequest, SnapshotResponse, SnapshotSync},
+    primitives::{Account, Keypair, Pubkey},
+    storage::{Error, Manifest, PersistentState, Snapshots, CHUNK_SIZE},
+    vm::{BlockOutput, State, StateStore, Transaction},
+  },
+  std::path::{Path, PathBuf},
+};
+
+/// A data directory that is removed at the end of a test.
+struct DataDir(PathBuf);
+
+impl DataDir {
+  fn new() -> Self {
+    let name = format!("rensa-state-sync-{}", Keypair::unique().public());
+    Self(std::env::temp_dir().join(name))
+  }
+
+  fn path(&self) -> PathBuf {
+    self.0.clone()
+  }
+}
+
+impl Drop for DataDir {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_dir_all(&self.0);
+  }
+}
+
+fn genesis() -> Genesis<Vec<Transaction>> {
+  Genesis::load(Path::new("test/genesis.json")).unwrap()
+}
+
+type History = Vec<(Produced<Vec<Transaction>>, BlockOutput)>;
+
+/// Finalized blocks at heights 1 to 12 along with their outputs, the
+/// accounts are large enough for the state to span several chunks.
+fn history(genesis: &Genesis<Vec<Transaction>>) -> History {
+  let producer = Keypair::unique();
+  let addresses: Vec<Pubkey> =
+    (0..10).map(|_| Keypair::unique().public()).collect();
+  let removed = *genesis.state.keys().next().unwrap();
+  let mut parent = genesis.hash().unwrap();
+
+  (1..=12u64)
+    .map(|height| {
+      let mut output = BlockOutput::default();
+      let address = addresses[height as usize % addresses.len()];
+      output
+        .state
+        .set(address, Account {
+          nonce: height,
+          executable: false,
+          owner: None,
+          data: Some(vec![height as u8; CHUNK_SIZE / 4]),
+        })
+        .unwrap();
+      if height == 6 {
+        output.state.remove(removed).unwrap();
+      }
+
+      let state_hash = *output.hash();
+      let block =
+        Produced::new(&producer, height, parent, vec![], state_hash, vec![])
+          .unwrap();
+      parent = block.hash().unwrap();
+      (block, output)
+    })
+    .collect()
+}
+
+/// Applies finalized blocks to the state of a validator
+/// that takes a snapshot every 4 blocks.
+fn finalize(
+  genesis: &Genesis<Vec<Transaction>>,
+  dir: &DataDir,
+  history: &History,
+) -> (PersistentState, Snapshots) {
+  let snapshots = Snapshots::new(dir.path(), 4).unwrap();
+  let state = PersistentState::new(genesis, dir.path())
+    .unwrap()
+    .with_snapshots(snapshots.clone());
+
+  for (block, output) in history {
+    state.apply(&output.state).unwrap();
+    state
+      .finalized(block.height, &block.hash().unwrap(), output)
+      .unwrap();
+  }
+
+  // snapshots are written in the background
+  snapshots.wait();
+  (state, snapshots)
+}
+
+/// The network identity of a validator.
+fn peer_id(keypair: &Keypair) -> PeerId {
+  identity::Keypair::Ed25519(
+    identity::ed25519::SecretKey::from_bytes(keypair.secret().to_bytes())
+      .unwrap()
+      .into(),
+  )
+  .public()
+  .to_peer_id()
+}
+
+/// Answers a snapshot request the way a validator does.
+fn respond(
+  snapshots: &Snapshots,
+  history: &History,
+  request: SnapshotRequest,
+) -> SnapshotResponse<Vec<Transaction>> {
+  match request {
+    SnapshotRequest::Manifests => SnapshotResponse::Manifests(
+      snapshots
+        .manifests()
+        .into_iter()
+        .map(|manifest| {
+          let (block, _) = &history[manifest.height as usize - 1];
+          (manifest, block.clone())
+        })
+        .collect(),
+    ),
+    SnapshotRequest::Chunk { height, index } => snapshots
+      .chunk(height, index)
+      .map(SnapshotResponse::Chunk)
+      .unwrap_or(SnapshotResponse::NotAvailable),
+  }
+}
+
+/// Replaces a chunk of a snapshot along with its hash in the manifest.
+fn forge(
+  manifest: &Manifest,
+  chunks: &[Vec<u8>],
+  index: usize,
+  chunk: Vec<u8>,
+) -> (Manifest, Vec<Vec<u8>>) {
+  let mut manifest = manifest.clone();
+  let mut chunks = chunks.to_vec();
+  manifest.chunks[index] = MultihashCode::Sha3_256.digest(&chunk);
+  chunks[index] = chunk;
+  (manifest, chunks)
+}
+
+#[test]
+fn snapshot_restore_test() {
+  let genesis = genesis();
+  let history = history(&genesis);
+  let (first, second, fresh) = (DataDir::new(), DataDir::new(), DataDir::new());
+
+  let (state, snapshots) = finalize(&genesis, &first, &history);
+
+  // only the most recent snapshots are kept
+  let manifests = snapshots.manifests();
+  let heights: Vec<_> = manifests.iter().map(|m| m.height).collect();
+  assert_eq!(heights, vec![12, 8]);
+
+  let manifest = &manifests[0];
+  assert!(manifest.chunks.len() > 1);
+
+  // validators take identical snapshots of the same state
+  let (_, replica) = finalize(&genesis, &second, &history);
+  assert_eq!(replica.manifests()[0].hash(), manifest.hash());
+
+  let mut chunks: Vec<_> = (0..manifest.chunks.len())
+    .map(|index| snapshots.chunk(12, index).unwrap())
+    .collect();
+  for (index, chunk) in chunks.iter().enumerate() {
+    assert!(manifest.verify_chunk(index, chunk));
+  }
+
+  let (block, _) = &history[11];
+  let restored =
+    PersistentState::restore(&genesis, fresh.path(), block, manifest, &chunks)
+      .unwrap();
+
+  let mut addresses: Vec<_> = genesis.state.keys().cloned().collect();
+  addresses.extend(
+    history
+      .iter()
+      .flat_map(|(_, output)| output.state.iter().map(|(address, _)| *address)),
+  );
+  for address in addresses {
+    assert_eq!(restored.get(&address), state.get(&address));
+  }
+
+  // snapshots are only restored at the block they were taken at
+  let (other, _) = &history[10];
+  let restored =
+    PersistentState::restore(&genesis, fresh.path(), other, manifest, &chunks);
+  assert!(matches!(restored, Err(Error::SnapshotMismatch(_))));
+
+  // the output of the block must match the state hash of the block
+  let state_chunks = chunks.len() - manifest.output_chunks;
+  let mut forged = manifest.clone();
+  let mut forged_chunks = chunks[..state_chunks].to_vec();
+  forged.chunks.truncate(state_chunks);
+  let (_, output) = &history[10];
+  for chunk in bincode::serialize(output).unwrap().chunks(CHUNK_SIZE) {
+    forged.chunks.push(MultihashCode::Sha3_256.digest(chunk));
+    forged_chunks.push(chunk.to_vec());
+  }
+  forged.output_chunks = forged.chunks.len() - state_chunks;
+  let restored = PersistentState::restore(
+    &genesis,
+    fresh.path(),
+    block,
+    &forged,
+    &forged_chunks,
+  );
+  assert!(matches!(restored, Err(Error::SnapshotMismatch(_))));
+
+  // and accounts changed by the block must match its output
+  let (_, output) = &history[11];
+  let (changed, _) = output.state.iter().next().unwrap();
+  let index = (0..state_chunks)
+    .find(|i| {
+      let accounts: Vec<(Pubkey, Account)> =
+        bincode::deserialize(&chunks[*i]).unwrap();
+      accounts.iter().any(|(address, _)| address == changed)
+    })
+    .unwrap();
+  let mut accounts: Vec<(Pubkey, Account)> =
+    bincode::deserialize(&chunks[index]).unwrap();
+  for (address, account) in accounts.iter_mut() {
+    if address == changed {
+      account.nonce += 1;
+    }
+  }
+  let chunk = bincode::serialize(&accounts).unwrap();
+  let (forged, forged_chunks) = forge(manifest, &chunks, index, chunk);
+  let restored = PersistentState::restore(
+    &genesis,
+    fresh.path(),
+    block,
+    &forged,
+    &forged_chunks,
+  );
+  assert!(matches!(restored, Err(Error::SnapshotMismatch(_))));
+
+  // chunks that don't match the manifest are refused
+  chunks[1][0] ^= 1;
+  let restored =
+    PersistentState::restore(&genesis, fresh.path(), block, manifest, &chunks);
+  assert!(matches!(restored, Err(Error::InvalidSnapshot(1))));
+}
+
+#[test]
+fn snapshot_fetch_test() {
+  let validators = [Keypair::unique(), Keypair::unique(), Keypair::unique()];
+  let mut genesis = genesis();
+  genesis.validators = validators
+    .iter()
+    .map(|v| Validator {
+      pubkey: v.public(),
+      stake: genesis.limits.minimum_stake,
+    })
+    .collect();
+
+  let history = history(&genesis);
+  let dirs = [DataDir::new(), DataDir::new(), DataDir::new()];
+  let nodes: Vec<_> = dirs
+    .iter()
+    .map(|dir| finalize(&genesis, dir, &history))
+    .collect();
+  let peers: Vec<_> = validators.iter().map(peer_id).collect();
+
+  // a new node downloads the most recent snapshot attested by
+  // validators holding more than two thirds of the stake.
+  let mut sync = SnapshotSync::new(&genesis);
+  for peer in &peers {
+    sync.add_peer(*peer);
+  }
+
+  let snapshot = loop {
+    if let Some(snapshot) = sync.complete() {
+      break snapshot;
+    }
+    let (peer, request) = sync
+      .next()
+      .now_or_never()
+      .flatten()
+      .expect("the snapshot is not complete yet");
+    let index = peers.iter().position(|p| p == &peer).unwrap();
+    let (_, snapshots) = &nodes[index];
+    sync
+      .receive(&peer, respond(snapshots, &history, request))
+      .unwrap();
+  };
+
+  let (block, _) = &history[11];
+  assert_eq!(snapshot.manifest.height, 12);
+  assert_eq!(snapshot.block.hash().unwrap(), block.hash().unwrap());
+
+  let fresh = DataDir::new();
+  let restored = PersistentState::restore(
+    &genesis,
+    fresh.path(),
+    &snapshot.block,
+    &snapshot.manifest,
+    &snapshot.chunks,
+  )
+  .unwrap();
+
+  let (state, _) = &nodes[0];
+  let mut addresses: Vec<_> = genesis.state.keys().cloned().collect();
+  addresses.extend(
+    history
+      .iter()
+      .flat_map(|(_, output)| output.state.iter().map(|(address, _)| *address)),
+  );
+  for address in addresses {
+    assert_eq!(restored.get(&address), state.get(&address));
+  }
+}